@@ -6,24 +6,31 @@ extern crate thiserror;
 extern crate zip;
 
 pub mod data;
+pub mod testing;
 
 mod error;
 pub use error::TchError;
 
 pub(crate) mod wrappers;
+pub use wrappers::autograd;
 pub use wrappers::device::{Cuda, Device};
+pub use wrappers::dlpack;
 pub use wrappers::jit::{self, CModule, IValue, TrainableCModule};
 pub use wrappers::kind::{self, Kind};
+pub use wrappers::profiler;
+#[cfg(feature = "python-extension")]
+pub use wrappers::python;
 pub use wrappers::scalar::Scalar;
 pub use wrappers::{
-    get_num_interop_threads, get_num_threads, manual_seed, set_num_interop_threads,
-    set_num_threads, QEngine,
+    are_deterministic_algorithms_enabled, get_num_interop_threads, get_num_threads, manual_seed,
+    set_num_interop_threads, set_num_threads, use_deterministic_algorithms, QEngine,
 };
 
 mod tensor;
 pub use tensor::{
-    autocast, index, no_grad, no_grad_guard, with_grad, IndexOp, NewAxis, NoGradGuard, Reduction,
-    Shape, Tensor, TensorIndexer,
+    autocast, index, no_grad, no_grad_guard, with_grad, BorrowedTensor, Ellipsis, IndexOp,
+    IntoTensorIndexers, NewAxis, NoGradGuard, Reduction, Shape, Step, Tensor, TensorIndexer,
+    TypedTensor,
 };
 
 pub mod nn;
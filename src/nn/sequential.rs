@@ -153,3 +153,70 @@ impl SequentialT {
         }
     }
 }
+
+/// Returns the outputs of some of the layers of a `SequentialT`.
+///
+/// This is typically used to extract the feature maps computed at various
+/// stages of a backbone network. The layers after the last returned one are
+/// not evaluated.
+#[derive(Debug)]
+pub struct IntermediateLayerGetter {
+    seq: SequentialT,
+    return_layers: Vec<(usize, String)>,
+}
+
+impl IntermediateLayerGetter {
+    /// Creates a new layer getter, `return_layers` contains the indexes of the
+    /// returned layers in `seq` together with the name used for their outputs.
+    ///
+    /// Panics if one of the indexes is not a valid layer index.
+    pub fn new(mut seq: SequentialT, return_layers: &[(usize, &str)]) -> Self {
+        let mut return_layers: Vec<_> = return_layers
+            .iter()
+            .map(|(index, name)| (*index, name.to_string()))
+            .collect();
+        return_layers.sort_by_key(|(index, _)| *index);
+        if let Some((index, _)) = return_layers.last() {
+            if *index >= seq.layers.len() {
+                panic!(
+                    "invalid layer index {} for a sequential layer of length {}",
+                    index,
+                    seq.layers.len()
+                )
+            }
+            seq.layers.truncate(index + 1)
+        }
+        IntermediateLayerGetter { seq, return_layers }
+    }
+
+    /// The names of the returned outputs, in the order of their layers.
+    pub fn names(&self) -> Vec<&str> {
+        self.return_layers.iter().map(|(_, n)| n.as_str()).collect()
+    }
+
+    /// Applies the forward pass and returns the named outputs of the selected
+    /// layers, in the order of these layers.
+    pub fn forward_features(&self, xs: &Tensor, train: bool) -> Vec<(String, Tensor)> {
+        let mut outputs = vec![];
+        let mut return_layers = self.return_layers.iter().peekable();
+        let mut xs = xs.shallow_clone();
+        for (index, layer) in self.seq.layers.iter().enumerate() {
+            xs = layer.forward_t(&xs, train);
+            while let Some((i, name)) = return_layers.peek() {
+                if *i != index {
+                    break;
+                }
+                outputs.push((name.to_string(), xs.shallow_clone()));
+                return_layers.next();
+            }
+        }
+        outputs
+    }
+}
+
+impl ModuleT for IntermediateLayerGetter {
+    /// Returns the output of the last selected layer.
+    fn forward_t(&self, xs: &Tensor, train: bool) -> Tensor {
+        self.seq.forward_t(xs, train)
+    }
+}
@@ -146,6 +146,78 @@ impl VarStore {
         Ok(())
     }
 
+    /// Saves the var-store variable values to a npz file.
+    ///
+    /// Weight values for all the tensors currently stored in the
+    /// var-store gets saved in the given file, the resulting file can be
+    /// read using `numpy.load`.
+    pub fn save_npz<T: AsRef<std::path::Path>>(&self, path: T) -> Result<(), TchError> {
+        let variables = self.variables_.lock().unwrap();
+        let named_tensors = variables.named_variables.iter().collect::<Vec<_>>();
+        Tensor::write_npz(named_tensors.as_slice(), path)
+    }
+
+    /// Loads the var-store variable values from a npz file.
+    ///
+    /// Weight values for all the tensors currently stored in the
+    /// var-store gets loaded from the given file. Note that the set of
+    /// variables stored in the var-store is not changed, only the values
+    /// for these tensors are modified.
+    pub fn load_npz<T: AsRef<std::path::Path>>(&mut self, path: T) -> Result<(), TchError> {
+        let named_tensors = Tensor::read_npz(&path)?;
+        let named_tensors: HashMap<_, _> = named_tensors.into_iter().collect();
+        let mut variables = self.variables_.lock().unwrap();
+        for (name, var) in variables.named_variables.iter_mut() {
+            match named_tensors.get(name) {
+                Some(src) => crate::no_grad(|| {
+                    var.f_copy_(&src.f_to_device(self.device)?)
+                        .map_err(|e| e.path_context(name))
+                })?,
+                None => {
+                    return Err(TchError::TensorNameNotFound(
+                        name.to_string(),
+                        path.as_ref().to_string_lossy().into_owned(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Saves the var-store variable values to a stream.
+    ///
+    /// Weight values for all the tensors currently stored in the
+    /// var-store gets saved in the given stream.
+    pub fn save_to_stream<W: std::io::Write>(&self, stream: W) -> Result<(), TchError> {
+        let variables = self.variables_.lock().unwrap();
+        let named_tensors = variables.named_variables.iter().collect::<Vec<_>>();
+        Tensor::save_multi_to_stream(named_tensors.as_slice(), stream)
+    }
+
+    /// Loads the var-store variable values from a stream.
+    ///
+    /// Weight values for all the tensors currently stored in the
+    /// var-store gets loaded from the given stream. Note that the set of
+    /// variables stored in the var-store is not changed, only the values
+    /// for these tensors are modified.
+    pub fn load_from_stream<R: std::io::Read>(&mut self, stream: R) -> Result<(), TchError> {
+        let named_tensors = Tensor::load_multi_from_stream_with_device(stream, self.device)?;
+        let named_tensors: HashMap<_, _> = named_tensors.into_iter().collect();
+        let mut variables = self.variables_.lock().unwrap();
+        for (name, var) in variables.named_variables.iter_mut() {
+            match named_tensors.get(name) {
+                Some(src) => crate::no_grad(|| var.f_copy_(src).map_err(|e| e.path_context(name)))?,
+                None => {
+                    return Err(TchError::TensorNameNotFound(
+                        name.to_string(),
+                        "stream".to_string(),
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+
     /// Loads the var-store variable values from a file if it exists.
     ///
     /// Weight values for the tensors currently stored in the var-store and the given file get
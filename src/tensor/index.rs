@@ -44,6 +44,28 @@
 //! assert_eq!(t.size(), &[2, 3, 1]);
 //! ```
 //!
+//! The `Ellipsis` index expands to as many full ranges as needed to index all
+//! the dimensions, and `Step` narrows a dimension using a step.
+//!
+//! ```ignore
+//! use crate::tch::{Ellipsis, IndexOp, Step, Tensor};
+//! let tensor = Tensor::arange(24, tch::kind::INT64_CPU).view((2, 3, 4));
+//! let t = tensor.i((Ellipsis, 1));
+//! assert_eq!(t.size(), &[2, 3]);
+//! let t = tensor.i((.., .., Step(0.., 2)));
+//! assert_eq!(t.size(), &[2, 3, 2]);
+//! ```
+//!
+//! Integer tensors select indexes along a dimension and boolean tensors
+//! act as masks over one or more dimensions.
+//!
+//! ```ignore
+//! use crate::tch::{IndexOp, Tensor};
+//! let tensor = Tensor::of_slice(&[1, 2, 3, 4, 5, 6]).view((2, 3));
+//! let t = tensor.i((.., vec![true, false, true]));
+//! assert_eq!(Vec::<i64>::from(t.contiguous().view(-1)), [1, 3, 4, 6]);
+//! ```
+//!
 //! Unlike NumPy, the `i` operation does not support advanced indexing.
 //! The result can be different from NumPy with same set of arguments.
 //! For example, `tensor.i(..1, vec![0, 3], vec![2, 1, 3])` does narrowing
@@ -51,8 +73,25 @@
 //! The analogous NumPy indexing `array[:1, [0, 3], [2, 1, 3]]` throws
 //! shape mismatch error due to advanced indexing rule. Another distinction
 //! is that `i` guarantees the input and result tensor shares the same
-//! underlying storage, while NumPy may copy the tensor in certain scenarios.
-use crate::{TchError, Tensor};
+//! underlying storage when no tensor index is used, while NumPy may copy
+//! the tensor in certain scenarios. The `i_numpy` operation follows the
+//! NumPy advanced indexing rules instead: tensor indexes, which can be
+//! multi-dimensional, are broadcast together.
+//!
+//! Values can be assigned using `i_set`, or `i_set_numpy` to use the
+//! NumPy rules for the index. `i_mut` returns a tensor sharing storage with
+//! the indexed tensor so that it can be modified in place, this is only
+//! possible when no tensor index is used.
+//!
+//! ```ignore
+//! use crate::tch::{IndexOp, Tensor};
+//! let mut tensor = Tensor::zeros(&[2, 3], tch::kind::INT64_CPU);
+//! tensor.i_set((.., 1), &Tensor::of_slice(&[1, 2]));
+//! tensor.i_set((1, vec![0, 2]), &Tensor::from(7));
+//! let _ = tensor.i_mut((0, 2..)).fill_(5);
+//! assert_eq!(Vec::<i64>::from(tensor.view(-1)), [0, 1, 5, 7, 2, 7]);
+//! ```
+use crate::{Kind, TchError, Tensor};
 use std::ops::{
     Bound, Range, RangeBounds, RangeFrom, RangeFull, RangeInclusive, RangeTo, RangeToInclusive,
 };
@@ -60,12 +99,24 @@ use std::ops::{
 #[derive(Debug, PartialEq)]
 pub struct NewAxis;
 
+/// An index expanding to full ranges over the dimensions that are not
+/// indexed otherwise.
+#[derive(Debug, PartialEq)]
+pub struct Ellipsis;
+
+/// A range index with a step, e.g. `Step(1.., 2)`. The step has to be
+/// positive.
+#[derive(Debug, PartialEq)]
+pub struct Step<R>(pub R, pub i64);
+
 #[derive(Debug, PartialEq)]
 pub enum TensorIndexer {
     Select(i64),
     Narrow(Bound<i64>, Bound<i64>),
+    StepNarrow(Bound<i64>, Bound<i64>, i64),
     IndexSelect(Tensor),
     InsertNewAxis,
+    Ellipsis,
 }
 
 impl From<NewAxis> for TensorIndexer {
@@ -74,6 +125,12 @@ impl From<NewAxis> for TensorIndexer {
     }
 }
 
+impl From<Ellipsis> for TensorIndexer {
+    fn from(_index: Ellipsis) -> Self {
+        TensorIndexer::Ellipsis
+    }
+}
+
 impl From<i64> for TensorIndexer {
     fn from(index: i64) -> Self {
         TensorIndexer::Select(index)
@@ -94,33 +151,49 @@ impl From<Vec<i64>> for TensorIndexer {
     }
 }
 
+impl From<&[bool]> for TensorIndexer {
+    fn from(index: &[bool]) -> Self {
+        TensorIndexer::IndexSelect(Tensor::of_slice(index))
+    }
+}
+
+impl From<Vec<bool>> for TensorIndexer {
+    fn from(index: Vec<bool>) -> Self {
+        TensorIndexer::IndexSelect(Tensor::of_slice(&index))
+    }
+}
+
 impl From<&Tensor> for TensorIndexer {
     fn from(tensor: &Tensor) -> Self {
         TensorIndexer::IndexSelect(tensor.shallow_clone())
     }
 }
 
+fn bound_of_ref(bound: Bound<&i64>) -> Bound<i64> {
+    match bound {
+        Bound::Included(idx) => Bound::Included(*idx),
+        Bound::Excluded(idx) => Bound::Excluded(*idx),
+        Bound::Unbounded => Bound::Unbounded,
+    }
+}
+
 macro_rules! impl_from_range {
     ($range_type:ty) => {
         impl From<$range_type> for TensorIndexer {
             fn from(range: $range_type) -> Self {
-                use std::ops::Bound::*;
-
-                let start = match range.start_bound() {
-                    Included(idx) => Included(*idx),
-                    Excluded(idx) => Excluded(*idx),
-                    Unbounded => Unbounded,
-                };
-
-                let end = match range.end_bound() {
-                    Included(idx) => Included(*idx),
-                    Excluded(idx) => Excluded(*idx),
-                    Unbounded => Unbounded,
-                };
-
+                let start = bound_of_ref(range.start_bound());
+                let end = bound_of_ref(range.end_bound());
                 TensorIndexer::Narrow(start, end)
             }
         }
+
+        impl From<Step<$range_type>> for TensorIndexer {
+            fn from(step: Step<$range_type>) -> Self {
+                let start = bound_of_ref(step.0.start_bound());
+                let end = bound_of_ref(step.0.end_bound());
+                TensorIndexer::StepNarrow(start, end, step.1)
+            }
+        }
     };
 }
 
@@ -131,235 +204,405 @@ impl_from_range!(RangeInclusive<i64>);
 impl_from_range!(RangeTo<i64>);
 impl_from_range!(RangeToInclusive<i64>);
 
-pub trait IndexOp<T> {
-    fn i(&self, index: T) -> Tensor;
+/// Types that can be used as an index: single indexers, tuples of indexers,
+/// and vectors of `TensorIndexer` for an arbitrary number of indexers.
+pub trait IntoTensorIndexers {
+    fn into_tensor_indexers(self) -> Vec<TensorIndexer>;
 }
 
-impl<A> IndexOp<A> for Tensor
+impl<A> IntoTensorIndexers for A
 where
     A: Into<TensorIndexer>,
 {
-    fn i(&self, index: A) -> Tensor {
-        self.indexer(&[index.into()])
+    fn into_tensor_indexers(self) -> Vec<TensorIndexer> {
+        vec![self.into()]
     }
 }
 
-impl<A> IndexOp<(A,)> for Tensor
-where
-    A: Into<TensorIndexer>,
-{
-    fn i(&self, index: (A,)) -> Tensor {
-        let idx_a = index.0.into();
-        self.indexer(&[idx_a])
+impl IntoTensorIndexers for Vec<TensorIndexer> {
+    fn into_tensor_indexers(self) -> Vec<TensorIndexer> {
+        self
     }
 }
 
-impl<A, B> IndexOp<(A, B)> for Tensor
-where
-    A: Into<TensorIndexer>,
-    B: Into<TensorIndexer>,
-{
-    fn i(&self, index: (A, B)) -> Tensor {
-        let idx_a = index.0.into();
-        let idx_b = index.1.into();
-        self.indexer(&[idx_a, idx_b])
-    }
+macro_rules! impl_tuple_indexers {
+    ($($ty:ident $idx:tt),+) => {
+        impl<$($ty),+> IntoTensorIndexers for ($($ty,)+)
+        where
+            $($ty: Into<TensorIndexer>,)+
+        {
+            fn into_tensor_indexers(self) -> Vec<TensorIndexer> {
+                vec![$(self.$idx.into()),+]
+            }
+        }
+    };
 }
 
-impl<A, B, C> IndexOp<(A, B, C)> for Tensor
-where
-    A: Into<TensorIndexer>,
-    B: Into<TensorIndexer>,
-    C: Into<TensorIndexer>,
-{
-    fn i(&self, index: (A, B, C)) -> Tensor {
-        let idx_a = index.0.into();
-        let idx_b = index.1.into();
-        let idx_c = index.2.into();
-        self.indexer(&[idx_a, idx_b, idx_c])
-    }
+impl_tuple_indexers!(A 0);
+impl_tuple_indexers!(A 0, B 1);
+impl_tuple_indexers!(A 0, B 1, C 2);
+impl_tuple_indexers!(A 0, B 1, C 2, D 3);
+impl_tuple_indexers!(A 0, B 1, C 2, D 3, E 4);
+impl_tuple_indexers!(A 0, B 1, C 2, D 3, E 4, F 5);
+impl_tuple_indexers!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_tuple_indexers!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+impl_tuple_indexers!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8);
+impl_tuple_indexers!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9);
+impl_tuple_indexers!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10);
+impl_tuple_indexers!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7, I 8, J 9, K 10, L 11);
+
+pub trait IndexOp<T> {
+    /// Indexes a tensor, tensor indexes are applied independently on each
+    /// dimension.
+    fn i(&self, index: T) -> Tensor;
+    fn f_i(&self, index: T) -> Result<Tensor, TchError>;
+
+    /// Indexes a tensor using the NumPy advanced indexing rules.
+    fn i_numpy(&self, index: T) -> Tensor;
+    fn f_i_numpy(&self, index: T) -> Result<Tensor, TchError>;
+
+    /// Returns a tensor sharing storage with the indexed part of this tensor,
+    /// tensor indexes are not supported.
+    fn i_mut(&mut self, index: T) -> Tensor;
+    fn f_i_mut(&mut self, index: T) -> Result<Tensor, TchError>;
+
+    /// Assigns some values to the indexed part of this tensor, the values are
+    /// broadcast to the shape returned by `i`.
+    fn i_set(&mut self, index: T, values: &Tensor);
+    fn f_i_set(&mut self, index: T, values: &Tensor) -> Result<(), TchError>;
+
+    /// Assigns some values to the indexed part of this tensor, the values are
+    /// broadcast to the shape returned by `i_numpy`.
+    fn i_set_numpy(&mut self, index: T, values: &Tensor);
+    fn f_i_set_numpy(&mut self, index: T, values: &Tensor) -> Result<(), TchError>;
 }
 
-impl<A, B, C, D> IndexOp<(A, B, C, D)> for Tensor
+impl<T> IndexOp<T> for Tensor
 where
-    A: Into<TensorIndexer>,
-    B: Into<TensorIndexer>,
-    C: Into<TensorIndexer>,
-    D: Into<TensorIndexer>,
+    T: IntoTensorIndexers,
 {
-    fn i(&self, index: (A, B, C, D)) -> Tensor {
-        let idx_a = index.0.into();
-        let idx_b = index.1.into();
-        let idx_c = index.2.into();
-        let idx_d = index.3.into();
-        self.indexer(&[idx_a, idx_b, idx_c, idx_d])
+    fn i(&self, index: T) -> Tensor {
+        self.f_i(index).unwrap()
     }
-}
 
-impl<A, B, C, D, E> IndexOp<(A, B, C, D, E)> for Tensor
-where
-    A: Into<TensorIndexer>,
-    B: Into<TensorIndexer>,
-    C: Into<TensorIndexer>,
-    D: Into<TensorIndexer>,
-    E: Into<TensorIndexer>,
-{
-    fn i(&self, index: (A, B, C, D, E)) -> Tensor {
-        let idx_a = index.0.into();
-        let idx_b = index.1.into();
-        let idx_c = index.2.into();
-        let idx_d = index.3.into();
-        let idx_e = index.4.into();
-        self.indexer(&[idx_a, idx_b, idx_c, idx_d, idx_e])
+    fn f_i(&self, index: T) -> Result<Tensor, TchError> {
+        self.f_indexer(&index.into_tensor_indexers())
     }
-}
 
-impl<A, B, C, D, E, F> IndexOp<(A, B, C, D, E, F)> for Tensor
-where
-    A: Into<TensorIndexer>,
-    B: Into<TensorIndexer>,
-    C: Into<TensorIndexer>,
-    D: Into<TensorIndexer>,
-    E: Into<TensorIndexer>,
-    F: Into<TensorIndexer>,
-{
-    fn i(&self, index: (A, B, C, D, E, F)) -> Tensor {
-        let idx_a = index.0.into();
-        let idx_b = index.1.into();
-        let idx_c = index.2.into();
-        let idx_d = index.3.into();
-        let idx_e = index.4.into();
-        let idx_f = index.5.into();
-        self.indexer(&[idx_a, idx_b, idx_c, idx_d, idx_e, idx_f])
+    fn i_numpy(&self, index: T) -> Tensor {
+        self.f_i_numpy(index).unwrap()
     }
-}
 
-impl<A, B, C, D, E, F, G> IndexOp<(A, B, C, D, E, F, G)> for Tensor
-where
-    A: Into<TensorIndexer>,
-    B: Into<TensorIndexer>,
-    C: Into<TensorIndexer>,
-    D: Into<TensorIndexer>,
-    E: Into<TensorIndexer>,
-    F: Into<TensorIndexer>,
-    G: Into<TensorIndexer>,
-{
-    fn i(&self, index: (A, B, C, D, E, F, G)) -> Tensor {
-        let idx_a = index.0.into();
-        let idx_b = index.1.into();
-        let idx_c = index.2.into();
-        let idx_d = index.3.into();
-        let idx_e = index.4.into();
-        let idx_f = index.5.into();
-        let idx_g = index.6.into();
-        self.indexer(&[idx_a, idx_b, idx_c, idx_d, idx_e, idx_f, idx_g])
+    fn f_i_numpy(&self, index: T) -> Result<Tensor, TchError> {
+        self.f_numpy_indexer(&index.into_tensor_indexers())
+    }
+
+    fn i_mut(&mut self, index: T) -> Tensor {
+        self.f_i_mut(index).unwrap()
+    }
+
+    fn f_i_mut(&mut self, index: T) -> Result<Tensor, TchError> {
+        let (tensor, indexes) = self.f_basic_indexer(&index.into_tensor_indexers(), false)?;
+        if indexes.iter().any(|(_, index)| index.is_some()) {
+            return Err(TchError::Shape(
+                "tensor indexes are not supported by i_mut, use i_set instead".to_string(),
+            ));
+        }
+        Ok(tensor)
+    }
+
+    fn i_set(&mut self, index: T, values: &Tensor) {
+        self.f_i_set(index, values).unwrap()
+    }
+
+    fn f_i_set(&mut self, index: T, values: &Tensor) -> Result<(), TchError> {
+        self.f_index_set(&index.into_tensor_indexers(), values, false)
+    }
+
+    fn i_set_numpy(&mut self, index: T, values: &Tensor) {
+        self.f_i_set_numpy(index, values).unwrap()
+    }
+
+    fn f_i_set_numpy(&mut self, index: T, values: &Tensor) -> Result<(), TchError> {
+        self.f_index_set(&index.into_tensor_indexers(), values, true)
     }
 }
 
+// The dimensions of the tensor resulting from basic indexing together with the
+// tensor index to apply to them if any. Each entry covers a single dimension
+// except for boolean masks which cover as many dimensions as the mask.
+type TensorIndexes = Vec<(usize, Option<Tensor>)>;
+
 impl Tensor {
-    fn f_indexer(&self, index_spec: &[TensorIndexer]) -> Result<Tensor, TchError> {
+    // Applies the indexers that result in a view of the tensor, i.e. all but
+    // tensor indexes which are returned so that they can be applied later.
+    fn f_basic_indexer(
+        &self,
+        index_spec: &[TensorIndexer],
+        numpy: bool,
+    ) -> Result<(Tensor, TensorIndexes), TchError> {
         use std::ops::Bound::*;
         use TensorIndexer::*;
 
-        // Make sure n. non-newaxis does not exceed n. of dimensions
-        let n_newaxis = index_spec
-            .iter()
-            .filter(|spec| *spec == &InsertNewAxis)
-            .count();
-
-        if index_spec.len() > self.size().len() + n_newaxis {
-            return Err(TchError::Shape(format!(
-                "too many indices for tensor of dimension {}",
-                self.size().len()
-            )));
+        let n_ellipsis = index_spec.iter().filter(|spec| *spec == &Ellipsis).count();
+        if n_ellipsis > 1 {
+            return Err(TchError::Shape(
+                "an index can only have a single ellipsis".to_string(),
+            ));
         }
 
         // Make sure tensors conform the format
+        let mut n_indexed_dims = 0;
         for spec in index_spec.iter() {
             use super::Kind::*;
-            if let IndexSelect(tensor) = spec {
-                if tensor.size().len() != 1 {
-                    return Err(TchError::Shape(
-                        "Multi-dimensional tensor is not supported for indexing".to_string(),
-                    ));
+            n_indexed_dims += match spec {
+                InsertNewAxis | Ellipsis => 0,
+                Select(_) | Narrow(_, _) => 1,
+                StepNarrow(_, _, step) => {
+                    if *step <= 0 {
+                        return Err(TchError::Shape(format!(
+                            "the step has to be positive, got {}",
+                            step
+                        )));
+                    }
+                    1
                 }
-                match tensor.f_kind()? {
-                    Int64 => {}
-                    Int16 => {}
-                    Int8 => {}
-                    Int => {}
+                IndexSelect(tensor) => match tensor.f_kind()? {
+                    Bool => {
+                        if tensor.dim() == 0 {
+                            return Err(TchError::Shape(
+                                "zero-dimensional masks are not supported for indexing".to_string(),
+                            ));
+                        }
+                        tensor.dim()
+                    }
+                    Int64 | Int16 | Int8 | Int => {
+                        if !numpy && tensor.dim() != 1 {
+                            return Err(TchError::Shape(
+                                "Multi-dimensional tensor is not supported for indexing"
+                                    .to_string(),
+                            ));
+                        }
+                        1
+                    }
                     _ => {
-                        return Err(TchError::Kind(format!("the kind of tensors used as indices must be one of {:?}, {:?}, {:?}, {:?}", Int64, Int16, Int8, Int)));
+                        return Err(TchError::Kind(format!("the kind of tensors used as indices must be one of {:?}, {:?}, {:?}, {:?}, {:?}", Int64, Int16, Int8, Int, Bool)));
                     }
-                }
+                },
             }
         }
 
+        // Make sure n. non-newaxis does not exceed n. of dimensions
+        let n_dims = self.dim();
+        if n_indexed_dims > n_dims {
+            return Err(TchError::Shape(format!(
+                "too many indices for tensor of dimension {}",
+                n_dims
+            )));
+        }
+
         // Apply indexing from left to right
         let mut curr_tensor = self.shallow_clone();
         let mut curr_idx: i64 = 0;
+        let mut indexes: TensorIndexes = vec![];
 
         for spec in index_spec.iter() {
             let (next_tensor, next_idx) = match spec {
-                InsertNewAxis => (curr_tensor.unsqueeze(curr_idx), curr_idx + 1),
+                InsertNewAxis => (curr_tensor.f_unsqueeze(curr_idx)?, curr_idx + 1),
+                Ellipsis => {
+                    let n = (n_dims - n_indexed_dims) as i64;
+                    (curr_tensor, curr_idx + n)
+                }
                 Select(index) => (
-                    curr_tensor.select(curr_idx, *index),
+                    curr_tensor.f_select(curr_idx, *index)?,
                     curr_idx, // not advanced because select() squeezes dimension
                 ),
                 Narrow(Unbounded, Unbounded) => (curr_tensor, curr_idx + 1),
                 Narrow(Included(start), Unbounded) => {
                     let dim_len = curr_tensor.size()[curr_idx as usize] as i64;
                     (
-                        curr_tensor.narrow(curr_idx, *start, dim_len - *start),
+                        curr_tensor.f_narrow(curr_idx, *start, dim_len - *start)?,
                         curr_idx + 1,
                     )
                 }
                 Narrow(Excluded(start), Unbounded) => {
                     let dim_len = curr_tensor.size()[curr_idx as usize] as i64;
                     (
-                        curr_tensor.narrow(curr_idx, *start + 1, dim_len - *start - 1),
+                        curr_tensor.f_narrow(curr_idx, *start + 1, dim_len - *start - 1)?,
                         curr_idx + 1,
                     )
                 }
                 Narrow(Unbounded, Included(end)) => {
-                    (curr_tensor.narrow(curr_idx, 0, *end + 1), curr_idx + 1)
+                    (curr_tensor.f_narrow(curr_idx, 0, *end + 1)?, curr_idx + 1)
                 }
                 Narrow(Unbounded, Excluded(end)) => {
-                    (curr_tensor.narrow(curr_idx, 0, *end), curr_idx + 1)
+                    (curr_tensor.f_narrow(curr_idx, 0, *end)?, curr_idx + 1)
                 }
                 Narrow(Included(start), Included(end)) => (
-                    curr_tensor.narrow(curr_idx, *start, *end - *start + 1),
+                    curr_tensor.f_narrow(curr_idx, *start, *end - *start + 1)?,
                     curr_idx + 1,
                 ),
                 Narrow(Included(start), Excluded(end)) => (
-                    curr_tensor.narrow(curr_idx, *start, *end - *start),
+                    curr_tensor.f_narrow(curr_idx, *start, *end - *start)?,
                     curr_idx + 1,
                 ),
                 Narrow(Excluded(start), Included(end)) => (
-                    curr_tensor.narrow(curr_idx, *start + 1, *end - *start),
+                    curr_tensor.f_narrow(curr_idx, *start + 1, *end - *start)?,
                     curr_idx + 1,
                 ),
                 Narrow(Excluded(start), Excluded(end)) => (
-                    curr_tensor.narrow(curr_idx, *start + 1, *end - *start - 1),
+                    curr_tensor.f_narrow(curr_idx, *start + 1, *end - *start - 1)?,
                     curr_idx + 1,
                 ),
-                IndexSelect(index_tensor) => {
-                    let index_tensor = index_tensor.to_device(curr_tensor.device());
+                StepNarrow(start, end, step) => {
+                    let dim_len = curr_tensor.size()[curr_idx as usize] as i64;
+                    let wrap = |i: i64| if i < 0 { i + dim_len } else { i };
+                    let start = match start {
+                        Included(start) => wrap(*start),
+                        Excluded(start) => wrap(*start) + 1,
+                        Unbounded => 0,
+                    };
+                    let end = match end {
+                        Included(end) => wrap(*end) + 1,
+                        Excluded(end) => wrap(*end),
+                        Unbounded => dim_len,
+                    };
+                    let start = start.max(0).min(dim_len);
+                    let end = end.max(start).min(dim_len);
                     (
-                        curr_tensor.index_select(curr_idx, &index_tensor),
+                        curr_tensor.f_slice(curr_idx, start, end, *step)?,
                         curr_idx + 1,
                     )
                 }
+                IndexSelect(index_tensor) => {
+                    let index_tensor = index_tensor.f_to_device(curr_tensor.device())?;
+                    let n = if index_tensor.f_kind()? == Kind::Bool {
+                        let dims = &curr_tensor.size()[curr_idx as usize..];
+                        let mask_dims = index_tensor.size();
+                        if dims.len() < mask_dims.len() || dims[..mask_dims.len()] != *mask_dims {
+                            return Err(TchError::Shape(format!(
+                                "mask of shape {:?} does not match the indexed dimensions {:?}",
+                                mask_dims, dims
+                            )));
+                        }
+                        mask_dims.len()
+                    } else {
+                        1
+                    };
+                    // Dimensions skipped by an ellipsis or newaxis are not indexed.
+                    for _ in indexes.iter().map(|(n, _)| n).sum::<usize>()..curr_idx as usize {
+                        indexes.push((1, None))
+                    }
+                    indexes.push((n, Some(index_tensor)));
+                    (curr_tensor, curr_idx + n as i64)
+                }
             };
 
             curr_tensor = next_tensor;
             curr_idx = next_idx;
         }
 
+        for _ in indexes.iter().map(|(n, _)| n).sum::<usize>()..curr_tensor.dim() {
+            indexes.push((1, None))
+        }
+        Ok((curr_tensor, indexes))
+    }
+
+    fn f_indexer(&self, index_spec: &[TensorIndexer]) -> Result<Tensor, TchError> {
+        let (mut curr_tensor, indexes) = self.f_basic_indexer(index_spec, false)?;
+        let mut curr_idx: i64 = 0;
+        for (n, index) in indexes.iter() {
+            if let Some(index) = index {
+                curr_tensor = if index.f_kind()? == Kind::Bool {
+                    let index = index.f_reshape(&[-1])?.f_nonzero()?.f_reshape(&[-1])?;
+                    curr_tensor
+                        .f_flatten(curr_idx, curr_idx + *n as i64 - 1)?
+                        .f_index_select(curr_idx, &index)?
+                } else {
+                    curr_tensor.f_index_select(curr_idx, index)?
+                };
+            }
+            curr_idx += 1
+        }
         Ok(curr_tensor)
     }
 
-    fn indexer(&self, index_spec: &[TensorIndexer]) -> Tensor {
-        self.f_indexer(index_spec).unwrap()
+    fn f_numpy_indexer(&self, index_spec: &[TensorIndexer]) -> Result<Tensor, TchError> {
+        let (tensor, indexes) = self.f_basic_indexer(index_spec, true)?;
+        if indexes.iter().all(|(_, index)| index.is_none()) {
+            return Ok(tensor);
+        }
+        tensor.f_index(&numpy_indexes(&indexes)?)
+    }
+
+    fn f_index_set(
+        &mut self,
+        index_spec: &[TensorIndexer],
+        values: &Tensor,
+        numpy: bool,
+    ) -> Result<(), TchError> {
+        let (mut tensor, indexes) = self.f_basic_indexer(index_spec, numpy)?;
+        let values = values
+            .f_to_kind(tensor.f_kind()?)?
+            .f_to_device(tensor.device())?;
+        if indexes.iter().all(|(_, index)| index.is_none()) {
+            return tensor.f_copy_(&values);
+        }
+        let indexes = if numpy {
+            numpy_indexes(&indexes)?
+        } else {
+            outer_indexes(&tensor, &indexes)?
+        };
+        let _ = tensor.f_index_put_(&indexes, &values, false)?;
+        Ok(())
+    }
+}
+
+// Tensor indexes as used by `Tensor::index`, the dimensions that are not
+// indexed use an undefined tensor.
+fn numpy_indexes(indexes: &[(usize, Option<Tensor>)]) -> Result<Vec<Tensor>, TchError> {
+    indexes
+        .iter()
+        .map(|(_, index)| match index {
+            None => Ok(Tensor::new()),
+            Some(index) if index.f_kind()? == Kind::Bool => Ok(index.shallow_clone()),
+            Some(index) => index.f_to_kind(Kind::Int64),
+        })
+        .collect()
+}
+
+// Tensor indexes selecting the same elements as `f_indexer` with
+// `Tensor::index_put_`. Each dimension gets an index tensor and the index
+// tensors are shaped so that broadcasting them results in the outer product
+// of the indexes.
+fn outer_indexes(
+    tensor: &Tensor,
+    indexes: &[(usize, Option<Tensor>)],
+) -> Result<Vec<Tensor>, TchError> {
+    let sizes = tensor.size();
+    let mut result = vec![];
+    let mut dim = 0;
+    for (result_dim, (n, index)) in indexes.iter().enumerate() {
+        let mut shape = vec![1i64; indexes.len()];
+        match index {
+            None => {
+                shape[result_dim] = sizes[dim];
+                let index = Tensor::f_arange(sizes[dim], (Kind::Int64, tensor.device()))?;
+                result.push(index.f_reshape(&shape)?)
+            }
+            Some(index) if index.f_kind()? == Kind::Bool => {
+                let coordinates = index.f_nonzero()?;
+                shape[result_dim] = coordinates.size()[0];
+                for i in 0..*n {
+                    result.push(coordinates.f_select(1, i as i64)?.f_reshape(&shape)?)
+                }
+            }
+            Some(index) => {
+                shape[result_dim] = index.size()[0];
+                result.push(index.f_to_kind(Kind::Int64)?.f_reshape(&shape)?)
+            }
+        }
+        dim += n
     }
+    Ok(result)
 }
@@ -1,6 +1,7 @@
 //! A Torch tensor.
 use crate::{Device, Kind, Scalar, TchError};
-use half::f16;
+use half::{bf16, f16};
+use num_complex::{Complex32, Complex64};
 use std::convert::TryFrom;
 use std::ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign};
 use torch_sys::*;
@@ -8,11 +9,15 @@ use torch_sys::*;
 pub mod index;
 mod iter;
 mod npy;
+#[cfg(feature = "serde")]
+mod serde;
+mod typed;
 
 pub use super::wrappers::tensor::{
-    autocast, no_grad, no_grad_guard, with_grad, NoGradGuard, Reduction, Tensor,
+    autocast, no_grad, no_grad_guard, with_grad, BorrowedTensor, NoGradGuard, Reduction, Tensor,
 };
-pub use index::{IndexOp, NewAxis, TensorIndexer};
+pub use index::{Ellipsis, IndexOp, IntoTensorIndexers, NewAxis, Step, TensorIndexer};
+pub use typed::TypedTensor;
 
 macro_rules! impl_op {
     ($trait:ident, $func:ident, $op:ident) => {
@@ -453,8 +458,20 @@ impl std::fmt::Debug for Tensor {
                         | Kind::ComplexFloat
                         | Kind::ComplexDouble => (false, false),
                     };
+                    let is_complex = matches!(
+                        kind,
+                        Kind::ComplexHalf | Kind::ComplexFloat | Kind::ComplexDouble
+                    );
                     match (self.size().as_slice(), is_int, is_float) {
                         ([], true, false) => write!(f, "[{}]", i64::from(self)),
+                        ([], false, false) if is_complex => {
+                            write!(f, "[{}]", Complex64::from(self))
+                        }
+                        ([s], false, false) if is_complex && *s < 10 => {
+                            let vs = Vec::<Complex64>::from(self);
+                            let vs: Vec<String> = vs.iter().map(|v| v.to_string()).collect();
+                            write!(f, "[{}]", vs.join(", "))
+                        }
                         ([s], true, false) if *s < 10 => write!(f, "{:?}", Vec::<i64>::from(self)),
                         ([], false, true) => write!(f, "[{}]", f64::from(self)),
                         ([s], false, true) if *s < 10 => write!(f, "{:?}", Vec::<f64>::from(self)),
@@ -481,6 +498,40 @@ impl Tensor {
     pub fn nll_loss(&self, targets: &Tensor) -> Tensor {
         self.g_nll_loss::<Tensor>(targets, None, Reduction::Mean, -100)
     }
+
+    // The C++ printer only handles real values, complex tensors are formatted
+    // on the Rust side using a similar layout.
+    pub(crate) fn complex_to_string(&self) -> Result<String, TchError> {
+        fn nested(values: &[String], size: &[i64], indent: usize) -> String {
+            match size {
+                [] => values[0].clone(),
+                [_] => format!("[{}]", values.join(", ")),
+                [d, rest @ ..] => {
+                    if values.is_empty() {
+                        return "[]".to_string();
+                    }
+                    let chunk = values.len() / *d as usize;
+                    let rows: Vec<String> = values
+                        .chunks(chunk)
+                        .map(|v| nested(v, rest, indent + 1))
+                        .collect();
+                    format!("[{}]", rows.join(&format!(",\n{}", " ".repeat(indent + 1))))
+                }
+            }
+        }
+        let size = self.size();
+        let values: Vec<String> = Vec::<Complex64>::from(&self.f_contiguous()?)
+            .iter()
+            .map(|v| v.to_string())
+            .collect();
+        let dims: Vec<String> = size.iter().map(|d| d.to_string()).collect();
+        Ok(format!(
+            "{}\n[ {:?}{{{}}} ]",
+            nested(&values, &size, 0),
+            self.f_kind()?,
+            dims.join(",")
+        ))
+    }
 }
 
 macro_rules! from_tensor {
@@ -551,8 +602,12 @@ macro_rules! from_tensor {
 from_tensor!(f64, 0f64, Double);
 from_tensor!(f32, 0f32, Float);
 from_tensor!(f16, f16::from_f64(0.0), Half);
+from_tensor!(bf16, bf16::from_f64(0.0), BFloat16);
+from_tensor!(Complex64, Complex64::new(0.0, 0.0), ComplexDouble);
+from_tensor!(Complex32, Complex32::new(0.0, 0.0), ComplexFloat);
 from_tensor!(i64, 0i64, Int64);
 from_tensor!(i32, 0i32, Int);
+from_tensor!(i16, 0i16, Int16);
 from_tensor!(i8, 0i8, Int8);
 from_tensor!(u8, 0u8, Uint8);
 from_tensor!(bool, false, Bool);
@@ -691,24 +746,34 @@ impl PartialEq for Tensor {
 macro_rules! try_from_impl {
     ($type:ident) => {
         #[cfg(feature = "ndarray")]
-        impl<D> TryFrom<&ndarray::Array<$type, D>> for Tensor
+        impl<D> TryFrom<ndarray::ArrayView<'_, $type, D>> for Tensor
         where
             D: ndarray::Dimension,
         {
             type Error = TchError;
 
-            fn try_from(value: &ndarray::Array<$type, D>) -> Result<Self, Self::Error> {
-                // TODO: Replace this with `?` once it works with `std::option::ErrorNone`
-                let slice = match value.as_slice() {
-                    None => return Err(TchError::Convert("cannot convert to slice".to_string())),
-                    Some(v) => v,
+            fn try_from(value: ndarray::ArrayView<'_, $type, D>) -> Result<Self, Self::Error> {
+                let tn = match value.as_slice() {
+                    Some(slice) => Self::f_of_slice(slice)?,
+                    None => Self::f_of_slice(&value.iter().cloned().collect::<Vec<_>>())?,
                 };
-                let tn = Self::f_of_slice(slice)?;
                 let shape: Vec<i64> = value.shape().iter().map(|s| *s as i64).collect();
                 Ok(tn.f_reshape(&shape)?)
             }
         }
 
+        #[cfg(feature = "ndarray")]
+        impl<D> TryFrom<&ndarray::Array<$type, D>> for Tensor
+        where
+            D: ndarray::Dimension,
+        {
+            type Error = TchError;
+
+            fn try_from(value: &ndarray::Array<$type, D>) -> Result<Self, Self::Error> {
+                Self::try_from(value.view())
+            }
+        }
+
         #[cfg(feature = "ndarray")]
         impl<D> TryFrom<ndarray::Array<$type, D>> for Tensor
         where
@@ -722,21 +787,27 @@ macro_rules! try_from_impl {
         }
 
         #[cfg(feature = "ndarray")]
-        impl TryFrom<&Tensor> for ndarray::ArrayD<$type> {
+        impl<D> TryFrom<&Tensor> for ndarray::Array<$type, D>
+        where
+            D: ndarray::Dimension,
+        {
             type Error = ndarray::ShapeError;
 
-            fn try_from(from: &Tensor) -> Result<ndarray::ArrayD<$type>, Self::Error> {
+            fn try_from(from: &Tensor) -> Result<ndarray::Array<$type, D>, Self::Error> {
                 let v: Vec<$type> = from.into();
                 let shape: Vec<usize> = from.size().iter().map(|s| *s as usize).collect();
-                ndarray::ArrayD::from_shape_vec(ndarray::IxDyn(&shape), v)
+                ndarray::ArrayD::from_shape_vec(ndarray::IxDyn(&shape), v)?.into_dimensionality()
             }
         }
 
         #[cfg(feature = "ndarray")]
-        impl TryFrom<Tensor> for ndarray::ArrayD<$type> {
+        impl<D> TryFrom<Tensor> for ndarray::Array<$type, D>
+        where
+            D: ndarray::Dimension,
+        {
             type Error = ndarray::ShapeError;
 
-            fn try_from(from: Tensor) -> Result<ndarray::ArrayD<$type>, Self::Error> {
+            fn try_from(from: Tensor) -> Result<ndarray::Array<$type, D>, Self::Error> {
                 Self::try_from(&from)
             }
         }
@@ -760,12 +831,195 @@ macro_rules! try_from_impl {
     };
 }
 
+try_from_impl!(u8);
+try_from_impl!(i8);
+try_from_impl!(i16);
+try_from_impl!(i32);
+try_from_impl!(i64);
 try_from_impl!(f16);
+try_from_impl!(bf16);
 try_from_impl!(f32);
-try_from_impl!(i32);
 try_from_impl!(f64);
-try_from_impl!(i64);
 try_from_impl!(bool);
+try_from_impl!(Complex32);
+try_from_impl!(Complex64);
+
+#[cfg(feature = "ndarray")]
+fn ndarray_dim<D: ndarray::Dimension>(dims: &[i64]) -> Result<D, TchError> {
+    let dims: Vec<usize> = dims.iter().map(|&d| d as usize).collect();
+    D::from_dimension(&ndarray::IxDyn(&dims)).ok_or_else(|| {
+        TchError::Shape(format!(
+            "cannot use {} dimensions for a tensor of shape {:?}",
+            D::NDIM.map_or("a dynamic number of".to_string(), |n| n.to_string()),
+            dims
+        ))
+    })
+}
+
+#[cfg(feature = "ndarray")]
+impl Tensor {
+    fn f_ndarray_shape<T: crate::kind::Element, D: ndarray::Dimension>(
+        &self,
+    ) -> Result<(ndarray::StrideShape<D>, *mut T), TchError> {
+        use ndarray::ShapeBuilder;
+        self.f_check_cpu_kind::<T>()?;
+        let size = self.size();
+        let stride = self.stride();
+        // Each element has to be reachable from a single index, this excludes
+        // the zero and overlapping strides produced by expand or as_strided.
+        if size.iter().all(|&s| s > 0) {
+            let mut dims: Vec<(i64, i64)> = size
+                .iter()
+                .zip(stride.iter())
+                .filter(|(&s, _)| s > 1)
+                .map(|(&s, &st)| (s, st))
+                .collect();
+            dims.sort_by_key(|&(_, st)| st);
+            let mut extent = 0;
+            for &(s, st) in dims.iter() {
+                if st <= extent {
+                    return Err(TchError::Shape(format!(
+                        "overlapping strides {:?} for shape {:?}",
+                        stride, size
+                    )));
+                }
+                extent += (s - 1) * st
+            }
+        }
+        let shape = ndarray_dim::<D>(&size)?.strides(ndarray_dim::<D>(&stride)?);
+        let ptr = if self.numel() == 0 {
+            std::ptr::NonNull::dangling().as_ptr()
+        } else {
+            self.data_ptr() as *mut T
+        };
+        Ok((shape, ptr))
+    }
+
+    /// Returns an ndarray view over the elements of a CPU tensor, the view
+    /// uses the same strides as the tensor. An error is returned for tensors
+    /// with zero or overlapping strides, e.g. the ones produced by `expand`.
+    ///
+    /// # Safety
+    ///
+    /// The storage of this tensor is shared with the tensors obtained via
+    /// `shallow_clone`, views, etc. None of these tensors may be modified
+    /// while the returned view is alive.
+    pub unsafe fn f_to_ndarray_view<T: crate::kind::Element, D: ndarray::Dimension>(
+        &self,
+    ) -> Result<ndarray::ArrayView<'_, T, D>, TchError> {
+        let (shape, ptr) = self.f_ndarray_shape::<T, D>()?;
+        Ok(ndarray::ArrayView::from_shape_ptr(shape, ptr))
+    }
+
+    /// Returns an ndarray view over the elements of a CPU tensor, the view
+    /// uses the same strides as the tensor.
+    ///
+    /// # Safety
+    ///
+    /// See `f_to_ndarray_view`.
+    pub unsafe fn to_ndarray_view<T: crate::kind::Element, D: ndarray::Dimension>(
+        &self,
+    ) -> ndarray::ArrayView<'_, T, D> {
+        self.f_to_ndarray_view().unwrap()
+    }
+
+    /// Returns a mutable ndarray view over the elements of a CPU tensor, the
+    /// view uses the same strides as the tensor. An error is returned for
+    /// tensors with zero or overlapping strides, e.g. the ones produced by
+    /// `expand`.
+    ///
+    /// # Safety
+    ///
+    /// The storage of this tensor is shared with the tensors obtained via
+    /// `shallow_clone`, views, etc. None of these tensors may be accessed
+    /// while the returned view is alive.
+    pub unsafe fn f_to_ndarray_view_mut<T: crate::kind::Element, D: ndarray::Dimension>(
+        &mut self,
+    ) -> Result<ndarray::ArrayViewMut<'_, T, D>, TchError> {
+        let (shape, ptr) = self.f_ndarray_shape::<T, D>()?;
+        Ok(ndarray::ArrayViewMut::from_shape_ptr(shape, ptr))
+    }
+
+    /// Returns a mutable ndarray view over the elements of a CPU tensor, the
+    /// view uses the same strides as the tensor.
+    ///
+    /// # Safety
+    ///
+    /// See `f_to_ndarray_view_mut`.
+    pub unsafe fn to_ndarray_view_mut<T: crate::kind::Element, D: ndarray::Dimension>(
+        &mut self,
+    ) -> ndarray::ArrayViewMut<'_, T, D> {
+        self.f_to_ndarray_view_mut().unwrap()
+    }
+
+    unsafe fn f_borrow_ndarray<'a, T: crate::kind::Element>(
+        ptr: *mut T,
+        shape: &[usize],
+        strides: &[isize],
+    ) -> Result<BorrowedTensor<'a>, TchError> {
+        if strides.iter().any(|&s| s < 0) {
+            return Err(TchError::Convert(format!(
+                "negative strides {:?} are not supported",
+                strides
+            )));
+        }
+        let shape: Vec<i64> = shape.iter().map(|&s| s as i64).collect();
+        let strides: Vec<i64> = strides.iter().map(|&s| s as i64).collect();
+        Tensor::f_borrow_raw(ptr, &shape, &strides)
+    }
+
+    /// Creates a tensor sharing memory with a mutable ndarray view, the view
+    /// does not have to be contiguous.
+    ///
+    /// # Safety
+    ///
+    /// The returned tensor and all the tensors sharing its storage, e.g. the
+    /// ones obtained via `shallow_clone` or views, must be dropped before the
+    /// end of lifetime `'a`.
+    pub unsafe fn f_from_ndarray_view_mut<'a, T: crate::kind::Element, D: ndarray::Dimension>(
+        mut view: ndarray::ArrayViewMut<'a, T, D>,
+    ) -> Result<BorrowedTensor<'a>, TchError> {
+        let strides = view.strides().to_vec();
+        Self::f_borrow_ndarray(view.as_mut_ptr(), view.shape(), &strides)
+    }
+
+    /// Creates a tensor sharing memory with a mutable ndarray view, the view
+    /// does not have to be contiguous.
+    ///
+    /// # Safety
+    ///
+    /// See `f_from_ndarray_view_mut`.
+    pub unsafe fn from_ndarray_view_mut<'a, T: crate::kind::Element, D: ndarray::Dimension>(
+        view: ndarray::ArrayViewMut<'a, T, D>,
+    ) -> BorrowedTensor<'a> {
+        Self::f_from_ndarray_view_mut(view).unwrap()
+    }
+
+    /// Creates a tensor sharing memory with an ndarray view, the view does not
+    /// have to be contiguous. Use `Tensor::try_from` to get a copy instead.
+    ///
+    /// # Safety
+    ///
+    /// The resulting tensor and the tensors sharing its storage must not be
+    /// modified, and must be dropped before the end of lifetime `'a`.
+    pub unsafe fn f_from_ndarray_view<'a, T: crate::kind::Element, D: ndarray::Dimension>(
+        view: ndarray::ArrayView<'a, T, D>,
+    ) -> Result<BorrowedTensor<'a>, TchError> {
+        Self::f_borrow_ndarray(view.as_ptr() as *mut T, view.shape(), view.strides())
+    }
+
+    /// Creates a tensor sharing memory with an ndarray view, the view does not
+    /// have to be contiguous. Use `Tensor::try_from` to get a copy instead.
+    ///
+    /// # Safety
+    ///
+    /// See `f_from_ndarray_view`.
+    pub unsafe fn from_ndarray_view<'a, T: crate::kind::Element, D: ndarray::Dimension>(
+        view: ndarray::ArrayView<'a, T, D>,
+    ) -> BorrowedTensor<'a> {
+        Self::f_from_ndarray_view(view).unwrap()
+    }
+}
 
 #[used]
 static INIT_ARRAY: [unsafe extern "C" fn(); 1] = [dummy_cuda_dependency];
@@ -5,7 +5,7 @@
 use crate::{Kind, TchError, Tensor};
 use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufReader, Read, Write};
+use std::io::{BufReader, Read, Seek, SeekFrom, Write};
 use std::path::Path;
 
 const NPY_MAGIC_STRING: &[u8] = b"\x93NUMPY";
@@ -40,9 +40,92 @@ fn read_header<R: Read>(buf_reader: &mut BufReader<R>) -> Result<String, TchErro
     Ok(String::from_utf8_lossy(&header).to_string())
 }
 
+// The type of the elements stored in a npy file. Unsigned integers wider
+// than a byte have no corresponding kind, they are converted to the next
+// wider signed integer kind when loaded.
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Descr {
+    Kind(Kind),
+    Uint16,
+    Uint32,
+    Uint64,
+}
+
+impl Descr {
+    fn parse(descr: &str) -> Result<Descr, TchError> {
+        let descr = match descr {
+            "f2" => Descr::Kind(Kind::Half),
+            "f4" => Descr::Kind(Kind::Float),
+            "f8" => Descr::Kind(Kind::Double),
+            "i4" => Descr::Kind(Kind::Int),
+            "i8" => Descr::Kind(Kind::Int64),
+            "i2" => Descr::Kind(Kind::Int16),
+            "i1" => Descr::Kind(Kind::Int8),
+            "u1" => Descr::Kind(Kind::Uint8),
+            "b1" => Descr::Kind(Kind::Bool),
+            "c8" => Descr::Kind(Kind::ComplexFloat),
+            "c16" => Descr::Kind(Kind::ComplexDouble),
+            "u2" => Descr::Uint16,
+            "u4" => Descr::Uint32,
+            "u8" => Descr::Uint64,
+            descr => {
+                return Err(TchError::FileFormat(format!(
+                    "unrecognized descr {}",
+                    descr
+                )))
+            }
+        };
+        Ok(descr)
+    }
+
+    fn to_str(&self) -> Result<&'static str, TchError> {
+        let descr = match self {
+            Descr::Kind(Kind::Half) => "f2",
+            Descr::Kind(Kind::Float) => "f4",
+            Descr::Kind(Kind::Double) => "f8",
+            Descr::Kind(Kind::Int) => "i4",
+            Descr::Kind(Kind::Int64) => "i8",
+            Descr::Kind(Kind::Int16) => "i2",
+            Descr::Kind(Kind::Int8) => "i1",
+            Descr::Kind(Kind::Uint8) => "u1",
+            Descr::Kind(Kind::Bool) => "b1",
+            Descr::Kind(Kind::ComplexFloat) => "c8",
+            Descr::Kind(Kind::ComplexDouble) => "c16",
+            Descr::Uint16 => "u2",
+            Descr::Uint32 => "u4",
+            Descr::Uint64 => "u8",
+            Descr::Kind(kind) => {
+                return Err(TchError::FileFormat(format!("unsupported kind {:?}", kind)))
+            }
+        };
+        Ok(descr)
+    }
+
+    fn elt_size_in_bytes(&self) -> usize {
+        match self {
+            Descr::Kind(kind) => kind.elt_size_in_bytes(),
+            Descr::Uint16 => 2,
+            Descr::Uint32 => 4,
+            Descr::Uint64 => 8,
+        }
+    }
+
+    // The size of the values that have to be byte-swapped, complex numbers
+    // are pairs of floats.
+    fn swap_size_in_bytes(&self) -> usize {
+        match self {
+            Descr::Kind(Kind::ComplexFloat) | Descr::Kind(Kind::ComplexDouble) => {
+                self.elt_size_in_bytes() / 2
+            }
+            _ => self.elt_size_in_bytes(),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq)]
 struct Header {
-    descr: Kind,
+    descr: Descr,
+    big_endian: bool,
     fortran_order: bool,
     shape: Vec<i64>,
 }
@@ -56,31 +139,64 @@ impl Header {
             .map(|x| x.to_string())
             .collect::<Vec<_>>()
             .join(",");
-        let descr = match self.descr {
-            Kind::Half => "f2",
-            Kind::Float => "f4",
-            Kind::Double => "f8",
-            Kind::Int => "i4",
-            Kind::Int64 => "i8",
-            Kind::Int16 => "i2",
-            Kind::Int8 => "i1",
-            Kind::Uint8 => "u1",
-            descr => {
-                return Err(TchError::FileFormat(format!(
-                    "unsupported kind {:?}",
-                    descr
-                )))
-            }
+        let descr = self.descr.to_str()?;
+        let byte_order = if self.descr.elt_size_in_bytes() == 1 {
+            '|'
+        } else if self.big_endian {
+            '>'
+        } else {
+            '<'
         };
         if !shape.is_empty() {
             shape.push(',')
         }
         Ok(format!(
-            "{{'descr': '<{}', 'fortran_order': {}, 'shape': ({}), }}",
-            descr, fortran_order, shape
+            "{{'descr': '{}{}', 'fortran_order': {}, 'shape': ({}), }}",
+            byte_order, descr, fortran_order, shape
         ))
     }
 
+    // The number of bytes used by the data, an error is returned if this
+    // does not fit in memory.
+    fn data_len(&self) -> Result<usize, TchError> {
+        self.shape
+            .iter()
+            .try_fold(self.descr.elt_size_in_bytes(), |acc, &d| {
+                acc.checked_mul(d as usize)
+            })
+            .ok_or_else(|| {
+                TchError::FileFormat(format!(
+                    "data too large for a shape {:?} and descr {:?}",
+                    self.shape, self.descr
+                ))
+            })
+    }
+
+    // The shape of the data as stored in the file, i.e. reversed for fortran
+    // order.
+    fn storage_shape(&self) -> Vec<i64> {
+        if self.fortran_order {
+            self.shape.iter().rev().cloned().collect()
+        } else {
+            self.shape.clone()
+        }
+    }
+
+    // Converts a tensor using the storage shape to a tensor using the
+    // header shape.
+    fn transpose(&self, tensor: Tensor) -> Result<Tensor, TchError> {
+        if self.fortran_order {
+            let dims = (0..self.shape.len() as i64).rev().collect::<Vec<_>>();
+            tensor.f_permute(&dims)
+        } else {
+            Ok(tensor)
+        }
+    }
+
+    fn native_endian(&self) -> bool {
+        self.descr.elt_size_in_bytes() == 1 || self.big_endian == cfg!(target_endian = "big")
+    }
+
     // Hacky parser for the npy header, a typical example would be:
     // {'descr': '<f8', 'fortran_order': False, 'shape': (128,), }
     fn parse(header: &str) -> Result<Header, TchError> {
@@ -136,34 +252,20 @@ impl Header {
                 }
             },
         };
-        let descr = match part_map.get("descr") {
+        let (descr, big_endian) = match part_map.get("descr") {
             None => return Err(TchError::FileFormat("no descr in header".to_string())),
             Some(descr) => {
                 if descr.is_empty() {
                     return Err(TchError::FileFormat("empty descr".to_string()));
                 }
-                if descr.starts_with('>') {
-                    return Err(TchError::FileFormat(format!(
-                        "little-endian descr {}",
-                        descr
-                    )));
-                }
-                match descr.trim_matches(|c: char| c == '=' || c == '<' || c == '|') {
-                    "f2" => Kind::Half,
-                    "f4" => Kind::Float,
-                    "f8" => Kind::Double,
-                    "i4" => Kind::Int,
-                    "i8" => Kind::Int64,
-                    "i2" => Kind::Int16,
-                    "i1" => Kind::Int8,
-                    "u1" => Kind::Uint8,
-                    descr => {
-                        return Err(TchError::FileFormat(format!(
-                            "unrecognized descr {}",
-                            descr
-                        )))
-                    }
-                }
+                let big_endian = match descr.chars().next() {
+                    Some('>') => true,
+                    Some('<') => false,
+                    _ => cfg!(target_endian = "big"),
+                };
+                let descr =
+                    descr.trim_matches(|c: char| c == '=' || c == '<' || c == '>' || c == '|');
+                (Descr::parse(descr)?, big_endian)
             }
         };
         let shape = match part_map.get("shape") {
@@ -180,28 +282,140 @@ impl Header {
                 }
             }
         };
+        if shape.iter().any(|&d| d < 0) {
+            return Err(TchError::FileFormat(format!(
+                "negative dimension in shape {:?}",
+                shape
+            )));
+        }
         Ok(Header {
             descr,
+            big_endian,
             fortran_order,
             shape,
         })
     }
 }
 
+// Converts the raw data of a npy file to a tensor.
+fn tensor_of_data(header: &Header, mut data: Vec<u8>) -> Result<Tensor, TchError> {
+    let len = header.data_len()?;
+    if data.len() < len {
+        return Err(TchError::FileFormat(format!(
+            "{} bytes of data for a shape {:?} and descr {:?}",
+            data.len(),
+            header.shape,
+            header.descr
+        )));
+    }
+    data.truncate(len);
+    if !header.native_endian() {
+        for elt in data.chunks_mut(header.descr.swap_size_in_bytes()) {
+            elt.reverse()
+        }
+    }
+    let shape = header.storage_shape();
+    let tensor = match header.descr {
+        Descr::Kind(kind) => Tensor::f_of_data_size(&data, &shape, kind)?,
+        Descr::Uint16 => {
+            let data = data
+                .chunks_exact(2)
+                .map(|v| u16::from_ne_bytes([v[0], v[1]]) as i32)
+                .collect::<Vec<_>>();
+            Tensor::f_from_vec(data, &shape)?
+        }
+        Descr::Uint32 => {
+            let data = data
+                .chunks_exact(4)
+                .map(|v| u32::from_ne_bytes([v[0], v[1], v[2], v[3]]) as i64)
+                .collect::<Vec<_>>();
+            Tensor::f_from_vec(data, &shape)?
+        }
+        Descr::Uint64 => {
+            let data = data
+                .chunks_exact(8)
+                .map(|v| {
+                    let v = u64::from_ne_bytes([v[0], v[1], v[2], v[3], v[4], v[5], v[6], v[7]]);
+                    if v > i64::MAX as u64 {
+                        Err(TchError::Convert(format!("{} does not fit in an int64", v)))
+                    } else {
+                        Ok(v as i64)
+                    }
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Tensor::f_from_vec(data, &shape)?
+        }
+    };
+    header.transpose(tensor)?.f_contiguous()
+}
+
 impl crate::Tensor {
     /// Reads a npy file and return the stored tensor.
     pub fn read_npy<T: AsRef<Path>>(path: T) -> Result<Tensor, TchError> {
-        let mut buf_reader = BufReader::new(File::open(path.as_ref())?);
+        Tensor::read_npy_from_stream(File::open(path.as_ref())?)
+    }
+
+    /// Reads a tensor in the npy format from a stream.
+    pub fn read_npy_from_stream<R: Read>(stream: R) -> Result<Tensor, TchError> {
+        let mut buf_reader = BufReader::new(stream);
         let header = read_header(&mut buf_reader)?;
         let header = Header::parse(&header)?;
-        if header.fortran_order {
-            return Err(TchError::FileFormat(
-                "fortran order not supported".to_string(),
-            ));
-        }
         let mut data: Vec<u8> = vec![];
         buf_reader.read_to_end(&mut data)?;
-        Tensor::f_of_data_size(&data, &header.shape, header.descr)
+        tensor_of_data(&header, data)
+    }
+
+    /// Reads a npy file by memory-mapping it rather than loading it in memory.
+    ///
+    /// The returned tensor uses a copy-on-write mapping of the file: modifying
+    /// it does not change the file. Fortran ordered arrays result in
+    /// non-contiguous tensors. Files that cannot be mapped directly, e.g.
+    /// because they use a non-native byte order or an unsigned type wider
+    /// than a byte, are loaded in memory instead.
+    pub fn read_npy_mmap<T: AsRef<Path>>(path: T) -> Result<Tensor, TchError> {
+        let file = File::open(path.as_ref())?;
+        let mut buf_reader = BufReader::new(&file);
+        let header = read_header(&mut buf_reader)?;
+        let header = Header::parse(&header)?;
+        let offset = buf_reader.seek(SeekFrom::Current(0))?;
+        let len = header.data_len()?;
+        let kind = match header.descr {
+            Descr::Kind(kind) if header.native_endian() && len > 0 => kind,
+            _ => {
+                let mut data: Vec<u8> = vec![];
+                buf_reader.read_to_end(&mut data)?;
+                return tensor_of_data(&header, data);
+            }
+        };
+        let file_len = file.metadata()?.len();
+        if file_len < offset + len as u64 {
+            return Err(TchError::FileFormat(format!(
+                "{} bytes of data for a shape {:?} and descr {:?}",
+                file_len.saturating_sub(offset),
+                header.shape,
+                header.descr
+            )));
+        }
+        let mut mmap = unsafe {
+            memmap2::MmapOptions::new()
+                .offset(offset)
+                .len(len)
+                .map_copy(&file)?
+        };
+        let ptr = mmap.as_mut_ptr();
+        if ptr as usize % kind.elt_size_in_bytes() != 0 {
+            let data = mmap.to_vec();
+            return tensor_of_data(&header, data);
+        }
+        let tensor = unsafe {
+            Tensor::f_of_owned_blob(
+                mmap,
+                ptr as *mut libc::c_void,
+                &header.storage_shape(),
+                kind,
+            )?
+        };
+        header.transpose(tensor)
     }
 
     /// Reads a npz file and returns some named tensors.
@@ -210,33 +424,25 @@ impl crate::Tensor {
         let mut zip = zip::ZipArchive::new(zip_reader)?;
         let mut result = vec![];
         for i in 0..zip.len() {
-            let file = zip.by_index(i).unwrap();
+            let file = zip.by_index(i)?;
             let name = {
                 let name = file.name();
                 name.strip_suffix(NPY_SUFFIX).unwrap_or(name).to_owned()
             };
-            let mut buf_reader = BufReader::new(file);
-            let header = read_header(&mut buf_reader)?;
-            let header = Header::parse(&header)?;
-            if header.fortran_order {
-                return Err(TchError::FileFormat(
-                    "fortran order not supported".to_string(),
-                ));
-            }
-            let mut data: Vec<u8> = vec![];
-            buf_reader.read_to_end(&mut data)?;
-            let tensor = Tensor::f_of_data_size(&data, &header.shape, header.descr)?;
+            let tensor = Tensor::read_npy_from_stream(file)?;
             result.push((name, tensor))
         }
         Ok(result)
     }
 
-    fn write<T: Write>(&self, f: &mut T) -> Result<(), TchError> {
+    /// Writes a tensor in the npy format to a stream.
+    pub fn write_npy_to_stream<T: Write>(&self, f: &mut T) -> Result<(), TchError> {
         f.write_all(NPY_MAGIC_STRING)?;
         f.write_all(&[1u8, 0u8])?;
         let kind = self.f_kind()?;
         let header = Header {
-            descr: kind,
+            descr: Descr::Kind(kind),
+            big_endian: cfg!(target_endian = "big"),
             fortran_order: false,
             shape: self.size(),
         };
@@ -258,28 +464,46 @@ impl crate::Tensor {
     /// Writes a tensor in the npy format so that it can be read using python.
     pub fn write_npy<T: AsRef<Path>>(&self, path: T) -> Result<(), TchError> {
         let mut f = File::create(path.as_ref())?;
-        self.write(&mut f)
+        self.write_npy_to_stream(&mut f)
     }
 
-    pub fn write_npz<S: AsRef<str>, T: AsRef<Tensor>, P: AsRef<Path>>(
+    fn write_npz_with_compression<S: AsRef<str>, T: AsRef<Tensor>, P: AsRef<Path>>(
         ts: &[(S, T)],
         path: P,
+        compression_method: zip::CompressionMethod,
     ) -> Result<(), TchError> {
         let mut zip = zip::ZipWriter::new(File::create(path.as_ref())?);
-        let options =
-            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        let options = zip::write::FileOptions::default().compression_method(compression_method);
 
         for (name, tensor) in ts.iter() {
             zip.start_file(format!("{}.npy", name.as_ref()), options)?;
-            tensor.as_ref().write(&mut zip)?
+            tensor.as_ref().write_npy_to_stream(&mut zip)?
         }
+        zip.finish()?;
         Ok(())
     }
+
+    /// Writes some named tensors in the npz format, similar to `numpy.savez`.
+    pub fn write_npz<S: AsRef<str>, T: AsRef<Tensor>, P: AsRef<Path>>(
+        ts: &[(S, T)],
+        path: P,
+    ) -> Result<(), TchError> {
+        Tensor::write_npz_with_compression(ts, path, zip::CompressionMethod::Stored)
+    }
+
+    /// Writes some named tensors in a deflate-compressed npz file, similar to
+    /// `numpy.savez_compressed`.
+    pub fn write_npz_compressed<S: AsRef<str>, T: AsRef<Tensor>, P: AsRef<Path>>(
+        ts: &[(S, T)],
+        path: P,
+    ) -> Result<(), TchError> {
+        Tensor::write_npz_with_compression(ts, path, zip::CompressionMethod::Deflated)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::Header;
+    use super::{Descr, Header};
 
     #[test]
     fn parse() {
@@ -287,7 +511,8 @@ mod tests {
         assert_eq!(
             Header::parse(h).unwrap(),
             Header {
-                descr: crate::Kind::Double,
+                descr: Descr::Kind(crate::Kind::Double),
+                big_endian: false,
                 fortran_order: false,
                 shape: vec![128]
             }
@@ -297,7 +522,8 @@ mod tests {
         assert_eq!(
             h,
             Header {
-                descr: crate::Kind::Float,
+                descr: Descr::Kind(crate::Kind::Float),
+                big_endian: false,
                 fortran_order: true,
                 shape: vec![256, 1, 128]
             }
@@ -308,7 +534,8 @@ mod tests {
         );
 
         let h = Header {
-            descr: crate::Kind::Int64,
+            descr: Descr::Kind(crate::Kind::Int64),
+            big_endian: false,
             fortran_order: false,
             shape: vec![],
         };
@@ -316,5 +543,27 @@ mod tests {
             h.to_string().unwrap(),
             "{'descr': '<i8', 'fortran_order': False, 'shape': (), }"
         );
+
+        let h = "{'descr': '<c16', 'fortran_order': False, 'shape': (3,), }";
+        let h = Header::parse(h).unwrap();
+        assert_eq!(h.descr, Descr::Kind(crate::Kind::ComplexDouble));
+        assert_eq!(
+            h.to_string().unwrap(),
+            "{'descr': '<c16', 'fortran_order': False, 'shape': (3,), }"
+        );
+
+        let h = "{'descr': '>u4', 'fortran_order': False, 'shape': (2, 3), }";
+        let h = Header::parse(h).unwrap();
+        assert_eq!(h.descr, Descr::Uint32);
+        assert!(h.big_endian);
+        assert_eq!(h.shape, vec![2, 3]);
+
+        let h = "{'descr': '|b1', 'fortran_order': False, 'shape': (4,), }";
+        let h = Header::parse(h).unwrap();
+        assert_eq!(h.descr, Descr::Kind(crate::Kind::Bool));
+        assert_eq!(
+            h.to_string().unwrap(),
+            "{'descr': '|b1', 'fortran_order': False, 'shape': (4,), }"
+        );
     }
 }
@@ -0,0 +1,309 @@
+//! Serde support for tensors.
+//!
+//! Tensors are serialized as their kind, their shape and their data. For
+//! human-readable formats such as JSON the data is represented as nested
+//! arrays, complex values use an additional dimension of size 2 for the real
+//! and imaginary parts. For binary formats the data is stored as raw
+//! little-endian bytes. Tensors are always deserialized on the CPU and the
+//! gradient information is not preserved.
+use crate::{Kind, TchError, Tensor};
+use serde::de::{self, Deserialize, Deserializer, SeqAccess, Visitor};
+use serde::ser::{self, Serialize, SerializeSeq, Serializer};
+
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(rename = "Tensor")]
+struct TensorRepr<D> {
+    kind: Kind,
+    shape: Vec<i64>,
+    data: D,
+}
+
+enum Category {
+    Int,
+    Float,
+    Bool,
+    Complex,
+}
+
+fn category(kind: Kind) -> Result<Category, TchError> {
+    match kind {
+        Kind::Uint8 | Kind::Int8 | Kind::Int16 | Kind::Int | Kind::Int64 => Ok(Category::Int),
+        Kind::Half | Kind::Float | Kind::Double | Kind::BFloat16 => Ok(Category::Float),
+        Kind::Bool => Ok(Category::Bool),
+        Kind::ComplexHalf | Kind::ComplexFloat | Kind::ComplexDouble => Ok(Category::Complex),
+        Kind::QInt8 | Kind::QUInt8 | Kind::QInt32 => Err(TchError::Kind(format!(
+            "serialization is not supported for {:?}",
+            kind
+        ))),
+    }
+}
+
+// Some flattened data serialized as nested arrays.
+struct Nested<'a, T> {
+    shape: &'a [i64],
+    data: &'a [T],
+}
+
+impl<'a, T: Serialize> Serialize for Nested<'a, T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.shape.split_first() {
+            None => self.data[0].serialize(serializer),
+            Some((&dim, shape)) => {
+                let mut seq = serializer.serialize_seq(Some(dim as usize))?;
+                let chunk_len = self.data.len() / (dim as usize).max(1);
+                for i in 0..dim as usize {
+                    let data = &self.data[i * chunk_len..(i + 1) * chunk_len];
+                    seq.serialize_element(&Nested { shape, data })?
+                }
+                seq.end()
+            }
+        }
+    }
+}
+
+struct Bytes(Vec<u8>);
+
+impl Serialize for Bytes {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_bytes(&self.0)
+    }
+}
+
+impl<'de> Deserialize<'de> for Bytes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BytesVisitor;
+
+        impl<'de> Visitor<'de> for BytesVisitor {
+            type Value = Bytes;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a byte array")
+            }
+
+            fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Bytes, E> {
+                Ok(Bytes(v.to_vec()))
+            }
+
+            fn visit_byte_buf<E: de::Error>(self, v: Vec<u8>) -> Result<Bytes, E> {
+                Ok(Bytes(v))
+            }
+
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Bytes, A::Error> {
+                let mut v = Vec::with_capacity(seq.size_hint().unwrap_or(0));
+                while let Some(b) = seq.next_element()? {
+                    v.push(b)
+                }
+                Ok(Bytes(v))
+            }
+        }
+
+        deserializer.deserialize_byte_buf(BytesVisitor)
+    }
+}
+
+// Swaps the bytes of each element on big-endian platforms so that the
+// serialized data is always little-endian.
+fn to_or_from_le(data: &mut [u8], kind: Kind) {
+    if cfg!(target_endian = "big") {
+        let elt_size = match kind {
+            // Complex values are pairs of floats.
+            Kind::ComplexHalf | Kind::ComplexFloat | Kind::ComplexDouble => {
+                kind.elt_size_in_bytes() / 2
+            }
+            _ => kind.elt_size_in_bytes(),
+        };
+        for elt in data.chunks_mut(elt_size) {
+            elt.reverse()
+        }
+    }
+}
+
+enum FlatData {
+    Int(Vec<i64>),
+    Float(Vec<f64>),
+    Bool(Vec<bool>),
+}
+
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum Value {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    List(Vec<Value>),
+}
+
+impl Value {
+    fn flatten(self, dst: &mut Vec<Value>) {
+        match self {
+            Value::List(vs) => vs.into_iter().for_each(|v| v.flatten(dst)),
+            v => dst.push(v),
+        }
+    }
+}
+
+impl Tensor {
+    // Returns the shape of the nested arrays together with the flattened data.
+    fn f_flat_data(&self) -> Result<(Vec<i64>, FlatData), TchError> {
+        let mut shape = self.size();
+        let data = match category(self.f_kind()?)? {
+            Category::Int => FlatData::Int(Vec::<i64>::from(self)),
+            Category::Float => FlatData::Float(Vec::<f64>::from(self)),
+            Category::Bool => FlatData::Bool(Vec::<bool>::from(self)),
+            Category::Complex => {
+                let data = self
+                    .f_to_kind(Kind::ComplexDouble)?
+                    .f_view_as_real()?
+                    .f_contiguous()?;
+                shape.push(2);
+                FlatData::Float(Vec::<f64>::from(&data))
+            }
+        };
+        Ok((shape, data))
+    }
+
+    fn f_raw_bytes(&self) -> Result<Vec<u8>, TchError> {
+        let kind = self.f_kind()?;
+        category(kind)?;
+        let tensor = self.f_to_device(crate::Device::Cpu)?.f_contiguous()?;
+        let numel = tensor.numel();
+        let mut data = vec![0u8; numel * kind.elt_size_in_bytes()];
+        tensor.f_copy_data_u8(&mut data, numel)?;
+        to_or_from_le(&mut data, kind);
+        Ok(data)
+    }
+
+    fn f_of_nested(repr: TensorRepr<Value>) -> Result<Tensor, TchError> {
+        let TensorRepr { kind, shape, data } = repr;
+        let mut values = vec![];
+        data.flatten(&mut values);
+        let mut expected_len = shape.iter().product::<i64>() as usize;
+        let category = category(kind)?;
+        if let Category::Complex = category {
+            expected_len *= 2
+        }
+        if values.len() != expected_len {
+            return Err(TchError::Shape(format!(
+                "{} values for a tensor of shape {:?}",
+                values.len(),
+                shape
+            )));
+        }
+        let unexpected = || TchError::Convert(format!("unexpected value for {:?}", kind));
+        let tensor = match category {
+            Category::Int => {
+                let values = values
+                    .iter()
+                    .map(|v| match v {
+                        Value::Int(v) => Ok(*v),
+                        _ => Err(unexpected()),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Tensor::f_of_slice(&values)?
+            }
+            Category::Bool => {
+                let values = values
+                    .iter()
+                    .map(|v| match v {
+                        Value::Bool(v) => Ok(*v),
+                        _ => Err(unexpected()),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                Tensor::f_of_slice(&values)?
+            }
+            Category::Float | Category::Complex => {
+                let values = values
+                    .iter()
+                    .map(|v| match v {
+                        Value::Int(v) => Ok(*v as f64),
+                        Value::Float(v) => Ok(*v),
+                        _ => Err(unexpected()),
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                let tensor = Tensor::f_of_slice(&values)?;
+                if let Category::Complex = category {
+                    tensor.f_view([-1, 2])?.f_view_as_complex()?
+                } else {
+                    tensor
+                }
+            }
+        };
+        tensor.f_to_kind(kind)?.f_reshape(&shape)
+    }
+
+    fn f_of_bytes(repr: TensorRepr<Bytes>) -> Result<Tensor, TchError> {
+        let TensorRepr { kind, shape, data } = repr;
+        category(kind)?;
+        let mut data = data.0;
+        let numel = shape.iter().product::<i64>() as usize;
+        if data.len() != numel * kind.elt_size_in_bytes() {
+            return Err(TchError::Shape(format!(
+                "{} bytes for a tensor of shape {:?} and kind {:?}",
+                data.len(),
+                shape,
+                kind
+            )));
+        }
+        to_or_from_le(&mut data, kind);
+        Tensor::f_of_data_size(&data, &shape, kind)
+    }
+}
+
+impl Serialize for Tensor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let kind = self.f_kind().map_err(ser::Error::custom)?;
+        let shape = self.size();
+        if serializer.is_human_readable() {
+            let (data_shape, data) = self.f_flat_data().map_err(ser::Error::custom)?;
+            let shape_ = &data_shape;
+            match &data {
+                FlatData::Int(data) => TensorRepr {
+                    kind,
+                    shape,
+                    data: Nested {
+                        shape: shape_,
+                        data,
+                    },
+                }
+                .serialize(serializer),
+                FlatData::Float(data) => TensorRepr {
+                    kind,
+                    shape,
+                    data: Nested {
+                        shape: shape_,
+                        data,
+                    },
+                }
+                .serialize(serializer),
+                FlatData::Bool(data) => TensorRepr {
+                    kind,
+                    shape,
+                    data: Nested {
+                        shape: shape_,
+                        data,
+                    },
+                }
+                .serialize(serializer),
+            }
+        } else {
+            let data = self.f_raw_bytes().map_err(ser::Error::custom)?;
+            TensorRepr {
+                kind,
+                shape,
+                data: Bytes(data),
+            }
+            .serialize(serializer)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Tensor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let result = if deserializer.is_human_readable() {
+            Tensor::f_of_nested(TensorRepr::deserialize(deserializer)?)
+        } else {
+            Tensor::f_of_bytes(TensorRepr::deserialize(deserializer)?)
+        };
+        result.map_err(de::Error::custom)
+    }
+}
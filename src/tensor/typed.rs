@@ -0,0 +1,429 @@
+//! Tensors with a statically known element type.
+//!
+//! A `TypedTensor<T>` wraps a `Tensor` whose kind is guaranteed to be
+//! `T::KIND`. The kind is checked once when converting from a `Tensor`, the
+//! operations available on typed tensors then return typed results so that
+//! mixing element types is caught at compile time.
+//!
+//! ```ignore
+//! use tch::{Device, Tensor, TypedTensor};
+//!
+//! let xs = TypedTensor::<f32>::of_slice(&[1.0, 2.0, 3.0]);
+//! let ys = (&xs * &xs).sum();
+//! let v: Vec<f32> = ys.to_vec();
+//! let mask: TypedTensor<bool> = xs.gt1(&ys);
+//! ```
+use crate::kind::{Element, FloatElement, NumericElement, SumElement};
+use crate::{Device, TchError, Tensor};
+use std::borrow::Borrow;
+use std::convert::TryFrom;
+use std::marker::PhantomData;
+use std::ops::{Add, Deref, Div, Mul, Neg, Sub};
+
+/// A tensor with elements of type `T`.
+#[must_use]
+pub struct TypedTensor<T: Element> {
+    tensor: Tensor,
+    _marker: PhantomData<T>,
+}
+
+macro_rules! typed_method {
+    ($f_name:ident, $name:ident, $elt:ty, ($($arg:ident: $ty:ty),*)) => {
+        pub fn $f_name(&self, $($arg: $ty),*) -> Result<TypedTensor<$elt>, TchError> {
+            Ok(TypedTensor::new_unchecked(self.tensor.$f_name($($arg),*)?))
+        }
+
+        pub fn $name(&self, $($arg: $ty),*) -> TypedTensor<$elt> {
+            self.$f_name($($arg),*).unwrap()
+        }
+    };
+}
+
+macro_rules! typed_binary_method {
+    ($f_name:ident, $name:ident, $elt:ty) => {
+        pub fn $f_name(&self, other: &TypedTensor<T>) -> Result<TypedTensor<$elt>, TchError> {
+            Ok(TypedTensor::new_unchecked(
+                self.tensor.$f_name(&other.tensor)?,
+            ))
+        }
+
+        pub fn $name(&self, other: &TypedTensor<T>) -> TypedTensor<$elt> {
+            self.$f_name(other).unwrap()
+        }
+    };
+}
+
+impl<T: Element> TypedTensor<T> {
+    // The caller has to ensure that the kind of `tensor` is `T::KIND`.
+    fn new_unchecked(tensor: Tensor) -> Self {
+        TypedTensor {
+            tensor,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Wraps a tensor, returns an error if its kind is not `T::KIND`.
+    pub fn f_new(tensor: Tensor) -> Result<Self, TchError> {
+        let kind = tensor.f_kind()?;
+        if kind != T::KIND {
+            return Err(TchError::Kind(format!(
+                "expected a tensor of kind {:?}, got {:?}",
+                T::KIND,
+                kind
+            )));
+        }
+        Ok(Self::new_unchecked(tensor))
+    }
+
+    /// Wraps a tensor, panics if its kind is not `T::KIND`.
+    pub fn new(tensor: Tensor) -> Self {
+        Self::f_new(tensor).unwrap()
+    }
+
+    /// Returns the underlying tensor.
+    pub fn as_tensor(&self) -> &Tensor {
+        &self.tensor
+    }
+
+    /// Returns the underlying tensor.
+    pub fn into_tensor(self) -> Tensor {
+        self.tensor
+    }
+
+    /// Converts a slice to a one dimension typed tensor.
+    pub fn f_of_slice(data: &[T]) -> Result<Self, TchError> {
+        Ok(Self::new_unchecked(Tensor::f_of_slice(data)?))
+    }
+
+    /// Converts a slice to a one dimension typed tensor.
+    pub fn of_slice(data: &[T]) -> Self {
+        Self::f_of_slice(data).unwrap()
+    }
+
+    /// Converts a vector to a typed tensor with the specified shape without
+    /// copying the data.
+    pub fn f_from_vec(data: Vec<T>, size: &[i64]) -> Result<Self, TchError>
+    where
+        T: Send + 'static,
+    {
+        Ok(Self::new_unchecked(Tensor::f_from_vec(data, size)?))
+    }
+
+    /// Converts a vector to a typed tensor with the specified shape without
+    /// copying the data.
+    pub fn from_vec(data: Vec<T>, size: &[i64]) -> Self
+    where
+        T: Send + 'static,
+    {
+        Self::f_from_vec(data, size).unwrap()
+    }
+
+    /// Creates a typed tensor filled with zeros.
+    pub fn f_zeros(size: &[i64], device: Device) -> Result<Self, TchError> {
+        Ok(Self::new_unchecked(Tensor::f_zeros(
+            size,
+            (T::KIND, device),
+        )?))
+    }
+
+    /// Creates a typed tensor filled with zeros.
+    pub fn zeros(size: &[i64], device: Device) -> Self {
+        Self::f_zeros(size, device).unwrap()
+    }
+
+    /// Creates a typed tensor filled with ones.
+    pub fn f_ones(size: &[i64], device: Device) -> Result<Self, TchError> {
+        Ok(Self::new_unchecked(Tensor::f_ones(
+            size,
+            (T::KIND, device),
+        )?))
+    }
+
+    /// Creates a typed tensor filled with ones.
+    pub fn ones(size: &[i64], device: Device) -> Self {
+        Self::f_ones(size, device).unwrap()
+    }
+
+    /// Concatenates some typed tensors along the given dimension.
+    pub fn f_cat<B: Borrow<TypedTensor<T>>>(tensors: &[B], dim: i64) -> Result<Self, TchError> {
+        let tensors = tensors
+            .iter()
+            .map(|t| &t.borrow().tensor)
+            .collect::<Vec<_>>();
+        Ok(Self::new_unchecked(Tensor::f_cat(&tensors, dim)?))
+    }
+
+    /// Concatenates some typed tensors along the given dimension.
+    pub fn cat<B: Borrow<TypedTensor<T>>>(tensors: &[B], dim: i64) -> Self {
+        Self::f_cat(tensors, dim).unwrap()
+    }
+
+    /// Stacks some typed tensors along a new dimension.
+    pub fn f_stack<B: Borrow<TypedTensor<T>>>(tensors: &[B], dim: i64) -> Result<Self, TchError> {
+        let tensors = tensors
+            .iter()
+            .map(|t| &t.borrow().tensor)
+            .collect::<Vec<_>>();
+        Ok(Self::new_unchecked(Tensor::f_stack(&tensors, dim)?))
+    }
+
+    /// Stacks some typed tensors along a new dimension.
+    pub fn stack<B: Borrow<TypedTensor<T>>>(tensors: &[B], dim: i64) -> Self {
+        Self::f_stack(tensors, dim).unwrap()
+    }
+
+    /// Returns a new typed tensor sharing its data with this one.
+    pub fn shallow_clone(&self) -> Self {
+        Self::new_unchecked(self.tensor.shallow_clone())
+    }
+
+    /// Converts the elements of the tensor to another type.
+    pub fn f_to_kind<U: Element>(&self) -> Result<TypedTensor<U>, TchError> {
+        Ok(TypedTensor::new_unchecked(self.tensor.f_to_kind(U::KIND)?))
+    }
+
+    /// Converts the elements of the tensor to another type.
+    pub fn to_kind<U: Element>(&self) -> TypedTensor<U> {
+        self.f_to_kind().unwrap()
+    }
+
+    /// Copies the elements of the tensor to a vector.
+    pub fn f_to_vec(&self) -> Result<Vec<T>, TchError>
+    where
+        T: Default + Clone,
+    {
+        let numel = self.tensor.numel();
+        let mut vec = vec![T::default(); numel];
+        self.tensor.f_copy_data(&mut vec, numel)?;
+        Ok(vec)
+    }
+
+    /// Copies the elements of the tensor to a vector.
+    pub fn to_vec(&self) -> Vec<T>
+    where
+        T: Default + Clone,
+    {
+        self.f_to_vec().unwrap()
+    }
+
+    /// Returns the elements of a contiguous CPU tensor as a slice.
+    ///
+    /// # Safety
+    ///
+    /// See `Tensor::f_as_slice`.
+    pub unsafe fn f_as_slice(&self) -> Result<&[T], TchError> {
+        self.tensor.f_as_slice()
+    }
+
+    /// Returns the elements of a contiguous CPU tensor as a slice.
+    ///
+    /// # Safety
+    ///
+    /// See `Tensor::f_as_slice`.
+    pub unsafe fn as_slice(&self) -> &[T] {
+        self.f_as_slice().unwrap()
+    }
+
+    typed_method!(f_to_device, to_device, T, (device: Device));
+    typed_method!(f_contiguous, contiguous, T, ());
+    typed_method!(f_detach, detach, T, ());
+    typed_method!(f_reshape, reshape, T, (shape: &[i64]));
+    typed_method!(f_view, view, T, (shape: &[i64]));
+    typed_method!(f_flatten, flatten, T, (start_dim: i64, end_dim: i64));
+    typed_method!(f_transpose, transpose, T, (dim0: i64, dim1: i64));
+    typed_method!(f_permute, permute, T, (dims: &[i64]));
+    typed_method!(f_unsqueeze, unsqueeze, T, (dim: i64));
+    typed_method!(f_squeeze1, squeeze1, T, (dim: i64));
+    typed_method!(f_narrow, narrow, T, (dim: i64, start: i64, length: i64));
+    typed_method!(f_select, select, T, (dim: i64, index: i64));
+    typed_method!(f_abs, abs, T, ());
+    typed_method!(f_max, max, T, ());
+    typed_method!(f_min, min, T, ());
+    typed_method!(f_argmax, argmax, i64, (dim: Option<i64>, keepdim: bool));
+
+    typed_binary_method!(f_add, add, T);
+    typed_binary_method!(f_mul, mul, T);
+    typed_binary_method!(f_matmul, matmul, T);
+    typed_binary_method!(f_eq1, eq1, bool);
+    typed_binary_method!(f_ne1, ne1, bool);
+    typed_binary_method!(f_lt1, lt1, bool);
+    typed_binary_method!(f_le1, le1, bool);
+    typed_binary_method!(f_gt1, gt1, bool);
+    typed_binary_method!(f_ge1, ge1, bool);
+
+    /// Returns the elements for which `mask` is true as a one dimension tensor.
+    pub fn f_masked_select(&self, mask: &TypedTensor<bool>) -> Result<Self, TchError> {
+        Ok(Self::new_unchecked(
+            self.tensor.f_masked_select(&mask.tensor)?,
+        ))
+    }
+
+    /// Returns the elements for which `mask` is true as a one dimension tensor.
+    pub fn masked_select(&self, mask: &TypedTensor<bool>) -> Self {
+        self.f_masked_select(mask).unwrap()
+    }
+
+    /// Selects the elements of `self` where `condition` is true and the
+    /// elements of `other` otherwise.
+    pub fn f_where1(&self, condition: &TypedTensor<bool>, other: &Self) -> Result<Self, TchError> {
+        Ok(Self::new_unchecked(
+            self.tensor.f_where1(&condition.tensor, &other.tensor)?,
+        ))
+    }
+
+    /// Selects the elements of `self` where `condition` is true and the
+    /// elements of `other` otherwise.
+    pub fn where1(&self, condition: &TypedTensor<bool>, other: &Self) -> Self {
+        self.f_where1(condition, other).unwrap()
+    }
+}
+
+impl<T: NumericElement> TypedTensor<T> {
+    typed_binary_method!(f_sub, sub, T);
+}
+
+impl<T: SumElement> TypedTensor<T> {
+    /// Returns the sum of all the elements, booleans and integers are summed
+    /// as int64 values.
+    pub fn f_sum(&self) -> Result<TypedTensor<T::Output>, TchError> {
+        Ok(TypedTensor::new_unchecked(
+            self.tensor.f_sum(<T::Output as Element>::KIND)?,
+        ))
+    }
+
+    /// Returns the sum of all the elements, booleans and integers are summed
+    /// as int64 values.
+    pub fn sum(&self) -> TypedTensor<T::Output> {
+        self.f_sum().unwrap()
+    }
+}
+
+impl<T: FloatElement> TypedTensor<T> {
+    typed_binary_method!(f_div, div, T);
+    typed_method!(f_exp, exp, T, ());
+    typed_method!(f_log, log, T, ());
+    typed_method!(f_sqrt, sqrt, T, ());
+
+    /// Returns the mean of all the elements.
+    pub fn f_mean(&self) -> Result<Self, TchError> {
+        Ok(Self::new_unchecked(self.tensor.f_mean(T::KIND)?))
+    }
+
+    /// Returns the mean of all the elements.
+    pub fn mean(&self) -> Self {
+        self.f_mean().unwrap()
+    }
+}
+
+impl Tensor {
+    /// Converts to a typed tensor sharing the same data, returns an error if
+    /// the tensor kind is not `T::KIND`.
+    pub fn f_typed<T: Element>(&self) -> Result<TypedTensor<T>, TchError> {
+        TypedTensor::f_new(self.shallow_clone())
+    }
+
+    /// Converts to a typed tensor sharing the same data, panics if the tensor
+    /// kind is not `T::KIND`.
+    pub fn typed<T: Element>(&self) -> TypedTensor<T> {
+        self.f_typed().unwrap()
+    }
+}
+
+impl<T: Element> Deref for TypedTensor<T> {
+    type Target = Tensor;
+
+    fn deref(&self) -> &Tensor {
+        &self.tensor
+    }
+}
+
+impl<T: Element> AsRef<Tensor> for TypedTensor<T> {
+    fn as_ref(&self) -> &Tensor {
+        &self.tensor
+    }
+}
+
+impl<T: Element> std::fmt::Debug for TypedTensor<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        std::fmt::Debug::fmt(&self.tensor, f)
+    }
+}
+
+impl<T: Element> TryFrom<Tensor> for TypedTensor<T> {
+    type Error = TchError;
+
+    fn try_from(tensor: Tensor) -> Result<Self, Self::Error> {
+        TypedTensor::f_new(tensor)
+    }
+}
+
+impl<T: Element> TryFrom<&Tensor> for TypedTensor<T> {
+    type Error = TchError;
+
+    fn try_from(tensor: &Tensor) -> Result<Self, Self::Error> {
+        tensor.f_typed()
+    }
+}
+
+impl<T: Element> From<TypedTensor<T>> for Tensor {
+    fn from(tensor: TypedTensor<T>) -> Tensor {
+        tensor.tensor
+    }
+}
+
+macro_rules! impl_op {
+    ($trait:ident, $func:ident, $bound:ident) => {
+        impl<T: $bound> $trait<TypedTensor<T>> for TypedTensor<T> {
+            type Output = TypedTensor<T>;
+
+            fn $func(self, rhs: TypedTensor<T>) -> Self::Output {
+                TypedTensor::$func(&self, &rhs)
+            }
+        }
+
+        impl<T: $bound> $trait<&TypedTensor<T>> for TypedTensor<T> {
+            type Output = TypedTensor<T>;
+
+            fn $func(self, rhs: &TypedTensor<T>) -> Self::Output {
+                TypedTensor::$func(&self, rhs)
+            }
+        }
+
+        impl<'a, T: $bound> $trait<&TypedTensor<T>> for &'a TypedTensor<T> {
+            type Output = TypedTensor<T>;
+
+            fn $func(self, rhs: &TypedTensor<T>) -> Self::Output {
+                TypedTensor::$func(self, rhs)
+            }
+        }
+
+        impl<'a, T: $bound> $trait<TypedTensor<T>> for &'a TypedTensor<T> {
+            type Output = TypedTensor<T>;
+
+            fn $func(self, rhs: TypedTensor<T>) -> Self::Output {
+                TypedTensor::$func(self, &rhs)
+            }
+        }
+    };
+}
+
+impl_op!(Add, add, Element);
+impl_op!(Sub, sub, NumericElement);
+impl_op!(Mul, mul, Element);
+impl_op!(Div, div, FloatElement);
+
+impl<T: NumericElement> Neg for TypedTensor<T> {
+    type Output = TypedTensor<T>;
+
+    fn neg(self) -> Self::Output {
+        TypedTensor::new_unchecked(-self.tensor)
+    }
+}
+
+impl<'a, T: NumericElement> Neg for &'a TypedTensor<T> {
+    type Output = TypedTensor<T>;
+
+    fn neg(self) -> Self::Output {
+        TypedTensor::new_unchecked(-&self.tensor)
+    }
+}
@@ -0,0 +1,366 @@
+//! Utilities for testing code based on tensors.
+//!
+//! `gradcheck` and `gradgradcheck` compare the gradients computed by the
+//! autograd engine with gradients estimated via finite differences, this is
+//! useful to validate custom layers and custom backward functions.
+//! `assert_close` compares two tensors element-wise.
+use crate::{Device, Kind, TchError, Tensor};
+
+/// Parameters used when numerically checking gradients.
+#[derive(Debug, Clone, Copy)]
+pub struct GradcheckConfig {
+    /// The perturbation used for finite differences.
+    pub eps: f64,
+    /// The absolute tolerance.
+    pub atol: f64,
+    /// The relative tolerance.
+    pub rtol: f64,
+}
+
+impl Default for GradcheckConfig {
+    fn default() -> Self {
+        GradcheckConfig {
+            eps: 1e-6,
+            atol: 1e-5,
+            rtol: 1e-3,
+        }
+    }
+}
+
+/// The result of a gradient check.
+///
+/// This reports the input element for which the analytical and numerical
+/// gradients differ the most relative to the tolerances.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GradcheckReport {
+    /// The position of the offending tensor in the inputs.
+    pub input: usize,
+    /// The index of the offending element in this input tensor.
+    pub index: Vec<i64>,
+    /// The index of the output element which gradient is reported.
+    pub output_index: Vec<i64>,
+    /// The gradient computed by the autograd engine.
+    pub analytical: f64,
+    /// The gradient estimated via finite differences.
+    pub numerical: f64,
+    /// Whether all the gradients are within the tolerances.
+    pub passed: bool,
+}
+
+impl std::fmt::Display for GradcheckReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "gradcheck {}: input {} at {:?}, output at {:?}, analytical {} numerical {} (diff {})",
+            if self.passed { "passed" } else { "failed" },
+            self.input,
+            self.index,
+            self.output_index,
+            self.analytical,
+            self.numerical,
+            (self.analytical - self.numerical).abs(),
+        )
+    }
+}
+
+fn to_vec(tensor: &Tensor) -> Result<Vec<f64>, TchError> {
+    let tensor = tensor
+        .f_to_device(Device::Cpu)?
+        .f_to_kind(Kind::Double)?
+        .f_contiguous()?;
+    let numel = tensor.numel();
+    let mut vec = vec![0f64; numel];
+    tensor.f_copy_data(&mut vec, numel)?;
+    Ok(vec)
+}
+
+fn unravel_index(mut index: usize, shape: &[i64]) -> Vec<i64> {
+    let mut result = vec![0; shape.len()];
+    for (r, &dim) in result.iter_mut().zip(shape.iter()).rev() {
+        *r = (index % dim as usize) as i64;
+        index /= dim as usize;
+    }
+    result
+}
+
+// Inputs that require gradients are converted to double precision, the
+// other ones are left unchanged.
+fn double_inputs(inputs: &[Tensor]) -> Result<Vec<Tensor>, TchError> {
+    inputs
+        .iter()
+        .map(|x| {
+            if x.requires_grad() {
+                x.f_detach()?
+                    .f_to_kind(Kind::Double)?
+                    .f_set_requires_grad(true)
+            } else {
+                x.f_detach()
+            }
+        })
+        .collect()
+}
+
+/// Checks the gradients of `f` with respect to the inputs that require
+/// gradients.
+///
+/// The analytical gradients are computed using `Tensor::run_backward` and
+/// compared to the gradients estimated via central finite differences. Inputs
+/// that require gradients are converted to double precision before being
+/// passed to `f`. An element passes the check if
+/// `|analytical - numerical| <= atol + rtol * |numerical|`.
+pub fn f_gradcheck<F>(
+    f: F,
+    inputs: &[Tensor],
+    config: &GradcheckConfig,
+) -> Result<GradcheckReport, TchError>
+where
+    F: Fn(&[Tensor]) -> Tensor,
+{
+    let inputs = double_inputs(inputs)?;
+    let diff: Vec<usize> = (0..inputs.len())
+        .filter(|&i| inputs[i].requires_grad())
+        .collect();
+    if diff.is_empty() {
+        return Err(TchError::Convert(
+            "gradcheck expects at least one input that requires grad".to_string(),
+        ));
+    }
+    let diff_inputs: Vec<&Tensor> = diff.iter().map(|&i| &inputs[i]).collect();
+
+    let output = f(&inputs).f_to_kind(Kind::Double)?;
+    let output_shape = output.size();
+    let output = output.f_reshape(&[-1])?;
+    let output_numel = output.numel();
+
+    // Jacobians are stored in row-major order, rows correspond to the output
+    // elements and columns to the input elements.
+    let mut analytical: Vec<Vec<f64>> = diff
+        .iter()
+        .map(|&i| vec![0.; output_numel * inputs[i].numel()])
+        .collect();
+    if output.requires_grad() {
+        for j in 0..output_numel {
+            let grads =
+                Tensor::f_run_backward(&[output.f_get(j as i64)?], &diff_inputs, true, false)?;
+            for (jacobian, grad) in analytical.iter_mut().zip(grads.iter()) {
+                if grad.defined() {
+                    let grad = to_vec(grad)?;
+                    let n = grad.len();
+                    jacobian[j * n..(j + 1) * n].copy_from_slice(&grad);
+                }
+            }
+        }
+    }
+
+    let mut numerical: Vec<Vec<f64>> = analytical.iter().map(|j| vec![0.; j.len()]).collect();
+    // The perturbed copies are contiguous so that their elements are visited
+    // in the same row-major order as the analytical jacobian columns.
+    let perturbed: Vec<Tensor> = crate::no_grad(|| -> Result<Vec<Tensor>, TchError> {
+        inputs
+            .iter()
+            .map(|x| {
+                let mut copy = Tensor::f_empty(&x.size(), (x.kind(), x.device()))?;
+                copy.f_copy_(x)?;
+                copy.f_set_requires_grad(x.requires_grad())
+            })
+            .collect()
+    })?;
+    let eval = |xs: &[Tensor]| -> Result<Vec<f64>, TchError> {
+        let output = f(xs);
+        to_vec(&output.f_detach()?)
+    };
+    for (jacobian, &i) in numerical.iter_mut().zip(diff.iter()) {
+        let flat = crate::no_grad(|| perturbed[i].f_view([-1]))?;
+        let n = flat.numel();
+        for k in 0..n {
+            let mut elem = crate::no_grad(|| flat.f_get(k as i64))?;
+            let orig = elem.f_double_value(&[])?;
+            let _ = crate::no_grad(|| elem.f_fill_(orig + config.eps))?;
+            let plus = eval(&perturbed)?;
+            let _ = crate::no_grad(|| elem.f_fill_(orig - config.eps))?;
+            let minus = eval(&perturbed)?;
+            let _ = crate::no_grad(|| elem.f_fill_(orig))?;
+            for j in 0..output_numel {
+                jacobian[j * n + k] = (plus[j] - minus[j]) / (2. * config.eps);
+            }
+        }
+    }
+
+    let mut report = GradcheckReport {
+        input: diff[0],
+        index: vec![],
+        output_index: vec![],
+        analytical: 0.,
+        numerical: 0.,
+        passed: true,
+    };
+    let mut worst_excess = f64::NEG_INFINITY;
+    for (idx, &i) in diff.iter().enumerate() {
+        let n = inputs[i].numel();
+        let input_shape = inputs[i].size();
+        for (pos, (&a, &num)) in analytical[idx]
+            .iter()
+            .zip(numerical[idx].iter())
+            .enumerate()
+        {
+            let excess = (a - num).abs() - config.atol - config.rtol * num.abs();
+            let excess = if excess.is_nan() {
+                f64::INFINITY
+            } else {
+                excess
+            };
+            if excess > worst_excess {
+                worst_excess = excess;
+                report.input = i;
+                report.index = unravel_index(pos % n, &input_shape);
+                report.output_index = unravel_index(pos / n, &output_shape);
+                report.analytical = a;
+                report.numerical = num;
+            }
+        }
+    }
+    report.passed = worst_excess <= 0.;
+    Ok(report)
+}
+
+/// Checks the gradients of `f` with respect to the inputs that require
+/// gradients.
+///
+/// Panics with a description of the worst offending input element if the
+/// check fails.
+pub fn gradcheck<F>(f: F, inputs: &[Tensor], config: &GradcheckConfig)
+where
+    F: Fn(&[Tensor]) -> Tensor,
+{
+    let report = f_gradcheck(f, inputs, config).unwrap();
+    if !report.passed {
+        panic!("{}", report)
+    }
+}
+
+/// Checks the second order gradients of `f` with respect to the inputs that
+/// require gradients.
+///
+/// This runs `f_gradcheck` on the function computing the gradients of
+/// `f` with respect to its inputs for a random output gradient. The output
+/// gradient is itself an input of the checked function and the report uses
+/// index `inputs.len()` for it.
+pub fn f_gradgradcheck<F>(
+    f: F,
+    inputs: &[Tensor],
+    config: &GradcheckConfig,
+) -> Result<GradcheckReport, TchError>
+where
+    F: Fn(&[Tensor]) -> Tensor,
+{
+    let mut inputs = double_inputs(inputs)?;
+    let output = f(&inputs).f_to_kind(Kind::Double)?;
+    let grad_output = Tensor::f_randn(&output.size(), (Kind::Double, output.device()))?
+        .f_set_requires_grad(true)?;
+    let ninputs = inputs.len();
+    inputs.push(grad_output);
+    f_gradcheck(
+        |xs| {
+            let (xs, grad_output) = xs.split_at(ninputs);
+            let output = f(xs).to_kind(Kind::Double);
+            let diff_inputs: Vec<&Tensor> = xs.iter().filter(|x| x.requires_grad()).collect();
+            if !output.requires_grad() {
+                let grads: Vec<Tensor> = diff_inputs
+                    .iter()
+                    .map(|x| x.zeros_like().view([-1]))
+                    .collect();
+                return Tensor::cat(&grads, 0);
+            }
+            let grads = Tensor::run_backward(
+                &[(output * &grad_output[0]).sum(Kind::Double)],
+                &diff_inputs,
+                true,
+                true,
+            );
+            let grads: Vec<Tensor> = grads
+                .iter()
+                .zip(diff_inputs.iter())
+                .map(|(g, x)| {
+                    if g.defined() {
+                        g.reshape(&[-1])
+                    } else {
+                        x.zeros_like().view([-1])
+                    }
+                })
+                .collect();
+            Tensor::cat(&grads, 0)
+        },
+        &inputs,
+        config,
+    )
+}
+
+/// Checks the second order gradients of `f` with respect to the inputs that
+/// require gradients.
+///
+/// Panics with a description of the worst offending input element if the
+/// check fails.
+pub fn gradgradcheck<F>(f: F, inputs: &[Tensor], config: &GradcheckConfig)
+where
+    F: Fn(&[Tensor]) -> Tensor,
+{
+    let report = f_gradgradcheck(f, inputs, config).unwrap();
+    if !report.passed {
+        panic!("{}", report)
+    }
+}
+
+/// Asserts that two tensors have the same shape and that their elements are
+/// close to each other.
+///
+/// Two elements `a` and `e` are considered close if
+/// `|a - e| <= atol + rtol * |e|`. On failure, the panic message contains
+/// the number of mismatched elements as well as the greatest absolute and
+/// relative differences together with their indexes.
+pub fn assert_close(actual: &Tensor, expected: &Tensor, rtol: f64, atol: f64) {
+    let (actual_shape, expected_shape) = (actual.size(), expected.size());
+    if actual_shape != expected_shape {
+        panic!(
+            "tensors are not close: shape mismatch, {:?} != {:?}",
+            actual_shape, expected_shape
+        )
+    }
+    let actual_ = to_vec(actual).unwrap();
+    let expected_ = to_vec(expected).unwrap();
+    let mut mismatched = 0;
+    let mut max_abs = (0., 0);
+    let mut max_rel = (0., 0);
+    for (index, (&a, &e)) in actual_.iter().zip(expected_.iter()).enumerate() {
+        let close = a == e || (a - e).abs() <= atol + rtol * e.abs();
+        if !close {
+            mismatched += 1;
+        }
+        let abs_diff = if a == e { 0. } else { (a - e).abs() };
+        let rel_diff = if a == e { 0. } else { abs_diff / e.abs() };
+        if abs_diff.is_nan() || abs_diff > max_abs.0 {
+            max_abs = (abs_diff, index)
+        }
+        if rel_diff.is_nan() || rel_diff > max_rel.0 {
+            max_rel = (rel_diff, index)
+        }
+    }
+    if mismatched > 0 {
+        panic!(
+            "tensors are not close: {} / {} elements mismatch (rtol {}, atol {})\n\
+             greatest absolute difference: {} at {:?} ({} vs {})\n\
+             greatest relative difference: {} at {:?} ({} vs {})",
+            mismatched,
+            actual_.len(),
+            rtol,
+            atol,
+            max_abs.0,
+            unravel_index(max_abs.1, &actual_shape),
+            actual_[max_abs.1],
+            expected_[max_abs.1],
+            max_rel.0,
+            unravel_index(max_rel.1, &actual_shape),
+            actual_[max_rel.1],
+            expected_[max_rel.1],
+        )
+    }
+}
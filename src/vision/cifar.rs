@@ -1,47 +1,59 @@
-//! The CIFAR-10 dataset.
+//! The CIFAR-10 and CIFAR-100 datasets.
 //!
 //! The files can be downloaded from the following page:
 //! https://www.cs.toronto.edu/~kriz/cifar.html
 //! The binary version of the dataset is used.
 use super::dataset::Dataset;
-use crate::{kind, IndexOp, Kind, Tensor};
+use crate::{Kind, Tensor};
 use std::fs::File;
-use std::io::{BufReader, Read, Result};
+use std::io::{self, BufReader, Read, Result};
 
 const W: i64 = 32;
 const H: i64 = 32;
 const C: i64 = 3;
-const BYTES_PER_IMAGE: i64 = W * H * C + 1;
-const SAMPLES_PER_FILE: i64 = 10000;
+const BYTES_PER_IMAGE: i64 = W * H * C;
 
-fn read_file_(filename: &std::path::Path) -> Result<(Tensor, Tensor)> {
+// Reads a file where each record is made of `label_bytes` label bytes followed
+// by the image, returns the images and the label at index `label_index`.
+fn read_file_(
+    filename: &std::path::Path,
+    label_bytes: i64,
+    label_index: i64,
+) -> Result<(Tensor, Tensor)> {
     let mut buf_reader = BufReader::new(File::open(filename)?);
-    let mut data = vec![0u8; (SAMPLES_PER_FILE * BYTES_PER_IMAGE) as usize];
-    buf_reader.read_exact(&mut data)?;
-    let content = Tensor::of_slice(&data);
-    let images = Tensor::zeros(&[SAMPLES_PER_FILE, C, H, W], kind::FLOAT_CPU);
-    let labels = Tensor::zeros(&[SAMPLES_PER_FILE], kind::INT64_CPU);
-    for index in 0..SAMPLES_PER_FILE {
-        let content_offset = BYTES_PER_IMAGE * index;
-        labels.i(index).copy_(&content.i(content_offset));
-        images.i(index).copy_(
-            &content
-                .narrow(0, 1 + content_offset, BYTES_PER_IMAGE - 1)
-                .view((C, H, W))
-                .to_kind(Kind::Float),
-        );
+    let mut data = vec![];
+    buf_reader.read_to_end(&mut data)?;
+    let record_bytes = label_bytes + BYTES_PER_IMAGE;
+    if data.is_empty() || data.len() as i64 % record_bytes != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected file size {}", data.len()),
+        ));
     }
-    Ok((images.to_kind(Kind::Float) / 255.0, labels))
+    let samples = data.len() as i64 / record_bytes;
+    let content = Tensor::of_slice(&data).view((samples, record_bytes));
+    let labels = content.select(1, label_index).to_kind(Kind::Int64);
+    let images = content
+        .narrow(1, label_bytes, BYTES_PER_IMAGE)
+        .view((samples, C, H, W))
+        .to_kind(Kind::Float);
+    Ok((images / 255.0, labels))
 }
 
-fn read_file(filename: &std::path::Path) -> Result<(Tensor, Tensor)> {
-    read_file_(filename)
+fn read_file(
+    filename: &std::path::Path,
+    label_bytes: i64,
+    label_index: i64,
+) -> Result<(Tensor, Tensor)> {
+    read_file_(filename, label_bytes, label_index)
         .map_err(|err| std::io::Error::new(err.kind(), format!("{:?} {}", filename, err)))
 }
 
+/// Loads the CIFAR-10 dataset from the directory containing the
+/// `data_batch_1.bin` to `data_batch_5.bin` and `test_batch.bin` files.
 pub fn load_dir<T: AsRef<std::path::Path>>(dir: T) -> Result<Dataset> {
     let dir = dir.as_ref();
-    let (test_images, test_labels) = read_file(&dir.join("test_batch.bin"))?;
+    let (test_images, test_labels) = read_file(&dir.join("test_batch.bin"), 1, 0)?;
     let train_images_and_labels = [
         "data_batch_1.bin",
         "data_batch_2.bin",
@@ -50,7 +62,7 @@ pub fn load_dir<T: AsRef<std::path::Path>>(dir: T) -> Result<Dataset> {
         "data_batch_5.bin",
     ]
     .iter()
-    .map(|x| read_file(&dir.join(x)))
+    .map(|x| read_file(&dir.join(x), 1, 0))
     .collect::<Result<Vec<_>>>()?;
     let (train_images, train_labels): (Vec<_>, Vec<_>) =
         train_images_and_labels.into_iter().unzip();
@@ -62,3 +74,35 @@ pub fn load_dir<T: AsRef<std::path::Path>>(dir: T) -> Result<Dataset> {
         labels: 10,
     })
 }
+
+/// The label granularity used by CIFAR-100.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cifar100Labels {
+    /// The 100 classes.
+    Fine,
+    /// The 20 superclasses.
+    Coarse,
+}
+
+/// Loads the CIFAR-100 dataset from the directory containing the `train.bin`
+/// and `test.bin` files.
+pub fn load_cifar100_dir<T: AsRef<std::path::Path>>(
+    dir: T,
+    labels: Cifar100Labels,
+) -> Result<Dataset> {
+    let dir = dir.as_ref();
+    // Each record starts with the coarse label followed by the fine label.
+    let (label_index, nlabels) = match labels {
+        Cifar100Labels::Fine => (1, 100),
+        Cifar100Labels::Coarse => (0, 20),
+    };
+    let (train_images, train_labels) = read_file(&dir.join("train.bin"), 2, label_index)?;
+    let (test_images, test_labels) = read_file(&dir.join("test.bin"), 2, label_index)?;
+    Ok(Dataset {
+        train_images,
+        train_labels,
+        test_images,
+        test_labels,
+        labels: nlabels,
+    })
+}
@@ -0,0 +1,466 @@
+//! The COCO object detection dataset.
+//!
+//! The images and annotations can be downloaded from the following page:
+//! https://cocodataset.org/#download
+//! The instances annotation files, e.g. `instances_val2017.json`, are used.
+//! Images are only decoded when accessed, annotations are read when creating
+//! the dataset.
+//! This module requires the `datasets` feature.
+//!
+//! ```ignore
+//! use tch::vision::coco::CocoDataset;
+//!
+//! let dataset = CocoDataset::new("coco/val2017", "coco/annotations/instances_val2017.json")?
+//!     .with_masks();
+//! let (image, target) = dataset.f_get(0)?;
+//! ```
+use super::detection::Target;
+use crate::{Device, Kind, TchError, Tensor};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+/// An object category.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Category {
+    pub id: i64,
+    pub name: String,
+    pub supercategory: String,
+}
+
+/// The description of an image from the annotation file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageInfo {
+    pub id: i64,
+    pub file_name: String,
+    pub height: i64,
+    pub width: i64,
+}
+
+#[derive(Debug, Clone)]
+enum Segmentation {
+    None,
+    Polygons(Vec<Vec<f64>>),
+    // Run-length encoding in column-major order, starting with a run of zeros.
+    Rle {
+        counts: Vec<usize>,
+        height: i64,
+        width: i64,
+    },
+}
+
+#[derive(Debug, Clone)]
+struct Annotation {
+    bbox: [f64; 4],
+    category_id: i64,
+    iscrowd: bool,
+    segmentation: Segmentation,
+}
+
+fn format_err<T>(msg: String) -> Result<T, TchError> {
+    Err(TchError::FileFormat(msg))
+}
+
+fn field<'a>(value: &'a Value, name: &str) -> Result<&'a Value, TchError> {
+    match value.get(name) {
+        Some(v) => Ok(v),
+        None => format_err(format!("missing field {}", name)),
+    }
+}
+
+fn as_i64(value: &Value, name: &str) -> Result<i64, TchError> {
+    match field(value, name)?.as_i64() {
+        Some(v) => Ok(v),
+        None => format_err(format!("field {} is not an integer", name)),
+    }
+}
+
+fn as_str<'a>(value: &'a Value, name: &str) -> Result<&'a str, TchError> {
+    match field(value, name)?.as_str() {
+        Some(v) => Ok(v),
+        None => format_err(format!("field {} is not a string", name)),
+    }
+}
+
+fn as_array<'a>(value: &'a Value, name: &str) -> Result<&'a Vec<Value>, TchError> {
+    match field(value, name)?.as_array() {
+        Some(v) => Ok(v),
+        None => format_err(format!("field {} is not an array", name)),
+    }
+}
+
+fn as_f64_vec(value: &Value, name: &str) -> Result<Vec<f64>, TchError> {
+    let values = match value.as_array() {
+        Some(v) => v,
+        None => return format_err(format!("field {} is not an array", name)),
+    };
+    values
+        .iter()
+        .map(|v| match v.as_f64() {
+            Some(v) => Ok(v),
+            None => format_err(format!("field {} contains a non-numeric value", name)),
+        })
+        .collect()
+}
+
+// Decodes the compressed RLE string format used by pycocotools, each count is
+// stored using groups of 5 bits offset by 48 and counts after the second one
+// are stored as differences with the count two positions before.
+fn decode_rle_string(s: &str) -> Result<Vec<usize>, TchError> {
+    let bytes = s.as_bytes();
+    let mut counts: Vec<i64> = vec![];
+    let mut p = 0;
+    while p < bytes.len() {
+        let mut x: i64 = 0;
+        let mut k = 0;
+        loop {
+            // At most 12 groups of 5 bits are used so that the shifts below
+            // stay within the 64 bits of x.
+            if p >= bytes.len() || bytes[p] < 48 || k >= 12 {
+                return format_err(format!("invalid compressed RLE {}", s));
+            }
+            let c = i64::from(bytes[p] - 48);
+            x |= (c & 0x1f) << (5 * k);
+            p += 1;
+            k += 1;
+            if c & 0x20 == 0 {
+                if c & 0x10 != 0 {
+                    x |= -1 << (5 * k);
+                }
+                break;
+            }
+        }
+        if counts.len() > 2 {
+            x = match x.checked_add(counts[counts.len() - 2]) {
+                Some(x) => x,
+                None => return format_err(format!("invalid compressed RLE {}", s)),
+            };
+        }
+        counts.push(x);
+    }
+    counts
+        .into_iter()
+        .map(|c| {
+            if c < 0 {
+                format_err(format!("negative count in compressed RLE {}", s))
+            } else {
+                Ok(c as usize)
+            }
+        })
+        .collect()
+}
+
+fn parse_segmentation(value: Option<&Value>) -> Result<Segmentation, TchError> {
+    let value = match value {
+        None | Some(Value::Null) => return Ok(Segmentation::None),
+        Some(value) => value,
+    };
+    if let Some(polygons) = value.as_array() {
+        let polygons = polygons
+            .iter()
+            .map(|p| as_f64_vec(p, "segmentation"))
+            .collect::<Result<Vec<_>, _>>()?;
+        return Ok(Segmentation::Polygons(polygons));
+    }
+    let size = as_array(value, "size")?;
+    let (height, width) = match (
+        size.get(0).and_then(|v| v.as_i64()),
+        size.get(1).and_then(|v| v.as_i64()),
+    ) {
+        (Some(h), Some(w)) if size.len() == 2 => (h, w),
+        _ => return format_err(format!("invalid RLE size {:?}", size)),
+    };
+    let counts = match field(value, "counts")? {
+        Value::String(s) => decode_rle_string(s)?,
+        Value::Array(counts) => counts
+            .iter()
+            .map(|v| match v.as_u64() {
+                Some(v) => Ok(v as usize),
+                None => format_err(format!("invalid RLE count {}", v)),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        v => return format_err(format!("invalid RLE counts {}", v)),
+    };
+    Ok(Segmentation::Rle {
+        counts,
+        height,
+        width,
+    })
+}
+
+fn parse_annotation(value: &Value) -> Result<(i64, Annotation), TchError> {
+    let bbox = as_f64_vec(field(value, "bbox")?, "bbox")?;
+    if bbox.len() != 4 {
+        return format_err(format!("invalid bbox {:?}", bbox));
+    }
+    let iscrowd = match value.get("iscrowd") {
+        None => false,
+        Some(v) => v.as_i64().unwrap_or(0) != 0 || v.as_bool().unwrap_or(false),
+    };
+    let annotation = Annotation {
+        bbox: [bbox[0], bbox[1], bbox[2], bbox[3]],
+        category_id: as_i64(value, "category_id")?,
+        iscrowd,
+        segmentation: parse_segmentation(value.get("segmentation"))?,
+    };
+    Ok((as_i64(value, "image_id")?, annotation))
+}
+
+// Fills a polygon using the even-odd rule, a pixel is part of the polygon when
+// its center is.
+fn fill_polygon(mask: &mut [u8], height: i64, width: i64, polygon: &[f64]) {
+    let points: Vec<(f64, f64)> = polygon.chunks_exact(2).map(|p| (p[0], p[1])).collect();
+    let n = points.len();
+    if n < 3 {
+        return;
+    }
+    let mut xs = vec![];
+    for y in 0..height {
+        let cy = y as f64 + 0.5;
+        xs.clear();
+        for i in 0..n {
+            let (x0, y0) = points[i];
+            let (x1, y1) = points[(i + 1) % n];
+            if (y0 <= cy) != (y1 <= cy) {
+                xs.push(x0 + (cy - y0) * (x1 - x0) / (y1 - y0));
+            }
+        }
+        xs.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        for span in xs.chunks_exact(2) {
+            let start = ((span[0] - 0.5).ceil() as i64).max(0);
+            let end = ((span[1] - 0.5).ceil() as i64).min(width);
+            for x in start..end {
+                mask[(y * width + x) as usize] = 1;
+            }
+        }
+    }
+}
+
+fn decode_mask(segmentation: &Segmentation, height: i64, width: i64) -> Result<Vec<u8>, TchError> {
+    let size = match height.checked_mul(width) {
+        Some(size) if height > 0 && width > 0 => size as usize,
+        _ => return format_err(format!("invalid image size {:?}", (height, width))),
+    };
+    let mut mask = vec![0u8; size];
+    match segmentation {
+        Segmentation::None => {}
+        Segmentation::Polygons(polygons) => {
+            for polygon in polygons.iter() {
+                fill_polygon(&mut mask, height, width, polygon)
+            }
+        }
+        Segmentation::Rle {
+            counts,
+            height: h,
+            width: w,
+        } => {
+            if (*h, *w) != (height, width) {
+                return format_err(format!(
+                    "RLE size {:?} does not match image size {:?}",
+                    (h, w),
+                    (height, width)
+                ));
+            }
+            let mut index: usize = 0;
+            for (i, &count) in counts.iter().enumerate() {
+                let end = match index.checked_add(count) {
+                    Some(end) if end <= mask.len() => end,
+                    _ => {
+                        return format_err(format!("RLE counts exceed the image size {:?}", (h, w)))
+                    }
+                };
+                if i % 2 == 1 {
+                    for j in index..end {
+                        // Convert the column-major index to a row-major one.
+                        let (x, y) = (j as i64 / height, j as i64 % height);
+                        mask[(y * width + x) as usize] = 1;
+                    }
+                }
+                index = end;
+            }
+        }
+    }
+    Ok(mask)
+}
+
+/// A COCO instances dataset.
+#[derive(Debug, Clone)]
+pub struct CocoDataset {
+    root: PathBuf,
+    images: Vec<ImageInfo>,
+    annotations: Vec<Vec<Annotation>>,
+    categories: Vec<Category>,
+    with_masks: bool,
+}
+
+impl CocoDataset {
+    /// Reads an annotation file, `root` is the directory containing the images.
+    ///
+    /// Images are sorted by id, annotations referring to an unknown image are
+    /// ignored.
+    pub fn new<T: AsRef<Path>, U: AsRef<Path>>(
+        root: T,
+        annotation_file: U,
+    ) -> Result<CocoDataset, TchError> {
+        let annotation_file = annotation_file.as_ref();
+        let json: Value = serde_json::from_reader(BufReader::new(File::open(annotation_file)?))
+            .map_err(|err| TchError::FileFormat(format!("{:?} {}", annotation_file, err)))?;
+        Self::from_json(root, &json)
+            .map_err(|err| TchError::FileFormat(format!("{:?} {}", annotation_file, err)))
+    }
+
+    fn from_json<T: AsRef<Path>>(root: T, json: &Value) -> Result<CocoDataset, TchError> {
+        let mut images = as_array(json, "images")?
+            .iter()
+            .map(|image| {
+                let height = as_i64(image, "height")?;
+                let width = as_i64(image, "width")?;
+                if height <= 0 || width <= 0 {
+                    return format_err(format!("invalid image size {:?}", (height, width)));
+                }
+                Ok(ImageInfo {
+                    id: as_i64(image, "id")?,
+                    file_name: as_str(image, "file_name")?.to_string(),
+                    height,
+                    width,
+                })
+            })
+            .collect::<Result<Vec<_>, TchError>>()?;
+        images.sort_by_key(|image| image.id);
+        let categories = match json.get("categories") {
+            None => vec![],
+            Some(_) => as_array(json, "categories")?
+                .iter()
+                .map(|category| {
+                    Ok(Category {
+                        id: as_i64(category, "id")?,
+                        name: as_str(category, "name")?.to_string(),
+                        supercategory: category
+                            .get("supercategory")
+                            .and_then(|v| v.as_str())
+                            .unwrap_or("")
+                            .to_string(),
+                    })
+                })
+                .collect::<Result<Vec<_>, TchError>>()?,
+        };
+        let index_of_id: HashMap<i64, usize> = images
+            .iter()
+            .enumerate()
+            .map(|(index, image)| (image.id, index))
+            .collect();
+        let mut annotations = vec![vec![]; images.len()];
+        if json.get("annotations").is_some() {
+            for annotation in as_array(json, "annotations")?.iter() {
+                let (image_id, annotation) = parse_annotation(annotation)?;
+                if let Some(&index) = index_of_id.get(&image_id) {
+                    annotations[index].push(annotation)
+                }
+            }
+        }
+        Ok(CocoDataset {
+            root: root.as_ref().to_path_buf(),
+            images,
+            annotations,
+            categories,
+            with_masks: false,
+        })
+    }
+
+    /// Also returns the instance masks in the targets, decoded from the
+    /// polygons or run-length encodings.
+    pub fn with_masks(mut self) -> CocoDataset {
+        self.with_masks = true;
+        self
+    }
+
+    /// The number of images in the dataset.
+    pub fn len(&self) -> usize {
+        self.images.len()
+    }
+
+    /// Returns true if the dataset does not contain any image.
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty()
+    }
+
+    /// The object categories, the target labels are the category ids.
+    pub fn categories(&self) -> &[Category] {
+        &self.categories
+    }
+
+    /// The images, sorted by id.
+    pub fn images(&self) -> &[ImageInfo] {
+        &self.images
+    }
+
+    fn image_info(&self, index: usize) -> Result<&ImageInfo, TchError> {
+        match self.images.get(index) {
+            Some(image) => Ok(image),
+            None => Err(TchError::Shape(format!(
+                "index {} out of range for a dataset of {} images",
+                index,
+                self.images.len()
+            ))),
+        }
+    }
+
+    /// Returns the annotations of an image without decoding it.
+    ///
+    /// The boxes are converted to the (xmin, ymin, xmax, ymax) format and
+    /// clipped to the image, annotations with an empty box are removed.
+    pub fn f_target(&self, index: usize) -> Result<Target, TchError> {
+        let image = self.image_info(index)?;
+        let (height, width) = (image.height, image.width);
+        let mut boxes = vec![];
+        let mut labels = vec![];
+        let mut iscrowd = vec![];
+        let mut masks = vec![];
+        for annotation in self.annotations[index].iter() {
+            let [x, y, w, h] = annotation.bbox;
+            let xmin = x.max(0.).min(width as f64);
+            let ymin = y.max(0.).min(height as f64);
+            let xmax = (x + w).max(0.).min(width as f64);
+            let ymax = (y + h).max(0.).min(height as f64);
+            if xmax <= xmin || ymax <= ymin {
+                continue;
+            }
+            boxes.extend_from_slice(&[xmin as f32, ymin as f32, xmax as f32, ymax as f32]);
+            labels.push(annotation.category_id);
+            iscrowd.push(annotation.iscrowd);
+            if self.with_masks {
+                masks.extend(decode_mask(&annotation.segmentation, height, width)?)
+            }
+        }
+        let n = labels.len() as i64;
+        let masks = if self.with_masks {
+            Some(Tensor::of_slice(&masks).f_view([n, height, width])?)
+        } else {
+            None
+        };
+        Ok(Target {
+            image_id: image.id,
+            boxes: Tensor::of_slice(&boxes).f_view([n, 4])?,
+            labels: Tensor::of_slice(&labels),
+            iscrowd: Tensor::of_slice(&iscrowd),
+            difficult: Tensor::f_zeros(&[n], (Kind::Bool, Device::Cpu))?,
+            masks,
+        })
+    }
+
+    /// Decodes an image, returns it as a uint8 tensor of shape [channel,
+    /// height, width] together with its annotations.
+    pub fn f_get(&self, index: usize) -> Result<(Tensor, Target), TchError> {
+        let path = self.root.join(&self.image_info(index)?.file_name);
+        let image = super::image::load(&path).map_err(|err| {
+            TchError::FileFormat(format!("unable to load image {:?}: {}", path, err))
+        })?;
+        Ok((image, self.f_target(index)?))
+    }
+
+    pub fn get(&self, index: usize) -> (Tensor, Target) {
+        self.f_get(index).unwrap()
+    }
+}
@@ -0,0 +1,171 @@
+//! ConvNeXt implementation.
+//!
+//! See "A ConvNet for the 2020s" Liu et al. 2022
+//! https://arxiv.org/abs/2201.03545
+use crate::{nn, nn::Module, nn::ModuleT, Tensor};
+
+// Layer normalization over the channel dimension of NCHW tensors.
+fn layer_norm2d(p: nn::Path, dim: i64) -> impl Module {
+    let config = nn::LayerNormConfig {
+        eps: 1e-6,
+        ..Default::default()
+    };
+    let ln = nn::layer_norm(p, vec![dim], config);
+    nn::func(move |xs| xs.permute(&[0, 2, 3, 1]).apply(&ln).permute(&[0, 3, 1, 2]))
+}
+
+// Drops the residual branch for whole samples during training.
+fn stochastic_depth(xs: &Tensor, prob: f64, train: bool) -> Tensor {
+    if !train || prob == 0. {
+        return xs.shallow_clone();
+    }
+    let size = xs.size();
+    let mut mask_size = vec![1; size.len()];
+    mask_size[0] = size[0];
+    let mask = Tensor::rand(&mask_size, (xs.kind(), xs.device()))
+        .ge(prob)
+        .to_kind(xs.kind());
+    xs * mask / (1. - prob)
+}
+
+fn block(p: nn::Path, dim: i64, stochastic_depth_prob: f64) -> impl ModuleT {
+    let b_p = &p / "block";
+    let conv_cfg = nn::ConvConfig {
+        padding: 3,
+        groups: dim,
+        ..Default::default()
+    };
+    let conv = nn::conv2d(&b_p / 0, dim, dim, 7, conv_cfg);
+    let ln_cfg = nn::LayerNormConfig {
+        eps: 1e-6,
+        ..Default::default()
+    };
+    let ln = nn::layer_norm(&b_p / 2, vec![dim], ln_cfg);
+    let linear1 = nn::linear(&b_p / 3, dim, 4 * dim, Default::default());
+    let linear2 = nn::linear(&b_p / 5, 4 * dim, dim, Default::default());
+    let layer_scale = p.var("layer_scale", &[dim, 1, 1], nn::Init::Const(1e-6));
+    nn::func_t(move |xs, train| {
+        let ys = xs
+            .apply(&conv)
+            .permute(&[0, 2, 3, 1])
+            .apply(&ln)
+            .apply(&linear1)
+            .gelu()
+            .apply(&linear2)
+            .permute(&[0, 3, 1, 2]);
+        stochastic_depth(&(&layer_scale * ys), stochastic_depth_prob, train) + xs
+    })
+}
+
+fn features(
+    p: &nn::Path,
+    dims: [i64; 4],
+    depths: [i64; 4],
+    stochastic_depth_prob: f64,
+) -> nn::IntermediateLayerGetter {
+    let f_p = p / "features";
+    let stem_p = &f_p / 0;
+    let stem_cfg = nn::ConvConfig {
+        stride: 4,
+        ..Default::default()
+    };
+    let stem = nn::seq_t()
+        .add(nn::conv2d(&stem_p / 0, 3, dims[0], 4, stem_cfg))
+        .add(layer_norm2d(&stem_p / 1, dims[0]));
+    let mut features = nn::seq_t().add(stem);
+    let total_blocks = depths.iter().sum::<i64>();
+    let mut block_id = 0;
+    for (stage_index, (&dim, &depth)) in dims.iter().zip(depths.iter()).enumerate() {
+        let s_p = &f_p / (2 * stage_index + 1);
+        let mut stage = nn::seq_t();
+        for block_index in 0..depth {
+            // The drop probability increases linearly with the block depth.
+            let prob = stochastic_depth_prob * block_id as f64 / (total_blocks - 1) as f64;
+            stage = stage.add(block(&s_p / block_index, dim, prob));
+            block_id += 1;
+        }
+        features = features.add(stage);
+        if stage_index + 1 < dims.len() {
+            let d_p = &f_p / (2 * stage_index + 2);
+            let conv_cfg = nn::ConvConfig {
+                stride: 2,
+                ..Default::default()
+            };
+            let downsample = nn::seq_t().add(layer_norm2d(&d_p / 0, dim)).add(nn::conv2d(
+                &d_p / 1,
+                dim,
+                dims[stage_index + 1],
+                2,
+                conv_cfg,
+            ));
+            features = features.add(downsample);
+        }
+    }
+    // The layer indexes match the torchvision ones, the stages are
+    // interleaved with the downsampling layers.
+    nn::IntermediateLayerGetter::new(
+        features,
+        &[(1, "layer1"), (3, "layer2"), (5, "layer3"), (7, "layer4")],
+    )
+}
+
+fn convnext(
+    p: &nn::Path,
+    dims: [i64; 4],
+    depths: [i64; 4],
+    stochastic_depth_prob: f64,
+    nclasses: i64,
+) -> impl ModuleT {
+    let features = features(p, dims, depths, stochastic_depth_prob);
+    let c_p = p / "classifier";
+    let norm = layer_norm2d(&c_p / 0, dims[3]);
+    let linear = nn::linear(&c_p / 2, dims[3], nclasses, Default::default());
+    nn::func_t(move |xs, train| {
+        xs.apply_t(&features, train)
+            .adaptive_avg_pool2d(&[1, 1])
+            .apply(&norm)
+            .flat_view()
+            .apply(&linear)
+    })
+}
+
+/// Creates a ConvNeXt Tiny model.
+pub fn convnext_tiny(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    convnext(p, [96, 192, 384, 768], [3, 3, 9, 3], 0.1, nclasses)
+}
+
+/// Creates a ConvNeXt Small model.
+pub fn convnext_small(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    convnext(p, [96, 192, 384, 768], [3, 3, 27, 3], 0.4, nclasses)
+}
+
+/// Creates a ConvNeXt Base model.
+pub fn convnext_base(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    convnext(p, [128, 256, 512, 1024], [3, 3, 27, 3], 0.5, nclasses)
+}
+
+/// Creates a ConvNeXt Large model.
+pub fn convnext_large(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    convnext(p, [192, 384, 768, 1536], [3, 3, 27, 3], 0.5, nclasses)
+}
+
+/// Creates a ConvNeXt Tiny feature extractor returning the outputs of the
+/// `layer1` to `layer4` stages, with strides of 4, 8, 16, and 32.
+pub fn convnext_tiny_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    features(p, [96, 192, 384, 768], [3, 3, 9, 3], 0.1)
+}
+
+/// Creates a ConvNeXt Small feature extractor, see `convnext_tiny_features`.
+pub fn convnext_small_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    features(p, [96, 192, 384, 768], [3, 3, 27, 3], 0.4)
+}
+
+/// Creates a ConvNeXt Base feature extractor, see `convnext_tiny_features`.
+pub fn convnext_base_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    features(p, [128, 256, 512, 1024], [3, 3, 27, 3], 0.5)
+}
+
+/// Creates a ConvNeXt Large feature extractor, see `convnext_tiny_features`.
+pub fn convnext_large_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    features(p, [192, 384, 768, 1536], [3, 3, 27, 3], 0.5)
+}
@@ -0,0 +1,635 @@
+//! Darknet models such as YOLO v3 or YOLO v4-tiny.
+//!
+//! Networks are described using the Darknet `.cfg` format and pre-trained
+//! weights can be loaded from Darknet `.weights` files.
+//!
+//! ```ignore
+//! let mut vs = nn::VarStore::new(Device::Cpu);
+//! let darknet = darknet::parse_config("yolov4-tiny.cfg")?;
+//! let model = darknet.build_model(&vs.root())?;
+//! darknet.load_weights(&vs.root(), "yolov4-tiny.weights")?;
+//! let predictions = model.forward_t(&image, false);
+//! let detections = darknet::detections(&predictions.get(0), 0.5, 0.4)?;
+//! ```
+use super::ops;
+use crate::nn::{self, FuncT, ModuleT};
+use crate::{TchError, Tensor};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+fn parse<T: FromStr>(key: &str, value: &str) -> Result<T, TchError> {
+    value
+        .trim()
+        .parse::<T>()
+        .map_err(|_| TchError::FileFormat(format!("invalid value for {}: {}", key, value)))
+}
+
+fn parse_list<T: FromStr>(key: &str, value: &str) -> Result<Vec<T>, TchError> {
+    value
+        .split(',')
+        .filter(|v| !v.trim().is_empty())
+        .map(|v| parse(key, v))
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+struct Block {
+    block_type: String,
+    parameters: BTreeMap<String, String>,
+}
+
+impl Block {
+    fn get(&self, key: &str) -> Result<&str, TchError> {
+        match self.parameters.get(key) {
+            None => Err(TchError::FileFormat(format!(
+                "cannot find {} in {}",
+                key, self.block_type
+            ))),
+            Some(value) => Ok(value),
+        }
+    }
+
+    fn parse<T: FromStr>(&self, key: &str) -> Result<T, TchError> {
+        parse(key, self.get(key)?)
+    }
+
+    fn parse_or<T: FromStr>(&self, key: &str, default: T) -> Result<T, TchError> {
+        match self.parameters.get(key) {
+            None => Ok(default),
+            Some(value) => parse(key, value),
+        }
+    }
+}
+
+/// A parsed Darknet configuration.
+#[derive(Debug, Clone)]
+pub struct Darknet {
+    blocks: Vec<Block>,
+    parameters: BTreeMap<String, String>,
+}
+
+impl FromStr for Darknet {
+    type Err = TchError;
+
+    fn from_str(config: &str) -> Result<Self, Self::Err> {
+        let mut blocks: Vec<Block> = vec![];
+        let mut parameters = None;
+        let mut current: Option<Block> = None;
+        for line in config.lines() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+                continue;
+            }
+            if line.starts_with('[') {
+                if !line.ends_with(']') {
+                    return Err(TchError::FileFormat(format!(
+                        "line does not end with ']' {}",
+                        line
+                    )));
+                }
+                if let Some(block) = current.take() {
+                    blocks.push(block)
+                }
+                current = Some(Block {
+                    block_type: line[1..line.len() - 1].trim().to_string(),
+                    parameters: BTreeMap::new(),
+                });
+            } else {
+                let block = match current.as_mut() {
+                    Some(block) => block,
+                    None => {
+                        return Err(TchError::FileFormat(format!(
+                            "parameter outside of a block {}",
+                            line
+                        )))
+                    }
+                };
+                let key_value: Vec<&str> = line.splitn(2, '=').collect();
+                if key_value.len() != 2 {
+                    return Err(TchError::FileFormat(format!("missing equal {}", line)));
+                }
+                let prev = block.parameters.insert(
+                    key_value[0].trim().to_owned(),
+                    key_value[1].trim().to_owned(),
+                );
+                if prev.is_some() {
+                    return Err(TchError::FileFormat(format!(
+                        "multiple values for key {}",
+                        line
+                    )));
+                }
+            }
+        }
+        if let Some(block) = current.take() {
+            blocks.push(block)
+        }
+        // The network parameters are stored in the [net] or [network] block.
+        let mut net_blocks = vec![];
+        for block in blocks.into_iter() {
+            match block.block_type.as_str() {
+                "net" | "network" => parameters = Some(block.parameters),
+                _ => net_blocks.push(block),
+            }
+        }
+        Ok(Darknet {
+            blocks: net_blocks,
+            parameters: parameters.unwrap_or_default(),
+        })
+    }
+}
+
+/// Parses a Darknet configuration file.
+pub fn parse_config<T: AsRef<std::path::Path>>(path: T) -> Result<Darknet, TchError> {
+    let config = std::fs::read_to_string(path)?;
+    config.parse()
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Activation {
+    Leaky,
+    Linear,
+    Logistic,
+    Mish,
+    Relu,
+}
+
+impl Activation {
+    fn parse(s: &str) -> Result<Activation, TchError> {
+        match s {
+            "leaky" => Ok(Activation::Leaky),
+            "linear" => Ok(Activation::Linear),
+            "logistic" => Ok(Activation::Logistic),
+            "mish" => Ok(Activation::Mish),
+            "relu" => Ok(Activation::Relu),
+            otherwise => Err(TchError::FileFormat(format!(
+                "unsupported activation {}",
+                otherwise
+            ))),
+        }
+    }
+
+    fn apply(self, xs: Tensor) -> Tensor {
+        match self {
+            Activation::Leaky => xs.max1(&(&xs * 0.1)),
+            Activation::Linear => xs,
+            Activation::Logistic => xs.sigmoid(),
+            Activation::Mish => &xs * xs.softplus().tanh(),
+            Activation::Relu => xs.relu(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Yolo {
+    classes: i64,
+    anchors: Vec<(i64, i64)>,
+    scale_x_y: f64,
+}
+
+#[derive(Debug)]
+enum Bl {
+    Layer(Box<dyn ModuleT>),
+    Route {
+        layers: Vec<usize>,
+        groups: i64,
+        group_id: i64,
+    },
+    Shortcut(usize),
+    Yolo(Yolo),
+}
+
+fn conv(vs: nn::Path, index: usize, p: i64, b: &Block) -> Result<(i64, Bl), TchError> {
+    let activation = Activation::parse(b.get("activation")?)?;
+    let filters = b.parse::<i64>("filters")?;
+    let pad = b.parse_or::<i64>("pad", 0)?;
+    let size = b.parse::<i64>("size")?;
+    let stride = b.parse_or::<i64>("stride", 1)?;
+    let groups = b.parse_or::<i64>("groups", 1)?;
+    let padding = if pad != 0 {
+        (size - 1) / 2
+    } else {
+        b.parse_or::<i64>("padding", 0)?
+    };
+    let (bn, bias) = if b.parse_or::<i64>("batch_normalize", 0)? != 0 {
+        let vs = &vs / format!("batch_norm_{}", index);
+        let bn = nn::batch_norm2d(&vs, filters, Default::default());
+        (Some(bn), false)
+    } else {
+        (None, true)
+    };
+    let conv_cfg = nn::ConvConfig {
+        stride,
+        padding,
+        groups,
+        bias,
+        ..Default::default()
+    };
+    let vs = &vs / format!("conv_{}", index);
+    let conv = nn::conv2d(vs, p, filters, size, conv_cfg);
+    let func = nn::func_t(move |xs, train| {
+        let xs = xs.apply(&conv);
+        let xs = match &bn {
+            Some(bn) => xs.apply_t(bn, train),
+            None => xs,
+        };
+        activation.apply(xs)
+    });
+    Ok((filters, Bl::Layer(Box::new(func))))
+}
+
+fn maxpool(p: i64, b: &Block) -> Result<(i64, Bl), TchError> {
+    let size = b.parse::<i64>("size")?;
+    let stride = b.parse_or::<i64>("stride", size)?;
+    // Darknet pads with a total of size - 1 values, replicating the border
+    // values does not change the maximums.
+    let padding = b.parse_or::<i64>("padding", size - 1)?;
+    let (left, right) = (padding / 2, padding - padding / 2);
+    let func = nn::func_t(move |xs, _train| {
+        xs.replication_pad2d(&[left, right, left, right])
+            .max_pool2d(&[size, size], &[stride, stride], &[0, 0], &[1, 1], false)
+    });
+    Ok((p, Bl::Layer(Box::new(func))))
+}
+
+fn upsample(p: i64, b: &Block) -> Result<(i64, Bl), TchError> {
+    let stride = b.parse_or::<i64>("stride", 2)?;
+    let func = nn::func_t(move |xs, _train| {
+        let (_n, _c, h, w) = xs.size4().unwrap();
+        xs.upsample_nearest2d(&[stride * h, stride * w], stride as f64, stride as f64)
+    });
+    Ok((p, Bl::Layer(Box::new(func))))
+}
+
+fn layer_index(index: usize, i: i64, nblocks: usize) -> Result<usize, TchError> {
+    let layer = if i >= 0 { i } else { index as i64 + i };
+    if layer < 0 || layer as usize >= nblocks {
+        Err(TchError::FileFormat(format!(
+            "invalid layer reference {} in block {}",
+            i, index
+        )))
+    } else {
+        Ok(layer as usize)
+    }
+}
+
+fn route(index: usize, p: &[(i64, Bl)], b: &Block) -> Result<(i64, Bl), TchError> {
+    let layers = parse_list::<i64>("layers", b.get("layers")?)?
+        .into_iter()
+        .map(|l| layer_index(index, l, p.len()))
+        .collect::<Result<Vec<_>, _>>()?;
+    let groups = b.parse_or::<i64>("groups", 1)?;
+    let group_id = b.parse_or::<i64>("group_id", 0)?;
+    if groups <= 0 || group_id < 0 || group_id >= groups {
+        return Err(TchError::FileFormat(format!(
+            "invalid groups {} and group_id {} in block {}",
+            groups, group_id, index
+        )));
+    }
+    // Each input layer is split in groups, the group_id slices are concatenated.
+    let channels = layers.iter().map(|&l| p[l].0 / groups).sum::<i64>();
+    let bl = Bl::Route {
+        layers,
+        groups,
+        group_id,
+    };
+    Ok((channels, bl))
+}
+
+fn shortcut(index: usize, p: i64, b: &Block) -> Result<(i64, Bl), TchError> {
+    let from = b.parse::<i64>("from")?;
+    Ok((p, Bl::Shortcut(layer_index(index, from, index)?)))
+}
+
+fn yolo(p: i64, b: &Block) -> Result<(i64, Bl), TchError> {
+    let classes = b.parse::<i64>("classes")?;
+    let flat = parse_list::<i64>("anchors", b.get("anchors")?)?;
+    if flat.len() % 2 != 0 {
+        return Err(TchError::FileFormat(format!(
+            "odd number of anchors values {}",
+            flat.len()
+        )));
+    }
+    let anchors: Vec<_> = flat.chunks(2).map(|a| (a[0], a[1])).collect();
+    let anchors = match b.parameters.get("mask") {
+        None => anchors,
+        Some(mask) => parse_list::<usize>("mask", mask)?
+            .into_iter()
+            .map(|i| {
+                anchors
+                    .get(i)
+                    .copied()
+                    .ok_or_else(|| TchError::FileFormat(format!("invalid anchor mask index {}", i)))
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+    };
+    let scale_x_y = b.parse_or::<f64>("scale_x_y", 1.)?;
+    let yolo = Yolo {
+        classes,
+        anchors,
+        scale_x_y,
+    };
+    Ok((p, Bl::Yolo(yolo)))
+}
+
+// Converts the output of a yolo layer to a tensor of shape
+// [batch, height * width * anchors, 5 + classes], each prediction being made
+// of the box center, width and height in pixels, the objectness score, and
+// the class scores.
+fn detect(xs: &Tensor, image_size: (i64, i64), yolo: &Yolo) -> Tensor {
+    let (bsize, _channels, height, width) = xs.size4().unwrap();
+    let (stride_h, stride_w) = (image_size.0 / height, image_size.1 / width);
+    let bbox_attrs = 5 + yolo.classes;
+    let nanchors = yolo.anchors.len() as i64;
+    let options = (xs.kind(), xs.device());
+    let xs = xs
+        .view((bsize, bbox_attrs * nanchors, height * width))
+        .transpose(1, 2)
+        .contiguous()
+        .view((bsize, height * width * nanchors, bbox_attrs));
+    let size = [height, width];
+    let x_offset = Tensor::arange(width, options)
+        .view([1, -1])
+        .expand(&size, false)
+        .reshape(&[-1, 1]);
+    let y_offset = Tensor::arange(height, options)
+        .view([-1, 1])
+        .expand(&size, false)
+        .reshape(&[-1, 1]);
+    let xy_offset = Tensor::cat(&[x_offset, y_offset], 1)
+        .repeat(&[1, nanchors])
+        .view((-1, 2))
+        .unsqueeze(0);
+    let stride = Tensor::of_slice(&[stride_w as f32, stride_h as f32])
+        .to_kind(options.0)
+        .to_device(options.1);
+    let anchors: Vec<f32> = yolo
+        .anchors
+        .iter()
+        .flat_map(|&(w, h)| vec![w as f32, h as f32].into_iter())
+        .collect();
+    let anchors = Tensor::of_slice(&anchors)
+        .to_kind(options.0)
+        .to_device(options.1)
+        .view((-1, 2))
+        .repeat(&[height * width, 1])
+        .unsqueeze(0);
+    let scale = yolo.scale_x_y;
+    let xy = xs.narrow(2, 0, 2).sigmoid() * scale - 0.5 * (scale - 1.);
+    let xy = (xy + xy_offset) * stride;
+    let wh = xs.narrow(2, 2, 2).exp() * anchors;
+    let scores = xs.narrow(2, 4, 1 + yolo.classes).sigmoid();
+    Tensor::cat(&[xy, wh, scores], 2)
+}
+
+impl Darknet {
+    fn get(&self, key: &str) -> Result<&str, TchError> {
+        match self.parameters.get(key) {
+            None => Err(TchError::FileFormat(format!(
+                "cannot find {} in net parameters",
+                key
+            ))),
+            Some(value) => Ok(value),
+        }
+    }
+
+    /// The height of the network input images.
+    pub fn height(&self) -> Result<i64, TchError> {
+        parse("height", self.get("height")?)
+    }
+
+    /// The width of the network input images.
+    pub fn width(&self) -> Result<i64, TchError> {
+        parse("width", self.get("width")?)
+    }
+
+    /// The number of input channels, defaults to 3.
+    pub fn channels(&self) -> Result<i64, TchError> {
+        match self.parameters.get("channels") {
+            None => Ok(3),
+            Some(channels) => parse("channels", channels),
+        }
+    }
+
+    /// Builds the model described by the configuration.
+    ///
+    /// The model takes as input a batch of images of shape
+    /// [batch, channels, height, width] with values between 0 and 1.
+    /// When the configuration contains yolo layers, the model returns the
+    /// predictions of all these layers as a tensor of shape
+    /// [batch, predictions, 5 + classes], see `detections` to extract the
+    /// boxes from these. Otherwise the output of the last layer is returned.
+    pub fn build_model(&self, vs: &nn::Path) -> Result<FuncT<'static>, TchError> {
+        let mut blocks: Vec<(i64, Bl)> = vec![];
+        let mut prev_channels = self.channels()?;
+        for (index, block) in self.blocks.iter().enumerate() {
+            let channels_and_bl = match block.block_type.as_str() {
+                "convolutional" => conv(vs / index, index, prev_channels, block)?,
+                "maxpool" => maxpool(prev_channels, block)?,
+                "upsample" => upsample(prev_channels, block)?,
+                "shortcut" => shortcut(index, prev_channels, block)?,
+                "route" => route(index, &blocks, block)?,
+                "yolo" => yolo(prev_channels, block)?,
+                otherwise => {
+                    return Err(TchError::FileFormat(format!(
+                        "unsupported block type {}",
+                        otherwise
+                    )))
+                }
+            };
+            prev_channels = channels_and_bl.0;
+            blocks.push(channels_and_bl);
+        }
+        let image_size = (self.height()?, self.width()?);
+        let func = nn::func_t(move |xs, train| {
+            let mut prev_ys: Vec<Tensor> = vec![];
+            let mut detections: Vec<Tensor> = vec![];
+            for (_, b) in blocks.iter() {
+                let ys = match b {
+                    Bl::Layer(l) => {
+                        let xs = prev_ys.last().unwrap_or(xs);
+                        l.forward_t(xs, train)
+                    }
+                    Bl::Route {
+                        layers,
+                        groups,
+                        group_id,
+                    } => {
+                        let layers: Vec<_> = layers
+                            .iter()
+                            .map(|&i| {
+                                let ys = &prev_ys[i];
+                                let channels = ys.size()[1] / groups;
+                                ys.narrow(1, group_id * channels, channels)
+                            })
+                            .collect();
+                        Tensor::cat(&layers, 1)
+                    }
+                    Bl::Shortcut(from) => prev_ys.last().unwrap() + &prev_ys[*from],
+                    Bl::Yolo(yolo) => {
+                        let xs = prev_ys.last().unwrap_or(xs);
+                        detections.push(detect(xs, image_size, yolo));
+                        xs.shallow_clone()
+                    }
+                };
+                prev_ys.push(ys);
+            }
+            if detections.is_empty() {
+                prev_ys.pop().unwrap_or_else(|| xs.shallow_clone())
+            } else {
+                Tensor::cat(&detections, 1)
+            }
+        });
+        Ok(func)
+    }
+
+    /// Loads the weights from a Darknet `.weights` file.
+    ///
+    /// The model should have been built using `build_model` with the same
+    /// `vs` path.
+    pub fn load_weights<T: AsRef<std::path::Path>>(
+        &self,
+        vs: &nn::Path,
+        path: T,
+    ) -> Result<(), TchError> {
+        let data = std::fs::read(path.as_ref())?;
+        let mut reader = WeightReader::new(&data)?;
+        for (index, block) in self.blocks.iter().enumerate() {
+            if block.block_type != "convolutional" {
+                continue;
+            }
+            let vs = vs / index;
+            let conv = &vs / format!("conv_{}", index);
+            if block.parse_or::<i64>("batch_normalize", 0)? != 0 {
+                let bn = &vs / format!("batch_norm_{}", index);
+                for name in ["bias", "weight", "running_mean", "running_var"].iter() {
+                    reader.read(&bn, name)?
+                }
+            } else {
+                reader.read(&conv, "bias")?
+            }
+            reader.read(&conv, "weight")?
+        }
+        Ok(())
+    }
+}
+
+// Reads the float values following the header of a Darknet weights file.
+struct WeightReader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> WeightReader<'a> {
+    fn new(data: &'a [u8]) -> Result<Self, TchError> {
+        let header_error = || TchError::FileFormat("invalid darknet weights header".to_string());
+        let version = |i: usize| -> Result<i32, TchError> {
+            let b = data.get(4 * i..4 * i + 4).ok_or_else(header_error)?;
+            Ok(i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        };
+        let (major, minor) = (version(0)?, version(1)?);
+        // The number of images seen during training is stored on 64 bits
+        // starting from version 0.2.
+        let header_len = if major * 10 + minor >= 2 { 20 } else { 16 };
+        let data = data.get(header_len..).ok_or_else(header_error)?;
+        Ok(WeightReader { data })
+    }
+
+    fn read(&mut self, vs: &nn::Path, name: &str) -> Result<(), TchError> {
+        let mut var = match vs.get(name) {
+            Some(var) => var,
+            None => {
+                let path = vs.components().chain(std::iter::once(name));
+                let path = path.collect::<Vec<_>>().join(".");
+                return Err(TchError::TensorNameNotFound(
+                    path,
+                    "darknet model".to_string(),
+                ));
+            }
+        };
+        let numel = var.numel();
+        if self.data.len() < 4 * numel {
+            return Err(TchError::FileFormat(format!(
+                "unexpected end of darknet weights file when reading {}",
+                name
+            )));
+        }
+        let (values, data) = self.data.split_at(4 * numel);
+        self.data = data;
+        let values: Vec<f32> = values
+            .chunks_exact(4)
+            .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect();
+        let src = Tensor::of_slice(&values).f_view(var.size().as_slice())?;
+        crate::no_grad(|| var.f_copy_(&src))
+    }
+}
+
+/// A detected object.
+///
+/// The box coordinates are in pixels of the network input image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Detection {
+    pub xmin: f64,
+    pub ymin: f64,
+    pub xmax: f64,
+    pub ymax: f64,
+    /// The objectness score.
+    pub confidence: f64,
+    pub class_index: usize,
+    pub class_confidence: f64,
+}
+
+/// Extracts the detected objects from the predictions made by a Darknet
+/// model on a single image.
+///
+/// `predictions` has shape [predictions, 5 + classes]. Predictions whose
+/// objectness score is not above `confidence_threshold` are discarded, and
+/// non-maximum suppression is performed for each class with `nms_threshold`.
+/// The detections are returned by decreasing objectness score.
+pub fn detections(
+    predictions: &Tensor,
+    confidence_threshold: f64,
+    nms_threshold: f64,
+) -> Result<Vec<Detection>, TchError> {
+    let (_npreds, pred_size) = predictions.size2()?;
+    let nclasses = pred_size - 5;
+    if nclasses <= 0 {
+        return Err(TchError::Shape(format!(
+            "unexpected prediction size {}",
+            pred_size
+        )));
+    }
+    let predictions = predictions.f_to_kind(crate::Kind::Float)?;
+    let keep = predictions
+        .f_select(1, 4)?
+        .f_gt(confidence_threshold)?
+        .f_nonzero()?
+        .f_view([-1])?;
+    let predictions = predictions.f_index_select(0, &keep)?;
+    let confidences = predictions.f_select(1, 4)?;
+    let (class_confidences, class_indexes) =
+        predictions.f_narrow(1, 5, nclasses)?.f_max2(1, false)?;
+    let boxes = ops::box_convert(
+        &predictions.f_narrow(1, 0, 4)?,
+        ops::BoxFormat::Cxcywh,
+        ops::BoxFormat::Xyxy,
+    )?;
+    let keep = ops::batched_nms(&boxes, &confidences, &class_indexes, nms_threshold)?;
+    let boxes = Vec::<Vec<f64>>::from(boxes.f_index_select(0, &keep)?);
+    let confidences = Vec::<f64>::from(confidences.f_index_select(0, &keep)?);
+    let class_confidences = Vec::<f64>::from(class_confidences.f_index_select(0, &keep)?);
+    let class_indexes = Vec::<i64>::from(class_indexes.f_index_select(0, &keep)?);
+    let detections = boxes
+        .iter()
+        .enumerate()
+        .map(|(i, b)| Detection {
+            xmin: b[0],
+            ymin: b[1],
+            xmax: b[2],
+            ymax: b[3],
+            confidence: confidences[i],
+            class_index: class_indexes[i] as usize,
+            class_confidence: class_confidences[i],
+        })
+        .collect();
+    Ok(detections)
+}
@@ -1,6 +1,6 @@
 //! A simple dataset structure shared by various computer vision datasets.
 use crate::data::Iter2;
-use crate::{IndexOp, Tensor};
+use crate::{IndexOp, Kind, Tensor};
 use rand::Rng;
 
 #[derive(Debug)]
@@ -98,3 +98,95 @@ pub fn augmentation(t: &Tensor, flip: bool, crop: i64, cutout: i64) -> Tensor {
     }
     t
 }
+
+fn check_mask(t: &Tensor, mask: &Tensor) {
+    let size = t.size();
+    let mask_size = mask.size();
+    if size.len() != 4
+        || mask_size.len() != 3
+        || size[0] != mask_size[0]
+        || size[2..] != mask_size[1..]
+    {
+        panic!(
+            "unexpected shapes for image {:?} and mask {:?}",
+            size, mask_size
+        )
+    }
+}
+
+/// Randomly applies horizontal flips to images and their segmentation masks.
+/// This expects a 4 dimension NCHW image tensor and a 3 dimension NHW mask
+/// tensor, the same flips are applied to an image and its mask.
+pub fn random_flip_with_mask(t: &Tensor, mask: &Tensor) -> (Tensor, Tensor) {
+    check_mask(t, mask);
+    let output = t.zeros_like();
+    let output_mask = mask.zeros_like();
+    for batch_index in 0..t.size()[0] {
+        let mut output_view = output.i(batch_index);
+        let mut output_mask_view = output_mask.i(batch_index);
+        let t_view = t.i(batch_index);
+        let mask_view = mask.i(batch_index);
+        if rand::random() {
+            output_view.copy_(&t_view);
+            output_mask_view.copy_(&mask_view)
+        } else {
+            output_view.copy_(&t_view.flip(&[2]));
+            output_mask_view.copy_(&mask_view.flip(&[1]))
+        }
+    }
+    (output, output_mask)
+}
+
+/// Pad images and their segmentation masks using reflections and take some
+/// random crops, the same crop is used for an image and its mask.
+/// This expects a 4 dimension NCHW image tensor and a 3 dimension NHW mask
+/// tensor and returns tensors with identical shapes.
+pub fn random_crop_with_mask(t: &Tensor, mask: &Tensor, pad: i64) -> (Tensor, Tensor) {
+    check_mask(t, mask);
+    let size = t.size();
+    let sz_h = size[2];
+    let sz_w = size[3];
+    let padded = t.reflection_pad2d(&[pad, pad, pad, pad]);
+    // Reflection padding is not available for integer tensors.
+    let padded_mask = mask
+        .to_kind(Kind::Float)
+        .unsqueeze(1)
+        .reflection_pad2d(&[pad, pad, pad, pad])
+        .squeeze1(1)
+        .to_kind(mask.kind());
+    let output = t.zeros_like();
+    let output_mask = mask.zeros_like();
+    for bindex in 0..size[0] {
+        let mut output_view = output.i(bindex);
+        let mut output_mask_view = output_mask.i(bindex);
+        // The range is inclusive so that a pad of 0 results in no crop.
+        let start_w = rand::thread_rng().gen_range(0..=(2 * pad));
+        let start_h = rand::thread_rng().gen_range(0..=(2 * pad));
+        let src = padded.i((bindex, .., start_h..start_h + sz_h, start_w..start_w + sz_w));
+        let src_mask = padded_mask.i((bindex, start_h..start_h + sz_h, start_w..start_w + sz_w));
+        output_view.copy_(&src);
+        output_mask_view.copy_(&src_mask)
+    }
+    (output, output_mask)
+}
+
+pub fn augmentation_with_mask(
+    t: &Tensor,
+    mask: &Tensor,
+    flip: bool,
+    crop: i64,
+) -> (Tensor, Tensor) {
+    let mut t = t.shallow_clone();
+    let mut mask = mask.shallow_clone();
+    if flip {
+        let (t_, mask_) = random_flip_with_mask(&t, &mask);
+        t = t_;
+        mask = mask_;
+    }
+    if crop > 0 {
+        let (t_, mask_) = random_crop_with_mask(&t, &mask, crop);
+        t = t_;
+        mask = mask_;
+    }
+    (t, mask)
+}
@@ -56,53 +56,76 @@ fn transition(p: nn::Path, c_in: i64, c_out: i64) -> impl ModuleT {
         .add_fn(|xs| xs.avg_pool2d_default(2))
 }
 
-fn densenet(
+// Returns the feature extractor and its number of output channels.
+fn features(
     p: &nn::Path,
     c_in: i64,
     bn_size: i64,
     growth: i64,
     block_config: &[i64],
-    c_out: i64,
-) -> impl ModuleT {
+) -> (nn::IntermediateLayerGetter, i64) {
     let fp = p / "features";
-    let mut seq = nn::seq_t()
+    let stem = nn::seq_t()
         .add(conv2d(&fp / "conv0", 3, c_in, 7, 3, 2))
         .add(nn::batch_norm2d(&fp / "norm0", c_in, Default::default()))
         .add_fn(|xs| {
             xs.relu()
                 .max_pool2d(&[3, 3], &[2, 2], &[1, 1], &[1, 1], false)
         });
+    let mut seq = nn::seq_t().add(stem);
+    let mut return_layers = vec![];
     let mut nfeat = c_in;
     for (i, &nlayers) in block_config.iter().enumerate() {
-        seq = seq.add(dense_block(
+        let block = dense_block(
             &fp / &format!("denseblock{}", 1 + i),
             nfeat,
             bn_size,
             growth,
             nlayers,
-        ));
+        );
         nfeat += nlayers * growth;
         if i + 1 != block_config.len() {
+            seq = seq.add(block);
+            return_layers.push((seq.len() as usize - 1, format!("denseblock{}", 1 + i)));
             seq = seq.add(transition(
                 &fp / &format!("transition{}", 1 + i),
                 nfeat,
                 nfeat / 2,
             ));
             nfeat /= 2
+        } else {
+            // The final batch norm is applied to the last block output.
+            let block = nn::seq_t()
+                .add(block)
+                .add(nn::batch_norm2d(&fp / "norm5", nfeat, Default::default()))
+                .add_fn(|xs| xs.relu());
+            seq = seq.add(block);
+            return_layers.push((seq.len() as usize - 1, format!("denseblock{}", 1 + i)));
         }
     }
-    seq.add(nn::batch_norm2d(&fp / "norm5", nfeat, Default::default()))
-        .add_fn(|xs| {
-            xs.relu()
-                .avg_pool2d(&[7, 7], &[1, 1], &[0, 0], false, true, 1)
-                .flat_view()
-        })
-        .add(nn::linear(
-            p / "classifier",
-            nfeat,
-            c_out,
-            Default::default(),
-        ))
+    let return_layers: Vec<_> = return_layers
+        .iter()
+        .map(|(i, name)| (*i, name.as_str()))
+        .collect();
+    (nn::IntermediateLayerGetter::new(seq, &return_layers), nfeat)
+}
+
+fn densenet(
+    p: &nn::Path,
+    c_in: i64,
+    bn_size: i64,
+    growth: i64,
+    block_config: &[i64],
+    c_out: i64,
+) -> impl ModuleT {
+    let (features, nfeat) = features(p, c_in, bn_size, growth, block_config);
+    let classifier = nn::linear(p / "classifier", nfeat, c_out, Default::default());
+    nn::func_t(move |xs, train| {
+        xs.apply_t(&features, train)
+            .avg_pool2d(&[7, 7], &[1, 1], &[0, 0], false, true, 1)
+            .flat_view()
+            .apply(&classifier)
+    })
 }
 
 pub fn densenet121(p: &nn::Path, nclasses: i64) -> impl ModuleT {
@@ -120,3 +143,24 @@ pub fn densenet169(p: &nn::Path, nclasses: i64) -> impl ModuleT {
 pub fn densenet201(p: &nn::Path, nclasses: i64) -> impl ModuleT {
     densenet(p, 64, 4, 32, &[6, 12, 48, 32], nclasses)
 }
+
+/// Creates a DenseNet-121 feature extractor returning the outputs of the
+/// `denseblock1` to `denseblock4` stages, with strides of 4, 8, 16, and 32.
+pub fn densenet121_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    features(p, 64, 4, 32, &[6, 12, 24, 16]).0
+}
+
+/// Creates a DenseNet-161 feature extractor, see `densenet121_features`.
+pub fn densenet161_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    features(p, 96, 4, 48, &[6, 12, 36, 24]).0
+}
+
+/// Creates a DenseNet-169 feature extractor, see `densenet121_features`.
+pub fn densenet169_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    features(p, 64, 4, 32, &[6, 12, 32, 32]).0
+}
+
+/// Creates a DenseNet-201 feature extractor, see `densenet121_features`.
+pub fn densenet201_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    features(p, 64, 4, 32, &[6, 12, 48, 32]).0
+}
@@ -0,0 +1,459 @@
+//! Object detection targets, batching and evaluation.
+//!
+//! The `coco` and `voc` datasets return images together with a `Target`
+//! holding their annotations, these datasets require the `datasets` feature.
+//! Samples can be grouped in mini-batches using `collate` and detections
+//! evaluated using `MeanAveragePrecision`.
+//!
+//! ```ignore
+//! use tch::vision::{coco::CocoDataset, detection};
+//!
+//! let dataset = CocoDataset::new("coco/val2017", "coco/instances_val2017.json")?;
+//! let samples = (0..4).map(|i| dataset.f_get(i)).collect::<Result<Vec<_>, _>>()?;
+//! let batch = detection::collate(&samples)?;
+//! ```
+use crate::{Kind, TchError, Tensor};
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+
+/// The annotations of an image.
+#[derive(Debug)]
+pub struct Target {
+    pub image_id: i64,
+    /// The boxes using (xmin, ymin, xmax, ymax) pixel coordinates, a float
+    /// tensor of shape [n, 4].
+    pub boxes: Tensor,
+    /// The class labels, an int64 tensor of shape [n].
+    pub labels: Tensor,
+    /// Whether an annotation is a COCO crowd region, a bool tensor of shape
+    /// [n]. These annotations are ignored during evaluation and a detection
+    /// matches them when it is mostly contained in the region.
+    pub iscrowd: Tensor,
+    /// Whether an annotation is a Pascal VOC difficult object, a bool tensor
+    /// of shape [n]. These annotations are ignored during evaluation.
+    pub difficult: Tensor,
+    /// The instance masks, a uint8 tensor of shape [n, height, width], only
+    /// set when requested.
+    pub masks: Option<Tensor>,
+}
+
+/// A mini-batch of images and targets.
+///
+/// Images are padded with zeros on the bottom and right to the largest height
+/// and width in the batch, the boxes of each image are padded to the largest
+/// number of boxes.
+#[derive(Debug)]
+pub struct Batch {
+    /// The images, a tensor of shape [batch, channel, height, width].
+    pub images: Tensor,
+    /// The height and width of each image before padding, an int64 tensor of
+    /// shape [batch, 2].
+    pub image_sizes: Tensor,
+    /// The image ids, an int64 tensor of shape [batch].
+    pub image_ids: Tensor,
+    /// The boxes, a float tensor of shape [batch, max_boxes, 4], padding
+    /// boxes are set to zero.
+    pub boxes: Tensor,
+    /// The labels, an int64 tensor of shape [batch, max_boxes], padding
+    /// labels are set to -1.
+    pub labels: Tensor,
+    /// A bool tensor of shape [batch, max_boxes].
+    pub iscrowd: Tensor,
+    /// A bool tensor of shape [batch, max_boxes].
+    pub difficult: Tensor,
+    /// Whether a box is an actual annotation rather than padding, a bool tensor
+    /// of shape [batch, max_boxes].
+    pub valid: Tensor,
+    /// The masks, a uint8 tensor of shape [batch, max_boxes, height, width],
+    /// only set when all the targets have masks.
+    pub masks: Option<Tensor>,
+}
+
+/// Groups images and targets in a mini-batch.
+pub fn collate(samples: &[(Tensor, Target)]) -> Result<Batch, TchError> {
+    let (image, _) = match samples.first() {
+        Some(sample) => sample,
+        None => return Err(TchError::Shape("cannot collate an empty batch".to_string())),
+    };
+    let channels = image.size()[0];
+    let mut max_height = 0;
+    let mut max_width = 0;
+    let mut max_boxes = 0;
+    for (image, target) in samples.iter() {
+        let size = image.size();
+        if size.len() != 3 || size[0] != channels {
+            return Err(TchError::Shape(format!(
+                "expected images with {} channels, got shape {:?}",
+                channels, size
+            )));
+        }
+        max_height = i64::max(max_height, size[1]);
+        max_width = i64::max(max_width, size[2]);
+        max_boxes = i64::max(max_boxes, target.labels.size()[0]);
+    }
+    let bsize = samples.len() as i64;
+    let options = (Kind::Float, image.device());
+    let images = Tensor::f_zeros(
+        &[bsize, channels, max_height, max_width],
+        (image.kind(), image.device()),
+    )?;
+    let boxes = Tensor::f_zeros(&[bsize, max_boxes, 4], options)?;
+    let labels = Tensor::f_full(&[bsize, max_boxes], -1, (Kind::Int64, image.device()))?;
+    let iscrowd = Tensor::f_zeros(&[bsize, max_boxes], (Kind::Bool, image.device()))?;
+    let difficult = Tensor::f_zeros(&[bsize, max_boxes], (Kind::Bool, image.device()))?;
+    let valid = Tensor::f_zeros(&[bsize, max_boxes], (Kind::Bool, image.device()))?;
+    let with_masks = samples.iter().all(|(_, target)| target.masks.is_some());
+    let masks = if with_masks {
+        Some(Tensor::f_zeros(
+            &[bsize, max_boxes, max_height, max_width],
+            (Kind::Uint8, image.device()),
+        )?)
+    } else {
+        None
+    };
+    let mut image_sizes = vec![];
+    let mut image_ids = vec![];
+    for (index, (image, target)) in samples.iter().enumerate() {
+        let index = index as i64;
+        let (_, height, width) = image.size3()?;
+        image_sizes.extend_from_slice(&[height, width]);
+        image_ids.push(target.image_id);
+        images
+            .f_get(index)?
+            .f_narrow(1, 0, height)?
+            .f_narrow(2, 0, width)?
+            .f_copy_(image)?;
+        let nboxes = target.labels.size()[0];
+        if nboxes == 0 {
+            continue;
+        }
+        boxes
+            .f_get(index)?
+            .f_narrow(0, 0, nboxes)?
+            .f_copy_(&target.boxes)?;
+        labels
+            .f_get(index)?
+            .f_narrow(0, 0, nboxes)?
+            .f_copy_(&target.labels)?;
+        iscrowd
+            .f_get(index)?
+            .f_narrow(0, 0, nboxes)?
+            .f_copy_(&target.iscrowd)?;
+        difficult
+            .f_get(index)?
+            .f_narrow(0, 0, nboxes)?
+            .f_copy_(&target.difficult)?;
+        let _ = valid.f_get(index)?.f_narrow(0, 0, nboxes)?.f_fill_(1)?;
+        if let (Some(masks), Some(target_masks)) = (&masks, &target.masks) {
+            let (_, mask_height, mask_width) = target_masks.size3()?;
+            masks
+                .f_get(index)?
+                .f_narrow(0, 0, nboxes)?
+                .f_narrow(1, 0, mask_height)?
+                .f_narrow(2, 0, mask_width)?
+                .f_copy_(target_masks)?;
+        }
+    }
+    Ok(Batch {
+        images,
+        image_sizes: Tensor::of_slice(&image_sizes).f_view([bsize, 2])?,
+        image_ids: Tensor::of_slice(&image_ids),
+        boxes,
+        labels,
+        iscrowd,
+        difficult,
+        valid,
+        masks,
+    })
+}
+
+#[derive(Debug, Default)]
+struct ImageDetections {
+    gt_boxes: Vec<[f64; 4]>,
+    gt_labels: Vec<i64>,
+    gt_crowd: Vec<bool>,
+    gt_difficult: Vec<bool>,
+    dt_boxes: Vec<[f64; 4]>,
+    dt_scores: Vec<f64>,
+    dt_labels: Vec<i64>,
+}
+
+fn to_boxes(t: &Tensor) -> Result<Vec<[f64; 4]>, TchError> {
+    let values = Vec::<f64>::from(&t.f_to_kind(Kind::Double)?.f_view([-1])?);
+    Ok(values
+        .chunks_exact(4)
+        .map(|b| [b[0], b[1], b[2], b[3]])
+        .collect())
+}
+
+fn check_size(t: &Tensor, name: &str, size: &[i64]) -> Result<(), TchError> {
+    if t.size() != size {
+        return Err(TchError::Shape(format!(
+            "expected {} with shape {:?}, got {:?}",
+            name,
+            size,
+            t.size()
+        )));
+    }
+    Ok(())
+}
+
+fn area(b: &[f64; 4]) -> f64 {
+    (b[2] - b[0]).max(0.) * (b[3] - b[1]).max(0.)
+}
+
+// The intersection over union, for crowd regions the intersection is divided
+// by the area of the detection.
+fn iou(dt: &[f64; 4], gt: &[f64; 4], crowd: bool) -> f64 {
+    let w = (dt[2].min(gt[2]) - dt[0].max(gt[0])).max(0.);
+    let h = (dt[3].min(gt[3]) - dt[1].max(gt[1])).max(0.);
+    let inter = w * h;
+    let union = if crowd {
+        area(dt)
+    } else {
+        area(dt) + area(gt) - inter
+    };
+    if union <= 0. {
+        0.
+    } else {
+        inter / union
+    }
+}
+
+/// The results of a mean average precision evaluation.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapSummary {
+    /// The mean average precision over the classes and IoU thresholds.
+    pub map: f64,
+    /// The mean average precision over the classes for each IoU threshold.
+    pub per_iou_threshold: Vec<(f64, f64)>,
+    /// The average precision over the IoU thresholds for each class that has
+    /// some ground truth annotations.
+    pub per_class: Vec<(i64, f64)>,
+}
+
+/// A COCO-style mean average precision evaluator for bounding boxes.
+///
+/// For each class and IoU threshold, detections are greedily matched to the
+/// ground truth boxes by decreasing score and the precision is interpolated
+/// at 101 recall points. Detections matched to crowd or difficult annotations
+/// are ignored and at most 100 detections per image are used. The overlap
+/// with a crowd region is computed relative to the area of the detection,
+/// the usual intersection over union is used for the other annotations.
+#[derive(Debug)]
+pub struct MeanAveragePrecision {
+    iou_thresholds: Vec<f64>,
+    max_detections: usize,
+    images: Vec<ImageDetections>,
+}
+
+impl Default for MeanAveragePrecision {
+    fn default() -> Self {
+        MeanAveragePrecision {
+            iou_thresholds: (0..10).map(|i| 0.5 + 0.05 * i as f64).collect(),
+            max_detections: 100,
+            images: vec![],
+        }
+    }
+}
+
+impl MeanAveragePrecision {
+    /// Creates an evaluator using the IoU thresholds 0.5, 0.55, ..., 0.95.
+    pub fn new() -> MeanAveragePrecision {
+        MeanAveragePrecision::default()
+    }
+
+    pub fn iou_thresholds(mut self, iou_thresholds: &[f64]) -> Self {
+        self.iou_thresholds = iou_thresholds.to_vec();
+        self
+    }
+
+    pub fn max_detections(mut self, max_detections: usize) -> Self {
+        self.max_detections = max_detections;
+        self
+    }
+
+    /// Adds the detections for an image, `boxes` has shape [n, 4] using
+    /// (xmin, ymin, xmax, ymax) coordinates, `scores` and `labels` have
+    /// shape [n]. An error is returned if some scores are not finite.
+    pub fn f_update(
+        &mut self,
+        boxes: &Tensor,
+        scores: &Tensor,
+        labels: &Tensor,
+        target: &Target,
+    ) -> Result<(), TchError> {
+        let n = scores.size().first().copied().unwrap_or(0);
+        check_size(boxes, "boxes", &[n, 4])?;
+        check_size(scores, "scores", &[n])?;
+        check_size(labels, "labels", &[n])?;
+        let gt_n = target.labels.size().first().copied().unwrap_or(0);
+        check_size(&target.boxes, "target boxes", &[gt_n, 4])?;
+        check_size(&target.iscrowd, "target iscrowd", &[gt_n])?;
+        check_size(&target.difficult, "target difficult", &[gt_n])?;
+        let dt_scores = Vec::<f64>::from(&scores.f_to_kind(Kind::Double)?);
+        if let Some(score) = dt_scores.iter().find(|s| !s.is_finite()) {
+            return Err(TchError::Convert(format!(
+                "detection scores have to be finite, got {}",
+                score
+            )));
+        }
+        self.images.push(ImageDetections {
+            gt_boxes: to_boxes(&target.boxes)?,
+            gt_labels: Vec::<i64>::from(&target.labels.f_to_kind(Kind::Int64)?),
+            gt_crowd: Vec::<bool>::from(&target.iscrowd.f_to_kind(Kind::Bool)?),
+            gt_difficult: Vec::<bool>::from(&target.difficult.f_to_kind(Kind::Bool)?),
+            dt_boxes: to_boxes(boxes)?,
+            dt_scores,
+            dt_labels: Vec::<i64>::from(&labels.f_to_kind(Kind::Int64)?),
+        });
+        Ok(())
+    }
+
+    pub fn update(&mut self, boxes: &Tensor, scores: &Tensor, labels: &Tensor, target: &Target) {
+        self.f_update(boxes, scores, labels, target).unwrap()
+    }
+
+    // The average precision of a class, None if the class has no ground truth
+    // annotation that is not ignored.
+    fn average_precision(&self, class: i64, iou_threshold: f64) -> Option<f64> {
+        // The score of each detection, whether it is a true positive, and
+        // whether it is ignored.
+        let mut detections: Vec<(f64, bool, bool)> = vec![];
+        let mut npositives = 0;
+        for image in self.images.iter() {
+            let ignored = |i: usize| image.gt_crowd[i] || image.gt_difficult[i];
+            let mut gts: Vec<usize> = (0..image.gt_labels.len())
+                .filter(|&i| image.gt_labels[i] == class)
+                .collect();
+            // Annotations that are not ignored are matched first.
+            gts.sort_by_key(|&i| ignored(i));
+            let mut dts: Vec<usize> = (0..image.dt_labels.len())
+                .filter(|&i| image.dt_labels[i] == class)
+                .collect();
+            dts.sort_by(|&i, &j| {
+                image.dt_scores[j]
+                    .partial_cmp(&image.dt_scores[i])
+                    .unwrap_or(Ordering::Equal)
+            });
+            dts.truncate(self.max_detections);
+            npositives += gts.iter().filter(|&&i| !ignored(i)).count();
+            let mut matched = vec![false; gts.len()];
+            for &dt in dts.iter() {
+                let mut best_iou = iou_threshold.min(1. - 1e-10);
+                let mut best_gt: Option<usize> = None;
+                for (index, &gt) in gts.iter().enumerate() {
+                    // Crowd regions can match several detections.
+                    let crowd = image.gt_crowd[gt];
+                    if matched[index] && !crowd {
+                        continue;
+                    }
+                    // Stop when reaching ignored annotations after a match.
+                    if let Some(best_gt) = best_gt {
+                        if !ignored(gts[best_gt]) && ignored(gt) {
+                            break;
+                        }
+                    }
+                    let iou = iou(&image.dt_boxes[dt], &image.gt_boxes[gt], crowd);
+                    if iou < best_iou {
+                        continue;
+                    }
+                    best_iou = iou;
+                    best_gt = Some(index);
+                }
+                let score = image.dt_scores[dt];
+                match best_gt {
+                    Some(index) => {
+                        matched[index] = true;
+                        detections.push((score, true, ignored(gts[index])))
+                    }
+                    None => detections.push((score, false, false)),
+                }
+            }
+        }
+        if npositives == 0 {
+            return None;
+        }
+        detections.sort_by(|d1, d2| d2.0.partial_cmp(&d1.0).unwrap_or(Ordering::Equal));
+        let mut recalls = vec![];
+        let mut precisions = vec![];
+        let (mut tp, mut fp) = (0., 0.);
+        for &(_, is_tp, ignored) in detections.iter() {
+            if ignored {
+                continue;
+            }
+            if is_tp {
+                tp += 1.
+            } else {
+                fp += 1.
+            }
+            recalls.push(tp / npositives as f64);
+            precisions.push(tp / (tp + fp));
+        }
+        for i in (1..precisions.len()).rev() {
+            if precisions[i] > precisions[i - 1] {
+                precisions[i - 1] = precisions[i]
+            }
+        }
+        let sum: f64 = (0..=100)
+            .map(|i| {
+                let recall = i as f64 / 100.;
+                match recalls.iter().position(|&r| r >= recall) {
+                    Some(index) => precisions[index],
+                    None => 0.,
+                }
+            })
+            .sum();
+        Some(sum / 101.)
+    }
+
+    /// Computes the mean average precision over the images added so far.
+    pub fn summarize(&self) -> MapSummary {
+        let classes: BTreeSet<i64> = self
+            .images
+            .iter()
+            .flat_map(|image| image.gt_labels.iter().copied())
+            .collect();
+        let mean = |values: &[f64]| {
+            if values.is_empty() {
+                f64::NAN
+            } else {
+                values.iter().sum::<f64>() / values.len() as f64
+            }
+        };
+        // The average precision per class and per IoU threshold.
+        let aps: Vec<(i64, Vec<f64>)> = classes
+            .iter()
+            .filter_map(|&class| {
+                let aps: Option<Vec<f64>> = self
+                    .iou_thresholds
+                    .iter()
+                    .map(|&threshold| self.average_precision(class, threshold))
+                    .collect();
+                aps.map(|aps| (class, aps))
+            })
+            .collect();
+        let per_iou_threshold = self
+            .iou_thresholds
+            .iter()
+            .enumerate()
+            .map(|(index, &threshold)| {
+                let aps: Vec<f64> = aps.iter().map(|(_, aps)| aps[index]).collect();
+                (threshold, mean(&aps))
+            })
+            .collect();
+        let all_aps: Vec<f64> = aps
+            .iter()
+            .flat_map(|(_, aps)| aps.iter().copied())
+            .collect();
+        MapSummary {
+            map: mean(&all_aps),
+            per_iou_threshold,
+            per_class: aps.iter().map(|(class, aps)| (*class, mean(aps))).collect(),
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.images.clear()
+    }
+}
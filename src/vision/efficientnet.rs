@@ -204,7 +204,8 @@ fn block(p: nn::Path, args: BlockArgs) -> impl ModuleT {
     })
 }
 
-fn efficientnet(p: &nn::Path, params: Params, nclasses: i64) -> impl ModuleT {
+// Returns the feature extractor and its number of output channels.
+fn features(p: &nn::Path, params: Params) -> (nn::IntermediateLayerGetter, i64) {
     let args = block_args();
     let bn2d = nn::BatchNormConfig {
         momentum: 1.0 - BATCH_NORM_MOMENTUM,
@@ -223,16 +224,18 @@ fn efficientnet(p: &nn::Path, params: Params, nclasses: i64) -> impl ModuleT {
     let out_c = params.round_filters(32);
     let conv_stem = conv2d(p / "_conv_stem", 3, out_c, 3, conv_s2);
     let bn0 = nn::batch_norm2d(p / "_bn0", out_c, bn2d);
-    let mut blocks = nn::seq_t();
+    let mut stages =
+        nn::seq_t().add_fn_t(move |xs, train| xs.apply(&conv_stem).apply_t(&bn0, train).swish());
     let block_p = p / "_blocks";
     let mut block_idx = 0;
+    // Each stage groups the blocks sharing the same block arguments.
     for &arg in args.iter() {
         let arg = BlockArgs {
             input_filters: params.round_filters(arg.input_filters),
             output_filters: params.round_filters(arg.output_filters),
             ..arg
         };
-        blocks = blocks.add(block(&block_p / block_idx, arg));
+        let mut stage = nn::seq_t().add(block(&block_p / block_idx, arg));
         block_idx += 1;
         let arg = BlockArgs {
             input_filters: arg.output_filters,
@@ -240,25 +243,30 @@ fn efficientnet(p: &nn::Path, params: Params, nclasses: i64) -> impl ModuleT {
             ..arg
         };
         for _i in 1..params.round_repeats(arg.num_repeat) {
-            blocks = blocks.add(block(&block_p / block_idx, arg));
+            stage = stage.add(block(&block_p / block_idx, arg));
             block_idx += 1;
         }
+        stages = stages.add(stage);
     }
     let in_channels = params.round_filters(args.last().unwrap().output_filters);
     let out_c = params.round_filters(1280);
     let conv_head = conv2d(p / "_conv_head", in_channels, out_c, 1, conv_no_bias);
     let bn1 = nn::batch_norm2d(p / "_bn1", out_c, bn2d);
+    stages = stages.add_fn_t(move |xs, train| xs.apply(&conv_head).apply_t(&bn1, train).swish());
+    let features = nn::IntermediateLayerGetter::new(
+        stages,
+        &[(2, "layer1"), (3, "layer2"), (5, "layer3"), (8, "layer4")],
+    );
+    (features, out_c)
+}
+
+fn efficientnet(p: &nn::Path, params: Params, nclasses: i64) -> impl ModuleT {
+    let (features, out_c) = features(p, params);
     let classifier = nn::seq_t()
         .add_fn_t(|xs, train| xs.dropout(0.2, train))
         .add(nn::linear(p / "_fc", out_c, nclasses, Default::default()));
     nn::func_t(move |xs, train| {
-        xs.apply(&conv_stem)
-            .apply_t(&bn0, train)
-            .swish()
-            .apply_t(&blocks, train)
-            .apply(&conv_head)
-            .apply_t(&bn1, train)
-            .swish()
+        xs.apply_t(&features, train)
             .adaptive_avg_pool2d(&[1, 1])
             .squeeze1(-1)
             .squeeze1(-1)
@@ -290,3 +298,37 @@ pub fn b6(p: &nn::Path, nclasses: i64) -> impl ModuleT {
 pub fn b7(p: &nn::Path, nclasses: i64) -> impl ModuleT {
     efficientnet(p, Params::b7(), nclasses)
 }
+
+/// Creates an EfficientNet-B0 feature extractor returning the outputs of the
+/// `layer1` to `layer4` stages, with strides of 4, 8, 16, and 32.
+pub fn b0_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    features(p, Params::b0()).0
+}
+/// Creates an EfficientNet-B1 feature extractor, see `b0_features`.
+pub fn b1_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    features(p, Params::b1()).0
+}
+/// Creates an EfficientNet-B2 feature extractor, see `b0_features`.
+pub fn b2_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    features(p, Params::b2()).0
+}
+/// Creates an EfficientNet-B3 feature extractor, see `b0_features`.
+pub fn b3_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    features(p, Params::b3()).0
+}
+/// Creates an EfficientNet-B4 feature extractor, see `b0_features`.
+pub fn b4_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    features(p, Params::b4()).0
+}
+/// Creates an EfficientNet-B5 feature extractor, see `b0_features`.
+pub fn b5_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    features(p, Params::b5()).0
+}
+/// Creates an EfficientNet-B6 feature extractor, see `b0_features`.
+pub fn b6_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    features(p, Params::b6()).0
+}
+/// Creates an EfficientNet-B7 feature extractor, see `b0_features`.
+pub fn b7_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    features(p, Params::b7()).0
+}
@@ -1,5 +1,5 @@
 //! Utility functions to manipulate images.
-use crate::wrappers::image::{load_hwc, resize_hwc, save_hwc};
+use crate::wrappers::image::{encode_hwc, load_hwc, load_hwc_from_memory, resize_hwc, save_hwc};
 use crate::{Device, TchError, Tensor};
 use std::io;
 use std::path::Path;
@@ -14,12 +14,259 @@ fn chw_to_hwc(tensor: &Tensor) -> Tensor {
 
 /// Loads an image from a file.
 ///
+/// The image is converted to rgb, see `load_with_config` for other modes.
 /// On success returns a tensor of shape [channel, height, width].
 pub fn load<T: AsRef<Path>>(path: T) -> Result<Tensor, TchError> {
     let tensor = load_hwc(path)?;
     Ok(hwc_to_chw(&tensor))
 }
 
+/// The channels to decode an image to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelMode {
+    /// Keeps the channels stored in the image, e.g. grayscale images result
+    /// in a single channel and images with transparency in an alpha channel.
+    Unchanged,
+    /// A single grayscale channel.
+    L,
+    /// Red, green, and blue channels.
+    Rgb,
+    /// Red, green, blue, and alpha channels.
+    Rgba,
+}
+
+impl ChannelMode {
+    fn channels(self) -> i64 {
+        match self {
+            ChannelMode::Unchanged => 0,
+            ChannelMode::L => 1,
+            ChannelMode::Rgb => 3,
+            ChannelMode::Rgba => 4,
+        }
+    }
+}
+
+/// Configuration for decoding images.
+#[derive(Debug, Clone, Copy)]
+pub struct DecodeConfig {
+    /// The channels of the resulting tensor.
+    pub mode: ChannelMode,
+    /// Rotates and flips jpeg and png images according to their EXIF
+    /// orientation tag.
+    pub apply_exif_orientation: bool,
+    /// Returns 16 bits images as int32 tensors with values ranging from
+    /// 0 to 65535. Otherwise these images are converted to 8 bits.
+    pub allow_16_bit: bool,
+}
+
+impl Default for DecodeConfig {
+    fn default() -> Self {
+        DecodeConfig {
+            mode: ChannelMode::Rgb,
+            apply_exif_orientation: false,
+            allow_16_bit: false,
+        }
+    }
+}
+
+/// Decodes an image stored in memory, e.g. the content of an image file.
+///
+/// Supported formats are jpeg, png, bmp, tga, gif, psd, hdr, pic, and pnm.
+/// On success returns a uint8 tensor of shape [3, height, width].
+pub fn load_from_bytes(data: &[u8]) -> Result<Tensor, TchError> {
+    load_from_bytes_with_config(data, &DecodeConfig::default())
+}
+
+/// Decodes an image stored in memory using the specified configuration.
+///
+/// On success returns a tensor of shape [channel, height, width].
+pub fn load_from_bytes_with_config(data: &[u8], config: &DecodeConfig) -> Result<Tensor, TchError> {
+    let tensor = load_hwc_from_memory(data, config.mode.channels(), config.allow_16_bit)?;
+    let tensor = hwc_to_chw(&tensor);
+    if config.apply_exif_orientation {
+        match exif_orientation(data) {
+            Some(orientation) => apply_orientation(&tensor, orientation),
+            None => Ok(tensor),
+        }
+    } else {
+        Ok(tensor)
+    }
+}
+
+/// Loads an image from a file using the specified configuration.
+///
+/// On success returns a tensor of shape [channel, height, width].
+pub fn load_with_config<T: AsRef<Path>>(
+    path: T,
+    config: &DecodeConfig,
+) -> Result<Tensor, TchError> {
+    let data = std::fs::read(path)?;
+    load_from_bytes_with_config(&data, config)
+}
+
+// Returns the TIFF data holding the EXIF metadata of a jpeg or png image.
+fn exif_data(data: &[u8]) -> Option<&[u8]> {
+    if data.starts_with(&[0xff, 0xd8]) {
+        let mut pos = 2;
+        while pos + 4 <= data.len() && data[pos] == 0xff {
+            let marker = data[pos + 1];
+            // Start of scan or end of image, the metadata come before these.
+            if marker == 0xda || marker == 0xd9 {
+                return None;
+            }
+            let len = u16::from_be_bytes([data[pos + 2], data[pos + 3]]) as usize;
+            let segment = data.get(pos + 4..pos + 2 + len)?;
+            if marker == 0xe1 && segment.starts_with(b"Exif\0\0") {
+                return Some(&segment[6..]);
+            }
+            pos += 2 + len;
+        }
+        None
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        let mut pos = 8;
+        while pos + 8 <= data.len() {
+            let len = u32::from_be_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]]);
+            let chunk = data.get(pos + 8..pos + 8 + len as usize)?;
+            match &data[pos + 4..pos + 8] {
+                b"eXIf" => return Some(chunk),
+                b"IDAT" | b"IEND" => return None,
+                _ => {}
+            }
+            // Skips the chunk type, data, and crc.
+            pos += 12 + len as usize;
+        }
+        None
+    } else {
+        None
+    }
+}
+
+// Returns the value of the orientation tag from the EXIF metadata if any.
+fn exif_orientation(data: &[u8]) -> Option<u16> {
+    let tiff = exif_data(data)?;
+    let little_endian = match tiff.get(0..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let u16_at = |pos: usize| -> Option<u16> {
+        let b = tiff.get(pos..pos + 2)?;
+        Some(if little_endian {
+            u16::from_le_bytes([b[0], b[1]])
+        } else {
+            u16::from_be_bytes([b[0], b[1]])
+        })
+    };
+    let u32_at = |pos: usize| -> Option<u32> {
+        let b = tiff.get(pos..pos + 4)?;
+        let b = [b[0], b[1], b[2], b[3]];
+        Some(if little_endian {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    };
+    if u16_at(2)? != 42 {
+        return None;
+    }
+    let ifd = u32_at(4)? as usize;
+    let entries = u16_at(ifd)? as usize;
+    for i in 0..entries {
+        let entry = ifd + 2 + 12 * i;
+        // The orientation tag is stored as a single short value.
+        if u16_at(entry)? == 0x0112 {
+            return u16_at(entry + 8);
+        }
+    }
+    None
+}
+
+// Applies an EXIF orientation to a tensor of shape [channel, height, width].
+fn apply_orientation(t: &Tensor, orientation: u16) -> Result<Tensor, TchError> {
+    let t = match orientation {
+        2 => t.f_flip(&[2])?,
+        3 => t.f_flip(&[1, 2])?,
+        4 => t.f_flip(&[1])?,
+        5 => t.f_transpose(1, 2)?,
+        6 => t.f_transpose(1, 2)?.f_flip(&[2])?,
+        7 => t.f_transpose(1, 2)?.f_flip(&[1, 2])?,
+        8 => t.f_transpose(1, 2)?.f_flip(&[1])?,
+        _ => return Ok(t.shallow_clone()),
+    };
+    t.f_contiguous()
+}
+
+/// The formats that images can be encoded to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    /// Jpeg encoding with a quality ranging from 1 to 100.
+    Jpeg {
+        quality: u8,
+    },
+    Bmp,
+    Tga,
+}
+
+impl ImageFormat {
+    /// Returns the format matching a filename suffix, png is used for unknown
+    /// suffixes and jpeg images use a quality of 90.
+    pub fn from_path<T: AsRef<Path>>(path: T) -> ImageFormat {
+        let extension = path
+            .as_ref()
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+        match extension.as_deref() {
+            Some("jpg") | Some("jpeg") => ImageFormat::Jpeg { quality: 90 },
+            Some("bmp") => ImageFormat::Bmp,
+            Some("tga") => ImageFormat::Tga,
+            _ => ImageFormat::Png,
+        }
+    }
+}
+
+// Converts a tensor of shape [channel, height, width], or [1, channel, height, width],
+// to a uint8 cpu tensor of shape [height, width, channel].
+fn to_hwc_uint8(t: &Tensor) -> Result<Tensor, TchError> {
+    let t = t.f_to_kind(crate::Kind::Uint8)?;
+    match t.size().as_slice() {
+        [1, _, _, _] => chw_to_hwc(&t.f_squeeze1(0)?).f_to_device(Device::Cpu),
+        [_, _, _] => chw_to_hwc(&t).f_to_device(Device::Cpu),
+        sz => Err(TchError::FileFormat(format!(
+            "unexpected size for image tensor {:?}",
+            sz
+        ))),
+    }
+}
+
+/// Encodes an image in memory.
+///
+/// This expects as input a tensor of shape [channel, height, width] with
+/// 1 (grayscale), 2 (grayscale and alpha), 3 (rgb), or 4 (rgba) channels.
+/// The tensor input should be of kind UInt8 with values ranging from
+/// 0 to 255.
+pub fn encode_to_vec(t: &Tensor, format: ImageFormat) -> Result<Vec<u8>, TchError> {
+    let t = to_hwc_uint8(t)?;
+    match format {
+        ImageFormat::Png => encode_hwc(&t, 0, 0),
+        ImageFormat::Jpeg { quality } => encode_hwc(&t, 1, quality.clamp(1, 100) as i64),
+        ImageFormat::Bmp => encode_hwc(&t, 2, 0),
+        ImageFormat::Tga => encode_hwc(&t, 3, 0),
+    }
+}
+
+/// Saves an image to a file using the specified format.
+pub fn save_with_format<T: AsRef<Path>>(
+    t: &Tensor,
+    path: T,
+    format: ImageFormat,
+) -> Result<(), TchError> {
+    let data = encode_to_vec(t, format)?;
+    std::fs::write(path, data)?;
+    Ok(())
+}
+
 /// Saves an image to a file.
 ///
 /// This expects as input a tensor of shape [channel, height, width].
@@ -27,16 +274,9 @@ pub fn load<T: AsRef<Path>>(path: T) -> Result<Tensor, TchError> {
 /// are jpg, png, tga, and bmp.
 /// The tensor input should be of kind UInt8 with values ranging from
 /// 0 to 255.
+/// Use `save_with_format` to set the jpeg quality.
 pub fn save<T: AsRef<Path>>(t: &Tensor, path: T) -> Result<(), TchError> {
-    let t = t.to_kind(crate::Kind::Uint8);
-    match t.size().as_slice() {
-        [1, _, _, _] => save_hwc(&chw_to_hwc(&t.squeeze1(0)).to_device(Device::Cpu), path),
-        [_, _, _] => save_hwc(&chw_to_hwc(&t).to_device(Device::Cpu), path),
-        sz => Err(TchError::FileFormat(format!(
-            "unexpected size for image tensor {:?}",
-            sz
-        ))),
-    }
+    save_hwc(&to_hwc_uint8(t)?, path)
 }
 
 /// Resizes an image.
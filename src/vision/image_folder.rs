@@ -0,0 +1,408 @@
+//! A dataset of images stored in a directory with a subdirectory per class.
+//!
+//! The images are only decoded when iterating over the dataset so that large
+//! datasets do not have to fit in memory.
+//!
+//! ```ignore
+//! use tch::vision::{image_folder::ImageFolder, transforms::*};
+//!
+//! let dataset = ImageFolder::new("data/train")?.with_transform(
+//!     compose()
+//!         .add(ToFloat)
+//!         .add(RandomResizedCrop::new(224, 224))
+//!         .add(Normalize::imagenet()),
+//! );
+//! for batch in dataset.iter(64).shuffle().num_workers(4) {
+//!     let (images, labels) = batch?;
+//!     ...
+//! }
+//! ```
+use super::transforms::Transform;
+use crate::{Device, TchError, Tensor};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Arc, Mutex};
+
+/// A dataset of labeled images.
+///
+/// The root directory should contain one subdirectory per class, the
+/// images of each class being stored in the corresponding subdirectory or in
+/// nested directories. Classes are indexed in the alphabetical order of their
+/// directory names.
+#[derive(Debug, Clone)]
+pub struct ImageFolder {
+    samples: Arc<Vec<(PathBuf, i64)>>,
+    classes: Vec<String>,
+    class_to_idx: HashMap<String, i64>,
+    transform: Option<Arc<dyn Transform>>,
+}
+
+fn visit_dirs(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), TchError> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            visit_dirs(&path, files)?;
+        } else if super::imagenet::has_image_suffix(&path) {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+impl ImageFolder {
+    /// Scans a directory and returns the corresponding dataset.
+    ///
+    /// Only the file names are read at this point, an error is returned if no
+    /// class subdirectory or no image can be found, or if a class directory
+    /// name is not valid utf-8.
+    pub fn new<T: AsRef<Path>>(root: T) -> Result<ImageFolder, TchError> {
+        let root = root.as_ref();
+        let mut classes = vec![];
+        for entry in std::fs::read_dir(root)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                match path.file_name().and_then(|n| n.to_str()) {
+                    Some(name) => classes.push(name.to_string()),
+                    None => {
+                        return Err(TchError::Convert(format!(
+                            "class directory name {:?} is not valid utf-8",
+                            path
+                        )))
+                    }
+                }
+            }
+        }
+        classes.sort();
+        let mut samples = vec![];
+        for (label, class) in classes.iter().enumerate() {
+            let mut files = vec![];
+            visit_dirs(&root.join(class), &mut files)?;
+            files.sort();
+            samples.extend(files.into_iter().map(|f| (f, label as i64)))
+        }
+        if samples.is_empty() {
+            return Err(TchError::Io(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("no image found in {:?}", root),
+            )));
+        }
+        let class_to_idx = classes
+            .iter()
+            .enumerate()
+            .map(|(i, c)| (c.to_string(), i as i64))
+            .collect();
+        Ok(ImageFolder {
+            samples: Arc::new(samples),
+            classes,
+            class_to_idx,
+            transform: None,
+        })
+    }
+
+    /// Sets the transform applied to each image after decoding.
+    ///
+    /// Without a transform, images are returned as uint8 tensors of shape
+    /// [channel, height, width].
+    pub fn with_transform<T: Transform + 'static>(mut self, transform: T) -> ImageFolder {
+        self.transform = Some(Arc::new(transform));
+        self
+    }
+
+    /// The number of images in the dataset.
+    pub fn len(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// Returns true if the dataset does not contain any image.
+    pub fn is_empty(&self) -> bool {
+        self.samples.is_empty()
+    }
+
+    /// The class names, sorted by class index.
+    pub fn classes(&self) -> &[String] {
+        &self.classes
+    }
+
+    /// The mapping from class names to class indexes.
+    pub fn class_to_idx(&self) -> &HashMap<String, i64> {
+        &self.class_to_idx
+    }
+
+    /// The image paths together with their class indexes.
+    pub fn samples(&self) -> &[(PathBuf, i64)] {
+        &self.samples
+    }
+
+    /// Decodes an image and applies the transform, returns the image together
+    /// with its class index.
+    pub fn f_get(
+        &self,
+        index: usize,
+        rng: &mut dyn rand::RngCore,
+    ) -> Result<(Tensor, i64), TchError> {
+        let (path, label) = match self.samples.get(index) {
+            Some(sample) => sample,
+            None => {
+                return Err(TchError::Shape(format!(
+                    "index {} out of range for a dataset of {} images",
+                    index,
+                    self.samples.len()
+                )))
+            }
+        };
+        load(path, &self.transform, rng).map(|image| (image, *label))
+    }
+
+    /// Returns an iterator over mini-batches of images and labels.
+    pub fn iter(&self, batch_size: usize) -> ImageFolderIter {
+        ImageFolderIter {
+            dataset: self.clone(),
+            batch_size: batch_size.max(1),
+            shuffle: false,
+            seed: None,
+            num_workers: 0,
+            device: Device::Cpu,
+            return_smaller_last_batch: false,
+            state: None,
+        }
+    }
+}
+
+fn load(
+    path: &Path,
+    transform: &Option<Arc<dyn Transform>>,
+    rng: &mut dyn rand::RngCore,
+) -> Result<Tensor, TchError> {
+    let image = super::image::load(path)
+        .map_err(|err| TchError::FileFormat(format!("unable to load image {:?}: {}", path, err)))?;
+    match transform {
+        None => Ok(image),
+        Some(transform) => transform.apply(&image, rng),
+    }
+}
+
+// The indexes of the images in each batch, the random number generators used
+// by the transforms are seeded using the batch index so that the results do
+// not depend on the number of workers.
+#[derive(Debug)]
+struct Batches {
+    batches: Vec<Vec<usize>>,
+    seed: u64,
+}
+
+fn load_batch(
+    dataset: &ImageFolder,
+    batches: &Batches,
+    batch_index: usize,
+) -> Result<(Tensor, Tensor), TchError> {
+    let mut rng = StdRng::seed_from_u64(batches.seed.wrapping_add(batch_index as u64));
+    let mut images = vec![];
+    let mut labels = vec![];
+    for &index in batches.batches[batch_index].iter() {
+        let (image, label) = dataset.f_get(index, &mut rng)?;
+        images.push(image);
+        labels.push(label);
+    }
+    Ok((Tensor::f_stack(&images, 0)?, Tensor::of_slice(&labels)))
+}
+
+type BatchResult = (usize, Result<(Tensor, Tensor), TchError>);
+
+// The batch indexes are handed to the workers by the iterator, at most
+// `prefetch` batches are being loaded or waiting to be returned at any time so
+// that the batches completed out of order do not accumulate.
+#[derive(Debug)]
+struct Workers {
+    jobs: Option<mpsc::Sender<usize>>,
+    receiver: Option<mpsc::Receiver<BatchResult>>,
+    handles: Vec<std::thread::JoinHandle<()>>,
+    stop: Arc<AtomicBool>,
+    prefetch: usize,
+    pending: HashMap<usize, Result<(Tensor, Tensor), TchError>>,
+}
+
+impl Drop for Workers {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        // Dropping the channels unblocks the workers waiting for a batch index
+        // or waiting to send a batch.
+        self.jobs = None;
+        self.receiver = None;
+        for handle in self.handles.drain(..) {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[derive(Debug)]
+struct IterState {
+    batches: Arc<Batches>,
+    next_batch: usize,
+    workers: Option<Workers>,
+}
+
+/// An iterator over mini-batches of an `ImageFolder` dataset.
+///
+/// Each item contains a batch of images and the corresponding labels, or the
+/// error that occurred when loading one of the images of the batch.
+#[derive(Debug)]
+pub struct ImageFolderIter {
+    dataset: ImageFolder,
+    batch_size: usize,
+    shuffle: bool,
+    seed: Option<u64>,
+    num_workers: usize,
+    device: Device,
+    return_smaller_last_batch: bool,
+    state: Option<IterState>,
+}
+
+impl ImageFolderIter {
+    /// Shuffles the images before grouping them in mini-batches.
+    pub fn shuffle(mut self) -> Self {
+        self.shuffle = true;
+        self
+    }
+
+    /// Seeds the shuffling and the random transforms.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Decodes the images using `num_workers` background threads, with 0
+    /// the images are decoded in the iterating thread.
+    pub fn num_workers(mut self, num_workers: usize) -> Self {
+        self.num_workers = num_workers;
+        self
+    }
+
+    /// Transfers the mini-batches to a specified device.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn to_device(mut self, device: Device) -> Self {
+        self.device = device;
+        self
+    }
+
+    /// When set, returns the last batch even if smaller than the batch size.
+    pub fn return_smaller_last_batch(mut self) -> Self {
+        self.return_smaller_last_batch = true;
+        self
+    }
+
+    fn start(&self) -> IterState {
+        let seed = self.seed.unwrap_or_else(rand::random);
+        let mut indexes = (0..self.dataset.len()).collect::<Vec<_>>();
+        if self.shuffle {
+            indexes.shuffle(&mut StdRng::seed_from_u64(seed));
+        }
+        let batches = indexes
+            .chunks(self.batch_size)
+            .filter(|b| self.return_smaller_last_batch || b.len() == self.batch_size)
+            .map(|b| b.to_vec())
+            .collect();
+        let batches = Arc::new(Batches { batches, seed });
+        let workers = if self.num_workers == 0 {
+            None
+        } else {
+            let prefetch = 2 * self.num_workers;
+            let (sender, receiver) = mpsc::sync_channel(prefetch);
+            let (jobs, job_receiver) = mpsc::channel();
+            for batch_index in 0..prefetch.min(batches.batches.len()) {
+                // The receiver is alive at this point.
+                jobs.send(batch_index).unwrap()
+            }
+            let job_receiver = Arc::new(Mutex::new(job_receiver));
+            let stop = Arc::new(AtomicBool::new(false));
+            let handles = (0..self.num_workers)
+                .map(|_| {
+                    let sender = sender.clone();
+                    let stop = stop.clone();
+                    let job_receiver = job_receiver.clone();
+                    let batches = batches.clone();
+                    let dataset = self.dataset.clone();
+                    std::thread::spawn(move || {
+                        while !stop.load(Ordering::SeqCst) {
+                            let job = match job_receiver.lock() {
+                                Ok(job_receiver) => job_receiver.recv(),
+                                Err(_) => break,
+                            };
+                            let batch_index = match job {
+                                Ok(batch_index) => batch_index,
+                                Err(_) => break,
+                            };
+                            let batch = load_batch(&dataset, &batches, batch_index);
+                            if sender.send((batch_index, batch)).is_err() {
+                                break;
+                            }
+                        }
+                    })
+                })
+                .collect();
+            Some(Workers {
+                jobs: Some(jobs),
+                receiver: Some(receiver),
+                handles,
+                stop,
+                prefetch,
+                pending: HashMap::new(),
+            })
+        };
+        IterState {
+            batches,
+            next_batch: 0,
+            workers,
+        }
+    }
+}
+
+impl Iterator for ImageFolderIter {
+    type Item = Result<(Tensor, Tensor), TchError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.state.is_none() {
+            self.state = Some(self.start())
+        }
+        let dataset = &self.dataset;
+        let state = self.state.as_mut().unwrap();
+        let batch_index = state.next_batch;
+        if batch_index >= state.batches.batches.len() {
+            return None;
+        }
+        state.next_batch += 1;
+        let batch = match state.workers.as_mut() {
+            None => load_batch(dataset, &state.batches, batch_index),
+            Some(workers) => loop {
+                // Batches may be completed out of order by the workers.
+                if let Some(batch) = workers.pending.remove(&batch_index) {
+                    let next_job = batch_index + workers.prefetch;
+                    if next_job < state.batches.batches.len() {
+                        if let Some(jobs) = workers.jobs.as_ref() {
+                            // A send error means that the workers have terminated,
+                            // this is reported by `recv` below.
+                            let _ = jobs.send(next_job);
+                        }
+                    }
+                    break batch;
+                }
+                match workers.receiver.as_ref().unwrap().recv() {
+                    Ok((index, batch)) => {
+                        let _ = workers.pending.insert(index, batch);
+                    }
+                    Err(_) => {
+                        break Err(TchError::Kind(
+                            "image folder worker thread terminated".to_string(),
+                        ))
+                    }
+                }
+            },
+        };
+        let device = self.device;
+        Some(batch.and_then(|(xs, ys)| Ok((xs.f_to_device(device)?, ys.f_to_device(device)?))))
+    }
+}
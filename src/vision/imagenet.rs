@@ -52,7 +52,7 @@ pub fn load_image_and_resize<T: AsRef<Path>>(path: T, w: i64, h: i64) -> Result<
     normalize(&super::image::load_and_resize(path, w, h)?)
 }
 
-fn has_image_suffix<T: AsRef<Path>>(path: T) -> bool {
+pub(crate) fn has_image_suffix<T: AsRef<Path>>(path: T) -> bool {
     match path.as_ref().extension() {
         None => false,
         Some(extension) => match extension.to_str() {
@@ -96,6 +96,8 @@ fn load_images_from_dir(dir: std::path::PathBuf) -> Result<Tensor, TchError> {
 /// In each of these datasets, there should be a subdirectory per class named
 /// in the same way.
 /// The ImageNet normalization is applied, image are resized to 224x224.
+/// All the images are loaded in memory, see `image_folder::ImageFolder` for
+/// a dataset that decodes the images on demand.
 pub fn load_from_dir<T: AsRef<Path>>(dir: T) -> Result<Dataset, TchError> {
     let train_path = dir.as_ref().join("train");
     let valid_path = dir.as_ref().join("val");
@@ -104,7 +106,6 @@ pub fn load_from_dir<T: AsRef<Path>>(dir: T) -> Result<Dataset, TchError> {
         .filter(|d| d.is_dir())
         .filter_map(|d| d.file_name().map(|d| d.to_os_string()))
         .collect::<Vec<_>>();
-    println!("classes: {:?}", classes);
     let mut train_images: Vec<Tensor> = vec![];
     let mut train_labels: Vec<Tensor> = vec![];
     let mut test_images: Vec<Tensor> = vec![];
@@ -153,7 +153,7 @@ fn inception_e(p: nn::Path, c_in: i64) -> impl ModuleT {
     })
 }
 
-pub fn v3(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+fn features(p: &nn::Path) -> nn::SequentialT {
     nn::seq_t()
         .add(conv_bn(p / "Conv2d_1a_3x3", 3, 32, 3, 0, 2))
         .add(conv_bn(p / "Conv2d_2a_3x3", 32, 32, 3, 0, 1))
@@ -173,6 +173,11 @@ pub fn v3(p: &nn::Path, nclasses: i64) -> impl ModuleT {
         .add(inception_d(p / "Mixed_7a", 768))
         .add(inception_e(p / "Mixed_7b", 1280))
         .add(inception_e(p / "Mixed_7c", 2048))
+}
+
+pub fn v3(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    nn::seq_t()
+        .add(features(p))
         .add_fn_t(|xs, train| {
             xs.adaptive_avg_pool2d(&[1, 1])
                 .dropout(0.5, train)
@@ -180,3 +185,19 @@ pub fn v3(p: &nn::Path, nclasses: i64) -> impl ModuleT {
         })
         .add(nn::linear(p / "fc", 2048, nclasses, Default::default()))
 }
+
+/// Creates an InceptionV3 feature extractor returning the outputs of the
+/// `Conv2d_2b_3x3`, `Conv2d_4a_3x3`, `Mixed_5d`, `Mixed_6e`, and `Mixed_7c`
+/// layers, with strides of 2, 4, 8, 16, and 32.
+pub fn v3_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    nn::IntermediateLayerGetter::new(
+        features(p),
+        &[
+            (2, "Conv2d_2b_3x3"),
+            (5, "Conv2d_4a_3x3"),
+            (9, "Mixed_5d"),
+            (14, "Mixed_6e"),
+            (17, "Mixed_7c"),
+        ],
+    )
+}
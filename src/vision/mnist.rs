@@ -2,10 +2,52 @@
 //!
 //! The files can be obtained from the following link:
 //! http://yann.lecun.com/exdb/mnist/
+//!
+//! The Fashion-MNIST and KMNIST datasets use the same file format and names
+//! and can be loaded with the same functions. The files can either be
+//! extracted or left gzip compressed with a `.gz` extension.
 use super::dataset::Dataset;
 use crate::{Kind, Tensor};
+use flate2::read::GzDecoder;
 use std::fs::File;
-use std::io::{self, BufReader, Read, Result};
+use std::io::{self, BufRead, BufReader, Read, Result};
+use std::path::{Path, PathBuf};
+
+/// The Fashion-MNIST class names, see
+/// https://github.com/zalandoresearch/fashion-mnist
+pub const FASHION_CLASSES: [&str; 10] = [
+    "T-shirt/top",
+    "Trouser",
+    "Pullover",
+    "Dress",
+    "Coat",
+    "Sandal",
+    "Shirt",
+    "Sneaker",
+    "Bag",
+    "Ankle boot",
+];
+
+/// The KMNIST class names, see https://github.com/rois-codh/kmnist
+pub const KMNIST_CLASSES: [&str; 10] = ["o", "ki", "su", "tsu", "na", "ha", "ma", "ya", "re", "wo"];
+
+// Opens a file that is possibly gzip compressed, when the file does not exist
+// the same file with a .gz extension is used.
+fn open(filename: &Path) -> Result<Box<dyn Read>> {
+    let filename = if filename.exists() {
+        filename.to_path_buf()
+    } else {
+        let mut filename = filename.as_os_str().to_owned();
+        filename.push(".gz");
+        PathBuf::from(filename)
+    };
+    let mut buf_reader = BufReader::new(File::open(&filename)?);
+    if buf_reader.fill_buf()?.starts_with(&[0x1f, 0x8b]) {
+        Ok(Box::new(GzDecoder::new(buf_reader)))
+    } else {
+        Ok(Box::new(buf_reader))
+    }
+}
 
 fn read_u32<T: Read>(reader: &mut T) -> Result<u32> {
     let mut b = vec![0u8; 4];
@@ -27,8 +69,8 @@ fn check_magic_number<T: Read>(reader: &mut T, expected: u32) -> Result<()> {
     Ok(())
 }
 
-fn read_labels_(filename: &std::path::Path) -> Result<Tensor> {
-    let mut buf_reader = BufReader::new(File::open(filename)?);
+fn read_labels_(filename: &Path) -> Result<Tensor> {
+    let mut buf_reader = open(filename)?;
     check_magic_number(&mut buf_reader, 2049)?;
     let samples = read_u32(&mut buf_reader)?;
     let mut data = vec![0u8; samples as usize];
@@ -36,8 +78,8 @@ fn read_labels_(filename: &std::path::Path) -> Result<Tensor> {
     Ok(Tensor::of_slice(&data).to_kind(Kind::Int64))
 }
 
-fn read_images_(filename: &std::path::Path) -> Result<Tensor> {
-    let mut buf_reader = BufReader::new(File::open(filename)?);
+fn read_images_(filename: &Path) -> Result<Tensor> {
+    let mut buf_reader = open(filename)?;
     check_magic_number(&mut buf_reader, 2051)?;
     let samples = read_u32(&mut buf_reader)?;
     let rows = read_u32(&mut buf_reader)?;
@@ -51,17 +93,20 @@ fn read_images_(filename: &std::path::Path) -> Result<Tensor> {
     Ok(tensor / 255.)
 }
 
-fn read_labels(filename: &std::path::Path) -> Result<Tensor> {
+fn read_labels(filename: &Path) -> Result<Tensor> {
     read_labels_(filename)
         .map_err(|err| std::io::Error::new(err.kind(), format!("{:?} {}", filename, err)))
 }
 
-fn read_images(filename: &std::path::Path) -> Result<Tensor> {
+fn read_images(filename: &Path) -> Result<Tensor> {
     read_images_(filename)
         .map_err(|err| std::io::Error::new(err.kind(), format!("{:?} {}", filename, err)))
 }
 
-pub fn load_dir<T: AsRef<std::path::Path>>(dir: T) -> Result<Dataset> {
+/// Loads the dataset from a directory containing the `train-images-idx3-ubyte`,
+/// `train-labels-idx1-ubyte`, `t10k-images-idx3-ubyte`, and
+/// `t10k-labels-idx1-ubyte` files.
+pub fn load_dir<T: AsRef<Path>>(dir: T) -> Result<Dataset> {
     let dir = dir.as_ref();
     let train_images = read_images(&dir.join("train-images-idx3-ubyte"))?;
     let train_labels = read_labels(&dir.join("train-labels-idx1-ubyte"))?;
@@ -75,3 +120,13 @@ pub fn load_dir<T: AsRef<std::path::Path>>(dir: T) -> Result<Dataset> {
         labels: 10,
     })
 }
+
+/// Loads the Fashion-MNIST dataset, the labels correspond to `FASHION_CLASSES`.
+pub fn load_fashion_dir<T: AsRef<Path>>(dir: T) -> Result<Dataset> {
+    load_dir(dir)
+}
+
+/// Loads the KMNIST dataset, the labels correspond to `KMNIST_CLASSES`.
+pub fn load_kmnist_dir<T: AsRef<Path>>(dir: T) -> Result<Dataset> {
+    load_dir(dir)
+}
@@ -1,6 +1,9 @@
-//! MobileNet V2 implementation.
+//! MobileNet V2 and V3 implementations.
 //! https://ai.googleblog.com/2018/04/mobilenetv2-next-generation-of-on.html
-use crate::nn::{self, ModuleT};
+//!
+//! See "Searching for MobileNetV3" Howard et al. 2019
+//! https://arxiv.org/abs/1905.02244
+use crate::nn::{self, Module, ModuleT};
 
 #[allow(clippy::identity_op)]
 // Conv2D + BatchNorm2D + ReLU6
@@ -51,10 +54,8 @@ const INVERTED_RESIDUAL_SETTINGS: [(i64, i64, i64, i64); 7] = [
     (6, 320, 1, 1),
 ];
 
-#[allow(clippy::identity_op)]
-pub fn v2(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+fn features(p: &nn::Path) -> nn::IntermediateLayerGetter {
     let f_p = p / "features";
-    let c_p = p / "classifier";
     let mut c_in = 32;
     let mut features = nn::seq_t().add(cbr(&f_p / "0", 3, c_in, 3, 2, 1));
     let mut layer_id = 1;
@@ -68,6 +69,17 @@ pub fn v2(p: &nn::Path, nclasses: i64) -> impl ModuleT {
         }
     }
     features = features.add(cbr(&f_p / layer_id, c_in, 1280, 1, 1, 1));
+    // The last layers before each reduction of the spatial resolution.
+    nn::IntermediateLayerGetter::new(
+        features,
+        &[(3, "layer1"), (6, "layer2"), (13, "layer3"), (18, "layer4")],
+    )
+}
+
+#[allow(clippy::identity_op)]
+pub fn v2(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    let c_p = p / "classifier";
+    let features = features(p);
     let classifier = nn::seq_t()
         .add_fn_t(|xs, train| xs.dropout(0.2, train))
         .add(nn::linear(&c_p / 1, 1280, nclasses, Default::default()));
@@ -78,3 +90,216 @@ pub fn v2(p: &nn::Path, nclasses: i64) -> impl ModuleT {
             .apply_t(&classifier, train)
     })
 }
+
+/// Creates a MobileNet V2 feature extractor returning the outputs of the
+/// `layer1` to `layer4` stages, with strides of 4, 8, 16, and 32.
+pub fn v2_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    features(p)
+}
+
+// MobileNet V3.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Activation {
+    ReLU,
+    HardSwish,
+}
+
+// Conv2D + BatchNorm2D + optional activation.
+fn cba(
+    p: nn::Path,
+    c_in: i64,
+    c_out: i64,
+    ks: i64,
+    stride: i64,
+    g: i64,
+    activation: Option<Activation>,
+) -> impl ModuleT {
+    let conv2d = nn::ConvConfig {
+        stride,
+        padding: (ks - 1) / 2,
+        groups: g,
+        bias: false,
+        ..Default::default()
+    };
+    let bn = nn::BatchNormConfig {
+        eps: 0.001,
+        momentum: 0.01,
+        ..Default::default()
+    };
+    nn::seq_t()
+        .add(nn::conv2d(&p / 0, c_in, c_out, ks, conv2d))
+        .add(nn::batch_norm2d(&p / 1, c_out, bn))
+        .add_fn(move |xs| match activation {
+            None => xs.shallow_clone(),
+            Some(Activation::ReLU) => xs.relu(),
+            Some(Activation::HardSwish) => xs.hardswish(),
+        })
+}
+
+// Rounds v to a multiple of divisor, without going below 90% of v. This is
+// also used to round the channel numbers of other models.
+pub(crate) fn make_divisible(v: i64, divisor: i64) -> i64 {
+    let new_v = i64::max(divisor, (v + divisor / 2) / divisor * divisor);
+    if (new_v as f64) < 0.9 * v as f64 {
+        new_v + divisor
+    } else {
+        new_v
+    }
+}
+
+fn squeeze_excitation(p: nn::Path, c: i64) -> impl Module {
+    let c_squeeze = make_divisible(c / 4, 8);
+    let fc1 = nn::conv2d(&p / "fc1", c, c_squeeze, 1, Default::default());
+    let fc2 = nn::conv2d(&p / "fc2", c_squeeze, c, 1, Default::default());
+    nn::func(move |xs| {
+        let scale = xs
+            .adaptive_avg_pool2d(&[1, 1])
+            .apply(&fc1)
+            .relu()
+            .apply(&fc2)
+            .hardsigmoid();
+        xs * scale
+    })
+}
+
+// c_in, kernel size, expanded channels, c_out, squeeze-excitation, activation, stride.
+type BlockConfig = (i64, i64, i64, i64, bool, Activation, i64);
+
+fn inv_v3(p: nn::Path, cfg: BlockConfig) -> impl ModuleT {
+    let (c_in, ks, c_exp, c_out, se, activation, stride) = cfg;
+    let p = &p / "block";
+    let mut block = nn::seq_t();
+    let mut id = 0;
+    if c_exp != c_in {
+        block = block.add(cba(&p / id, c_in, c_exp, 1, 1, 1, Some(activation)));
+        id += 1;
+    }
+    block = block.add(cba(
+        &p / id,
+        c_exp,
+        c_exp,
+        ks,
+        stride,
+        c_exp,
+        Some(activation),
+    ));
+    id += 1;
+    if se {
+        block = block.add(squeeze_excitation(&p / id, c_exp));
+        id += 1;
+    }
+    block = block.add(cba(&p / id, c_exp, c_out, 1, 1, 1, None));
+    nn::func_t(move |xs, train| {
+        let ys = xs.apply_t(&block, train);
+        if stride == 1 && c_in == c_out {
+            xs + ys
+        } else {
+            ys
+        }
+    })
+}
+
+const V3_LARGE_SETTINGS: [BlockConfig; 15] = [
+    (16, 3, 16, 16, false, Activation::ReLU, 1),
+    (16, 3, 64, 24, false, Activation::ReLU, 2),
+    (24, 3, 72, 24, false, Activation::ReLU, 1),
+    (24, 5, 72, 40, true, Activation::ReLU, 2),
+    (40, 5, 120, 40, true, Activation::ReLU, 1),
+    (40, 5, 120, 40, true, Activation::ReLU, 1),
+    (40, 3, 240, 80, false, Activation::HardSwish, 2),
+    (80, 3, 200, 80, false, Activation::HardSwish, 1),
+    (80, 3, 184, 80, false, Activation::HardSwish, 1),
+    (80, 3, 184, 80, false, Activation::HardSwish, 1),
+    (80, 3, 480, 112, true, Activation::HardSwish, 1),
+    (112, 3, 672, 112, true, Activation::HardSwish, 1),
+    (112, 5, 672, 160, true, Activation::HardSwish, 2),
+    (160, 5, 960, 160, true, Activation::HardSwish, 1),
+    (160, 5, 960, 160, true, Activation::HardSwish, 1),
+];
+
+const V3_SMALL_SETTINGS: [BlockConfig; 11] = [
+    (16, 3, 16, 16, true, Activation::ReLU, 2),
+    (16, 3, 72, 24, false, Activation::ReLU, 2),
+    (24, 3, 88, 24, false, Activation::ReLU, 1),
+    (24, 5, 96, 40, true, Activation::HardSwish, 2),
+    (40, 5, 240, 40, true, Activation::HardSwish, 1),
+    (40, 5, 240, 40, true, Activation::HardSwish, 1),
+    (40, 5, 120, 48, true, Activation::HardSwish, 1),
+    (48, 5, 144, 48, true, Activation::HardSwish, 1),
+    (48, 5, 288, 96, true, Activation::HardSwish, 2),
+    (96, 5, 576, 96, true, Activation::HardSwish, 1),
+    (96, 5, 576, 96, true, Activation::HardSwish, 1),
+];
+
+// Returns the feature extractor and its number of output channels.
+fn v3_backbone(p: &nn::Path, settings: &[BlockConfig]) -> (nn::IntermediateLayerGetter, i64) {
+    let f_p = p / "features";
+    let mut features = nn::seq_t().add(cba(&f_p / 0, 3, 16, 3, 2, 1, Some(Activation::HardSwish)));
+    for (layer_id, &cfg) in settings.iter().enumerate() {
+        features = features.add(inv_v3(&f_p / (layer_id + 1), cfg));
+    }
+    let c_in = settings[settings.len() - 1].3;
+    let c_out = 6 * c_in;
+    let last = settings.len() + 1;
+    features = features.add(cba(
+        &f_p / last,
+        c_in,
+        c_out,
+        1,
+        1,
+        1,
+        Some(Activation::HardSwish),
+    ));
+    // The last layers before each reduction of the spatial resolution, the
+    // block using settings[i] is the layer i + 1.
+    let reductions: Vec<usize> = (0..settings.len()).filter(|&i| settings[i].6 > 1).collect();
+    let n = reductions.len();
+    let features = nn::IntermediateLayerGetter::new(
+        features,
+        &[
+            (reductions[n - 3], "layer1"),
+            (reductions[n - 2], "layer2"),
+            (reductions[n - 1], "layer3"),
+            (last, "layer4"),
+        ],
+    );
+    (features, c_out)
+}
+
+fn v3(p: &nn::Path, settings: &[BlockConfig], c_last: i64, nclasses: i64) -> impl ModuleT {
+    let (features, c_out) = v3_backbone(p, settings);
+    let c_p = p / "classifier";
+    let classifier = nn::seq_t()
+        .add(nn::linear(&c_p / 0, c_out, c_last, Default::default()))
+        .add_fn(|xs| xs.hardswish())
+        .add_fn_t(|xs, train| xs.dropout(0.2, train))
+        .add(nn::linear(&c_p / 3, c_last, nclasses, Default::default()));
+    nn::func_t(move |xs, train| {
+        xs.apply_t(&features, train)
+            .adaptive_avg_pool2d(&[1, 1])
+            .flat_view()
+            .apply_t(&classifier, train)
+    })
+}
+
+/// Creates a MobileNet V3 Large model.
+pub fn v3_large(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    v3(p, &V3_LARGE_SETTINGS, 1280, nclasses)
+}
+
+/// Creates a MobileNet V3 Small model.
+pub fn v3_small(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    v3(p, &V3_SMALL_SETTINGS, 1024, nclasses)
+}
+
+/// Creates a MobileNet V3 Large feature extractor returning the outputs of
+/// the `layer1` to `layer4` stages, with strides of 4, 8, 16, and 32.
+pub fn v3_large_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    v3_backbone(p, &V3_LARGE_SETTINGS).0
+}
+
+/// Creates a MobileNet V3 Small feature extractor, see `v3_large_features`.
+pub fn v3_small_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    v3_backbone(p, &V3_SMALL_SETTINGS).0
+}
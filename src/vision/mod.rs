@@ -4,10 +4,22 @@ pub mod dataset;
 
 pub mod image;
 
+pub mod transforms;
+
+pub mod ops;
+
+pub mod darknet;
+
+pub mod image_folder;
+
 pub mod mnist;
 
 pub mod cifar;
 
+pub mod svhn;
+
+pub mod stl10;
+
 pub mod alexnet;
 
 pub mod inception;
@@ -25,3 +37,21 @@ pub mod mobilenet;
 pub mod imagenet;
 
 pub mod efficientnet;
+
+pub mod shufflenet;
+
+pub mod regnet;
+
+pub mod convnext;
+
+pub mod vit;
+
+pub mod segmentation;
+
+pub mod detection;
+
+#[cfg(feature = "datasets")]
+pub mod coco;
+
+#[cfg(feature = "datasets")]
+pub mod voc;
@@ -0,0 +1,488 @@
+//! Operators for object detection models.
+//!
+//! Unless specified otherwise boxes are represented as float tensors of shape
+//! [n, 4] using the (x1, y1, x2, y2) format with 0 <= x1 < x2 and 0 <= y1 < y2.
+use crate::{Device, Kind, TchError, Tensor};
+
+// Small value used to avoid divisions by zero.
+const EPS: f64 = 1e-7;
+
+fn to_vec_f64(t: &Tensor) -> Result<Vec<f64>, TchError> {
+    let t = t.f_to_kind(Kind::Double)?.f_to_device(Device::Cpu)?;
+    let numel = t.numel();
+    let mut vec = vec![0f64; numel];
+    t.f_copy_data(&mut vec, numel)?;
+    Ok(vec)
+}
+
+fn check_boxes(boxes: &Tensor) -> Result<(), TchError> {
+    match boxes.size().as_slice() {
+        [_, 4] => Ok(()),
+        size => Err(TchError::Shape(format!(
+            "expected boxes of shape [n, 4], got {:?}",
+            size
+        ))),
+    }
+}
+
+fn coordinates(boxes: &Tensor) -> Result<(Tensor, Tensor, Tensor, Tensor), TchError> {
+    check_boxes(boxes)?;
+    Ok((
+        boxes.f_select(1, 0)?,
+        boxes.f_select(1, 1)?,
+        boxes.f_select(1, 2)?,
+        boxes.f_select(1, 3)?,
+    ))
+}
+
+/// The formats used to represent boxes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BoxFormat {
+    /// The top-left and bottom-right corners (x1, y1, x2, y2).
+    Xyxy,
+    /// The top-left corner followed by the width and height (x, y, w, h).
+    Xywh,
+    /// The center followed by the width and height (cx, cy, w, h).
+    Cxcywh,
+}
+
+/// Converts boxes from one format to another.
+pub fn box_convert(boxes: &Tensor, from: BoxFormat, to: BoxFormat) -> Result<Tensor, TchError> {
+    let (a, b, c, d) = coordinates(boxes)?;
+    let (x1, y1, x2, y2) = match from {
+        BoxFormat::Xyxy => (a, b, c, d),
+        BoxFormat::Xywh => {
+            let x2 = &a + &c;
+            let y2 = &b + &d;
+            (a, b, x2, y2)
+        }
+        BoxFormat::Cxcywh => {
+            let (hw, hh) = (&c * 0.5, &d * 0.5);
+            (&a - &hw, &b - &hh, &a + &hw, &b + &hh)
+        }
+    };
+    let (a, b, c, d) = match to {
+        BoxFormat::Xyxy => (x1, y1, x2, y2),
+        BoxFormat::Xywh => {
+            let w = &x2 - &x1;
+            let h = &y2 - &y1;
+            (x1, y1, w, h)
+        }
+        BoxFormat::Cxcywh => ((&x1 + &x2) * 0.5, (&y1 + &y2) * 0.5, &x2 - &x1, &y2 - &y1),
+    };
+    Tensor::f_stack(&[a, b, c, d], 1)
+}
+
+/// Returns the areas of some boxes as a tensor of shape [n].
+pub fn box_area(boxes: &Tensor) -> Result<Tensor, TchError> {
+    let (x1, y1, x2, y2) = coordinates(boxes)?;
+    Ok((x2 - x1) * (y2 - y1))
+}
+
+/// Clips boxes so that they lie inside an image of the given size.
+pub fn clip_boxes_to_image(boxes: &Tensor, height: i64, width: i64) -> Result<Tensor, TchError> {
+    let (x1, y1, x2, y2) = coordinates(boxes)?;
+    let (w, h) = (width as f64, height as f64);
+    Tensor::f_stack(
+        &[
+            x1.f_clamp(0., w)?,
+            y1.f_clamp(0., h)?,
+            x2.f_clamp(0., w)?,
+            y2.f_clamp(0., h)?,
+        ],
+        1,
+    )
+}
+
+/// Returns the indexes of the boxes whose sides are both at least `min_size`.
+pub fn remove_small_boxes(boxes: &Tensor, min_size: f64) -> Result<Tensor, TchError> {
+    let (x1, y1, x2, y2) = coordinates(boxes)?;
+    let keep = (x2 - x1)
+        .f_ge(min_size)?
+        .f_logical_and(&(y2 - y1).f_ge(min_size)?)?;
+    keep.f_nonzero()?.f_view([-1])
+}
+
+// Returns the pairwise intersections and unions, both of shape [n, m].
+fn box_inter_union(boxes1: &Tensor, boxes2: &Tensor) -> Result<(Tensor, Tensor), TchError> {
+    let area1 = box_area(boxes1)?;
+    let area2 = box_area(boxes2)?;
+    let b1 = boxes1.f_unsqueeze(1)?;
+    let lt = b1.f_narrow(2, 0, 2)?.f_max1(&boxes2.f_narrow(1, 0, 2)?)?;
+    let rb = b1.f_narrow(2, 2, 2)?.f_min1(&boxes2.f_narrow(1, 2, 2)?)?;
+    let wh = (rb - lt).f_clamp_min(0.)?;
+    let inter = wh.f_select(2, 0)? * wh.f_select(2, 1)?;
+    let union = area1.f_unsqueeze(1)? + area2.f_unsqueeze(0)? - &inter;
+    Ok((inter, union))
+}
+
+// Returns the pairwise smallest enclosing boxes corners, of shape [n, m, 2].
+fn enclosing_corners(boxes1: &Tensor, boxes2: &Tensor) -> Result<(Tensor, Tensor), TchError> {
+    let b1 = boxes1.f_unsqueeze(1)?;
+    let lt = b1.f_narrow(2, 0, 2)?.f_min1(&boxes2.f_narrow(1, 0, 2)?)?;
+    let rb = b1.f_narrow(2, 2, 2)?.f_max1(&boxes2.f_narrow(1, 2, 2)?)?;
+    Ok((lt, rb))
+}
+
+/// Returns the pairwise intersection over union between two sets of boxes.
+///
+/// For inputs of shape [n, 4] and [m, 4], the result has shape [n, m].
+pub fn box_iou(boxes1: &Tensor, boxes2: &Tensor) -> Result<Tensor, TchError> {
+    let (inter, union) = box_inter_union(boxes1, boxes2)?;
+    Ok(inter / union.f_clamp_min(EPS)?)
+}
+
+/// Returns the pairwise generalized intersection over union between two sets
+/// of boxes, see [Generalized Intersection over Union](https://giou.stanford.edu/).
+pub fn generalized_box_iou(boxes1: &Tensor, boxes2: &Tensor) -> Result<Tensor, TchError> {
+    let (inter, union) = box_inter_union(boxes1, boxes2)?;
+    let iou = &inter / union.f_clamp_min(EPS)?;
+    let (lt, rb) = enclosing_corners(boxes1, boxes2)?;
+    let wh = (rb - lt).f_clamp_min(0.)?;
+    let area = (wh.f_select(2, 0)? * wh.f_select(2, 1)?).f_clamp_min(EPS)?;
+    Ok(iou - (&area - union) / area)
+}
+
+/// Returns the pairwise distance intersection over union between two sets of
+/// boxes, see [Distance-IoU Loss](https://arxiv.org/abs/1911.08287).
+pub fn distance_box_iou(boxes1: &Tensor, boxes2: &Tensor) -> Result<Tensor, TchError> {
+    let iou = box_iou(boxes1, boxes2)?;
+    let (lt, rb) = enclosing_corners(boxes1, boxes2)?;
+    let diagonal = (rb - lt).f_pow(2)?.f_sum1(&[2], false, boxes1.kind())?;
+    let centers1 = (boxes1.f_narrow(1, 0, 2)? + boxes1.f_narrow(1, 2, 2)?) * 0.5;
+    let centers2 = (boxes2.f_narrow(1, 0, 2)? + boxes2.f_narrow(1, 2, 2)?) * 0.5;
+    let distance =
+        (centers1.f_unsqueeze(1)? - centers2)
+            .f_pow(2)?
+            .f_sum1(&[2], false, boxes1.kind())?;
+    Ok(iou - distance / diagonal.f_clamp_min(EPS)?)
+}
+
+/// Performs non-maximum suppression.
+///
+/// Boxes are processed by decreasing score, a box is discarded when its
+/// intersection over union with an already selected box is strictly larger
+/// than `iou_threshold`.
+/// Returns the int64 indexes of the selected boxes sorted by decreasing score.
+pub fn nms(boxes: &Tensor, scores: &Tensor, iou_threshold: f64) -> Result<Tensor, TchError> {
+    check_boxes(boxes)?;
+    // The indexes of the boxes that have not been selected or suppressed yet,
+    // sorted by decreasing score. Each step selects the first remaining box
+    // and only computes its overlaps with the other remaining boxes.
+    let mut remaining = scores.f_argsort(0, true)?;
+    let mut keep = vec![];
+    while remaining.size()[0] > 0 {
+        let index = remaining.f_int64_value(&[0])?;
+        keep.push(index);
+        let others = remaining.f_narrow(0, 1, remaining.size()[0] - 1)?;
+        if others.size()[0] == 0 {
+            break;
+        }
+        let overlaps = box_iou(
+            &boxes.f_narrow(0, index, 1)?,
+            &boxes.f_index_select(0, &others)?,
+        )?
+        .f_squeeze1(0)?;
+        remaining = others.f_masked_select(&overlaps.f_gt(iou_threshold)?.f_logical_not()?)?;
+    }
+    Tensor::of_slice(&keep).f_to_device(boxes.device())
+}
+
+/// Performs non-maximum suppression independently for each category.
+///
+/// `idxs` is an int64 tensor of shape [n] with the category of each box,
+/// boxes from different categories never suppress each other.
+/// Returns the int64 indexes of the selected boxes sorted by decreasing score.
+pub fn batched_nms(
+    boxes: &Tensor,
+    scores: &Tensor,
+    idxs: &Tensor,
+    iou_threshold: f64,
+) -> Result<Tensor, TchError> {
+    check_boxes(boxes)?;
+    if boxes.size()[0] == 0 {
+        return Tensor::f_zeros(&[0], (Kind::Int64, boxes.device()));
+    }
+    // Offsets the boxes of each category so that they do not overlap.
+    let max_coordinate = boxes.f_max()?.f_to_kind(boxes.kind())?;
+    let offsets = idxs.f_to_kind(boxes.kind())? * (max_coordinate + 1.);
+    nms(&(boxes + offsets.f_unsqueeze(1)?), scores, iou_threshold)
+}
+
+fn check_rois(input: &Tensor, rois: &Tensor) -> Result<(), TchError> {
+    match (input.size().as_slice(), rois.size().as_slice()) {
+        ([_, _, _, _], [_, 5]) => Ok(()),
+        (input, rois) => Err(TchError::Shape(format!(
+            "expected an input of shape [n, c, h, w] and rois of shape [k, 5], got {:?} {:?}",
+            input, rois
+        ))),
+    }
+}
+
+/// Region of interest align, as used in Mask R-CNN.
+///
+/// `input` has shape [n, c, h, w] and `rois` has shape [k, 5], each region
+/// being represented by the batch index of its image followed by its
+/// coordinates in the (x1, y1, x2, y2) format.
+/// The coordinates are multiplied by `spatial_scale` to map them to the
+/// input. Each output bin averages `sampling_ratio` x `sampling_ratio`
+/// bilinearly interpolated points, or an adaptive number of points when
+/// `sampling_ratio` is not positive. With `aligned`, the coordinates are
+/// shifted by half a pixel so that they match pixel centers.
+/// Returns a tensor of shape [k, c, output_h, output_w].
+pub fn roi_align(
+    input: &Tensor,
+    rois: &Tensor,
+    output_size: (i64, i64),
+    spatial_scale: f64,
+    sampling_ratio: i64,
+    aligned: bool,
+) -> Result<Tensor, TchError> {
+    check_rois(input, rois)?;
+    let (out_h, out_w) = output_size;
+    let (c, h, w) = (input.size()[1], input.size()[2], input.size()[3]);
+    let rois_vec = to_vec_f64(rois)?;
+    let offset = if aligned { 0.5 } else { 0. };
+    let options = (input.kind(), input.device());
+    let mut outputs = vec![];
+    for roi in rois_vec.chunks(5) {
+        let x1 = roi[1] * spatial_scale - offset;
+        let y1 = roi[2] * spatial_scale - offset;
+        let mut roi_w = roi[3] * spatial_scale - offset - x1;
+        let mut roi_h = roi[4] * spatial_scale - offset - y1;
+        if !aligned {
+            roi_w = roi_w.max(1.);
+            roi_h = roi_h.max(1.);
+        }
+        let (bin_h, bin_w) = (roi_h / out_h as f64, roi_w / out_w as f64);
+        let (ratio_h, ratio_w) = if sampling_ratio > 0 {
+            (sampling_ratio, sampling_ratio)
+        } else {
+            (bin_h.ceil().max(1.) as i64, bin_w.ceil().max(1.) as i64)
+        };
+        // Sampling points in pixel coordinates, mapped to the [-1, 1] range
+        // used by the grid sampler.
+        let ys = Tensor::f_arange(out_h * ratio_h, options)?;
+        let ys =
+            ((ys + 0.5) * (bin_h / ratio_h as f64) + y1) * (2. / h as f64) + (1. / h as f64 - 1.);
+        let xs = Tensor::f_arange(out_w * ratio_w, options)?;
+        let xs =
+            ((xs + 0.5) * (bin_w / ratio_w as f64) + x1) * (2. / w as f64) + (1. / w as f64 - 1.);
+        let size = [out_h * ratio_h, out_w * ratio_w];
+        let grid = Tensor::f_stack(
+            &[
+                xs.f_view([1, -1])?.f_expand(&size, false)?,
+                ys.f_view([-1, 1])?.f_expand(&size, false)?,
+            ],
+            2,
+        )?
+        .f_unsqueeze(0)?;
+        let image = input.f_get(roi[0] as i64)?.f_unsqueeze(0)?;
+        // Bilinear interpolation with the border padding mode.
+        let samples = image.f_grid_sampler(&grid, 0, 1, false)?;
+        let output = samples.f_avg_pool2d(
+            &[ratio_h, ratio_w],
+            &[ratio_h, ratio_w],
+            &[0, 0],
+            false,
+            true,
+            None,
+        )?;
+        outputs.push(output)
+    }
+    if outputs.is_empty() {
+        Tensor::f_zeros(&[0, c, out_h, out_w], options)
+    } else {
+        Tensor::f_cat(&outputs, 0)
+    }
+}
+
+/// Region of interest pooling, as used in Fast R-CNN.
+///
+/// The inputs are the same as for `roi_align`, the region coordinates are
+/// rounded to the nearest pixel and each output bin takes the maximum of the
+/// input values it covers. The bins are computed on the whole region and then
+/// clipped to the input, bins that lie outside of the input result in zeros.
+/// Returns a tensor of shape [k, c, output_h, output_w].
+pub fn roi_pool(
+    input: &Tensor,
+    rois: &Tensor,
+    output_size: (i64, i64),
+    spatial_scale: f64,
+) -> Result<Tensor, TchError> {
+    check_rois(input, rois)?;
+    let (out_h, out_w) = output_size;
+    let (c, h, w) = (input.size()[1], input.size()[2], input.size()[3]);
+    let options = (input.kind(), input.device());
+    let rois_vec = to_vec_f64(rois)?;
+    // The [start, end) ranges of the bins along a dimension of size len,
+    // clipped to [0, len).
+    let bins = |start: i64, end: i64, nbins: i64, len: i64| -> Vec<(i64, i64)> {
+        let bin_size = (end - start + 1).max(1) as f64 / nbins as f64;
+        (0..nbins)
+            .map(|i| {
+                let lo = (i as f64 * bin_size).floor() as i64 + start;
+                let hi = ((i + 1) as f64 * bin_size).ceil() as i64 + start;
+                (lo.max(0).min(len), hi.max(0).min(len))
+            })
+            .collect()
+    };
+    let mut outputs = vec![];
+    for roi in rois_vec.chunks(5) {
+        let x1 = (roi[1] * spatial_scale).round() as i64;
+        let y1 = (roi[2] * spatial_scale).round() as i64;
+        let x2 = (roi[3] * spatial_scale).round() as i64;
+        let y2 = (roi[4] * spatial_scale).round() as i64;
+        let image = input.f_get(roi[0] as i64)?;
+        let mut values = vec![];
+        for &(y_lo, y_hi) in bins(y1, y2, out_h, h).iter() {
+            for &(x_lo, x_hi) in bins(x1, x2, out_w, w).iter() {
+                let value = if y_hi <= y_lo || x_hi <= x_lo {
+                    Tensor::f_zeros(&[c], options)?
+                } else {
+                    image
+                        .f_narrow(1, y_lo, y_hi - y_lo)?
+                        .f_narrow(2, x_lo, x_hi - x_lo)?
+                        .f_amax(&[1, 2], false)?
+                };
+                values.push(value)
+            }
+        }
+        let output = Tensor::f_stack(&values, 1)?.f_view([1, c, out_h, out_w])?;
+        outputs.push(output)
+    }
+    if outputs.is_empty() {
+        Tensor::f_zeros(&[0, c, out_h, out_w], options)
+    } else {
+        Tensor::f_cat(&outputs, 0)
+    }
+}
+
+/// Generates anchor boxes for a feature map.
+///
+/// There is an anchor per pair of size and aspect ratio (height / width)
+/// centered on each feature map location.
+#[derive(Debug, Clone)]
+pub struct AnchorGenerator {
+    sizes: Vec<f64>,
+    aspect_ratios: Vec<f64>,
+}
+
+impl AnchorGenerator {
+    pub fn new(sizes: &[f64], aspect_ratios: &[f64]) -> AnchorGenerator {
+        AnchorGenerator {
+            sizes: sizes.to_vec(),
+            aspect_ratios: aspect_ratios.to_vec(),
+        }
+    }
+
+    /// The number of anchors generated for each feature map location.
+    pub fn num_anchors_per_location(&self) -> usize {
+        self.sizes.len() * self.aspect_ratios.len()
+    }
+
+    /// Returns the anchors centered on (0, 0) as a tensor of shape [a, 4].
+    pub fn cell_anchors(&self, device: Device) -> Result<Tensor, TchError> {
+        let mut anchors = vec![];
+        for &ratio in self.aspect_ratios.iter() {
+            let h_ratio = ratio.sqrt();
+            for &size in self.sizes.iter() {
+                let w = size / h_ratio;
+                let h = size * h_ratio;
+                anchors.extend_from_slice(&[
+                    (-w / 2.).round() as f32,
+                    (-h / 2.).round() as f32,
+                    (w / 2.).round() as f32,
+                    (h / 2.).round() as f32,
+                ])
+            }
+        }
+        Tensor::of_slice(&anchors)
+            .f_view([-1, 4])?
+            .f_to_device(device)
+    }
+
+    /// Returns the anchors for a feature map of size (height, width) with the
+    /// given stride (in pixels) as a tensor of shape [height * width * a, 4].
+    pub fn grid_anchors(
+        &self,
+        feature_size: (i64, i64),
+        stride: (i64, i64),
+        device: Device,
+    ) -> Result<Tensor, TchError> {
+        let (h, w) = feature_size;
+        let options = (Kind::Float, device);
+        let shifts_y = Tensor::f_arange(h, options)? * stride.0 as f64;
+        let shifts_x = Tensor::f_arange(w, options)? * stride.1 as f64;
+        let size = [h, w];
+        let shifts_x = shifts_x
+            .f_view([1, -1])?
+            .f_expand(&size, false)?
+            .f_reshape(&[-1])?;
+        let shifts_y = shifts_y
+            .f_view([-1, 1])?
+            .f_expand(&size, false)?
+            .f_reshape(&[-1])?;
+        let shifts = Tensor::f_stack(&[&shifts_x, &shifts_y, &shifts_x, &shifts_y], 1)?;
+        let anchors = shifts.f_view([-1, 1, 4])? + self.cell_anchors(device)?.f_view([1, -1, 4])?;
+        anchors.f_reshape(&[-1, 4])
+    }
+}
+
+/// Encodes boxes relative to reference boxes and decodes them back, as used
+/// for bounding box regression in R-CNN style detectors.
+#[derive(Debug, Clone, Copy)]
+pub struct BoxCoder {
+    /// The weights applied to the (dx, dy, dw, dh) offsets.
+    pub weights: [f64; 4],
+    /// The maximum value of dw and dh when decoding.
+    pub bbox_xform_clip: f64,
+}
+
+impl Default for BoxCoder {
+    fn default() -> Self {
+        BoxCoder::new([1., 1., 1., 1.])
+    }
+}
+
+impl BoxCoder {
+    pub fn new(weights: [f64; 4]) -> BoxCoder {
+        BoxCoder {
+            weights,
+            bbox_xform_clip: (1000f64 / 16.).ln(),
+        }
+    }
+
+    /// Returns the (dx, dy, dw, dh) offsets, as a tensor of shape [n, 4],
+    /// that map the `anchors` boxes to the `boxes` ones.
+    pub fn encode(&self, boxes: &Tensor, anchors: &Tensor) -> Result<Tensor, TchError> {
+        let [wx, wy, ww, wh] = self.weights;
+        let anchors = box_convert(anchors, BoxFormat::Xyxy, BoxFormat::Cxcywh)?;
+        let boxes = box_convert(boxes, BoxFormat::Xyxy, BoxFormat::Cxcywh)?;
+        let (acx, acy, aw, ah) = coordinates(&anchors)?;
+        let (cx, cy, w, h) = coordinates(&boxes)?;
+        let dx = (cx - &acx) / &aw * wx;
+        let dy = (cy - &acy) / &ah * wy;
+        let dw = (w / aw).f_log()? * ww;
+        let dh = (h / ah).f_log()? * wh;
+        Tensor::f_stack(&[dx, dy, dw, dh], 1)
+    }
+
+    /// Applies the (dx, dy, dw, dh) offsets of shape [n, 4] to the `anchors`
+    /// boxes, this is the inverse of `encode`.
+    pub fn decode(&self, offsets: &Tensor, anchors: &Tensor) -> Result<Tensor, TchError> {
+        let [wx, wy, ww, wh] = self.weights;
+        let anchors = box_convert(anchors, BoxFormat::Xyxy, BoxFormat::Cxcywh)?;
+        let (acx, acy, aw, ah) = coordinates(&anchors)?;
+        let (dx, dy, dw, dh) = coordinates(offsets)?;
+        let cx = dx / wx * &aw + acx;
+        let cy = dy / wy * &ah + acy;
+        let w = (dw / ww).f_clamp_max(self.bbox_xform_clip)?.f_exp()? * aw;
+        let h = (dh / wh).f_clamp_max(self.bbox_xform_clip)?.f_exp()? * ah;
+        box_convert(
+            &Tensor::f_stack(&[cx, cy, w, h], 1)?,
+            BoxFormat::Cxcywh,
+            BoxFormat::Xyxy,
+        )
+    }
+}
@@ -0,0 +1,349 @@
+//! RegNet implementation.
+//!
+//! See "Designing Network Design Spaces" Radosavovic et al. 2020
+//! https://arxiv.org/abs/2003.13678
+use super::mobilenet::make_divisible;
+use crate::{nn, nn::Module, nn::ModuleT};
+
+/// The parameters generating the widths and depths of the RegNet stages.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RegNetParams {
+    /// The total number of blocks.
+    pub depth: i64,
+    /// The initial width.
+    pub w_0: i64,
+    /// The slope of the linear width parametrization.
+    pub w_a: f64,
+    /// The multiplier used to quantize the widths.
+    pub w_m: f64,
+    /// The number of channels per group in the 3x3 convolutions.
+    pub group_width: i64,
+    pub bottleneck_multiplier: f64,
+    /// The squeeze-excitation ratio, only used by RegNetY models.
+    pub se_ratio: Option<f64>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Stage {
+    width: i64,
+    depth: i64,
+    group_width: i64,
+}
+
+impl RegNetParams {
+    fn stages(&self) -> Vec<Stage> {
+        let mut stages: Vec<Stage> = vec![];
+        for i in 0..self.depth {
+            let width = i as f64 * self.w_a + self.w_0 as f64;
+            let capacity = ((width / self.w_0 as f64).ln() / self.w_m.ln()).round();
+            let width = self.w_0 as f64 * self.w_m.powf(capacity);
+            let width = (width / 8.).round() as i64 * 8;
+            match stages.last_mut() {
+                Some(stage) if stage.width == width => stage.depth += 1,
+                _ => stages.push(Stage {
+                    width,
+                    depth: 1,
+                    group_width: self.group_width,
+                }),
+            }
+        }
+        // Makes the bottleneck widths compatible with the group widths.
+        let b = self.bottleneck_multiplier;
+        for stage in stages.iter_mut() {
+            let w_bot = (stage.width as f64 * b) as i64;
+            stage.group_width = i64::min(stage.group_width, w_bot);
+            let w_bot = make_divisible(w_bot, stage.group_width);
+            stage.width = (w_bot as f64 / b) as i64;
+        }
+        stages
+    }
+}
+
+// Conv2D + BatchNorm2D + optional ReLU.
+fn cbr(
+    p: nn::Path,
+    c_in: i64,
+    c_out: i64,
+    ksize: i64,
+    stride: i64,
+    groups: i64,
+    relu: bool,
+) -> impl ModuleT {
+    let conv2d_cfg = nn::ConvConfig {
+        stride,
+        padding: (ksize - 1) / 2,
+        groups,
+        bias: false,
+        ..Default::default()
+    };
+    nn::seq_t()
+        .add(nn::conv2d(&p / 0, c_in, c_out, ksize, conv2d_cfg))
+        .add(nn::batch_norm2d(&p / 1, c_out, Default::default()))
+        .add_fn(move |xs| if relu { xs.relu() } else { xs.shallow_clone() })
+}
+
+fn squeeze_excitation(p: nn::Path, c: i64, c_squeeze: i64) -> impl Module {
+    let fc1 = nn::conv2d(&p / "fc1", c, c_squeeze, 1, Default::default());
+    let fc2 = nn::conv2d(&p / "fc2", c_squeeze, c, 1, Default::default());
+    nn::func(move |xs| {
+        let scale = xs
+            .adaptive_avg_pool2d(&[1, 1])
+            .apply(&fc1)
+            .relu()
+            .apply(&fc2)
+            .sigmoid();
+        xs * scale
+    })
+}
+
+fn res_bottleneck_block(
+    p: nn::Path,
+    c_in: i64,
+    c_out: i64,
+    stride: i64,
+    group_width: i64,
+    params: &RegNetParams,
+) -> impl ModuleT {
+    let proj = if c_in != c_out || stride != 1 {
+        Some(cbr(&p / "proj", c_in, c_out, 1, stride, 1, false))
+    } else {
+        None
+    };
+    let f_p = &p / "f";
+    let w_b = (c_out as f64 * params.bottleneck_multiplier).round() as i64;
+    let groups = w_b / group_width;
+    let mut f = nn::seq_t()
+        .add(cbr(&f_p / "a", c_in, w_b, 1, 1, 1, true))
+        .add(cbr(&f_p / "b", w_b, w_b, 3, stride, groups, true));
+    if let Some(se_ratio) = params.se_ratio {
+        let c_squeeze = (se_ratio * c_in as f64).round() as i64;
+        f = f.add(squeeze_excitation(&f_p / "se", w_b, c_squeeze));
+    }
+    f = f.add(cbr(&f_p / "c", w_b, c_out, 1, 1, 1, false));
+    nn::func_t(move |xs, train| {
+        let ys = xs.apply_t(&f, train);
+        let xs = match &proj {
+            Some(proj) => xs.apply_t(proj, train),
+            None => xs.shallow_clone(),
+        };
+        (xs + ys).relu()
+    })
+}
+
+// Returns the feature extractor and its number of output channels.
+fn features(p: &nn::Path, params: &RegNetParams) -> (nn::IntermediateLayerGetter, i64) {
+    let stem_width = 32;
+    let mut features = nn::seq_t().add(cbr(p / "stem", 3, stem_width, 3, 2, 1, true));
+    let t_p = p / "trunk_output";
+    let mut c_in = stem_width;
+    let stages = params.stages();
+    for (stage_index, stage) in stages.iter().enumerate() {
+        let s_p = &t_p / format!("block{}", stage_index + 1);
+        let mut blocks = nn::seq_t();
+        for block_index in 0..stage.depth {
+            let name = format!("block{}-{}", stage_index + 1, block_index);
+            let stride = if block_index == 0 { 2 } else { 1 };
+            blocks = blocks.add(res_bottleneck_block(
+                &s_p / name,
+                c_in,
+                stage.width,
+                stride,
+                stage.group_width,
+                params,
+            ));
+            c_in = stage.width;
+        }
+        features = features.add(blocks);
+    }
+    // Each stage halves the spatial resolution, the last four stages are
+    // returned.
+    let n = stages.len();
+    let features = nn::IntermediateLayerGetter::new(
+        features,
+        &[
+            (n - 3, "layer1"),
+            (n - 2, "layer2"),
+            (n - 1, "layer3"),
+            (n, "layer4"),
+        ],
+    );
+    (features, c_in)
+}
+
+/// Creates a RegNet model from its design space parameters.
+pub fn regnet(p: &nn::Path, params: &RegNetParams, nclasses: i64) -> impl ModuleT {
+    let (features, c_out) = features(p, params);
+    let fc = nn::linear(p / "fc", c_out, nclasses, Default::default());
+    nn::func_t(move |xs, train| {
+        xs.apply_t(&features, train)
+            .adaptive_avg_pool2d(&[1, 1])
+            .flat_view()
+            .apply(&fc)
+    })
+}
+
+/// Creates a RegNet feature extractor returning the outputs of the `layer1`
+/// to `layer4` stages, with strides of 4, 8, 16, and 32.
+pub fn regnet_features(p: &nn::Path, params: &RegNetParams) -> nn::IntermediateLayerGetter {
+    features(p, params).0
+}
+
+const fn params(
+    depth: i64,
+    w_0: i64,
+    w_a: f64,
+    w_m: f64,
+    group_width: i64,
+    se: bool,
+) -> RegNetParams {
+    RegNetParams {
+        depth,
+        w_0,
+        w_a,
+        w_m,
+        group_width,
+        bottleneck_multiplier: 1.0,
+        se_ratio: if se { Some(0.25) } else { None },
+    }
+}
+
+const REGNET_Y_400MF: RegNetParams = params(16, 48, 27.89, 2.09, 8, true);
+const REGNET_Y_800MF: RegNetParams = params(14, 56, 38.84, 2.4, 16, true);
+const REGNET_Y_1_6GF: RegNetParams = params(27, 48, 20.71, 2.65, 24, true);
+const REGNET_Y_3_2GF: RegNetParams = params(21, 80, 42.63, 2.66, 24, true);
+const REGNET_Y_8GF: RegNetParams = params(17, 192, 76.82, 2.19, 56, true);
+const REGNET_Y_16GF: RegNetParams = params(18, 200, 106.23, 2.48, 112, true);
+const REGNET_Y_32GF: RegNetParams = params(20, 232, 115.89, 2.53, 232, true);
+const REGNET_X_400MF: RegNetParams = params(22, 24, 24.48, 2.54, 16, false);
+const REGNET_X_800MF: RegNetParams = params(16, 56, 35.73, 2.28, 16, false);
+const REGNET_X_1_6GF: RegNetParams = params(18, 80, 34.01, 2.25, 24, false);
+const REGNET_X_3_2GF: RegNetParams = params(25, 88, 26.31, 2.25, 48, false);
+const REGNET_X_8GF: RegNetParams = params(23, 80, 49.56, 2.88, 120, false);
+const REGNET_X_16GF: RegNetParams = params(22, 216, 55.59, 2.1, 128, false);
+const REGNET_X_32GF: RegNetParams = params(23, 320, 69.86, 2.0, 168, false);
+
+pub fn regnet_y_400mf(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    regnet(p, &REGNET_Y_400MF, nclasses)
+}
+
+pub fn regnet_y_800mf(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    regnet(p, &REGNET_Y_800MF, nclasses)
+}
+
+pub fn regnet_y_1_6gf(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    regnet(p, &REGNET_Y_1_6GF, nclasses)
+}
+
+pub fn regnet_y_3_2gf(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    regnet(p, &REGNET_Y_3_2GF, nclasses)
+}
+
+pub fn regnet_y_8gf(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    regnet(p, &REGNET_Y_8GF, nclasses)
+}
+
+pub fn regnet_y_16gf(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    regnet(p, &REGNET_Y_16GF, nclasses)
+}
+
+pub fn regnet_y_32gf(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    regnet(p, &REGNET_Y_32GF, nclasses)
+}
+
+pub fn regnet_x_400mf(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    regnet(p, &REGNET_X_400MF, nclasses)
+}
+
+pub fn regnet_x_800mf(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    regnet(p, &REGNET_X_800MF, nclasses)
+}
+
+pub fn regnet_x_1_6gf(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    regnet(p, &REGNET_X_1_6GF, nclasses)
+}
+
+pub fn regnet_x_3_2gf(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    regnet(p, &REGNET_X_3_2GF, nclasses)
+}
+
+pub fn regnet_x_8gf(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    regnet(p, &REGNET_X_8GF, nclasses)
+}
+
+pub fn regnet_x_16gf(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    regnet(p, &REGNET_X_16GF, nclasses)
+}
+
+pub fn regnet_x_32gf(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    regnet(p, &REGNET_X_32GF, nclasses)
+}
+
+/// Creates a RegNetY-400MF feature extractor, see `regnet_features`.
+pub fn regnet_y_400mf_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    regnet_features(p, &REGNET_Y_400MF)
+}
+
+/// Creates a RegNetY-800MF feature extractor, see `regnet_features`.
+pub fn regnet_y_800mf_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    regnet_features(p, &REGNET_Y_800MF)
+}
+
+/// Creates a RegNetY-1.6GF feature extractor, see `regnet_features`.
+pub fn regnet_y_1_6gf_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    regnet_features(p, &REGNET_Y_1_6GF)
+}
+
+/// Creates a RegNetY-3.2GF feature extractor, see `regnet_features`.
+pub fn regnet_y_3_2gf_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    regnet_features(p, &REGNET_Y_3_2GF)
+}
+
+/// Creates a RegNetY-8GF feature extractor, see `regnet_features`.
+pub fn regnet_y_8gf_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    regnet_features(p, &REGNET_Y_8GF)
+}
+
+/// Creates a RegNetY-16GF feature extractor, see `regnet_features`.
+pub fn regnet_y_16gf_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    regnet_features(p, &REGNET_Y_16GF)
+}
+
+/// Creates a RegNetY-32GF feature extractor, see `regnet_features`.
+pub fn regnet_y_32gf_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    regnet_features(p, &REGNET_Y_32GF)
+}
+
+/// Creates a RegNetX-400MF feature extractor, see `regnet_features`.
+pub fn regnet_x_400mf_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    regnet_features(p, &REGNET_X_400MF)
+}
+
+/// Creates a RegNetX-800MF feature extractor, see `regnet_features`.
+pub fn regnet_x_800mf_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    regnet_features(p, &REGNET_X_800MF)
+}
+
+/// Creates a RegNetX-1.6GF feature extractor, see `regnet_features`.
+pub fn regnet_x_1_6gf_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    regnet_features(p, &REGNET_X_1_6GF)
+}
+
+/// Creates a RegNetX-3.2GF feature extractor, see `regnet_features`.
+pub fn regnet_x_3_2gf_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    regnet_features(p, &REGNET_X_3_2GF)
+}
+
+/// Creates a RegNetX-8GF feature extractor, see `regnet_features`.
+pub fn regnet_x_8gf_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    regnet_features(p, &REGNET_X_8GF)
+}
+
+/// Creates a RegNetX-16GF feature extractor, see `regnet_features`.
+pub fn regnet_x_16gf_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    regnet_features(p, &REGNET_X_16GF)
+}
+
+/// Creates a RegNetX-32GF feature extractor, see `regnet_features`.
+pub fn regnet_x_32gf_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    regnet_features(p, &REGNET_X_32GF)
+}
@@ -2,6 +2,11 @@
 //!
 //! See "Deep Residual Learning for Image Recognition" He et al. 2015
 //! https://arxiv.org/abs/1512.03385
+//!
+//! The ResNeXt and Wide ResNet variants use grouped and wider bottleneck
+//! convolutions, see "Aggregated Residual Transformations for Deep Neural
+//! Networks" Xie et al. 2016 https://arxiv.org/abs/1611.05431 and "Wide
+//! Residual Networks" Zagoruyko et al. 2016 https://arxiv.org/abs/1605.07146
 use crate::{nn, nn::Conv2D, nn::FuncT, nn::ModuleT};
 
 fn conv2d(p: nn::Path, c_in: i64, c_out: i64, ksize: i64, padding: i64, stride: i64) -> Conv2D {
@@ -49,6 +54,37 @@ fn basic_layer(p: nn::Path, c_in: i64, c_out: i64, stride: i64, cnt: i64) -> imp
     layer
 }
 
+fn stem(p: &nn::Path) -> impl ModuleT {
+    let conv1 = conv2d(p / "conv1", 3, 64, 7, 3, 2);
+    let bn1 = nn::batch_norm2d(p / "bn1", 64, Default::default());
+    nn::func_t(move |xs, train| {
+        xs.apply(&conv1).apply_t(&bn1, train).relu().max_pool2d(
+            &[3, 3],
+            &[2, 2],
+            &[1, 1],
+            &[1, 1],
+            false,
+        )
+    })
+}
+
+fn features(p: &nn::Path, layers: nn::SequentialT) -> nn::IntermediateLayerGetter {
+    let seq = nn::seq_t().add(stem(p)).add(layers);
+    nn::IntermediateLayerGetter::new(
+        seq,
+        &[(1, "layer1"), (2, "layer2"), (3, "layer3"), (4, "layer4")],
+    )
+}
+
+fn basic_features(p: &nn::Path, c1: i64, c2: i64, c3: i64, c4: i64) -> nn::IntermediateLayerGetter {
+    let layers = nn::seq_t()
+        .add(basic_layer(p / "layer1", 64, 64, 1, c1))
+        .add(basic_layer(p / "layer2", 64, 128, 2, c2))
+        .add(basic_layer(p / "layer3", 128, 256, 2, c3))
+        .add(basic_layer(p / "layer4", 256, 512, 2, c4));
+    features(p, layers)
+}
+
 fn resnet(
     p: &nn::Path,
     nclasses: Option<i64>,
@@ -57,22 +93,10 @@ fn resnet(
     c3: i64,
     c4: i64,
 ) -> FuncT<'static> {
-    let conv1 = conv2d(p / "conv1", 3, 64, 7, 3, 2);
-    let bn1 = nn::batch_norm2d(p / "bn1", 64, Default::default());
-    let layer1 = basic_layer(p / "layer1", 64, 64, 1, c1);
-    let layer2 = basic_layer(p / "layer2", 64, 128, 2, c2);
-    let layer3 = basic_layer(p / "layer3", 128, 256, 2, c3);
-    let layer4 = basic_layer(p / "layer4", 256, 512, 2, c4);
+    let features = basic_features(p, c1, c2, c3, c4);
     let fc = nclasses.map(|n| nn::linear(p / "fc", 512, n, Default::default()));
     nn::func_t(move |xs, train| {
-        xs.apply(&conv1)
-            .apply_t(&bn1, train)
-            .relu()
-            .max_pool2d(&[3, 3], &[2, 2], &[1, 1], &[1, 1], false)
-            .apply_t(&layer1, train)
-            .apply_t(&layer2, train)
-            .apply_t(&layer3, train)
-            .apply_t(&layer4, train)
+        xs.apply_t(&features, train)
             .adaptive_avg_pool2d(&[1, 1])
             .flat_view()
             .apply_opt(&fc)
@@ -91,6 +115,12 @@ pub fn resnet18_no_final_layer(p: &nn::Path) -> FuncT<'static> {
     resnet(p, None, 2, 2, 2, 2)
 }
 
+/// Creates a ResNet-18 feature extractor returning the outputs of the
+/// `layer1` to `layer4` stages, with strides of 4, 8, 16, and 32.
+pub fn resnet18_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    basic_features(p, 2, 2, 2, 2)
+}
+
 /// Creates a ResNet-34 model.
 ///
 /// Pre-trained weights can be downloaded at the following link:
@@ -103,15 +133,49 @@ pub fn resnet34_no_final_layer(p: &nn::Path) -> FuncT<'static> {
     resnet(p, None, 3, 4, 6, 3)
 }
 
-// Bottleneck versions for ResNet 50, 101, and 152.
+/// Creates a ResNet-34 feature extractor, see `resnet18_features`.
+pub fn resnet34_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    basic_features(p, 3, 4, 6, 3)
+}
 
-fn bottleneck_block(p: nn::Path, c_in: i64, c_out: i64, stride: i64, e: i64) -> impl ModuleT {
-    let e_dim = e * c_out;
-    let conv1 = conv2d(&p / "conv1", c_in, c_out, 1, 0, 1);
-    let bn1 = nn::batch_norm2d(&p / "bn1", c_out, Default::default());
-    let conv2 = conv2d(&p / "conv2", c_out, c_out, 3, 1, stride);
-    let bn2 = nn::batch_norm2d(&p / "bn2", c_out, Default::default());
-    let conv3 = conv2d(&p / "conv3", c_out, e_dim, 1, 0, 1);
+// Bottleneck versions for ResNet 50, 101, and 152, as well as ResNeXt and
+// Wide ResNet. The width of the 3x3 convolution is `c_out * base_width / 64`
+// per group.
+
+#[derive(Debug, Clone, Copy)]
+struct Bottleneck {
+    groups: i64,
+    base_width: i64,
+}
+
+const RESNET: Bottleneck = Bottleneck {
+    groups: 1,
+    base_width: 64,
+};
+
+fn bottleneck_block(
+    p: nn::Path,
+    c_in: i64,
+    c_out: i64,
+    stride: i64,
+    dilation: i64,
+    b: Bottleneck,
+) -> impl ModuleT {
+    let e_dim = 4 * c_out;
+    let width = c_out * b.base_width / 64 * b.groups;
+    let conv1 = conv2d(&p / "conv1", c_in, width, 1, 0, 1);
+    let bn1 = nn::batch_norm2d(&p / "bn1", width, Default::default());
+    let conv2_cfg = nn::ConvConfig {
+        stride,
+        padding: dilation,
+        dilation,
+        groups: b.groups,
+        bias: false,
+        ..Default::default()
+    };
+    let conv2 = nn::conv2d(&p / "conv2", width, width, 3, conv2_cfg);
+    let bn2 = nn::batch_norm2d(&p / "bn2", width, Default::default());
+    let conv3 = conv2d(&p / "conv3", width, e_dim, 1, 0, 1);
     let bn3 = nn::batch_norm2d(&p / "bn3", e_dim, Default::default());
     let downsample = downsample(&p / "downsample", c_in, e_dim, stride);
     nn::func_t(move |xs, train| {
@@ -128,44 +192,82 @@ fn bottleneck_block(p: nn::Path, c_in: i64, c_out: i64, stride: i64, e: i64) ->
     })
 }
 
-fn bottleneck_layer(p: nn::Path, c_in: i64, c_out: i64, stride: i64, cnt: i64) -> impl ModuleT {
-    let mut layer = nn::seq_t().add(bottleneck_block(&p / "0", c_in, c_out, stride, 4));
+// The dilation is given for the first block and for the other blocks.
+fn bottleneck_layer(
+    p: nn::Path,
+    c_in: i64,
+    c_out: i64,
+    stride: i64,
+    cnt: i64,
+    b: Bottleneck,
+    dilation: (i64, i64),
+) -> impl ModuleT {
+    let mut layer = nn::seq_t().add(bottleneck_block(
+        &p / "0",
+        c_in,
+        c_out,
+        stride,
+        dilation.0,
+        b,
+    ));
     for block_index in 1..cnt {
         layer = layer.add(bottleneck_block(
             &p / &block_index.to_string(),
             4 * c_out,
             c_out,
             1,
-            4,
+            dilation.1,
+            b,
         ))
     }
     layer
 }
 
+// When dilated, the strides of the last two layers are replaced by dilations
+// so that the output stride is 8 rather than 32.
+fn bottleneck_features(
+    p: &nn::Path,
+    b: Bottleneck,
+    dilated: bool,
+    c1: i64,
+    c2: i64,
+    c3: i64,
+    c4: i64,
+) -> nn::IntermediateLayerGetter {
+    let ((s3, d3), (s4, d4)) = if dilated {
+        ((1, (1, 2)), (1, (2, 4)))
+    } else {
+        ((2, (1, 1)), (2, (1, 1)))
+    };
+    let layers = nn::seq_t()
+        .add(bottleneck_layer(p / "layer1", 64, 64, 1, c1, b, (1, 1)))
+        .add(bottleneck_layer(
+            p / "layer2",
+            4 * 64,
+            128,
+            2,
+            c2,
+            b,
+            (1, 1),
+        ))
+        .add(bottleneck_layer(p / "layer3", 4 * 128, 256, s3, c3, b, d3))
+        .add(bottleneck_layer(p / "layer4", 4 * 256, 512, s4, c4, b, d4));
+    features(p, layers)
+}
+
 fn bottleneck_resnet(
     p: &nn::Path,
     nclasses: Option<i64>,
+    b: Bottleneck,
     c1: i64,
     c2: i64,
     c3: i64,
     c4: i64,
 ) -> impl ModuleT {
-    let conv1 = conv2d(p / "conv1", 3, 64, 7, 3, 2);
-    let bn1 = nn::batch_norm2d(p / "bn1", 64, Default::default());
-    let layer1 = bottleneck_layer(p / "layer1", 64, 64, 1, c1);
-    let layer2 = bottleneck_layer(p / "layer2", 4 * 64, 128, 2, c2);
-    let layer3 = bottleneck_layer(p / "layer3", 4 * 128, 256, 2, c3);
-    let layer4 = bottleneck_layer(p / "layer4", 4 * 256, 512, 2, c4);
+    let features = bottleneck_features(p, b, false, c1, c2, c3, c4);
     let fc = nclasses.map(|n| nn::linear(p / "fc", 4 * 512, n, Default::default()));
     nn::func_t(move |xs, train| {
-        xs.apply(&conv1)
-            .apply_t(&bn1, train)
-            .relu()
-            .max_pool2d(&[3, 3], &[2, 2], &[1, 1], &[1, 1], false)
-            .apply_t(&layer1, train)
-            .apply_t(&layer2, train)
-            .apply_t(&layer3, train)
-            .apply_t(&layer4, train)
+        xs.apply_t(&features, train)
             .adaptive_avg_pool2d(&[1, 1])
             .flat_view()
             .apply_opt(&fc)
@@ -173,25 +275,144 @@ fn bottleneck_resnet(
 }
 
 pub fn resnet50(p: &nn::Path, num_classes: i64) -> impl ModuleT {
-    bottleneck_resnet(p, Some(num_classes), 3, 4, 6, 3)
+    bottleneck_resnet(p, Some(num_classes), RESNET, 3, 4, 6, 3)
 }
 
 pub fn resnet50_no_final_layer(p: &nn::Path) -> impl ModuleT {
-    bottleneck_resnet(p, None, 3, 4, 6, 3)
+    bottleneck_resnet(p, None, RESNET, 3, 4, 6, 3)
+}
+
+/// Creates a ResNet-50 feature extractor, see `resnet18_features`.
+pub fn resnet50_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    bottleneck_features(p, RESNET, false, 3, 4, 6, 3)
 }
 
 pub fn resnet101(p: &nn::Path, num_classes: i64) -> impl ModuleT {
-    bottleneck_resnet(p, Some(num_classes), 3, 4, 23, 3)
+    bottleneck_resnet(p, Some(num_classes), RESNET, 3, 4, 23, 3)
 }
 
 pub fn resnet101_no_final_layer(p: &nn::Path) -> impl ModuleT {
-    bottleneck_resnet(p, None, 3, 4, 23, 3)
+    bottleneck_resnet(p, None, RESNET, 3, 4, 23, 3)
+}
+
+/// Creates a ResNet-101 feature extractor, see `resnet18_features`.
+pub fn resnet101_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    bottleneck_features(p, RESNET, false, 3, 4, 23, 3)
+}
+
+/// Creates a dilated ResNet-50 feature extractor as used by segmentation
+/// models, the `layer3` and `layer4` stages use dilated convolutions
+/// and have a stride of 8.
+pub fn resnet50_dilated_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    bottleneck_features(p, RESNET, true, 3, 4, 6, 3)
+}
+
+/// Creates a dilated ResNet-101 feature extractor, see
+/// `resnet50_dilated_features`.
+pub fn resnet101_dilated_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    bottleneck_features(p, RESNET, true, 3, 4, 23, 3)
 }
 
 pub fn resnet152(p: &nn::Path, num_classes: i64) -> impl ModuleT {
-    bottleneck_resnet(p, Some(num_classes), 3, 8, 36, 3)
+    bottleneck_resnet(p, Some(num_classes), RESNET, 3, 8, 36, 3)
 }
 
 pub fn resnet150_no_final_layer(p: &nn::Path) -> impl ModuleT {
-    bottleneck_resnet(p, None, 3, 8, 36, 3)
+    bottleneck_resnet(p, None, RESNET, 3, 8, 36, 3)
+}
+
+/// Creates a ResNet-152 feature extractor, see `resnet18_features`.
+pub fn resnet152_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    bottleneck_features(p, RESNET, false, 3, 8, 36, 3)
+}
+
+const RESNEXT_32X4D: Bottleneck = Bottleneck {
+    groups: 32,
+    base_width: 4,
+};
+
+const RESNEXT_32X8D: Bottleneck = Bottleneck {
+    groups: 32,
+    base_width: 8,
+};
+
+const RESNEXT_64X4D: Bottleneck = Bottleneck {
+    groups: 64,
+    base_width: 4,
+};
+
+const WIDE_RESNET: Bottleneck = Bottleneck {
+    groups: 1,
+    base_width: 128,
+};
+
+/// Creates a ResNeXt-50 32x4d model.
+pub fn resnext50_32x4d(p: &nn::Path, num_classes: i64) -> impl ModuleT {
+    bottleneck_resnet(p, Some(num_classes), RESNEXT_32X4D, 3, 4, 6, 3)
+}
+
+pub fn resnext50_32x4d_no_final_layer(p: &nn::Path) -> impl ModuleT {
+    bottleneck_resnet(p, None, RESNEXT_32X4D, 3, 4, 6, 3)
+}
+
+/// Creates a ResNeXt-50 32x4d feature extractor, see `resnet18_features`.
+pub fn resnext50_32x4d_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    bottleneck_features(p, RESNEXT_32X4D, false, 3, 4, 6, 3)
+}
+
+/// Creates a ResNeXt-101 32x8d model.
+pub fn resnext101_32x8d(p: &nn::Path, num_classes: i64) -> impl ModuleT {
+    bottleneck_resnet(p, Some(num_classes), RESNEXT_32X8D, 3, 4, 23, 3)
+}
+
+pub fn resnext101_32x8d_no_final_layer(p: &nn::Path) -> impl ModuleT {
+    bottleneck_resnet(p, None, RESNEXT_32X8D, 3, 4, 23, 3)
+}
+
+/// Creates a ResNeXt-101 32x8d feature extractor, see `resnet18_features`.
+pub fn resnext101_32x8d_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    bottleneck_features(p, RESNEXT_32X8D, false, 3, 4, 23, 3)
+}
+
+/// Creates a ResNeXt-101 64x4d model.
+pub fn resnext101_64x4d(p: &nn::Path, num_classes: i64) -> impl ModuleT {
+    bottleneck_resnet(p, Some(num_classes), RESNEXT_64X4D, 3, 4, 23, 3)
+}
+
+pub fn resnext101_64x4d_no_final_layer(p: &nn::Path) -> impl ModuleT {
+    bottleneck_resnet(p, None, RESNEXT_64X4D, 3, 4, 23, 3)
+}
+
+/// Creates a ResNeXt-101 64x4d feature extractor, see `resnet18_features`.
+pub fn resnext101_64x4d_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    bottleneck_features(p, RESNEXT_64X4D, false, 3, 4, 23, 3)
+}
+
+/// Creates a Wide ResNet-50-2 model, the bottleneck convolutions having
+/// twice as many channels as in ResNet-50.
+pub fn wide_resnet50_2(p: &nn::Path, num_classes: i64) -> impl ModuleT {
+    bottleneck_resnet(p, Some(num_classes), WIDE_RESNET, 3, 4, 6, 3)
+}
+
+pub fn wide_resnet50_2_no_final_layer(p: &nn::Path) -> impl ModuleT {
+    bottleneck_resnet(p, None, WIDE_RESNET, 3, 4, 6, 3)
+}
+
+/// Creates a Wide ResNet-50-2 feature extractor, see `resnet18_features`.
+pub fn wide_resnet50_2_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    bottleneck_features(p, WIDE_RESNET, false, 3, 4, 6, 3)
+}
+
+/// Creates a Wide ResNet-101-2 model.
+pub fn wide_resnet101_2(p: &nn::Path, num_classes: i64) -> impl ModuleT {
+    bottleneck_resnet(p, Some(num_classes), WIDE_RESNET, 3, 4, 23, 3)
+}
+
+pub fn wide_resnet101_2_no_final_layer(p: &nn::Path) -> impl ModuleT {
+    bottleneck_resnet(p, None, WIDE_RESNET, 3, 4, 23, 3)
+}
+
+/// Creates a Wide ResNet-101-2 feature extractor, see `resnet18_features`.
+pub fn wide_resnet101_2_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    bottleneck_features(p, WIDE_RESNET, false, 3, 4, 23, 3)
 }
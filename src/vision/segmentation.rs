@@ -0,0 +1,391 @@
+//! Semantic segmentation models, losses and metrics.
+//!
+//! See "Fully Convolutional Networks for Semantic Segmentation" Long et al. 2015
+//! https://arxiv.org/abs/1411.4038, "Rethinking Atrous Convolution for
+//! Semantic Image Segmentation" Chen et al. 2017
+//! https://arxiv.org/abs/1706.05587, and "U-Net: Convolutional Networks for
+//! Biomedical Image Segmentation" Ronneberger et al. 2015
+//! https://arxiv.org/abs/1505.04597
+//!
+//! The FCN and DeepLabV3 models use the torchvision variable names. Models
+//! take images of shape [batch, 3, height, width] and return logits of shape
+//! [batch, nclasses, height, width]. Targets are class indexes of shape
+//! [batch, height, width], the pixels with a target outside of the class range,
+//! e.g. the 255 boundary label of Pascal VOC, are ignored by the losses and
+//! metrics.
+use super::resnet;
+use crate::{nn, nn::FuncT, nn::ModuleT, Device, Kind, TchError, Tensor};
+
+// Conv2D + BatchNorm2D + ReLU.
+fn cbr(p: nn::Path, c_in: i64, c_out: i64, ksize: i64, dilation: i64) -> impl ModuleT {
+    let conv2d_cfg = nn::ConvConfig {
+        padding: dilation * (ksize - 1) / 2,
+        dilation,
+        bias: false,
+        ..Default::default()
+    };
+    nn::seq_t()
+        .add(nn::conv2d(&p / 0, c_in, c_out, ksize, conv2d_cfg))
+        .add(nn::batch_norm2d(&p / 1, c_out, Default::default()))
+        .add_fn(|xs| xs.relu())
+}
+
+fn upsample(xs: &Tensor, height: i64, width: i64) -> Tensor {
+    xs.upsample_bilinear2d(&[height, width], false, None, None)
+}
+
+fn fcn_head(p: nn::Path, c_in: i64, nclasses: i64) -> nn::SequentialT {
+    let c_inter = c_in / 4;
+    let conv2d_cfg = nn::ConvConfig {
+        padding: 1,
+        bias: false,
+        ..Default::default()
+    };
+    nn::seq_t()
+        .add(nn::conv2d(&p / 0, c_in, c_inter, 3, conv2d_cfg))
+        .add(nn::batch_norm2d(&p / 1, c_inter, Default::default()))
+        .add_fn(|xs| xs.relu())
+        .add_fn_t(|xs, train| xs.dropout(0.1, train))
+        .add(nn::conv2d(&p / 4, c_inter, nclasses, 1, Default::default()))
+}
+
+// Atrous Spatial Pyramid Pooling.
+fn aspp(p: nn::Path, c_in: i64, rates: [i64; 3]) -> impl ModuleT {
+    let c_out = 256;
+    let c_p = &p / "convs";
+    let convs = vec![
+        cbr(&c_p / 0, c_in, c_out, 1, 1),
+        cbr(&c_p / 1, c_in, c_out, 3, rates[0]),
+        cbr(&c_p / 2, c_in, c_out, 3, rates[1]),
+        cbr(&c_p / 3, c_in, c_out, 3, rates[2]),
+    ];
+    let pool_p = &c_p / 4;
+    let pooling = nn::seq_t()
+        .add(nn::conv2d(&pool_p / 1, c_in, c_out, 1, nn::no_bias()))
+        .add(nn::batch_norm2d(&pool_p / 2, c_out, Default::default()))
+        .add_fn(|xs| xs.relu());
+    let project = cbr(&p / "project", 5 * c_out, c_out, 1, 1);
+    nn::func_t(move |xs, train| {
+        let size = xs.size();
+        let mut ys: Vec<Tensor> = convs.iter().map(|conv| xs.apply_t(conv, train)).collect();
+        let pooled = xs.adaptive_avg_pool2d(&[1, 1]).apply_t(&pooling, train);
+        ys.push(upsample(&pooled, size[2], size[3]));
+        Tensor::cat(&ys, 1)
+            .apply_t(&project, train)
+            .dropout(0.5, train)
+    })
+}
+
+fn deeplab_head(p: nn::Path, c_in: i64, nclasses: i64) -> nn::SequentialT {
+    let conv2d_cfg = nn::ConvConfig {
+        padding: 1,
+        bias: false,
+        ..Default::default()
+    };
+    nn::seq_t()
+        .add(aspp(&p / 0, c_in, [12, 24, 36]))
+        .add(nn::conv2d(&p / 1, 256, 256, 3, conv2d_cfg))
+        .add(nn::batch_norm2d(&p / 2, 256, Default::default()))
+        .add_fn(|xs| xs.relu())
+        .add(nn::conv2d(&p / 4, 256, nclasses, 1, Default::default()))
+}
+
+/// A segmentation model made of a dilated ResNet backbone, a classifier
+/// head applied on the `layer4` features and an optional auxiliary
+/// classifier applied on the `layer3` features.
+#[derive(Debug)]
+pub struct SegmentationModel {
+    backbone: nn::IntermediateLayerGetter,
+    classifier: nn::SequentialT,
+    aux_classifier: Option<nn::SequentialT>,
+}
+
+impl SegmentationModel {
+    /// Returns the logits of the classifier and of the auxiliary classifier,
+    /// both upsampled to the size of the input images.
+    pub fn forward_aux(&self, xs: &Tensor, train: bool) -> (Tensor, Option<Tensor>) {
+        let size = xs.size();
+        let (h, w) = (size[size.len() - 2], size[size.len() - 1]);
+        let features = self.backbone.forward_features(xs, train);
+        let feature = |name: &str| {
+            features
+                .iter()
+                .find(|(n, _)| n.as_str() == name)
+                .map(|(_, t)| t)
+                .unwrap()
+        };
+        let out = feature("layer4").apply_t(&self.classifier, train);
+        let aux = self
+            .aux_classifier
+            .as_ref()
+            .map(|aux| upsample(&feature("layer3").apply_t(aux, train), h, w));
+        (upsample(&out, h, w), aux)
+    }
+}
+
+impl ModuleT for SegmentationModel {
+    fn forward_t(&self, xs: &Tensor, train: bool) -> Tensor {
+        self.forward_aux(xs, train).0
+    }
+}
+
+fn segmentation_model<F>(
+    p: &nn::Path,
+    backbone: nn::IntermediateLayerGetter,
+    head: F,
+    nclasses: i64,
+    aux: bool,
+) -> SegmentationModel
+where
+    F: FnOnce(nn::Path, i64, i64) -> nn::SequentialT,
+{
+    SegmentationModel {
+        backbone,
+        classifier: head(p / "classifier", 2048, nclasses),
+        aux_classifier: if aux {
+            Some(fcn_head(p / "aux_classifier", 1024, nclasses))
+        } else {
+            None
+        },
+    }
+}
+
+/// Creates a FCN model with a ResNet-50 backbone.
+pub fn fcn_resnet50(p: &nn::Path, nclasses: i64, aux: bool) -> SegmentationModel {
+    let backbone = resnet::resnet50_dilated_features(&(p / "backbone"));
+    segmentation_model(p, backbone, fcn_head, nclasses, aux)
+}
+
+/// Creates a FCN model with a ResNet-101 backbone.
+pub fn fcn_resnet101(p: &nn::Path, nclasses: i64, aux: bool) -> SegmentationModel {
+    let backbone = resnet::resnet101_dilated_features(&(p / "backbone"));
+    segmentation_model(p, backbone, fcn_head, nclasses, aux)
+}
+
+/// Creates a DeepLabV3 model with a ResNet-50 backbone.
+pub fn deeplabv3_resnet50(p: &nn::Path, nclasses: i64, aux: bool) -> SegmentationModel {
+    let backbone = resnet::resnet50_dilated_features(&(p / "backbone"));
+    segmentation_model(p, backbone, deeplab_head, nclasses, aux)
+}
+
+/// Creates a DeepLabV3 model with a ResNet-101 backbone.
+pub fn deeplabv3_resnet101(p: &nn::Path, nclasses: i64, aux: bool) -> SegmentationModel {
+    let backbone = resnet::resnet101_dilated_features(&(p / "backbone"));
+    segmentation_model(p, backbone, deeplab_head, nclasses, aux)
+}
+
+fn unet_block(p: nn::Path, c_in: i64, c_out: i64) -> impl ModuleT {
+    nn::seq_t()
+        .add(cbr(&p / "conv1", c_in, c_out, 3, 1))
+        .add(cbr(&p / "conv2", c_out, c_out, 3, 1))
+}
+
+/// Creates a U-Net model using an encoder returning four feature maps with
+/// strides 4, 8, 16, and 32, e.g. `resnet::resnet34_features`.
+///
+/// The decoder upsamples the deepest features and merges them with the
+/// shallower ones, the encoder variables should be created under `p / "encoder"`.
+pub fn unet(
+    p: &nn::Path,
+    encoder: nn::IntermediateLayerGetter,
+    encoder_channels: [i64; 4],
+    nclasses: i64,
+) -> FuncT<'static> {
+    let d_p = p / "decoder";
+    let decoder_channels = [256, 128, 64];
+    let mut c_in = encoder_channels[3];
+    let mut blocks = vec![];
+    for (i, &c_out) in decoder_channels.iter().enumerate() {
+        let c_skip = encoder_channels[2 - i];
+        blocks.push(unet_block(&d_p / i, c_in + c_skip, c_out));
+        c_in = c_out;
+    }
+    let head = nn::conv2d(p / "head", c_in, nclasses, 1, Default::default());
+    nn::func_t(move |xs, train| {
+        let size = xs.size();
+        let features = encoder.forward_features(xs, train);
+        let mut ys = features[3].1.shallow_clone();
+        for (block, (_, skip)) in blocks.iter().zip(features[..3].iter().rev()) {
+            let skip_size = skip.size();
+            let ys_up = upsample(&ys, skip_size[2], skip_size[3]);
+            ys = Tensor::cat(&[&ys_up, skip], 1).apply_t(block, train);
+        }
+        upsample(&ys.apply(&head), size[2], size[3])
+    })
+}
+
+/// Creates a U-Net model with a ResNet-18 encoder.
+pub fn unet_resnet18(p: &nn::Path, nclasses: i64) -> FuncT<'static> {
+    let encoder = resnet::resnet18_features(&(p / "encoder"));
+    unet(p, encoder, [64, 128, 256, 512], nclasses)
+}
+
+/// Creates a U-Net model with a ResNet-34 encoder.
+pub fn unet_resnet34(p: &nn::Path, nclasses: i64) -> FuncT<'static> {
+    let encoder = resnet::resnet34_features(&(p / "encoder"));
+    unet(p, encoder, [64, 128, 256, 512], nclasses)
+}
+
+/// Creates a U-Net model with a ResNet-50 encoder.
+pub fn unet_resnet50(p: &nn::Path, nclasses: i64) -> FuncT<'static> {
+    let encoder = resnet::resnet50_features(&(p / "encoder"));
+    unet(p, encoder, [256, 512, 1024, 2048], nclasses)
+}
+
+fn check_shapes(logits: &Tensor, targets: &Tensor) -> Result<(), TchError> {
+    let l_size = logits.size();
+    let t_size = targets.size();
+    if l_size.len() != 4
+        || t_size.len() != 3
+        || l_size[0] != t_size[0]
+        || l_size[2..] != t_size[1..]
+    {
+        return Err(TchError::Shape(format!(
+            "expected logits of shape [n, c, h, w] and targets of shape [n, h, w], got {:?} and {:?}",
+            l_size, t_size
+        )));
+    }
+    Ok(())
+}
+
+// Returns the per-class intersections, probability sums and target sums.
+fn soft_statistics(
+    logits: &Tensor,
+    targets: &Tensor,
+) -> Result<(Tensor, Tensor, Tensor), TchError> {
+    check_shapes(logits, targets)?;
+    let nclasses = logits.size()[1];
+    let kind = logits.kind();
+    let valid = targets.f_ge(0)?.f_logical_and(&targets.f_lt(nclasses)?)?;
+    let one_hot = targets
+        .f_where1(&valid, &targets.f_zeros_like()?)?
+        .f_to_kind(Kind::Int64)?
+        .f_one_hot(nclasses)?
+        .f_permute(&[0, 3, 1, 2])?
+        .f_to_kind(kind)?;
+    let valid = valid.f_unsqueeze(1)?.f_to_kind(kind)?;
+    let probs = logits.f_softmax(1, kind)?.f_mul(&valid)?;
+    let one_hot = one_hot.f_mul(&valid)?;
+    let dims = [0, 2, 3];
+    let intersection = probs.f_mul(&one_hot)?.f_sum1(&dims, false, kind)?;
+    Ok((
+        intersection,
+        probs.f_sum1(&dims, false, kind)?,
+        one_hot.f_sum1(&dims, false, kind)?,
+    ))
+}
+
+/// The soft Dice loss averaged over classes.
+///
+/// `smooth` is added to the numerator and denominator of the per-class Dice
+/// coefficients to avoid divisions by zero, 1.0 is a common value.
+pub fn dice_loss(logits: &Tensor, targets: &Tensor, smooth: f64) -> Result<Tensor, TchError> {
+    let (intersection, probs, targets) = soft_statistics(logits, targets)?;
+    let dice = (intersection * 2. + smooth) / (probs + targets + smooth);
+    Ok(1. - dice.f_mean(logits.kind())?)
+}
+
+/// The soft IoU (Jaccard) loss averaged over classes, see `dice_loss`.
+pub fn iou_loss(logits: &Tensor, targets: &Tensor, smooth: f64) -> Result<Tensor, TchError> {
+    let (intersection, probs, targets) = soft_statistics(logits, targets)?;
+    let union = probs + targets - &intersection;
+    let iou = (intersection + smooth) / (union + smooth);
+    Ok(1. - iou.f_mean(logits.kind())?)
+}
+
+/// A confusion matrix accumulated over batches of predictions, used to
+/// compute the per-class intersection over union and its mean (mIoU).
+#[derive(Debug)]
+pub struct ConfusionMatrix {
+    nclasses: i64,
+    // Rows correspond to targets and columns to predictions.
+    matrix: Tensor,
+}
+
+impl ConfusionMatrix {
+    pub fn new(nclasses: i64) -> ConfusionMatrix {
+        ConfusionMatrix {
+            nclasses,
+            matrix: Tensor::zeros(&[nclasses, nclasses], (Kind::Int64, Device::Cpu)),
+        }
+    }
+
+    /// Adds predicted class indexes, e.g. `logits.argmax(1, false)`, and the
+    /// corresponding targets, both having the same shape.
+    pub fn f_update(&mut self, predictions: &Tensor, targets: &Tensor) -> Result<(), TchError> {
+        if predictions.size() != targets.size() {
+            return Err(TchError::Shape(format!(
+                "predictions and targets have different shapes {:?} {:?}",
+                predictions.size(),
+                targets.size()
+            )));
+        }
+        let n = self.nclasses;
+        let valid = targets
+            .f_ge(0)?
+            .f_logical_and(&targets.f_lt(n)?)?
+            .f_logical_and(&predictions.f_ge(0)?)?
+            .f_logical_and(&predictions.f_lt(n)?)?;
+        let targets = targets.f_masked_select(&valid)?.f_to_kind(Kind::Int64)?;
+        let predictions = predictions
+            .f_masked_select(&valid)?
+            .f_to_kind(Kind::Int64)?;
+        let counts = (targets * n + predictions)
+            .f_bincount::<Tensor>(None, n * n)?
+            .f_view([n, n])?
+            .f_to_device(Device::Cpu)?;
+        self.matrix = self.matrix.f_add(&counts)?;
+        Ok(())
+    }
+
+    pub fn update(&mut self, predictions: &Tensor, targets: &Tensor) {
+        self.f_update(predictions, targets).unwrap()
+    }
+
+    /// The confusion matrix, rows correspond to targets and columns to
+    /// predictions.
+    pub fn matrix(&self) -> &Tensor {
+        &self.matrix
+    }
+
+    pub fn reset(&mut self) {
+        self.matrix = self.matrix.zeros_like()
+    }
+
+    /// The per-class intersection over union, NaN for the classes that
+    /// appear neither in the targets nor in the predictions.
+    pub fn iou(&self) -> Vec<f64> {
+        let matrix = Vec::<Vec<i64>>::from(&self.matrix);
+        (0..matrix.len())
+            .map(|i| {
+                let intersection = matrix[i][i];
+                let targets: i64 = matrix[i].iter().sum();
+                let predictions: i64 = matrix.iter().map(|row| row[i]).sum();
+                let union = targets + predictions - intersection;
+                if union == 0 {
+                    f64::NAN
+                } else {
+                    intersection as f64 / union as f64
+                }
+            })
+            .collect()
+    }
+
+    /// The intersection over union averaged over the classes that appear in
+    /// the targets or in the predictions.
+    ///
+    /// Returns NaN when no class appears, e.g. when nothing has been
+    /// accumulated yet.
+    pub fn mean_iou(&self) -> f64 {
+        let iou: Vec<f64> = self.iou().into_iter().filter(|v| !v.is_nan()).collect();
+        iou.iter().sum::<f64>() / iou.len() as f64
+    }
+
+    /// The ratio of correctly classified pixels, NaN when nothing has been
+    /// accumulated yet.
+    pub fn pixel_accuracy(&self) -> f64 {
+        let correct = self.matrix.diag(0).sum(Kind::Int64).int64_value(&[]);
+        let total = self.matrix.sum(Kind::Int64).int64_value(&[]);
+        correct as f64 / total as f64
+    }
+}
@@ -0,0 +1,155 @@
+//! ShuffleNet V2 implementation.
+//!
+//! See "ShuffleNet V2: Practical Guidelines for Efficient CNN Architecture
+//! Design" Ma et al. 2018 https://arxiv.org/abs/1807.11164
+use crate::{nn, nn::ModuleT, Tensor};
+
+fn conv2d(p: nn::Path, c_in: i64, c_out: i64, ksize: i64, stride: i64, groups: i64) -> nn::Conv2D {
+    let conv2d_cfg = nn::ConvConfig {
+        stride,
+        padding: (ksize - 1) / 2,
+        groups,
+        bias: false,
+        ..Default::default()
+    };
+    nn::conv2d(p, c_in, c_out, ksize, conv2d_cfg)
+}
+
+fn channel_shuffle(xs: &Tensor, groups: i64) -> Tensor {
+    let (b, c, h, w) = xs.size4().unwrap();
+    xs.view([b, groups, c / groups, h, w])
+        .transpose(1, 2)
+        .contiguous()
+        .view([b, c, h, w])
+}
+
+fn inverted_residual(p: nn::Path, c_in: i64, c_out: i64, stride: i64) -> impl ModuleT {
+    let c_branch = c_out / 2;
+    let branch1 = if stride > 1 {
+        let p = &p / "branch1";
+        nn::seq_t()
+            .add(conv2d(&p / 0, c_in, c_in, 3, stride, c_in))
+            .add(nn::batch_norm2d(&p / 1, c_in, Default::default()))
+            .add(conv2d(&p / 2, c_in, c_branch, 1, 1, 1))
+            .add(nn::batch_norm2d(&p / 3, c_branch, Default::default()))
+            .add_fn(|xs| xs.relu())
+    } else {
+        nn::seq_t()
+    };
+    let p = &p / "branch2";
+    let c_in2 = if stride > 1 { c_in } else { c_branch };
+    let branch2 = nn::seq_t()
+        .add(conv2d(&p / 0, c_in2, c_branch, 1, 1, 1))
+        .add(nn::batch_norm2d(&p / 1, c_branch, Default::default()))
+        .add_fn(|xs| xs.relu())
+        .add(conv2d(&p / 3, c_branch, c_branch, 3, stride, c_branch))
+        .add(nn::batch_norm2d(&p / 4, c_branch, Default::default()))
+        .add(conv2d(&p / 5, c_branch, c_branch, 1, 1, 1))
+        .add(nn::batch_norm2d(&p / 6, c_branch, Default::default()))
+        .add_fn(|xs| xs.relu());
+    nn::func_t(move |xs, train| {
+        let ys = if stride == 1 {
+            let xs = xs.chunk(2, 1);
+            Tensor::cat(&[&xs[0], &xs[1].apply_t(&branch2, train)], 1)
+        } else {
+            Tensor::cat(
+                &[xs.apply_t(&branch1, train), xs.apply_t(&branch2, train)],
+                1,
+            )
+        };
+        channel_shuffle(&ys, 2)
+    })
+}
+
+fn stage(p: nn::Path, c_in: i64, c_out: i64, cnt: i64) -> impl ModuleT {
+    let mut stage = nn::seq_t().add(inverted_residual(&p / 0, c_in, c_out, 2));
+    for block_index in 1..cnt {
+        stage = stage.add(inverted_residual(&p / block_index, c_out, c_out, 1))
+    }
+    stage
+}
+
+// Returns the feature extractor, the stages are preceded by the first
+// convolution and pooling layers and followed by the last convolution.
+fn features(p: &nn::Path, channels: [i64; 5]) -> nn::IntermediateLayerGetter {
+    let c1_p = p / "conv1";
+    let conv1 = nn::seq_t()
+        .add(conv2d(&c1_p / 0, 3, channels[0], 3, 2, 1))
+        .add(nn::batch_norm2d(&c1_p / 1, channels[0], Default::default()))
+        .add_fn(|xs| {
+            xs.relu()
+                .max_pool2d(&[3, 3], &[2, 2], &[1, 1], &[1, 1], false)
+        });
+    let c5_p = p / "conv5";
+    let conv5 = nn::seq_t()
+        .add(conv2d(&c5_p / 0, channels[3], channels[4], 1, 1, 1))
+        .add(nn::batch_norm2d(&c5_p / 1, channels[4], Default::default()))
+        .add_fn(|xs| xs.relu());
+    let features = nn::seq_t()
+        .add(conv1)
+        .add(stage(p / "stage2", channels[0], channels[1], 4))
+        .add(stage(p / "stage3", channels[1], channels[2], 8))
+        .add(stage(p / "stage4", channels[2], channels[3], 4))
+        .add(conv5);
+    nn::IntermediateLayerGetter::new(
+        features,
+        &[(0, "layer1"), (1, "layer2"), (2, "layer3"), (4, "layer4")],
+    )
+}
+
+fn shufflenet_v2(p: &nn::Path, channels: [i64; 5], nclasses: i64) -> impl ModuleT {
+    let features = features(p, channels);
+    let fc = nn::linear(p / "fc", channels[4], nclasses, Default::default());
+    nn::func_t(move |xs, train| {
+        let xs = xs.apply_t(&features, train);
+        xs.mean1(&[2, 3], false, xs.kind()).apply(&fc)
+    })
+}
+
+// The output channels of the first convolution, of the three stages, and
+// of the last convolution.
+const V2_X0_5: [i64; 5] = [24, 48, 96, 192, 1024];
+const V2_X1_0: [i64; 5] = [24, 116, 232, 464, 1024];
+const V2_X1_5: [i64; 5] = [24, 176, 352, 704, 1024];
+const V2_X2_0: [i64; 5] = [24, 244, 488, 976, 2048];
+
+/// Creates a ShuffleNet V2 model with 0.5x output channels.
+pub fn v2_x0_5(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    shufflenet_v2(p, V2_X0_5, nclasses)
+}
+
+/// Creates a ShuffleNet V2 model with 1.0x output channels.
+pub fn v2_x1_0(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    shufflenet_v2(p, V2_X1_0, nclasses)
+}
+
+/// Creates a ShuffleNet V2 model with 1.5x output channels.
+pub fn v2_x1_5(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    shufflenet_v2(p, V2_X1_5, nclasses)
+}
+
+/// Creates a ShuffleNet V2 model with 2.0x output channels.
+pub fn v2_x2_0(p: &nn::Path, nclasses: i64) -> impl ModuleT {
+    shufflenet_v2(p, V2_X2_0, nclasses)
+}
+
+/// Creates a ShuffleNet V2 0.5x feature extractor returning the outputs of
+/// the `layer1` to `layer4` stages, with strides of 4, 8, 16, and 32.
+pub fn v2_x0_5_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    features(p, V2_X0_5)
+}
+
+/// Creates a ShuffleNet V2 1.0x feature extractor, see `v2_x0_5_features`.
+pub fn v2_x1_0_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    features(p, V2_X1_0)
+}
+
+/// Creates a ShuffleNet V2 1.5x feature extractor, see `v2_x0_5_features`.
+pub fn v2_x1_5_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    features(p, V2_X1_5)
+}
+
+/// Creates a ShuffleNet V2 2.0x feature extractor, see `v2_x0_5_features`.
+pub fn v2_x2_0_features(p: &nn::Path) -> nn::IntermediateLayerGetter {
+    features(p, V2_X2_0)
+}
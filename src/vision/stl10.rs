@@ -0,0 +1,96 @@
+//! The STL-10 dataset.
+//!
+//! The files can be downloaded from the following page:
+//! https://cs.stanford.edu/~acoates/stl10/
+//! The binary version of the dataset is used, the unlabeled images are not
+//! loaded.
+use super::dataset::Dataset;
+use crate::{Kind, Tensor};
+use std::fs::File;
+use std::io::{self, BufReader, Read, Result};
+use std::path::Path;
+
+const W: i64 = 96;
+const H: i64 = 96;
+const C: i64 = 3;
+
+pub const CLASSES: [&str; 10] = [
+    "airplane", "bird", "car", "cat", "deer", "dog", "horse", "monkey", "ship", "truck",
+];
+
+fn read_bytes(filename: &Path) -> Result<Vec<u8>> {
+    let mut data = vec![];
+    BufReader::new(File::open(filename)?).read_to_end(&mut data)?;
+    Ok(data)
+}
+
+fn read_images_(filename: &Path) -> Result<Tensor> {
+    let data = read_bytes(filename)?;
+    if data.is_empty() || data.len() as i64 % (C * H * W) != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected file size {}", data.len()),
+        ));
+    }
+    // The images are stored in column-major order.
+    let images = Tensor::of_slice(&data)
+        .view((-1, C, W, H))
+        .transpose(2, 3)
+        .to_kind(Kind::Float);
+    Ok(images / 255.)
+}
+
+fn read_labels_(filename: &Path) -> Result<Tensor> {
+    let data = read_bytes(filename)?;
+    // The labels range from 1 to 10.
+    if let Some(label) = data.iter().find(|&&l| l < 1 || l > 10) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("unexpected label {}", label),
+        ));
+    }
+    Ok(Tensor::of_slice(&data).to_kind(Kind::Int64) - 1)
+}
+
+fn read_images(filename: &Path) -> Result<Tensor> {
+    read_images_(filename)
+        .map_err(|err| std::io::Error::new(err.kind(), format!("{:?} {}", filename, err)))
+}
+
+fn read_labels(filename: &Path) -> Result<Tensor> {
+    read_labels_(filename)
+        .map_err(|err| std::io::Error::new(err.kind(), format!("{:?} {}", filename, err)))
+}
+
+// Reads the images and labels of the train or test split.
+fn read_split(dir: &Path, split: &str) -> Result<(Tensor, Tensor)> {
+    let images = read_images(&dir.join(format!("{}_X.bin", split)))?;
+    let labels = read_labels(&dir.join(format!("{}_y.bin", split)))?;
+    if images.size()[0] != labels.size()[0] {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "{} {} images for {} labels",
+                split,
+                images.size()[0],
+                labels.size()[0]
+            ),
+        ));
+    }
+    Ok((images, labels))
+}
+
+/// Loads the dataset from the `stl10_binary` directory containing the
+/// `train_X.bin`, `train_y.bin`, `test_X.bin`, and `test_y.bin` files.
+pub fn load_dir<T: AsRef<Path>>(dir: T) -> Result<Dataset> {
+    let dir = dir.as_ref();
+    let (train_images, train_labels) = read_split(dir, "train")?;
+    let (test_images, test_labels) = read_split(dir, "test")?;
+    Ok(Dataset {
+        train_images,
+        train_labels,
+        test_images,
+        test_labels,
+        labels: 10,
+    })
+}
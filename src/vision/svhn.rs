@@ -0,0 +1,220 @@
+//! The Street View House Numbers (SVHN) dataset.
+//!
+//! The files can be downloaded from the following page:
+//! http://ufldl.stanford.edu/housenumbers/
+//! The cropped digits version of the dataset is used, the `.mat` files are
+//! read directly. Digits are labeled from 0 to 9.
+use super::dataset::Dataset;
+use crate::{Kind, Tensor};
+use flate2::read::ZlibDecoder;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, BufReader, Read, Result};
+use std::path::Path;
+
+// MAT-file v5 data types.
+const MI_INT8: u32 = 1;
+const MI_UINT8: u32 = 2;
+const MI_INT16: u32 = 3;
+const MI_UINT16: u32 = 4;
+const MI_INT32: u32 = 5;
+const MI_UINT32: u32 = 6;
+const MI_SINGLE: u32 = 7;
+const MI_DOUBLE: u32 = 9;
+const MI_INT64: u32 = 12;
+const MI_UINT64: u32 = 13;
+const MI_MATRIX: u32 = 14;
+const MI_COMPRESSED: u32 = 15;
+
+// The numeric array classes, from mxDOUBLE_CLASS to mxUINT64_CLASS.
+const MX_NUMERIC_CLASSES: std::ops::RangeInclusive<u8> = 6..=15;
+const MX_COMPLEX_FLAG: u8 = 0x08;
+
+fn invalid_data<T>(msg: String) -> Result<T> {
+    Err(io::Error::new(io::ErrorKind::InvalidData, msg))
+}
+
+fn u32_le(b: &[u8]) -> u32 {
+    u32::from_le_bytes([b[0], b[1], b[2], b[3]])
+}
+
+// Splits a data element into its type and content, also returns the bytes
+// following the element.
+fn read_element(data: &[u8]) -> Result<(u32, &[u8], &[u8])> {
+    if data.len() < 8 {
+        return invalid_data(format!("truncated data element {}", data.len()));
+    }
+    let tag = u32_le(&data[0..4]);
+    if tag >> 16 != 0 {
+        // Small data element, the size is stored with the type and the data
+        // uses the 4 remaining bytes.
+        let size = (tag >> 16) as usize;
+        if size > 4 {
+            return invalid_data(format!("invalid small data element size {}", size));
+        }
+        return Ok((tag & 0xffff, &data[4..4 + size], &data[8..]));
+    }
+    let size = u32_le(&data[4..8]) as usize;
+    if data.len() < 8 + size {
+        return invalid_data(format!("truncated data element {} < {}", data.len(), size));
+    }
+    // Data elements are padded to 64 bits, except for compressed ones.
+    let end = if tag == MI_COMPRESSED {
+        8 + size
+    } else {
+        usize::min(8 + (size + 7) / 8 * 8, data.len())
+    };
+    Ok((tag, &data[8..8 + size], &data[end..]))
+}
+
+fn numeric_tensor(typ: u32, data: &[u8]) -> Result<Tensor> {
+    macro_rules! of_le_bytes {
+        ($typ:ty, $n:expr, $out:ty) => {{
+            let values: Vec<$out> = data
+                .chunks_exact($n)
+                .map(|b| {
+                    let mut bytes = [0u8; $n];
+                    bytes.copy_from_slice(b);
+                    <$typ>::from_le_bytes(bytes) as $out
+                })
+                .collect();
+            Tensor::of_slice(&values)
+        }};
+    }
+    let tensor = match typ {
+        MI_INT8 => of_le_bytes!(i8, 1, i8),
+        MI_UINT8 => Tensor::of_slice(data),
+        MI_INT16 => of_le_bytes!(i16, 2, i16),
+        MI_UINT16 => of_le_bytes!(u16, 2, i32),
+        MI_INT32 => of_le_bytes!(i32, 4, i32),
+        MI_UINT32 => of_le_bytes!(u32, 4, i64),
+        MI_SINGLE => of_le_bytes!(f32, 4, f32),
+        MI_DOUBLE => of_le_bytes!(f64, 8, f64),
+        MI_INT64 => of_le_bytes!(i64, 8, i64),
+        MI_UINT64 => {
+            // There is no unsigned 64 bits kind, values that do not fit in an
+            // int64 are rejected rather than wrapped.
+            let values = data
+                .chunks_exact(8)
+                .map(|b| {
+                    let mut bytes = [0u8; 8];
+                    bytes.copy_from_slice(b);
+                    let v = u64::from_le_bytes(bytes);
+                    match i64::try_from(v) {
+                        Ok(v) => Ok(v),
+                        Err(_) => invalid_data(format!("{} does not fit in an int64", v)),
+                    }
+                })
+                .collect::<Result<Vec<_>>>()?;
+            Tensor::of_slice(&values)
+        }
+        _ => return invalid_data(format!("unsupported data type {}", typ)),
+    };
+    Ok(tensor)
+}
+
+// Returns the name and content of a numeric array, None for other arrays.
+fn read_matrix(data: &[u8]) -> Result<Option<(String, Tensor)>> {
+    let (_, flags, data) = read_element(data)?;
+    let (_, dims, data) = read_element(data)?;
+    let (_, name, data) = read_element(data)?;
+    if flags.len() < 2 || !MX_NUMERIC_CLASSES.contains(&flags[0]) || flags[1] & MX_COMPLEX_FLAG != 0
+    {
+        return Ok(None);
+    }
+    // Arrays are stored in column-major order, the dimensions are reversed
+    // to get a row-major tensor.
+    let dims: Vec<i64> = dims
+        .chunks_exact(4)
+        .rev()
+        .map(|b| i64::from(u32_le(b) as i32))
+        .collect();
+    let numel = dims.iter().try_fold(
+        1i64,
+        |acc, &d| if d < 0 { None } else { acc.checked_mul(d) },
+    );
+    let (typ, real, _) = read_element(data)?;
+    let tensor = numeric_tensor(typ, real)?;
+    if numel != Some(tensor.numel() as i64) {
+        return invalid_data(format!("unexpected number of elements for {:?}", dims));
+    }
+    let tensor = tensor
+        .f_view(dims.as_slice())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))?;
+    Ok(Some((String::from_utf8_lossy(name).to_string(), tensor)))
+}
+
+// Reads the numeric arrays of a MAT-file v5.
+fn read_mat(filename: &Path) -> Result<HashMap<String, Tensor>> {
+    let mut data = vec![];
+    BufReader::new(File::open(filename)?).read_to_end(&mut data)?;
+    if data.len() < 128 || &data[126..128] != b"IM" {
+        return invalid_data("not a little-endian MAT-file v5".to_string());
+    }
+    let mut arrays = HashMap::new();
+    let mut data = &data[128..];
+    while data.len() >= 8 {
+        let (typ, content, rest) = read_element(data)?;
+        let decompressed;
+        let (typ, content) = if typ == MI_COMPRESSED {
+            let mut buffer = vec![];
+            ZlibDecoder::new(content).read_to_end(&mut buffer)?;
+            decompressed = buffer;
+            let (typ, content, _) = read_element(&decompressed)?;
+            (typ, content)
+        } else {
+            (typ, content)
+        };
+        if typ == MI_MATRIX {
+            if let Some((name, tensor)) = read_matrix(content)? {
+                arrays.insert(name, tensor);
+            }
+        }
+        data = rest;
+    }
+    Ok(arrays)
+}
+
+fn read_file_(filename: &Path) -> Result<(Tensor, Tensor)> {
+    let mut arrays = read_mat(filename)?;
+    let (images, labels) = match (arrays.remove("X"), arrays.remove("y")) {
+        (Some(images), Some(labels)) => (images, labels),
+        _ => return invalid_data("missing X or y array".to_string()),
+    };
+    // X has dimensions [32, 32, 3, samples] in MATLAB, i.e. [samples, 3, width, height]
+    // once reversed.
+    if images.dim() != 4 {
+        return invalid_data(format!("unexpected image dimensions {:?}", images.size()));
+    }
+    let images = images.transpose(2, 3).to_kind(Kind::Float) / 255.;
+    // The digit 0 uses the label 10.
+    let labels = labels.view([-1]).to_kind(Kind::Int64).fmod(10);
+    Ok((images, labels))
+}
+
+fn read_file(filename: &Path) -> Result<(Tensor, Tensor)> {
+    read_file_(filename)
+        .map_err(|err| std::io::Error::new(err.kind(), format!("{:?} {}", filename, err)))
+}
+
+/// Loads the dataset from a directory containing the `train_32x32.mat` and
+/// `test_32x32.mat` files.
+pub fn load_dir<T: AsRef<Path>>(dir: T) -> Result<Dataset> {
+    let dir = dir.as_ref();
+    let (train_images, train_labels) = read_file(&dir.join("train_32x32.mat"))?;
+    let (test_images, test_labels) = read_file(&dir.join("test_32x32.mat"))?;
+    Ok(Dataset {
+        train_images,
+        train_labels,
+        test_images,
+        test_labels,
+        labels: 10,
+    })
+}
+
+/// Loads the images and labels of the additional training samples from the
+/// `extra_32x32.mat` file.
+pub fn load_extra<T: AsRef<Path>>(dir: T) -> Result<(Tensor, Tensor)> {
+    read_file(&dir.as_ref().join("extra_32x32.mat"))
+}
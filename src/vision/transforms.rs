@@ -0,0 +1,1200 @@
+//! Composable image transforms for data augmentation and preprocessing.
+//!
+//! Transforms operate either on a single image of shape [channel, height, width]
+//! or on a batch of shape [batch, channel, height, width]. Images are expected to
+//! be float tensors with values between 0 and 1 and to use the RGB channel order
+//! for color transforms, `ToFloat` can be used to convert images as returned by
+//! `vision::image::load`.
+//!
+//! Random transforms draw their parameters from a user provided random number
+//! generator so that pipelines can be seeded. When applied on a batch, the
+//! parameters are drawn independently for each image.
+//!
+//! ```ignore
+//! use tch::vision::transforms::*;
+//!
+//! let transforms = compose()
+//!     .add(ToFloat)
+//!     .add(RandomResizedCrop::new(224, 224))
+//!     .add(RandomHorizontalFlip::new(0.5))
+//!     .add(ColorJitter::new(0.4, 0.4, 0.4, 0.1))
+//!     .add(Normalize::imagenet())
+//!     .seed(42);
+//! let batch = images.apply(&transforms);
+//! ```
+use crate::{Kind, TchError, Tensor};
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, RngCore, SeedableRng};
+use std::sync::Mutex;
+
+/// An image transform.
+pub trait Transform: std::fmt::Debug + Send + Sync {
+    /// Applies the transform to an image of shape [channel, height, width].
+    fn apply(&self, image: &Tensor, rng: &mut dyn RngCore) -> Result<Tensor, TchError>;
+
+    /// Applies the transform to a batch of images of shape
+    /// [batch, channel, height, width].
+    ///
+    /// The default implementation applies the transform to each image, the
+    /// resulting images must all have the same shape.
+    fn apply_batch(&self, images: &Tensor, rng: &mut dyn RngCore) -> Result<Tensor, TchError> {
+        check_dim(images, 4)?;
+        let images = (0..images.size()[0])
+            .map(|i| self.apply(&images.get(i), rng))
+            .collect::<Result<Vec<_>, _>>()?;
+        Tensor::f_stack(&images, 0)
+    }
+}
+
+fn check_dim(t: &Tensor, dim: usize) -> Result<(), TchError> {
+    if t.dim() != dim {
+        return Err(TchError::Shape(format!(
+            "expected a tensor with {} dimensions, got shape {:?}",
+            dim,
+            t.size()
+        )));
+    }
+    Ok(())
+}
+
+fn check_image(t: &Tensor) -> Result<(), TchError> {
+    match t.dim() {
+        3 | 4 => Ok(()),
+        _ => Err(TchError::Shape(format!(
+            "expected an image of shape [c, h, w] or [n, c, h, w], got {:?}",
+            t.size()
+        ))),
+    }
+}
+
+// Returns the height and width of an image or a batch of images.
+fn height_width(t: &Tensor) -> Result<(i64, i64), TchError> {
+    check_image(t)?;
+    let size = t.size();
+    Ok((size[size.len() - 2], size[size.len() - 1]))
+}
+
+// Returns true with probability `p`. Contrary to `gen_bool` this does not
+// panic when `p` is outside of [0, 1]: the result is always false for
+// `p <= 0` and always true for `p >= 1`.
+fn sample_bool(rng: &mut dyn RngCore, p: f64) -> bool {
+    rng.gen::<f64>() < p
+}
+
+// Applies a function expecting a batch of images to either a single image
+// or a batch.
+fn batched<F>(t: &Tensor, f: F) -> Result<Tensor, TchError>
+where
+    F: FnOnce(&Tensor) -> Result<Tensor, TchError>,
+{
+    check_image(t)?;
+    if t.dim() == 3 {
+        f(&t.f_unsqueeze(0)?)?.f_squeeze1(0)
+    } else {
+        f(t)
+    }
+}
+
+fn uniform(rng: &mut dyn RngCore, lo: f64, hi: f64) -> f64 {
+    if lo < hi {
+        rng.gen_range(lo..hi)
+    } else {
+        lo
+    }
+}
+
+fn grayscale(t: &Tensor) -> Result<Tensor, TchError> {
+    let c = t.size()[t.dim() - 3];
+    if c == 1 {
+        return Ok(t.shallow_clone());
+    }
+    if c != 3 {
+        return Err(TchError::Shape(format!(
+            "expected an image with 1 or 3 channels, got shape {:?}",
+            t.size()
+        )));
+    }
+    let weights = Tensor::of_slice(&[0.2989f32, 0.587, 0.114])
+        .f_to_device(t.device())?
+        .f_to_kind(t.kind())?
+        .f_view([3, 1, 1])?;
+    t.f_mul(&weights)?.f_sum1(&[-3], true, t.kind())
+}
+
+fn blend(t1: &Tensor, t2: &Tensor, ratio: f64) -> Result<Tensor, TchError> {
+    (t1 * ratio)
+        .f_add(&(t2 * (1f64 - ratio)))?
+        .f_clamp(0.0, 1.0)
+}
+
+/// Resizes an image to the given height and width using bilinear interpolation.
+///
+/// Uint8 images are interpolated as floats and rounded back to uint8.
+pub fn resize(t: &Tensor, height: i64, width: i64) -> Result<Tensor, TchError> {
+    if t.f_kind()? == Kind::Uint8 {
+        return resize(&t.f_to_kind(Kind::Float)?, height, width)?
+            .f_round()?
+            .f_clamp(0.0, 255.0)?
+            .f_to_kind(Kind::Uint8);
+    }
+    batched(t, |t| {
+        t.f_upsample_bilinear2d(&[height, width], false, None, None)
+    })
+}
+
+/// Crops an image, `top` and `left` are the coordinates of the top-left corner.
+pub fn crop(t: &Tensor, top: i64, left: i64, height: i64, width: i64) -> Result<Tensor, TchError> {
+    let (h, w) = height_width(t)?;
+    if top < 0 || left < 0 || top + height > h || left + width > w {
+        return Err(TchError::Shape(format!(
+            "crop ({}, {}, {}, {}) out of an image of size {}x{}",
+            top, left, height, width, h, w
+        )));
+    }
+    let dim = t.dim() as i64;
+    t.f_narrow(dim - 2, top, height)?
+        .f_narrow(dim - 1, left, width)
+}
+
+/// Crops the center of an image.
+pub fn center_crop(t: &Tensor, height: i64, width: i64) -> Result<Tensor, TchError> {
+    let (h, w) = height_width(t)?;
+    crop(t, (h - height) / 2, (w - width) / 2, height, width)
+}
+
+/// Flips an image horizontally.
+pub fn hflip(t: &Tensor) -> Result<Tensor, TchError> {
+    check_image(t)?;
+    t.f_flip(&[-1])
+}
+
+/// Flips an image vertically.
+pub fn vflip(t: &Tensor) -> Result<Tensor, TchError> {
+    check_image(t)?;
+    t.f_flip(&[-2])
+}
+
+/// Normalizes an image using the given per-channel mean and standard deviation.
+pub fn normalize(t: &Tensor, mean: &[f64], std: &[f64]) -> Result<Tensor, TchError> {
+    check_image(t)?;
+    let mean = Tensor::of_slice(mean)
+        .f_to_device(t.device())?
+        .f_to_kind(t.kind())?
+        .f_view([-1, 1, 1])?;
+    let std = Tensor::of_slice(std)
+        .f_to_device(t.device())?
+        .f_to_kind(t.kind())?
+        .f_view([-1, 1, 1])?;
+    t.f_sub(&mean)?.f_div(&std)
+}
+
+/// Multiplies the image values by `factor`.
+pub fn adjust_brightness(t: &Tensor, factor: f64) -> Result<Tensor, TchError> {
+    check_image(t)?;
+    (t * factor).f_clamp(0.0, 1.0)
+}
+
+/// Adjusts the contrast, a factor of 0 results in a uniform gray image.
+pub fn adjust_contrast(t: &Tensor, factor: f64) -> Result<Tensor, TchError> {
+    check_image(t)?;
+    let mean = grayscale(t)?.f_mean1(&[-3, -2, -1], true, t.kind())?;
+    blend(t, &mean, factor)
+}
+
+/// Adjusts the saturation, a factor of 0 results in a grayscale image.
+pub fn adjust_saturation(t: &Tensor, factor: f64) -> Result<Tensor, TchError> {
+    check_image(t)?;
+    blend(t, &grayscale(t)?, factor)
+}
+
+fn rgb_to_hsv(t: &Tensor) -> Result<(Tensor, Tensor, Tensor), TchError> {
+    let kind = t.kind();
+    let r = t.f_select(-3, 0)?;
+    let g = t.f_select(-3, 1)?;
+    let b = t.f_select(-3, 2)?;
+    let maxc = t.f_amax(&[-3], false)?;
+    let minc = t.f_amin(&[-3], false)?;
+    let eqc = maxc.f_eq1(&minc)?;
+    let ones = maxc.f_ones_like()?;
+    let cr = maxc.f_sub(&minc)?;
+    let s = cr.f_div(&ones.f_where1(&eqc, &maxc)?)?;
+    let cr_divisor = ones.f_where1(&eqc, &cr)?;
+    let rc = maxc.f_sub(&r)?.f_div(&cr_divisor)?;
+    let gc = maxc.f_sub(&g)?.f_div(&cr_divisor)?;
+    let bc = maxc.f_sub(&b)?.f_div(&cr_divisor)?;
+    let max_r = maxc.f_eq1(&r)?.f_to_kind(kind)?;
+    let max_g = maxc.f_eq1(&g)?.f_to_kind(kind)?;
+    let hr = &max_r * (&bc - &gc);
+    let hg = &max_g * (1f64 - &max_r) * (&rc - &bc + 2.0);
+    let hb = (1f64 - &max_g) * (1f64 - &max_r) * (&gc - &rc + 4.0);
+    let h = ((hr + hg + hb) / 6.0 + 1.0).f_fmod(1.0)?;
+    Ok((h, s, maxc))
+}
+
+fn hsv_to_rgb(h: &Tensor, s: &Tensor, v: &Tensor) -> Result<Tensor, TchError> {
+    let kind = h.kind();
+    let i = (h * 6.0).f_floor()?;
+    let f = h * 6.0 - &i;
+    let i = i.f_to_kind(Kind::Int64)?.f_remainder(6)?;
+    let p = (v * (1f64 - s)).f_clamp(0.0, 1.0)?;
+    let q = (v * (1f64 - s * &f)).f_clamp(0.0, 1.0)?;
+    let t = (v * (1f64 - s * (1f64 - &f))).f_clamp(0.0, 1.0)?;
+    let range = Tensor::f_arange(6, (Kind::Int64, h.device()))?.f_view([-1, 1, 1])?;
+    let mask = i.f_unsqueeze(-3)?.f_eq1(&range)?.f_to_kind(kind)?;
+    let channel = |values: &[&Tensor]| -> Result<Tensor, TchError> {
+        Tensor::f_stack(values, -3)?
+            .f_mul(&mask)?
+            .f_sum1(&[-3], false, kind)
+    };
+    let r = channel(&[v, &q, &p, &p, &t, v])?;
+    let g = channel(&[&t, v, v, &q, &p, &p])?;
+    let b = channel(&[&p, &p, &t, v, v, &q])?;
+    Tensor::f_stack(&[r, g, b], -3)
+}
+
+/// Shifts the hue of an RGB image, `factor` should be between -0.5 and 0.5.
+pub fn adjust_hue(t: &Tensor, factor: f64) -> Result<Tensor, TchError> {
+    check_image(t)?;
+    if !(-0.5..=0.5).contains(&factor) {
+        return Err(TchError::Kind(format!(
+            "hue factor {} is not between -0.5 and 0.5",
+            factor
+        )));
+    }
+    let (h, s, v) = rgb_to_hsv(t)?;
+    let h = (h + factor).f_remainder(1.0)?;
+    hsv_to_rgb(&h, &s, &v)
+}
+
+/// Adjusts the sharpness, a factor of 0 results in a blurred image.
+pub fn adjust_sharpness(t: &Tensor, factor: f64) -> Result<Tensor, TchError> {
+    let (h, w) = height_width(t)?;
+    if h <= 2 || w <= 2 {
+        return Ok(t.shallow_clone());
+    }
+    batched(t, |t| {
+        let c = t.size()[1];
+        let kernel = Tensor::of_slice(&[1f32, 1., 1., 1., 5., 1., 1., 1., 1.])
+            .f_to_device(t.device())?
+            .f_to_kind(t.kind())?
+            .f_div1(13.0)?
+            .f_view([1, 1, 3, 3])?
+            .f_expand(&[c, 1, 3, 3], false)?;
+        let blurred = t.f_conv2d::<Tensor>(&kernel, None, &[1, 1], &[0, 0], &[1, 1], c)?;
+        let degenerate = t.copy();
+        degenerate
+            .f_narrow(2, 1, h - 2)?
+            .f_narrow(3, 1, w - 2)?
+            .f_copy_(&blurred)?;
+        blend(t, &degenerate, factor)
+    })
+}
+
+/// Inverts the colors of an image.
+pub fn invert(t: &Tensor) -> Result<Tensor, TchError> {
+    check_image(t)?;
+    Ok(1f64 - t)
+}
+
+/// Only keeps the `bits` most significant bits of each channel, the values
+/// of float images are quantized to 8 bits first. The result has the same
+/// kind as the input.
+pub fn posterize(t: &Tensor, bits: i64) -> Result<Tensor, TchError> {
+    check_image(t)?;
+    let mask = -(1i64 << (8 - bits.max(0).min(8))) & 255;
+    let kind = t.f_kind()?;
+    if kind == Kind::Uint8 {
+        return t.f_bitwise_and(mask);
+    }
+    let t = (t * 255.0)
+        .f_round()?
+        .f_to_kind(Kind::Uint8)?
+        .f_bitwise_and(mask)?;
+    t.f_to_kind(kind)?.f_div1(255.0)
+}
+
+/// Inverts all the values above `threshold`.
+pub fn solarize(t: &Tensor, threshold: f64) -> Result<Tensor, TchError> {
+    check_image(t)?;
+    (1f64 - t).f_where1(&t.f_ge(threshold)?, t)
+}
+
+/// Rescales each channel so that its values span the full [0, 1] range.
+pub fn autocontrast(t: &Tensor) -> Result<Tensor, TchError> {
+    check_image(t)?;
+    let minimum = t.f_amin(&[-2, -1], true)?;
+    let maximum = t.f_amax(&[-2, -1], true)?;
+    let eq = maximum.f_eq1(&minimum)?;
+    let range = maximum.f_sub(&minimum)?;
+    let scale = range.f_ones_like()?.f_where1(&eq, &range.f_reciprocal()?)?;
+    let minimum = minimum.f_zeros_like()?.f_where1(&eq, &minimum)?;
+    t.f_sub(&minimum)?.f_mul(&scale)?.f_clamp(0.0, 1.0)
+}
+
+// Equalizes a single channel with values between 0 and 255.
+fn equalize_channel(channel: &Tensor) -> Result<Tensor, TchError> {
+    let hist = Vec::<i64>::from(&channel.f_flatten(0, -1)?.f_bincount::<Tensor>(None, 256)?);
+    let nonzero = hist.iter().filter(|&&h| h > 0).collect::<Vec<_>>();
+    let last = nonzero.last().map_or(0, |&&h| h);
+    let step = (nonzero.iter().map(|&&h| h).sum::<i64>() - last) / 255;
+    if step == 0 {
+        return Ok(channel.shallow_clone());
+    }
+    let mut lut = Vec::with_capacity(256);
+    let mut cumsum = 0;
+    for h in hist.iter() {
+        lut.push(((cumsum + step / 2) / step).min(255));
+        cumsum += h;
+    }
+    let lut = Tensor::of_slice(&lut).f_to_device(channel.device())?;
+    lut.f_index_select(0, &channel.f_flatten(0, -1)?)?
+        .f_view_as(channel)
+}
+
+/// Equalizes the histogram of each channel.
+pub fn equalize(t: &Tensor) -> Result<Tensor, TchError> {
+    let kind = t.kind();
+    batched(t, |t| {
+        let t = (t * 255.0).f_round()?.f_to_kind(Kind::Int64)?;
+        let size = t.size();
+        let mut images = vec![];
+        for i in 0..size[0] {
+            let image = t.get(i);
+            let channels = (0..size[1])
+                .map(|c| equalize_channel(&image.get(c)))
+                .collect::<Result<Vec<_>, _>>()?;
+            images.push(Tensor::f_stack(&channels, 0)?)
+        }
+        Tensor::f_stack(&images, 0)?.f_to_kind(kind)?.f_div1(255.0)
+    })
+}
+
+/// Blurs an image using a gaussian kernel, the kernel sizes must be odd.
+pub fn gaussian_blur(
+    t: &Tensor,
+    kernel_size: (i64, i64),
+    sigma: (f64, f64),
+) -> Result<Tensor, TchError> {
+    let (kh, kw) = kernel_size;
+    if kh <= 0 || kw <= 0 || kh % 2 == 0 || kw % 2 == 0 {
+        return Err(TchError::Shape(format!(
+            "kernel sizes should be odd and positive, got {:?}",
+            kernel_size
+        )));
+    }
+    let kernel1d = |size: i64, sigma: f64| {
+        let half = (size - 1) as f64 / 2.0;
+        let values = (0..size)
+            .map(|i| (-0.5 * ((i as f64 - half) / sigma).powi(2)).exp())
+            .collect::<Vec<_>>();
+        let sum: f64 = values.iter().sum();
+        values.iter().map(|v| (v / sum) as f32).collect::<Vec<_>>()
+    };
+    let ky = Tensor::of_slice(&kernel1d(kh, sigma.0)).f_view([-1, 1])?;
+    let kx = Tensor::of_slice(&kernel1d(kw, sigma.1)).f_view([1, -1])?;
+    batched(t, |t| {
+        let c = t.size()[1];
+        let kernel = ky
+            .f_mul(&kx)?
+            .f_to_device(t.device())?
+            .f_to_kind(t.kind())?
+            .f_expand(&[c, 1, kh, kw], false)?;
+        let padded = t.f_reflection_pad2d(&[kw / 2, kw / 2, kh / 2, kh / 2])?;
+        padded.f_conv2d::<Tensor>(&kernel, None, &[1, 1], &[0, 0], &[1, 1], c)
+    })
+}
+
+/// The parameters of an affine transformation, the transformation is centered
+/// on the middle of the image.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AffineParams {
+    /// The rotation angle in degrees, counter-clockwise.
+    pub angle: f64,
+    /// The horizontal and vertical translations in pixels.
+    pub translate: (f64, f64),
+    pub scale: f64,
+    /// The horizontal and vertical shear angles in degrees.
+    pub shear: (f64, f64),
+}
+
+impl Default for AffineParams {
+    fn default() -> Self {
+        AffineParams {
+            angle: 0.0,
+            translate: (0.0, 0.0),
+            scale: 1.0,
+            shear: (0.0, 0.0),
+        }
+    }
+}
+
+impl AffineParams {
+    // Returns the matrix mapping the normalized output coordinates to the
+    // normalized input coordinates as expected by `affine_grid_generator`.
+    fn theta(&self, height: i64, width: i64) -> [f32; 6] {
+        let (angle, shx, shy) = (
+            self.angle.to_radians(),
+            self.shear.0.to_radians(),
+            self.shear.1.to_radians(),
+        );
+        // Rotation * shear * scale, using coordinates where y goes down.
+        let (cos, sin) = (angle.cos(), angle.sin());
+        let (tx, ty) = (shx.tan(), shy.tan());
+        let a = self.scale * (cos + sin * ty);
+        let b = self.scale * (cos * tx + sin);
+        let c = self.scale * (-sin + cos * ty);
+        let d = self.scale * (-sin * tx + cos);
+        let det = a * d - b * c;
+        let (ia, ib, ic, id) = (d / det, -b / det, -c / det, a / det);
+        let (w, h) = (width as f64 / 2.0, height as f64 / 2.0);
+        let (dx, dy) = self.translate;
+        [
+            ia as f32,
+            (ib * h / w) as f32,
+            (-(ia * dx + ib * dy) / w) as f32,
+            (ic * w / h) as f32,
+            id as f32,
+            (-(ic * dx + id * dy) / h) as f32,
+        ]
+    }
+}
+
+// Applies one affine transformation per image of a batch, the pixels outside of
+// the input image are set to zero.
+fn affine_batch(t: &Tensor, params: &[AffineParams]) -> Result<Tensor, TchError> {
+    let size = t.size();
+    let (h, w) = (size[2], size[3]);
+    let theta = params
+        .iter()
+        .flat_map(|p| p.theta(h, w).to_vec())
+        .collect::<Vec<_>>();
+    let theta = Tensor::of_slice(&theta)
+        .f_view([-1, 2, 3])?
+        .f_to_device(t.device())?
+        .f_to_kind(t.kind())?;
+    let grid = Tensor::f_affine_grid_generator(&theta, &size, false)?;
+    t.f_grid_sampler(&grid, 0, 0, false)
+}
+
+/// Applies an affine transformation, using bilinear interpolation.
+pub fn affine(t: &Tensor, params: &AffineParams) -> Result<Tensor, TchError> {
+    batched(t, |t| {
+        let params = vec![*params; t.size()[0] as usize];
+        affine_batch(t, &params)
+    })
+}
+
+/// Converts images with values between 0 and 255 to float images with values
+/// between 0 and 1.
+#[derive(Debug, Clone, Copy)]
+pub struct ToFloat;
+
+impl Transform for ToFloat {
+    fn apply(&self, image: &Tensor, _rng: &mut dyn RngCore) -> Result<Tensor, TchError> {
+        image.f_to_kind(Kind::Float)?.f_div1(255.0)
+    }
+
+    fn apply_batch(&self, images: &Tensor, rng: &mut dyn RngCore) -> Result<Tensor, TchError> {
+        self.apply(images, rng)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum ResizeSize {
+    Exact(i64, i64),
+    ShorterSide(i64),
+}
+
+/// Resizes images, either to a given size or so that their shorter side has
+/// a given length.
+#[derive(Debug, Clone, Copy)]
+pub struct Resize(ResizeSize);
+
+impl Resize {
+    /// Resizes images to the given height and width.
+    pub fn new(height: i64, width: i64) -> Resize {
+        Resize(ResizeSize::Exact(height, width))
+    }
+
+    /// Resizes images so that their shorter side is `size`, preserving the
+    /// aspect ratio.
+    pub fn shorter_side(size: i64) -> Resize {
+        Resize(ResizeSize::ShorterSide(size))
+    }
+
+    fn f_forward(&self, t: &Tensor) -> Result<Tensor, TchError> {
+        let (h, w) = height_width(t)?;
+        let (height, width) = match self.0 {
+            ResizeSize::Exact(height, width) => (height, width),
+            ResizeSize::ShorterSide(size) if h <= w => (size, size * w / h),
+            ResizeSize::ShorterSide(size) => (size * h / w, size),
+        };
+        resize(t, height, width)
+    }
+}
+
+impl Transform for Resize {
+    fn apply(&self, image: &Tensor, _rng: &mut dyn RngCore) -> Result<Tensor, TchError> {
+        self.f_forward(image)
+    }
+
+    fn apply_batch(&self, images: &Tensor, _rng: &mut dyn RngCore) -> Result<Tensor, TchError> {
+        self.f_forward(images)
+    }
+}
+
+/// Crops the center of images.
+#[derive(Debug, Clone, Copy)]
+pub struct CenterCrop {
+    pub height: i64,
+    pub width: i64,
+}
+
+impl CenterCrop {
+    pub fn new(height: i64, width: i64) -> CenterCrop {
+        CenterCrop { height, width }
+    }
+}
+
+impl Transform for CenterCrop {
+    fn apply(&self, image: &Tensor, _rng: &mut dyn RngCore) -> Result<Tensor, TchError> {
+        center_crop(image, self.height, self.width)
+    }
+
+    fn apply_batch(&self, images: &Tensor, _rng: &mut dyn RngCore) -> Result<Tensor, TchError> {
+        center_crop(images, self.height, self.width)
+    }
+}
+
+/// Crops a random part of images with a random aspect ratio and resizes it to
+/// a given size.
+#[derive(Debug, Clone, Copy)]
+pub struct RandomResizedCrop {
+    pub height: i64,
+    pub width: i64,
+    /// The range of the cropped area relative to the image area.
+    pub scale: (f64, f64),
+    /// The range of the aspect ratio of the cropped area.
+    pub ratio: (f64, f64),
+}
+
+impl RandomResizedCrop {
+    pub fn new(height: i64, width: i64) -> RandomResizedCrop {
+        RandomResizedCrop {
+            height,
+            width,
+            scale: (0.08, 1.0),
+            ratio: (3.0 / 4.0, 4.0 / 3.0),
+        }
+    }
+
+    // Returns the top, left, height and width of the crop.
+    fn crop_params(&self, h: i64, w: i64, rng: &mut dyn RngCore) -> (i64, i64, i64, i64) {
+        let area = (h * w) as f64;
+        let (log_ratio_lo, log_ratio_hi) = (self.ratio.0.ln(), self.ratio.1.ln());
+        for _ in 0..10 {
+            let target_area = area * uniform(rng, self.scale.0, self.scale.1);
+            let ratio = uniform(rng, log_ratio_lo, log_ratio_hi).exp();
+            let cw = (target_area * ratio).sqrt().round() as i64;
+            let ch = (target_area / ratio).sqrt().round() as i64;
+            if 0 < cw && cw <= w && 0 < ch && ch <= h {
+                let top = rng.gen_range(0..=h - ch);
+                let left = rng.gen_range(0..=w - cw);
+                return (top, left, ch, cw);
+            }
+        }
+        // Fallback to a center crop.
+        let in_ratio = w as f64 / h as f64;
+        let (ch, cw) = if in_ratio < self.ratio.0 {
+            ((w as f64 / self.ratio.0).round() as i64, w)
+        } else if in_ratio > self.ratio.1 {
+            (h, (h as f64 * self.ratio.1).round() as i64)
+        } else {
+            (h, w)
+        };
+        ((h - ch) / 2, (w - cw) / 2, ch, cw)
+    }
+}
+
+impl Transform for RandomResizedCrop {
+    fn apply(&self, image: &Tensor, rng: &mut dyn RngCore) -> Result<Tensor, TchError> {
+        let (h, w) = height_width(image)?;
+        let (top, left, ch, cw) = self.crop_params(h, w, rng);
+        resize(&crop(image, top, left, ch, cw)?, self.height, self.width)
+    }
+}
+
+/// Flips images horizontally with a given probability.
+///
+/// Images are never flipped when `p <= 0` and always flipped when `p >= 1`.
+#[derive(Debug, Clone, Copy)]
+pub struct RandomHorizontalFlip {
+    pub p: f64,
+}
+
+impl RandomHorizontalFlip {
+    pub fn new(p: f64) -> RandomHorizontalFlip {
+        RandomHorizontalFlip { p }
+    }
+}
+
+impl Transform for RandomHorizontalFlip {
+    fn apply(&self, image: &Tensor, rng: &mut dyn RngCore) -> Result<Tensor, TchError> {
+        if sample_bool(rng, self.p) {
+            hflip(image)
+        } else {
+            Ok(image.shallow_clone())
+        }
+    }
+}
+
+/// Randomly changes the brightness, contrast, saturation and hue of images.
+///
+/// The brightness, contrast and saturation factors are sampled uniformly in
+/// `[max(0, 1 - v), 1 + v]`, the hue shift is sampled in `[-hue, hue]`. The
+/// adjustments are applied in a random order.
+#[derive(Debug, Clone, Copy)]
+pub struct ColorJitter {
+    pub brightness: f64,
+    pub contrast: f64,
+    pub saturation: f64,
+    pub hue: f64,
+}
+
+impl ColorJitter {
+    pub fn new(brightness: f64, contrast: f64, saturation: f64, hue: f64) -> ColorJitter {
+        ColorJitter {
+            brightness,
+            contrast,
+            saturation,
+            hue,
+        }
+    }
+}
+
+impl Transform for ColorJitter {
+    fn apply(&self, image: &Tensor, rng: &mut dyn RngCore) -> Result<Tensor, TchError> {
+        let mut order = [0, 1, 2, 3];
+        order.shuffle(rng);
+        let mut image = image.shallow_clone();
+        for &i in order.iter() {
+            image = match i {
+                0 if self.brightness > 0.0 => {
+                    let f = uniform(
+                        rng,
+                        (1f64 - self.brightness).max(0.0),
+                        1.0 + self.brightness,
+                    );
+                    adjust_brightness(&image, f)?
+                }
+                1 if self.contrast > 0.0 => {
+                    let f = uniform(rng, (1f64 - self.contrast).max(0.0), 1.0 + self.contrast);
+                    adjust_contrast(&image, f)?
+                }
+                2 if self.saturation > 0.0 => {
+                    let f = uniform(
+                        rng,
+                        (1f64 - self.saturation).max(0.0),
+                        1.0 + self.saturation,
+                    );
+                    adjust_saturation(&image, f)?
+                }
+                3 if self.hue > 0.0 => adjust_hue(&image, uniform(rng, -self.hue, self.hue))?,
+                _ => image,
+            }
+        }
+        Ok(image)
+    }
+}
+
+/// Applies random affine transformations.
+#[derive(Debug, Clone, Copy)]
+pub struct RandomAffine {
+    /// The range of rotation angles in degrees.
+    pub degrees: (f64, f64),
+    /// The maximal horizontal and vertical translations, as fractions of the
+    /// image width and height.
+    pub translate: (f64, f64),
+    /// The range of scaling factors.
+    pub scale: (f64, f64),
+    /// The range of horizontal shear angles in degrees.
+    pub shear: (f64, f64),
+}
+
+impl RandomAffine {
+    /// Random affine transformations with only a random rotation between
+    /// `-degrees` and `degrees`.
+    pub fn new(degrees: f64) -> RandomAffine {
+        RandomAffine {
+            degrees: (-degrees, degrees),
+            translate: (0.0, 0.0),
+            scale: (1.0, 1.0),
+            shear: (0.0, 0.0),
+        }
+    }
+
+    fn params(&self, h: i64, w: i64, rng: &mut dyn RngCore) -> AffineParams {
+        let max_dx = self.translate.0 * w as f64;
+        let max_dy = self.translate.1 * h as f64;
+        AffineParams {
+            angle: uniform(rng, self.degrees.0, self.degrees.1),
+            translate: (
+                uniform(rng, -max_dx, max_dx).round(),
+                uniform(rng, -max_dy, max_dy).round(),
+            ),
+            scale: uniform(rng, self.scale.0, self.scale.1),
+            shear: (uniform(rng, self.shear.0, self.shear.1), 0.0),
+        }
+    }
+}
+
+impl Transform for RandomAffine {
+    fn apply(&self, image: &Tensor, rng: &mut dyn RngCore) -> Result<Tensor, TchError> {
+        let (h, w) = height_width(image)?;
+        affine(image, &self.params(h, w, rng))
+    }
+
+    fn apply_batch(&self, images: &Tensor, rng: &mut dyn RngCore) -> Result<Tensor, TchError> {
+        check_dim(images, 4)?;
+        let (h, w) = height_width(images)?;
+        let params = (0..images.size()[0])
+            .map(|_| self.params(h, w, rng))
+            .collect::<Vec<_>>();
+        affine_batch(images, &params)
+    }
+}
+
+/// Rotates images by a random angle between `-degrees` and `degrees`.
+#[derive(Debug, Clone, Copy)]
+pub struct RandomRotation {
+    pub degrees: f64,
+}
+
+impl RandomRotation {
+    pub fn new(degrees: f64) -> RandomRotation {
+        RandomRotation { degrees }
+    }
+}
+
+impl Transform for RandomRotation {
+    fn apply(&self, image: &Tensor, rng: &mut dyn RngCore) -> Result<Tensor, TchError> {
+        RandomAffine::new(self.degrees).apply(image, rng)
+    }
+
+    fn apply_batch(&self, images: &Tensor, rng: &mut dyn RngCore) -> Result<Tensor, TchError> {
+        RandomAffine::new(self.degrees).apply_batch(images, rng)
+    }
+}
+
+/// Blurs images with a gaussian kernel using a random standard deviation.
+#[derive(Debug, Clone, Copy)]
+pub struct GaussianBlur {
+    /// The kernel height and width, these must be odd.
+    pub kernel_size: (i64, i64),
+    /// The range of the standard deviation.
+    pub sigma: (f64, f64),
+}
+
+impl GaussianBlur {
+    pub fn new(kernel_size: i64) -> GaussianBlur {
+        GaussianBlur {
+            kernel_size: (kernel_size, kernel_size),
+            sigma: (0.1, 2.0),
+        }
+    }
+}
+
+impl Transform for GaussianBlur {
+    fn apply(&self, image: &Tensor, rng: &mut dyn RngCore) -> Result<Tensor, TchError> {
+        let sigma = uniform(rng, self.sigma.0, self.sigma.1);
+        gaussian_blur(image, self.kernel_size, (sigma, sigma))
+    }
+}
+
+/// Normalizes images using some per-channel mean and standard deviation.
+#[derive(Debug, Clone)]
+pub struct Normalize {
+    pub mean: Vec<f64>,
+    pub std: Vec<f64>,
+}
+
+impl Normalize {
+    pub fn new(mean: &[f64], std: &[f64]) -> Normalize {
+        Normalize {
+            mean: mean.to_vec(),
+            std: std.to_vec(),
+        }
+    }
+
+    /// The normalization used by models pre-trained on ImageNet.
+    pub fn imagenet() -> Normalize {
+        Normalize::new(&[0.485, 0.456, 0.406], &[0.229, 0.224, 0.225])
+    }
+}
+
+impl Transform for Normalize {
+    fn apply(&self, image: &Tensor, _rng: &mut dyn RngCore) -> Result<Tensor, TchError> {
+        normalize(image, &self.mean, &self.std)
+    }
+
+    fn apply_batch(&self, images: &Tensor, _rng: &mut dyn RngCore) -> Result<Tensor, TchError> {
+        normalize(images, &self.mean, &self.std)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum AugmentOp {
+    Identity,
+    ShearX,
+    ShearY,
+    TranslateX,
+    TranslateY,
+    Rotate,
+    Brightness,
+    Color,
+    Contrast,
+    Sharpness,
+    Posterize,
+    Solarize,
+    AutoContrast,
+    Equalize,
+    Invert,
+}
+
+impl AugmentOp {
+    // Returns the magnitude for the given bin, following the augmentation
+    // spaces used by torchvision.
+    fn magnitude(self, bin: usize, num_bins: usize, h: i64, w: i64) -> f64 {
+        let r = if num_bins > 1 {
+            bin as f64 / (num_bins - 1) as f64
+        } else {
+            0.0
+        };
+        match self {
+            AugmentOp::ShearX | AugmentOp::ShearY => 0.3 * r,
+            AugmentOp::TranslateX => 150.0 / 331.0 * w as f64 * r,
+            AugmentOp::TranslateY => 150.0 / 331.0 * h as f64 * r,
+            AugmentOp::Rotate => 30.0 * r,
+            AugmentOp::Brightness
+            | AugmentOp::Color
+            | AugmentOp::Contrast
+            | AugmentOp::Sharpness => 0.9 * r,
+            AugmentOp::Posterize => 8.0 - (4.0 * r).round(),
+            AugmentOp::Solarize => 1.0 - r,
+            AugmentOp::Identity
+            | AugmentOp::AutoContrast
+            | AugmentOp::Equalize
+            | AugmentOp::Invert => 0.0,
+        }
+    }
+
+    fn signed(self) -> bool {
+        matches!(
+            self,
+            AugmentOp::ShearX
+                | AugmentOp::ShearY
+                | AugmentOp::TranslateX
+                | AugmentOp::TranslateY
+                | AugmentOp::Rotate
+                | AugmentOp::Brightness
+                | AugmentOp::Color
+                | AugmentOp::Contrast
+                | AugmentOp::Sharpness
+        )
+    }
+
+    fn apply(self, t: &Tensor, magnitude: f64) -> Result<Tensor, TchError> {
+        let affine_params = AffineParams::default();
+        match self {
+            AugmentOp::Identity => Ok(t.shallow_clone()),
+            AugmentOp::ShearX => affine(
+                t,
+                &AffineParams {
+                    shear: (magnitude.atan().to_degrees(), 0.0),
+                    ..affine_params
+                },
+            ),
+            AugmentOp::ShearY => affine(
+                t,
+                &AffineParams {
+                    shear: (0.0, magnitude.atan().to_degrees()),
+                    ..affine_params
+                },
+            ),
+            AugmentOp::TranslateX => affine(
+                t,
+                &AffineParams {
+                    translate: (magnitude.trunc(), 0.0),
+                    ..affine_params
+                },
+            ),
+            AugmentOp::TranslateY => affine(
+                t,
+                &AffineParams {
+                    translate: (0.0, magnitude.trunc()),
+                    ..affine_params
+                },
+            ),
+            AugmentOp::Rotate => affine(
+                t,
+                &AffineParams {
+                    angle: magnitude,
+                    ..affine_params
+                },
+            ),
+            AugmentOp::Brightness => adjust_brightness(t, 1.0 + magnitude),
+            AugmentOp::Color => adjust_saturation(t, 1.0 + magnitude),
+            AugmentOp::Contrast => adjust_contrast(t, 1.0 + magnitude),
+            AugmentOp::Sharpness => adjust_sharpness(t, 1.0 + magnitude),
+            AugmentOp::Posterize => posterize(t, magnitude as i64),
+            AugmentOp::Solarize => solarize(t, magnitude),
+            AugmentOp::AutoContrast => autocontrast(t),
+            AugmentOp::Equalize => equalize(t),
+            AugmentOp::Invert => invert(t),
+        }
+    }
+
+    fn apply_bin(
+        self,
+        t: &Tensor,
+        bin: usize,
+        num_bins: usize,
+        rng: &mut dyn RngCore,
+    ) -> Result<Tensor, TchError> {
+        let (h, w) = height_width(t)?;
+        let mut magnitude = self.magnitude(bin, num_bins, h, w);
+        if self.signed() && rng.gen_bool(0.5) {
+            magnitude = -magnitude
+        }
+        self.apply(t, magnitude)
+    }
+}
+
+/// Applies `num_ops` augmentations picked at random with a fixed magnitude.
+/// RandAugment: https://arxiv.org/abs/1909.13719
+#[derive(Debug, Clone, Copy)]
+pub struct RandAugment {
+    pub num_ops: usize,
+    /// The magnitude bin, between 0 and `num_magnitude_bins - 1`.
+    pub magnitude: usize,
+    pub num_magnitude_bins: usize,
+}
+
+impl Default for RandAugment {
+    fn default() -> Self {
+        RandAugment {
+            num_ops: 2,
+            magnitude: 9,
+            num_magnitude_bins: 31,
+        }
+    }
+}
+
+impl Transform for RandAugment {
+    fn apply(&self, image: &Tensor, rng: &mut dyn RngCore) -> Result<Tensor, TchError> {
+        const OPS: [AugmentOp; 14] = [
+            AugmentOp::Identity,
+            AugmentOp::ShearX,
+            AugmentOp::ShearY,
+            AugmentOp::TranslateX,
+            AugmentOp::TranslateY,
+            AugmentOp::Rotate,
+            AugmentOp::Brightness,
+            AugmentOp::Color,
+            AugmentOp::Contrast,
+            AugmentOp::Sharpness,
+            AugmentOp::Posterize,
+            AugmentOp::Solarize,
+            AugmentOp::AutoContrast,
+            AugmentOp::Equalize,
+        ];
+        let mut image = image.shallow_clone();
+        for _ in 0..self.num_ops {
+            let op = OPS[rng.gen_range(0..OPS.len())];
+            image = op.apply_bin(&image, self.magnitude, self.num_magnitude_bins, rng)?
+        }
+        Ok(image)
+    }
+}
+
+/// The policies learned on different datasets for `AutoAugment`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AutoAugmentPolicy {
+    ImageNet,
+    Cifar10,
+}
+
+// A sub-policy is a pair of (operation, probability, magnitude bin).
+type SubPolicy = [(AugmentOp, f64, usize); 2];
+
+impl AutoAugmentPolicy {
+    fn sub_policies(self) -> &'static [SubPolicy] {
+        use AugmentOp::*;
+        match self {
+            AutoAugmentPolicy::ImageNet => &[
+                [(Posterize, 0.4, 8), (Rotate, 0.6, 9)],
+                [(Solarize, 0.6, 5), (AutoContrast, 0.6, 0)],
+                [(Equalize, 0.8, 0), (Equalize, 0.6, 0)],
+                [(Posterize, 0.6, 7), (Posterize, 0.6, 6)],
+                [(Equalize, 0.4, 0), (Solarize, 0.2, 4)],
+                [(Equalize, 0.4, 0), (Rotate, 0.8, 8)],
+                [(Solarize, 0.6, 3), (Equalize, 0.6, 0)],
+                [(Posterize, 0.8, 5), (Equalize, 1.0, 0)],
+                [(Rotate, 0.2, 3), (Solarize, 0.6, 8)],
+                [(Equalize, 0.6, 0), (Posterize, 0.4, 6)],
+                [(Rotate, 0.8, 8), (Color, 0.4, 0)],
+                [(Rotate, 0.4, 9), (Equalize, 0.6, 0)],
+                [(Equalize, 0.0, 0), (Equalize, 0.8, 0)],
+                [(Invert, 0.6, 0), (Equalize, 1.0, 0)],
+                [(Color, 0.6, 4), (Contrast, 1.0, 8)],
+                [(Rotate, 0.8, 8), (Color, 1.0, 2)],
+                [(Color, 0.8, 8), (Solarize, 0.8, 7)],
+                [(Sharpness, 0.4, 7), (Invert, 0.6, 0)],
+                [(ShearX, 0.6, 5), (Equalize, 1.0, 0)],
+                [(Color, 0.4, 0), (Equalize, 0.6, 0)],
+                [(Equalize, 0.4, 0), (Solarize, 0.2, 4)],
+                [(Solarize, 0.6, 5), (AutoContrast, 0.6, 0)],
+                [(Invert, 0.6, 0), (Equalize, 1.0, 0)],
+                [(Color, 0.6, 4), (Contrast, 1.0, 8)],
+                [(Equalize, 0.8, 0), (Equalize, 0.6, 0)],
+            ],
+            AutoAugmentPolicy::Cifar10 => &[
+                [(Invert, 0.1, 0), (Contrast, 0.2, 6)],
+                [(Rotate, 0.7, 2), (TranslateX, 0.3, 9)],
+                [(Sharpness, 0.8, 1), (Sharpness, 0.9, 3)],
+                [(ShearY, 0.5, 8), (TranslateY, 0.7, 9)],
+                [(AutoContrast, 0.5, 0), (Equalize, 0.9, 0)],
+                [(ShearY, 0.2, 7), (Posterize, 0.3, 7)],
+                [(Color, 0.4, 3), (Brightness, 0.6, 7)],
+                [(Sharpness, 0.3, 9), (Brightness, 0.7, 9)],
+                [(Equalize, 0.6, 0), (Equalize, 0.5, 0)],
+                [(Contrast, 0.6, 7), (Sharpness, 0.6, 5)],
+                [(Color, 0.7, 7), (TranslateX, 0.5, 8)],
+                [(Equalize, 0.3, 0), (AutoContrast, 0.4, 0)],
+                [(TranslateY, 0.4, 3), (Sharpness, 0.2, 6)],
+                [(Brightness, 0.9, 6), (Color, 0.2, 8)],
+                [(Solarize, 0.5, 2), (Invert, 0.0, 0)],
+                [(Equalize, 0.2, 0), (AutoContrast, 0.6, 0)],
+                [(Equalize, 0.2, 0), (Equalize, 0.6, 0)],
+                [(Color, 0.9, 9), (Equalize, 0.6, 0)],
+                [(AutoContrast, 0.8, 0), (Solarize, 0.2, 8)],
+                [(Brightness, 0.1, 3), (Color, 0.7, 0)],
+                [(Solarize, 0.4, 5), (AutoContrast, 0.9, 0)],
+                [(TranslateY, 0.9, 9), (TranslateY, 0.7, 9)],
+                [(AutoContrast, 0.9, 0), (Solarize, 0.8, 3)],
+                [(Equalize, 0.8, 0), (Invert, 0.1, 0)],
+                [(TranslateY, 0.7, 9), (AutoContrast, 0.9, 0)],
+            ],
+        }
+    }
+}
+
+/// Applies a sub-policy picked at random from a learned augmentation policy.
+/// AutoAugment: https://arxiv.org/abs/1805.09501
+#[derive(Debug, Clone, Copy)]
+pub struct AutoAugment {
+    pub policy: AutoAugmentPolicy,
+}
+
+impl AutoAugment {
+    pub fn new(policy: AutoAugmentPolicy) -> AutoAugment {
+        AutoAugment { policy }
+    }
+}
+
+impl Transform for AutoAugment {
+    fn apply(&self, image: &Tensor, rng: &mut dyn RngCore) -> Result<Tensor, TchError> {
+        let sub_policies = self.policy.sub_policies();
+        let sub_policy = &sub_policies[rng.gen_range(0..sub_policies.len())];
+        let mut image = image.shallow_clone();
+        for &(op, p, bin) in sub_policy.iter() {
+            if sample_bool(rng, p) {
+                image = op.apply_bin(&image, bin, 10, rng)?
+            }
+        }
+        Ok(image)
+    }
+}
+
+/// A sequence of transforms applied one after the other.
+///
+/// The random number generator used by `forward` is stored in the `Compose`
+/// object and can be seeded, applying the same seeded pipeline to the same
+/// inputs results in the same outputs.
+#[derive(Debug)]
+pub struct Compose {
+    transforms: Vec<Box<dyn Transform>>,
+    rng: Mutex<StdRng>,
+}
+
+/// Creates a new empty sequence of transforms.
+pub fn compose() -> Compose {
+    Compose {
+        transforms: vec![],
+        rng: Mutex::new(StdRng::from_entropy()),
+    }
+}
+
+impl Compose {
+    /// Appends a transform to the sequence.
+    #[allow(clippy::should_implement_trait)]
+    pub fn add<T: Transform + 'static>(mut self, transform: T) -> Self {
+        self.transforms.push(Box::new(transform));
+        self
+    }
+
+    /// Seeds the random number generator used by `forward`.
+    pub fn seed(self, seed: u64) -> Self {
+        *self.rng.lock().unwrap() = StdRng::seed_from_u64(seed);
+        self
+    }
+
+    /// The number of transforms in the sequence.
+    pub fn len(&self) -> i64 {
+        self.transforms.len() as i64
+    }
+
+    /// Returns true if the sequence does not contain any transform.
+    pub fn is_empty(&self) -> bool {
+        self.transforms.is_empty()
+    }
+
+    /// Applies the transforms to an image of shape [channel, height, width]
+    /// or to a batch of images of shape [batch, channel, height, width].
+    pub fn f_forward(&self, xs: &Tensor) -> Result<Tensor, TchError> {
+        let mut rng = self.rng.lock().unwrap();
+        match xs.dim() {
+            3 => self.apply(xs, &mut *rng),
+            4 => self.apply_batch(xs, &mut *rng),
+            _ => Err(TchError::Shape(format!(
+                "expected an image of shape [c, h, w] or [n, c, h, w], got {:?}",
+                xs.size()
+            ))),
+        }
+    }
+}
+
+impl Transform for Compose {
+    fn apply(&self, image: &Tensor, rng: &mut dyn RngCore) -> Result<Tensor, TchError> {
+        let mut image = image.shallow_clone();
+        for transform in self.transforms.iter() {
+            image = transform.apply(&image, rng)?
+        }
+        Ok(image)
+    }
+
+    fn apply_batch(&self, images: &Tensor, rng: &mut dyn RngCore) -> Result<Tensor, TchError> {
+        let mut images = images.shallow_clone();
+        for transform in self.transforms.iter() {
+            images = transform.apply_batch(&images, rng)?
+        }
+        Ok(images)
+    }
+}
+
+impl crate::nn::Module for Compose {
+    fn forward(&self, xs: &Tensor) -> Tensor {
+        self.f_forward(xs).unwrap()
+    }
+}
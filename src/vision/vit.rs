@@ -0,0 +1,205 @@
+//! Vision Transformer (ViT) implementation.
+//!
+//! See "An Image is Worth 16x16 Words: Transformers for Image Recognition at
+//! Scale" Dosovitskiy et al. 2020 https://arxiv.org/abs/2010.11929
+//!
+//! The variables are named as in the torchvision implementation so that its
+//! ViT weights can be loaded. The DeiT configurations from "Training
+//! data-efficient image transformers & distillation through attention"
+//! Touvron et al. 2020 https://arxiv.org/abs/2012.12877 only provide the
+//! dimensions of these models: torchvision has no DeiT weights and the
+//! checkpoints released with the paper use the timm variable names. The
+//! distillation token is not supported.
+use crate::{nn, nn::Module, Tensor};
+
+/// The dimensions of a Vision Transformer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Config {
+    pub image_size: i64,
+    pub patch_size: i64,
+    pub num_layers: i64,
+    pub num_heads: i64,
+    pub hidden_dim: i64,
+    pub mlp_dim: i64,
+}
+
+impl Config {
+    fn new(patch_size: i64, num_layers: i64, num_heads: i64, hidden_dim: i64) -> Config {
+        Config {
+            image_size: 224,
+            patch_size,
+            num_layers,
+            num_heads,
+            hidden_dim,
+            mlp_dim: 4 * hidden_dim,
+        }
+    }
+
+    pub fn vit_b_16() -> Config {
+        Config::new(16, 12, 12, 768)
+    }
+
+    pub fn vit_b_32() -> Config {
+        Config::new(32, 12, 12, 768)
+    }
+
+    pub fn vit_l_16() -> Config {
+        Config::new(16, 24, 16, 1024)
+    }
+
+    pub fn vit_l_32() -> Config {
+        Config::new(32, 24, 16, 1024)
+    }
+
+    pub fn vit_h_14() -> Config {
+        Config::new(14, 32, 16, 1280)
+    }
+
+    pub fn deit_tiny_16() -> Config {
+        Config::new(16, 12, 3, 192)
+    }
+
+    pub fn deit_small_16() -> Config {
+        Config::new(16, 12, 6, 384)
+    }
+
+    pub fn deit_base_16() -> Config {
+        Config::vit_b_16()
+    }
+
+    /// The number of tokens, including the class token.
+    pub fn seq_length(&self) -> i64 {
+        let n = self.image_size / self.patch_size;
+        n * n + 1
+    }
+}
+
+fn layer_norm(p: nn::Path, dim: i64) -> nn::LayerNorm {
+    let config = nn::LayerNormConfig {
+        eps: 1e-6,
+        ..Default::default()
+    };
+    nn::layer_norm(p, vec![dim], config)
+}
+
+// Multi-head self-attention using the parameter names of torch.nn.MultiheadAttention.
+fn self_attention(p: nn::Path, dim: i64, num_heads: i64) -> impl Module {
+    let in_proj_weight = p.var("in_proj_weight", &[3 * dim, dim], nn::Init::KaimingUniform);
+    let in_proj_bias = p.var("in_proj_bias", &[3 * dim], nn::Init::Const(0.));
+    let out_proj = nn::linear(&p / "out_proj", dim, dim, Default::default());
+    let head_dim = dim / num_heads;
+    nn::func(move |xs| {
+        let (b, s, _) = xs.size3().unwrap();
+        let qkv = (xs.matmul(&in_proj_weight.tr()) + &in_proj_bias)
+            .view([b, s, 3, num_heads, head_dim])
+            .permute(&[2, 0, 3, 1, 4]);
+        let (q, k, v) = (qkv.get(0), qkv.get(1), qkv.get(2));
+        let attention = (q.matmul(&k.transpose(-2, -1)) / (head_dim as f64).sqrt())
+            .softmax(-1, xs.kind())
+            .matmul(&v);
+        attention
+            .transpose(1, 2)
+            .contiguous()
+            .view([b, s, dim])
+            .apply(&out_proj)
+    })
+}
+
+fn encoder_block(p: nn::Path, config: &Config) -> impl Module {
+    let dim = config.hidden_dim;
+    let ln_1 = layer_norm(&p / "ln_1", dim);
+    let attention = self_attention(&p / "self_attention", dim, config.num_heads);
+    let ln_2 = layer_norm(&p / "ln_2", dim);
+    let mlp_p = &p / "mlp";
+    let mlp = nn::seq()
+        .add(nn::linear(
+            &mlp_p / 0,
+            dim,
+            config.mlp_dim,
+            Default::default(),
+        ))
+        .add_fn(|xs| xs.gelu())
+        .add(nn::linear(
+            &mlp_p / 3,
+            config.mlp_dim,
+            dim,
+            Default::default(),
+        ));
+    nn::func(move |xs| {
+        let xs = xs + xs.apply(&ln_1).apply(&attention);
+        &xs + xs.apply(&ln_2).apply(&mlp)
+    })
+}
+
+/// Creates a Vision Transformer model.
+///
+/// The input images must have a size of `config.image_size` pixels for the
+/// position embeddings to match.
+pub fn vision_transformer(p: &nn::Path, config: &Config, nclasses: i64) -> impl Module {
+    let dim = config.hidden_dim;
+    let patch_size = config.patch_size;
+    let conv_cfg = nn::ConvConfig {
+        stride: patch_size,
+        ..Default::default()
+    };
+    let conv_proj = nn::conv2d(p / "conv_proj", 3, dim, patch_size, conv_cfg);
+    let class_token = p.var("class_token", &[1, 1, dim], nn::Init::Const(0.));
+    let e_p = p / "encoder";
+    let pos_embedding = e_p.var(
+        "pos_embedding",
+        &[1, config.seq_length(), dim],
+        nn::Init::Randn {
+            mean: 0.,
+            stdev: 0.02,
+        },
+    );
+    let l_p = &e_p / "layers";
+    let mut layers = nn::seq();
+    for i in 0..config.num_layers {
+        layers = layers.add(encoder_block(&l_p / format!("encoder_layer_{}", i), config));
+    }
+    let ln = layer_norm(&e_p / "ln", dim);
+    let head = nn::linear(&(p / "heads") / "head", dim, nclasses, Default::default());
+    nn::func(move |xs| {
+        let b = xs.size()[0];
+        let xs = xs.apply(&conv_proj).view([b, dim, -1]).permute(&[0, 2, 1]);
+        let class_token = class_token.expand(&[b, -1, -1], false);
+        (Tensor::cat(&[class_token, xs], 1) + &pos_embedding)
+            .apply(&layers)
+            .apply(&ln)
+            .select(1, 0)
+            .apply(&head)
+    })
+}
+
+pub fn vit_b_16(p: &nn::Path, nclasses: i64) -> impl Module {
+    vision_transformer(p, &Config::vit_b_16(), nclasses)
+}
+
+pub fn vit_b_32(p: &nn::Path, nclasses: i64) -> impl Module {
+    vision_transformer(p, &Config::vit_b_32(), nclasses)
+}
+
+pub fn vit_l_16(p: &nn::Path, nclasses: i64) -> impl Module {
+    vision_transformer(p, &Config::vit_l_16(), nclasses)
+}
+
+pub fn vit_l_32(p: &nn::Path, nclasses: i64) -> impl Module {
+    vision_transformer(p, &Config::vit_l_32(), nclasses)
+}
+
+pub fn vit_h_14(p: &nn::Path, nclasses: i64) -> impl Module {
+    vision_transformer(p, &Config::vit_h_14(), nclasses)
+}
+
+pub fn deit_tiny_16(p: &nn::Path, nclasses: i64) -> impl Module {
+    vision_transformer(p, &Config::deit_tiny_16(), nclasses)
+}
+
+pub fn deit_small_16(p: &nn::Path, nclasses: i64) -> impl Module {
+    vision_transformer(p, &Config::deit_small_16(), nclasses)
+}
+
+pub fn deit_base_16(p: &nn::Path, nclasses: i64) -> impl Module {
+    vision_transformer(p, &Config::deit_base_16(), nclasses)
+}
@@ -0,0 +1,237 @@
+//! The Pascal VOC object detection dataset.
+//!
+//! The dataset can be downloaded from the following page:
+//! http://host.robots.ox.ac.uk/pascal/VOC/
+//! The root directory is the one containing the `Annotations`, `ImageSets`,
+//! and `JPEGImages` subdirectories, e.g. `VOCdevkit/VOC2012`. Annotations are
+//! read when creating the dataset, images are only decoded when accessed.
+//! This module requires the `datasets` feature.
+//!
+//! ```ignore
+//! use tch::vision::voc::VocDataset;
+//!
+//! let dataset = VocDataset::new("VOCdevkit/VOC2012", "train")?;
+//! let (image, target) = dataset.f_get(0)?;
+//! ```
+use super::detection::Target;
+use crate::{Device, Kind, TchError, Tensor};
+use std::path::{Path, PathBuf};
+
+/// The object classes, the target labels are indexes in this array.
+pub const CLASSES: [&str; 20] = [
+    "aeroplane",
+    "bicycle",
+    "bird",
+    "boat",
+    "bottle",
+    "bus",
+    "car",
+    "cat",
+    "chair",
+    "cow",
+    "diningtable",
+    "dog",
+    "horse",
+    "motorbike",
+    "person",
+    "pottedplant",
+    "sheep",
+    "sofa",
+    "train",
+    "tvmonitor",
+];
+
+fn format_err<T>(msg: String) -> Result<T, TchError> {
+    Err(TchError::FileFormat(msg))
+}
+
+type Node<'a, 'input> = roxmltree::Node<'a, 'input>;
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children()
+        .find(|c| c.is_element() && c.tag_name().name() == name)
+}
+
+fn text_of<'a>(node: Node<'a, '_>, name: &str) -> Result<&'a str, TchError> {
+    match child(node, name) {
+        Some(c) => Ok(c.text().unwrap_or("").trim()),
+        None => format_err(format!(
+            "missing element {} in {}",
+            name,
+            node.tag_name().name()
+        )),
+    }
+}
+
+fn number_of(node: Node, name: &str) -> Result<f64, TchError> {
+    let text = text_of(node, name)?;
+    match text.parse::<f64>() {
+        Ok(v) => Ok(v),
+        Err(_) => format_err(format!("invalid number {} for {}", text, name)),
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Object {
+    label: i64,
+    bbox: [f32; 4],
+    difficult: bool,
+}
+
+#[derive(Debug, Clone)]
+struct Annotation {
+    filename: String,
+    objects: Vec<Object>,
+}
+
+fn parse_annotation(xml: &str) -> Result<Annotation, TchError> {
+    let document = match roxmltree::Document::parse(xml) {
+        Ok(document) => document,
+        Err(err) => return format_err(format!("invalid xml: {}", err)),
+    };
+    let root = document.root_element();
+    if root.tag_name().name() != "annotation" {
+        return format_err(format!(
+            "unexpected root element {}",
+            root.tag_name().name()
+        ));
+    }
+    let mut objects = vec![];
+    for object in root
+        .children()
+        .filter(|c| c.is_element() && c.tag_name().name() == "object")
+    {
+        let name = text_of(object, "name")?;
+        let label = match CLASSES.iter().position(|&c| c == name) {
+            Some(label) => label as i64,
+            None => return format_err(format!("unknown class {}", name)),
+        };
+        let difficult = match child(object, "difficult") {
+            Some(d) => d.text().map_or(false, |t| t.trim() == "1"),
+            None => false,
+        };
+        let bndbox = match child(object, "bndbox") {
+            Some(bndbox) => bndbox,
+            None => return format_err(format!("missing bndbox for {}", name)),
+        };
+        // The coordinates are 1-based and inclusive.
+        let bbox = [
+            number_of(bndbox, "xmin")? as f32 - 1.,
+            number_of(bndbox, "ymin")? as f32 - 1.,
+            number_of(bndbox, "xmax")? as f32,
+            number_of(bndbox, "ymax")? as f32,
+        ];
+        objects.push(Object {
+            label,
+            bbox,
+            difficult,
+        })
+    }
+    Ok(Annotation {
+        filename: text_of(root, "filename")?.to_string(),
+        objects,
+    })
+}
+
+/// A Pascal VOC detection dataset.
+#[derive(Debug, Clone)]
+pub struct VocDataset {
+    root: PathBuf,
+    ids: Vec<String>,
+    annotations: Vec<Annotation>,
+}
+
+impl VocDataset {
+    /// Reads the annotations of the images listed in
+    /// `ImageSets/Main/{image_set}.txt`, e.g. with `image_set` set to `train`,
+    /// `val`, or `trainval`.
+    pub fn new<T: AsRef<Path>>(root: T, image_set: &str) -> Result<VocDataset, TchError> {
+        let root = root.as_ref();
+        let image_set_file = root
+            .join("ImageSets")
+            .join("Main")
+            .join(format!("{}.txt", image_set));
+        let ids: Vec<String> = std::fs::read_to_string(&image_set_file)?
+            .lines()
+            .filter_map(|line| line.split_whitespace().next())
+            .map(|id| id.to_string())
+            .collect();
+        let annotations = ids
+            .iter()
+            .map(|id| {
+                let path = root.join("Annotations").join(format!("{}.xml", id));
+                parse_annotation(&std::fs::read_to_string(&path)?)
+                    .map_err(|err| TchError::FileFormat(format!("{:?} {}", path, err)))
+            })
+            .collect::<Result<Vec<_>, TchError>>()?;
+        Ok(VocDataset {
+            root: root.to_path_buf(),
+            ids,
+            annotations,
+        })
+    }
+
+    /// The number of images in the dataset.
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    /// Returns true if the dataset does not contain any image.
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    /// The image identifiers, the image id of a target is the index in this
+    /// slice.
+    pub fn ids(&self) -> &[String] {
+        &self.ids
+    }
+
+    /// Returns the annotations of an image without decoding it.
+    ///
+    /// The boxes use 0-based (xmin, ymin, xmax, ymax) pixel coordinates,
+    /// difficult objects are marked using the `difficult` field so that they
+    /// are ignored during evaluation.
+    pub fn f_target(&self, index: usize) -> Result<Target, TchError> {
+        let annotation = match self.annotations.get(index) {
+            Some(annotation) => annotation,
+            None => {
+                return Err(TchError::Shape(format!(
+                    "index {} out of range for a dataset of {} images",
+                    index,
+                    self.annotations.len()
+                )))
+            }
+        };
+        let objects = &annotation.objects;
+        let boxes: Vec<f32> = objects.iter().flat_map(|o| o.bbox.to_vec()).collect();
+        let labels: Vec<i64> = objects.iter().map(|o| o.label).collect();
+        let difficult: Vec<bool> = objects.iter().map(|o| o.difficult).collect();
+        Ok(Target {
+            image_id: index as i64,
+            boxes: Tensor::of_slice(&boxes).f_view([objects.len() as i64, 4])?,
+            labels: Tensor::of_slice(&labels),
+            iscrowd: Tensor::f_zeros(&[objects.len() as i64], (Kind::Bool, Device::Cpu))?,
+            difficult: Tensor::of_slice(&difficult),
+            masks: None,
+        })
+    }
+
+    /// Decodes an image, returns it as a uint8 tensor of shape [channel,
+    /// height, width] together with its annotations.
+    pub fn f_get(&self, index: usize) -> Result<(Tensor, Target), TchError> {
+        let target = self.f_target(index)?;
+        let path = self
+            .root
+            .join("JPEGImages")
+            .join(&self.annotations[index].filename);
+        let image = super::image::load(&path).map_err(|err| {
+            TchError::FileFormat(format!("unable to load image {:?}: {}", path, err))
+        })?;
+        Ok((image, target))
+    }
+
+    pub fn get(&self, index: usize) -> (Tensor, Target) {
+        self.f_get(index).unwrap()
+    }
+}
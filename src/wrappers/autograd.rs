@@ -0,0 +1,385 @@
+//! Automatic differentiation utilities.
+//!
+//! Custom differentiable operations can be defined by implementing the
+//! `CustomFunction` trait. The backward pass is written in Rust and gets
+//! called by the libtorch autograd engine, so these functions can be mixed
+//! with the usual tensor operations and participate in `backward` and
+//! `run_backward` like any other operation.
+//!
+//! Gradients of intermediate tensors can be inspected or modified by
+//! registering hooks with `Tensor::register_hook`.
+//!
+//! `detect_anomaly` enables checks for NaN values produced by the backward
+//! pass, the resulting errors include the traceback of the forward operation
+//! that produced the failing node.
+//!
+//! ```no_run
+//! use tch::autograd::{CustomFunction, FunctionCtx};
+//! use tch::{Kind, TchError, Tensor};
+//!
+//! // Rounds its input in the forward pass, the gradient goes through unchanged.
+//! struct StraightThroughRound;
+//!
+//! impl CustomFunction for StraightThroughRound {
+//!     fn forward(&self, _: &mut FunctionCtx, xs: &[Tensor]) -> Result<Vec<Tensor>, TchError> {
+//!         Ok(vec![xs[0].f_round()?])
+//!     }
+//!
+//!     fn backward(&self, _: &mut FunctionCtx, grads: &[Tensor]) -> Result<Vec<Tensor>, TchError> {
+//!         Ok(vec![grads[0].shallow_clone()])
+//!     }
+//! }
+//!
+//! let xs = Tensor::of_slice(&[0.2f32, 1.7]).set_requires_grad(true);
+//! let ys = StraightThroughRound.apply(&[&xs]);
+//! ys[0].sum(Kind::Float).backward();
+//! assert_eq!(Vec::<f32>::from(&xs.grad()), [1.0, 1.0]);
+//! ```
+use super::tensor::Tensor;
+use crate::TchError;
+use libc::{c_char, c_int, c_void};
+use std::borrow::Borrow;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use torch_sys::*;
+
+/// The context used to pass data from the forward pass of a custom
+/// function to its backward pass.
+pub struct FunctionCtx {
+    c_ctx: *mut C_autograd_ctx,
+}
+
+extern "C" fn add_tensor_callback(data: *mut c_void, c_tensor: *mut C_tensor) {
+    let v: &mut Vec<Tensor> = unsafe { &mut *(data as *mut Vec<Tensor>) };
+    v.push(Tensor { c_tensor })
+}
+
+impl FunctionCtx {
+    /// Saves some tensors so that they can be used in the backward pass.
+    ///
+    /// This can only be called during the forward pass. The saved tensors
+    /// can be inputs or outputs of the function.
+    pub fn f_save_for_backward<T: Borrow<Tensor>>(
+        &mut self,
+        tensors: &[T],
+    ) -> Result<(), TchError> {
+        let tensors: Vec<_> = tensors.iter().map(|x| x.borrow().c_tensor).collect();
+        unsafe_torch_err!(at_autograd_ctx_save_for_backward(
+            self.c_ctx,
+            tensors.as_ptr(),
+            tensors.len() as c_int
+        ));
+        Ok(())
+    }
+
+    /// Saves some tensors so that they can be used in the backward pass.
+    pub fn save_for_backward<T: Borrow<Tensor>>(&mut self, tensors: &[T]) {
+        self.f_save_for_backward(tensors).unwrap()
+    }
+
+    /// Returns the tensors saved in the forward pass.
+    ///
+    /// This can only be called during the backward pass.
+    pub fn f_saved_tensors(&self) -> Result<Vec<Tensor>, TchError> {
+        let mut v: Vec<Tensor> = vec![];
+        unsafe_torch_err!(at_autograd_ctx_saved_tensors(
+            self.c_ctx,
+            &mut v as *mut _ as *mut c_void,
+            add_tensor_callback
+        ));
+        Ok(v)
+    }
+
+    /// Returns the tensors saved in the forward pass.
+    pub fn saved_tensors(&self) -> Vec<Tensor> {
+        self.f_saved_tensors().unwrap()
+    }
+
+    /// Marks some outputs of the forward pass as not differentiable.
+    pub fn f_mark_non_differentiable<T: Borrow<Tensor>>(
+        &mut self,
+        tensors: &[T],
+    ) -> Result<(), TchError> {
+        let tensors: Vec<_> = tensors.iter().map(|x| x.borrow().c_tensor).collect();
+        unsafe_torch_err!(at_autograd_ctx_mark_non_differentiable(
+            self.c_ctx,
+            tensors.as_ptr(),
+            tensors.len() as c_int
+        ));
+        Ok(())
+    }
+
+    /// Marks some outputs of the forward pass as not differentiable.
+    pub fn mark_non_differentiable<T: Borrow<Tensor>>(&mut self, tensors: &[T]) {
+        self.f_mark_non_differentiable(tensors).unwrap()
+    }
+}
+
+/// A differentiable operation which backward pass is implemented in Rust.
+///
+/// The forward pass is run with gradient tracking disabled, the returned
+/// tensors are then attached to the autograd graph so that the backward
+/// pass gets called when gradients are computed.
+pub trait CustomFunction: Send + 'static {
+    /// Computes the outputs of the function.
+    fn forward(&self, ctx: &mut FunctionCtx, inputs: &[Tensor]) -> Result<Vec<Tensor>, TchError>;
+
+    /// Computes the gradients with respect to the inputs given the gradients
+    /// with respect to the outputs.
+    ///
+    /// The returned vector must have one element per input, an undefined
+    /// tensor (`Tensor::new()`) can be used for inputs that do not require
+    /// a gradient.
+    fn backward(
+        &self,
+        ctx: &mut FunctionCtx,
+        grad_outputs: &[Tensor],
+    ) -> Result<Vec<Tensor>, TchError>;
+
+    /// Applies the function to some inputs, recording the operation in the
+    /// autograd graph.
+    fn f_apply<T: Borrow<Tensor>>(self, inputs: &[T]) -> Result<Vec<Tensor>, TchError>
+    where
+        Self: Sized,
+    {
+        f_apply(self, inputs)
+    }
+
+    /// Applies the function to some inputs, recording the operation in the
+    /// autograd graph.
+    fn apply<T: Borrow<Tensor>>(self, inputs: &[T]) -> Vec<Tensor>
+    where
+        Self: Sized,
+    {
+        f_apply(self, inputs).unwrap()
+    }
+}
+
+// Converts an error to a string that can be freed on the C++ side.
+fn error_to_c(err: String) -> *mut c_char {
+    let err = std::ffi::CString::new(err.replace('\0', " ")).unwrap();
+    unsafe { libc::strdup(err.as_ptr()) }
+}
+
+fn run_callback<F>(
+    c_ctx: *mut C_autograd_ctx,
+    tensors: *const *mut C_tensor,
+    ntensors: c_int,
+    outputs: *mut c_void,
+    push: extern "C" fn(*mut c_void, *mut C_tensor),
+    f: F,
+) -> *mut c_char
+where
+    F: FnOnce(&mut FunctionCtx, &[Tensor]) -> Result<Vec<Tensor>, TchError>,
+{
+    let result = catch_unwind(AssertUnwindSafe(|| {
+        let tensors = unsafe { std::slice::from_raw_parts(tensors, ntensors as usize) };
+        let tensors = tensors
+            .iter()
+            .map(|&c_tensor| unsafe { Tensor::clone_from_ptr(c_tensor) })
+            .collect::<Vec<_>>();
+        let mut ctx = FunctionCtx { c_ctx };
+        f(&mut ctx, &tensors)
+    }));
+    match result {
+        Ok(Ok(tensors)) => {
+            for tensor in tensors.iter() {
+                push(outputs, tensor.c_tensor)
+            }
+            std::ptr::null_mut()
+        }
+        Ok(Err(err)) => error_to_c(err.to_string()),
+        Err(_) => error_to_c("panic in custom function".to_string()),
+    }
+}
+
+extern "C" fn forward_callback<F: CustomFunction>(
+    data: *mut c_void,
+    c_ctx: *mut C_autograd_ctx,
+    tensors: *const *mut C_tensor,
+    ntensors: c_int,
+    outputs: *mut c_void,
+    push: extern "C" fn(*mut c_void, *mut C_tensor),
+) -> *mut c_char {
+    let function = unsafe { &*(data as *const F) };
+    run_callback(c_ctx, tensors, ntensors, outputs, push, |ctx, xs| {
+        function.forward(ctx, xs)
+    })
+}
+
+extern "C" fn backward_callback<F: CustomFunction>(
+    data: *mut c_void,
+    c_ctx: *mut C_autograd_ctx,
+    tensors: *const *mut C_tensor,
+    ntensors: c_int,
+    outputs: *mut c_void,
+    push: extern "C" fn(*mut c_void, *mut C_tensor),
+) -> *mut c_char {
+    let function = unsafe { &*(data as *const F) };
+    run_callback(c_ctx, tensors, ntensors, outputs, push, |ctx, xs| {
+        function.backward(ctx, xs)
+    })
+}
+
+extern "C" fn free_callback<F: CustomFunction>(data: *mut c_void) {
+    let _ = catch_unwind(|| unsafe { Box::from_raw(data as *mut F) });
+}
+
+/// Applies a custom function to some inputs, recording the operation in the
+/// autograd graph.
+pub fn f_apply<F, T>(function: F, inputs: &[T]) -> Result<Vec<Tensor>, TchError>
+where
+    F: CustomFunction,
+    T: Borrow<Tensor>,
+{
+    let inputs: Vec<_> = inputs.iter().map(|x| x.borrow().c_tensor).collect();
+    // The ownership of the function is transferred to the autograd graph which
+    // releases it via free_callback.
+    let data = Box::into_raw(Box::new(function)) as *mut c_void;
+    let mut outputs: Vec<Tensor> = vec![];
+    unsafe_torch_err!(at_custom_function_apply(
+        inputs.as_ptr(),
+        inputs.len() as c_int,
+        data,
+        forward_callback::<F>,
+        backward_callback::<F>,
+        free_callback::<F>,
+        &mut outputs as *mut _ as *mut c_void,
+        add_tensor_callback
+    ));
+    Ok(outputs)
+}
+
+/// Applies a custom function to some inputs, recording the operation in the
+/// autograd graph.
+pub fn apply<F, T>(function: F, inputs: &[T]) -> Vec<Tensor>
+where
+    F: CustomFunction,
+    T: Borrow<Tensor>,
+{
+    f_apply(function, inputs).unwrap()
+}
+
+/// A hook registered on a tensor via `Tensor::register_hook`.
+///
+/// The hook is removed when this handle is dropped. The handle only holds a
+/// weak reference to the tensor so it does not keep the tensor nor its
+/// autograd graph alive.
+#[must_use]
+pub struct HookHandle {
+    weak_tensor: *mut c_void,
+    pos: c_int,
+}
+
+// The weak reference uses atomic reference counts.
+unsafe impl Send for HookHandle {}
+
+impl HookHandle {
+    /// Removes the hook from the tensor.
+    pub fn remove(self) {}
+}
+
+impl std::fmt::Debug for HookHandle {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "HookHandle({})", self.pos)
+    }
+}
+
+impl Drop for HookHandle {
+    fn drop(&mut self) {
+        unsafe_torch!(at_remove_hook(self.weak_tensor, self.pos));
+        unsafe_torch!(at_free_weak_ref(self.weak_tensor))
+    }
+}
+
+type Hook = std::sync::Mutex<Box<dyn FnMut(&Tensor) -> Option<Tensor> + Send>>;
+
+extern "C" fn hook_callback(
+    data: *mut c_void,
+    grad: *mut C_tensor,
+    result: *mut *mut C_tensor,
+) -> *mut c_char {
+    let hook = unsafe { &*(data as *const Hook) };
+    let output = catch_unwind(AssertUnwindSafe(|| {
+        let grad = unsafe { Tensor::clone_from_ptr(grad) };
+        let mut hook = hook.lock().unwrap_or_else(|e| e.into_inner());
+        (*hook)(&grad)
+    }));
+    match output {
+        Ok(None) => std::ptr::null_mut(),
+        Ok(Some(tensor)) => {
+            // The C++ side takes ownership of the returned tensor.
+            unsafe { *result = tensor.c_tensor };
+            std::mem::forget(tensor);
+            std::ptr::null_mut()
+        }
+        Err(_) => error_to_c("panic in tensor hook".to_string()),
+    }
+}
+
+extern "C" fn free_hook_callback(data: *mut c_void) {
+    let _ = catch_unwind(|| unsafe { Box::from_raw(data as *mut Hook) });
+}
+
+impl Tensor {
+    /// Registers a hook called with the gradient of this tensor each time
+    /// this gradient is computed.
+    ///
+    /// The hook can return a new tensor to replace the gradient, or `None`
+    /// to leave it unchanged. The hook stays registered as long as the
+    /// returned handle is alive.
+    pub fn f_register_hook<F>(&self, hook: F) -> Result<HookHandle, TchError>
+    where
+        F: FnMut(&Tensor) -> Option<Tensor> + Send + 'static,
+    {
+        let weak_tensor = unsafe_torch_err!(at_weak_ref(self.c_tensor));
+        let hook: Hook = std::sync::Mutex::new(Box::new(hook));
+        // The hook is released by libtorch via free_hook_callback, including
+        // when the registration fails.
+        let data = Box::into_raw(Box::new(hook)) as *mut c_void;
+        let pos =
+            unsafe { at_register_hook(self.c_tensor, data, hook_callback, free_hook_callback) };
+        if let Err(err) = super::utils::read_and_clean_error() {
+            unsafe_torch!(at_free_weak_ref(weak_tensor));
+            return Err(err);
+        }
+        Ok(HookHandle { weak_tensor, pos })
+    }
+
+    /// Registers a hook called with the gradient of this tensor each time
+    /// this gradient is computed.
+    pub fn register_hook<F>(&self, hook: F) -> HookHandle
+    where
+        F: FnMut(&Tensor) -> Option<Tensor> + Send + 'static,
+    {
+        self.f_register_hook(hook).unwrap()
+    }
+}
+
+fn anomaly_set_enabled(b: bool) -> bool {
+    unsafe_torch!(at_anomaly_set_enabled(if b { 1 } else { 0 }) != 0)
+}
+
+/// A RAII guard that enables anomaly detection until deallocated.
+pub struct DetectAnomalyGuard {
+    enabled: bool,
+}
+
+/// Enables anomaly detection, the previous mode is restored when the
+/// returned value gets deallocated.
+///
+/// When enabled, the backward pass returns an error as soon as a NaN value
+/// is produced. The error message mentions the failing backward node and
+/// includes a backtrace of the forward call that created it. Anomaly
+/// detection is a global setting shared by all threads and significantly
+/// slows down the computations, it should only be used for debugging.
+pub fn detect_anomaly() -> DetectAnomalyGuard {
+    DetectAnomalyGuard {
+        enabled: anomaly_set_enabled(true),
+    }
+}
+
+impl Drop for DetectAnomalyGuard {
+    fn drop(&mut self) {
+        let _enabled = anomaly_set_enabled(self.enabled);
+    }
+}
@@ -2,6 +2,7 @@
 
 /// A torch device.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Device {
     /// The main CPU device.
     Cpu,
@@ -37,6 +38,18 @@ impl Cuda {
     pub fn cudnn_set_benchmark(b: bool) {
         unsafe_torch!(torch_sys::atc_set_benchmark_cudnn(if b { 1 } else { 0 }))
     }
+
+    /// Sets cudnn deterministic mode.
+    ///
+    /// When set cudnn only uses deterministic convolution algorithms, this
+    /// may be slower than the default mode.
+    pub fn cudnn_set_deterministic(b: bool) {
+        unsafe_torch!(torch_sys::atc_set_deterministic_cudnn(if b {
+            1
+        } else {
+            0
+        }))
+    }
 }
 
 impl Device {
@@ -0,0 +1,181 @@
+//! DLPack support, to share tensors with other array libraries without copy.
+//!
+//! The layout of the structures follows the version 0.3 of the DLPack spec:
+//! https://github.com/dmlc/dlpack/blob/v0.3/include/dlpack/dlpack.h
+use super::device::Device;
+use crate::{TchError, Tensor};
+use libc::{c_int, c_void};
+use std::ptr::NonNull;
+use torch_sys::*;
+
+/// The DLPack device type for CPU memory.
+pub const DL_CPU: c_int = 1;
+/// The DLPack device type for CUDA memory.
+pub const DL_GPU: c_int = 2;
+
+/// The DLPack type code for signed integers.
+pub const DL_INT: u8 = 0;
+/// The DLPack type code for unsigned integers.
+pub const DL_UINT: u8 = 1;
+/// The DLPack type code for floating point numbers.
+pub const DL_FLOAT: u8 = 2;
+/// The DLPack type code for bfloat16 numbers.
+pub const DL_BFLOAT: u8 = 4;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DLContext {
+    pub device_type: c_int,
+    pub device_id: c_int,
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct DLDataType {
+    pub code: u8,
+    pub bits: u8,
+    pub lanes: u16,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct DLTensor {
+    pub data: *mut c_void,
+    pub ctx: DLContext,
+    pub ndim: c_int,
+    pub dtype: DLDataType,
+    pub shape: *mut i64,
+    /// Strides in number of elements, null for a compact row-major tensor.
+    pub strides: *mut i64,
+    pub byte_offset: u64,
+}
+
+#[repr(C)]
+#[derive(Debug)]
+pub struct DLManagedTensor {
+    pub dl_tensor: DLTensor,
+    pub manager_ctx: *mut c_void,
+    pub deleter: Option<unsafe extern "C" fn(*mut DLManagedTensor)>,
+}
+
+/// An owned `DLManagedTensor`, its deleter is called when dropped unless it
+/// has been passed to another library via `into_raw`.
+#[derive(Debug)]
+pub struct ManagedTensor {
+    ptr: NonNull<DLManagedTensor>,
+}
+
+unsafe impl Send for ManagedTensor {}
+
+impl ManagedTensor {
+    /// Takes ownership of a `DLManagedTensor` produced by another library.
+    ///
+    /// # Safety
+    ///
+    /// The pointer has to point to a valid `DLManagedTensor` that is not
+    /// used by its producer anymore.
+    pub unsafe fn from_raw(ptr: *mut DLManagedTensor) -> Result<ManagedTensor, TchError> {
+        match NonNull::new(ptr) {
+            Some(ptr) => Ok(ManagedTensor { ptr }),
+            None => Err(TchError::Convert("null DLManagedTensor".to_string())),
+        }
+    }
+
+    /// Releases ownership of the `DLManagedTensor`, the consumer is then
+    /// responsible for calling its deleter.
+    pub fn into_raw(self) -> *mut DLManagedTensor {
+        let ptr = self.ptr.as_ptr();
+        std::mem::forget(self);
+        ptr
+    }
+
+    /// The description of the shared tensor.
+    pub fn dl_tensor(&self) -> &DLTensor {
+        unsafe { &self.ptr.as_ref().dl_tensor }
+    }
+
+    /// The dimensions of the shared tensor.
+    pub fn shape(&self) -> Vec<i64> {
+        let t = self.dl_tensor();
+        if t.ndim == 0 {
+            return vec![];
+        }
+        unsafe { std::slice::from_raw_parts(t.shape, t.ndim as usize) }.to_vec()
+    }
+
+    /// The strides of the shared tensor, computed from the shape for compact
+    /// row-major tensors.
+    pub fn strides(&self) -> Vec<i64> {
+        let t = self.dl_tensor();
+        if t.strides.is_null() {
+            let shape = self.shape();
+            let mut strides = vec![1; shape.len()];
+            for i in (0..shape.len().saturating_sub(1)).rev() {
+                strides[i] = strides[i + 1] * shape[i + 1]
+            }
+            strides
+        } else {
+            unsafe { std::slice::from_raw_parts(t.strides, t.ndim as usize) }.to_vec()
+        }
+    }
+
+    /// The device holding the shared tensor.
+    pub fn device(&self) -> Result<Device, TchError> {
+        let ctx = self.dl_tensor().ctx;
+        match ctx.device_type {
+            DL_CPU => Ok(Device::Cpu),
+            DL_GPU => Ok(Device::Cuda(ctx.device_id as usize)),
+            d => Err(TchError::Convert(format!(
+                "unsupported DLPack device {}",
+                d
+            ))),
+        }
+    }
+}
+
+impl Drop for ManagedTensor {
+    fn drop(&mut self) {
+        unsafe {
+            if let Some(deleter) = self.ptr.as_ref().deleter {
+                deleter(self.ptr.as_ptr())
+            }
+        }
+    }
+}
+
+impl Tensor {
+    /// Exports a tensor to DLPack, the returned tensor shares memory with
+    /// this one.
+    pub fn f_to_dlpack(&self) -> Result<ManagedTensor, TchError> {
+        let ptr = unsafe_torch_err!(at_to_dlpack(self.c_tensor));
+        unsafe { ManagedTensor::from_raw(ptr as *mut DLManagedTensor) }
+    }
+
+    /// Exports a tensor to DLPack, the returned tensor shares memory with
+    /// this one.
+    pub fn to_dlpack(&self) -> ManagedTensor {
+        self.f_to_dlpack().unwrap()
+    }
+
+    /// Imports a DLPack tensor, the resulting tensor shares memory with it
+    /// and the DLPack deleter is called once the tensor storage is released.
+    pub fn f_from_dlpack(managed: ManagedTensor) -> Result<Tensor, TchError> {
+        let ptr = managed.into_raw();
+        let c_tensor = unsafe_torch_err!({
+            let c_tensor = at_from_dlpack(ptr as *mut c_void);
+            if c_tensor.is_null() {
+                // The conversion fails before libtorch takes ownership, e.g.
+                // on unsupported devices or types, so release the tensor here.
+                drop(ManagedTensor::from_raw(ptr));
+            }
+            c_tensor
+        });
+        Ok(Tensor { c_tensor })
+    }
+
+    /// Imports a DLPack tensor, the resulting tensor shares memory with it
+    /// and the DLPack deleter is called once the tensor storage is released.
+    pub fn from_dlpack(managed: ManagedTensor) -> Tensor {
+        Self::f_from_dlpack(managed).unwrap()
+    }
+}
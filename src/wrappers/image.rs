@@ -1,9 +1,16 @@
 use super::tensor::Tensor;
 use super::utils::path_to_cstring;
 use crate::TchError;
-use libc::c_int;
+use libc::{c_char, c_int, c_void};
 use std::path::Path;
 
+extern "C" fn write_to_vec(data: *mut c_void, buf: *const c_char, size: libc::size_t) -> c_int {
+    let data = unsafe { &mut *(data as *mut Vec<u8>) };
+    let buf = unsafe { std::slice::from_raw_parts(buf as *const u8, size) };
+    data.extend_from_slice(buf);
+    1
+}
+
 /// On success returns a tensor of shape [width, height, channels].
 pub fn load_hwc<T: AsRef<Path>>(path: T) -> Result<Tensor, TchError> {
     let path = path_to_cstring(path)?;
@@ -11,6 +18,40 @@ pub fn load_hwc<T: AsRef<Path>>(path: T) -> Result<Tensor, TchError> {
     Ok(Tensor { c_tensor })
 }
 
+/// Decodes an image stored in memory, `channels` is the number of channels
+/// to decode to or 0 to keep the number of channels from the image.
+/// When `allow_16_bit` is set, 16 bits images result in an int32 tensor,
+/// otherwise the returned tensor is of kind uint8.
+/// On success returns a tensor of shape [width, height, channels].
+pub fn load_hwc_from_memory(
+    data: &[u8],
+    channels: i64,
+    allow_16_bit: bool,
+) -> Result<Tensor, TchError> {
+    let c_tensor = unsafe_torch_err!(torch_sys::at_load_image_from_memory(
+        data.as_ptr(),
+        data.len(),
+        channels as c_int,
+        allow_16_bit as c_int
+    ));
+    Ok(Tensor { c_tensor })
+}
+
+/// Expects a tensor of shape [width, height, channels].
+/// The format is 0 for png, 1 for jpg, 2 for bmp, and 3 for tga, the quality
+/// is only used for jpg.
+pub fn encode_hwc(t: &Tensor, format: i64, quality: i64) -> Result<Vec<u8>, TchError> {
+    let mut data: Vec<u8> = vec![];
+    unsafe_torch_err!(torch_sys::at_encode_image(
+        t.c_tensor,
+        format as c_int,
+        quality as c_int,
+        &mut data as *mut _ as *mut c_void,
+        write_to_vec
+    ));
+    Ok(data)
+}
+
 /// Expects a tensor of shape [width, height, channels].
 pub fn save_hwc<T: AsRef<Path>>(t: &Tensor, path: T) -> Result<(), TchError> {
     let path = path_to_cstring(path)?;
@@ -1,9 +1,11 @@
 //! The different kind of elements supported in Torch.
 
-use half::f16;
+use half::{bf16, f16};
+use num_complex::{Complex32, Complex64};
 
 /// The different kind of elements that a Tensor can hold.
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Kind {
     Uint8,
     Int8,
@@ -126,6 +128,10 @@ impl Element for f16 {
     const KIND: Kind = Kind::Half;
 }
 
+impl Element for bf16 {
+    const KIND: Kind = Kind::BFloat16;
+}
+
 impl Element for f32 {
     const KIND: Kind = Kind::Float;
 }
@@ -134,6 +140,80 @@ impl Element for f64 {
     const KIND: Kind = Kind::Double;
 }
 
+impl Element for Complex32 {
+    const KIND: Kind = Kind::ComplexFloat;
+}
+
+impl Element for Complex64 {
+    const KIND: Kind = Kind::ComplexDouble;
+}
+
 impl Element for bool {
     const KIND: Kind = Kind::Bool;
 }
+
+/// Element types using a numeric kind, i.e. all the element types except
+/// booleans. Subtractions and negations are only available on these for
+/// typed tensors.
+pub trait NumericElement: Element {}
+
+impl NumericElement for u8 {}
+
+impl NumericElement for i8 {}
+
+impl NumericElement for i16 {}
+
+impl NumericElement for i32 {}
+
+impl NumericElement for i64 {}
+
+impl NumericElement for f16 {}
+
+impl NumericElement for bf16 {}
+
+impl NumericElement for f32 {}
+
+impl NumericElement for f64 {}
+
+impl NumericElement for Complex32 {}
+
+impl NumericElement for Complex64 {}
+
+/// The element type used when summing elements of a given type, booleans and
+/// integers are summed as int64 values as in PyTorch.
+pub trait SumElement: Element {
+    type Output: Element;
+}
+
+macro_rules! sum_element {
+    ($ty:ty, $output:ty) => {
+        impl SumElement for $ty {
+            type Output = $output;
+        }
+    };
+}
+
+sum_element!(bool, i64);
+sum_element!(u8, i64);
+sum_element!(i8, i64);
+sum_element!(i16, i64);
+sum_element!(i32, i64);
+sum_element!(i64, i64);
+sum_element!(f16, f16);
+sum_element!(bf16, bf16);
+sum_element!(f32, f32);
+sum_element!(f64, f64);
+sum_element!(Complex32, Complex32);
+sum_element!(Complex64, Complex64);
+
+/// Element types using a floating point kind, some operations such as
+/// divisions or means are only available on these for typed tensors.
+pub trait FloatElement: NumericElement {}
+
+impl FloatElement for f16 {}
+
+impl FloatElement for bf16 {}
+
+impl FloatElement for f32 {}
+
+impl FloatElement for f64 {}
@@ -1,15 +1,20 @@
 #[macro_use]
 mod utils;
 pub use utils::{
-    get_num_interop_threads, get_num_threads, manual_seed, set_num_interop_threads,
-    set_num_threads, QEngine,
+    are_deterministic_algorithms_enabled, get_num_interop_threads, get_num_threads, manual_seed,
+    set_num_interop_threads, set_num_threads, use_deterministic_algorithms, QEngine,
 };
 
+pub mod autograd;
 pub(crate) mod device;
+pub mod dlpack;
 pub(crate) mod image;
 pub mod jit;
 pub mod kind;
 pub(crate) mod optimizer;
+pub mod profiler;
+#[cfg(feature = "python-extension")]
+pub mod python;
 pub(crate) mod scalar;
 pub(crate) mod tensor;
 pub(crate) mod tensor_fallible_generated;
@@ -0,0 +1,488 @@
+//! Operator level profiling.
+//!
+//! This wraps the libtorch autograd profiler. The profiler is enabled by
+//! creating a `ProfilerGuard`, the operations run until the guard is
+//! finished are recorded together with their input shapes, CPU time and
+//! memory usage.
+//!
+//! ```no_run
+//! use tch::profiler::{self, GroupBy};
+//! # fn main() -> Result<(), tch::TchError> {
+//! let xs = tch::Tensor::randn(&[64, 32], tch::kind::FLOAT_CPU);
+//! let guard = profiler::profile();
+//! {
+//!     let _scope = profiler::record_function("block");
+//!     let _ys = xs.matmul(&xs.tr()).relu();
+//! }
+//! let profile = guard.finish();
+//! println!("{}", profile.table(GroupBy::Op));
+//! profile.export_chrome_trace("trace.json")?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Operations can be grouped in user defined scopes using `record_function`
+//! or `record_path`, the latter uses the name of a `nn::Path` so that the
+//! time spent in the different parts of a model can be compared.
+use super::utils::read_and_clean_error;
+use crate::TchError;
+use libc::{c_char, c_int, c_void};
+use std::collections::HashMap;
+use torch_sys::*;
+
+/// Profiler options.
+#[derive(Debug, Clone, Copy)]
+pub struct ProfilerConfig {
+    /// Records the shapes of the operation inputs.
+    pub record_shapes: bool,
+    /// Tracks the memory allocated by operations.
+    pub profile_memory: bool,
+}
+
+impl Default for ProfilerConfig {
+    fn default() -> Self {
+        ProfilerConfig {
+            record_shapes: true,
+            profile_memory: true,
+        }
+    }
+}
+
+/// A RAII guard that keeps the profiler enabled until finished or
+/// deallocated.
+///
+/// Dropping the guard without calling `finish` discards the records.
+pub struct ProfilerGuard {
+    finished: bool,
+    // The profiler state is thread-local.
+    _not_send: std::marker::PhantomData<*const ()>,
+}
+
+/// Enables the profiler with the given options.
+///
+/// An error is returned if the profiler is already enabled.
+pub fn f_profile_with_config(config: ProfilerConfig) -> Result<ProfilerGuard, TchError> {
+    unsafe_torch_err!(at_profiler_enable(
+        config.record_shapes as c_int,
+        config.profile_memory as c_int
+    ));
+    Ok(ProfilerGuard {
+        finished: false,
+        _not_send: std::marker::PhantomData,
+    })
+}
+
+/// Enables the profiler with the given options.
+pub fn profile_with_config(config: ProfilerConfig) -> ProfilerGuard {
+    f_profile_with_config(config).unwrap()
+}
+
+/// Enables the profiler, recording input shapes and memory usage.
+pub fn profile() -> ProfilerGuard {
+    profile_with_config(ProfilerConfig::default())
+}
+
+struct Event {
+    kind: String,
+    name: String,
+    thread_id: u64,
+    handle: u64,
+    is_user_scope: bool,
+    cpu_us: f64,
+    cpu_memory_usage: i64,
+    shapes: Vec<Vec<i64>>,
+}
+
+extern "C" fn add_event_callback(
+    data: *mut c_void,
+    kind: *const c_char,
+    name: *const c_char,
+    thread_id: u64,
+    handle: u64,
+    is_user_scope: c_int,
+    cpu_us: f64,
+    cpu_memory_usage: i64,
+    dims: *const i64,
+    ndims: *const i64,
+    nshapes: c_int,
+) {
+    let kind = unsafe {
+        std::ffi::CStr::from_ptr(kind)
+            .to_string_lossy()
+            .into_owned()
+    };
+    let name = unsafe {
+        std::ffi::CStr::from_ptr(name)
+            .to_string_lossy()
+            .into_owned()
+    };
+    let ndims = unsafe { std::slice::from_raw_parts(ndims, nshapes as usize) };
+    let mut shapes = Vec::with_capacity(ndims.len());
+    let mut offset = 0;
+    for &ndim in ndims.iter() {
+        let shape = unsafe { std::slice::from_raw_parts(dims.add(offset), ndim as usize) };
+        shapes.push(shape.to_vec());
+        offset += ndim as usize;
+    }
+    let v: &mut Vec<Event> = unsafe { &mut *(data as *mut Vec<Event>) };
+    v.push(Event {
+        kind,
+        name,
+        thread_id,
+        handle,
+        is_user_scope: is_user_scope != 0,
+        cpu_us,
+        cpu_memory_usage,
+        shapes,
+    })
+}
+
+impl ProfilerGuard {
+    /// Disables the profiler and returns the recorded operations.
+    pub fn f_finish(mut self) -> Result<Profile, TchError> {
+        self.finished = true;
+        let mut events: Vec<Event> = vec![];
+        unsafe_torch_err!(at_profiler_disable(
+            &mut events as *mut _ as *mut c_void,
+            add_event_callback
+        ));
+        Ok(Profile::of_events(events))
+    }
+
+    /// Disables the profiler and returns the recorded operations.
+    pub fn finish(self) -> Profile {
+        self.f_finish().unwrap()
+    }
+}
+
+impl Drop for ProfilerGuard {
+    fn drop(&mut self) {
+        if !self.finished {
+            let mut events: Vec<Event> = vec![];
+            unsafe {
+                at_profiler_disable(&mut events as *mut _ as *mut c_void, add_event_callback)
+            };
+            let _ = read_and_clean_error();
+        }
+    }
+}
+
+/// A RAII guard for a user defined scope, see `record_function`.
+pub struct RecordFunctionGuard {
+    c_rec: *mut c_void,
+}
+
+/// Records a user defined scope until the returned guard is deallocated.
+///
+/// The scope appears as a range in the profiler records, operations run
+/// within this range are attributed to the scope.
+pub fn f_record_function(name: &str) -> Result<RecordFunctionGuard, TchError> {
+    let name = std::ffi::CString::new(name)?;
+    let c_rec = unsafe_torch_err!(at_profiler_record_function_enter(name.as_ptr()));
+    Ok(RecordFunctionGuard { c_rec })
+}
+
+/// Records a user defined scope until the returned guard is deallocated.
+pub fn record_function(name: &str) -> RecordFunctionGuard {
+    f_record_function(name).unwrap()
+}
+
+/// Records a scope named after a variable store path until the returned
+/// guard is deallocated.
+pub fn record_path(path: &crate::nn::Path) -> RecordFunctionGuard {
+    let name: Vec<&str> = path.components().collect();
+    record_function(&name.join("."))
+}
+
+impl Drop for RecordFunctionGuard {
+    fn drop(&mut self) {
+        unsafe_torch!(at_profiler_record_function_exit(self.c_rec))
+    }
+}
+
+/// A profiled operation or user defined scope.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProfilerRecord {
+    /// The operation name, e.g. `aten::matmul`, or the scope name.
+    pub name: String,
+    /// The identifier of the thread that ran the operation.
+    pub thread_id: u64,
+    /// The shapes of the tensor inputs, empty if not recorded.
+    pub input_shapes: Vec<Vec<i64>>,
+    /// The start time in microseconds since the profiler was enabled.
+    pub start_us: f64,
+    /// The CPU time in microseconds, including nested operations.
+    pub cpu_time_us: f64,
+    /// The CPU time in microseconds, excluding nested operations.
+    pub self_cpu_time_us: f64,
+    /// The CPU memory allocated in bytes, including nested operations.
+    pub cpu_memory_usage: i64,
+    /// The CPU memory allocated in bytes, excluding nested operations.
+    pub self_cpu_memory_usage: i64,
+    /// The index of the enclosing record if any.
+    pub parent: Option<usize>,
+    /// The innermost user defined scope enclosing this record.
+    pub scope: Option<String>,
+    /// Whether this record is a user defined scope.
+    pub is_scope: bool,
+}
+
+/// The key used to aggregate records.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum GroupBy {
+    /// Groups operations by name.
+    Op,
+    /// Groups operations by innermost user defined scope.
+    Scope,
+}
+
+/// Statistics for a group of records.
+#[derive(Debug, Clone, PartialEq)]
+pub struct AggregatedRecord {
+    /// The operation name or the scope name, the empty string is used for
+    /// operations that do not belong to any scope.
+    pub key: String,
+    /// The number of operation calls or of times the scope was entered.
+    pub count: usize,
+    /// The total CPU time in microseconds, including nested operations.
+    pub cpu_time_total_us: f64,
+    /// The total CPU time in microseconds, excluding nested operations and
+    /// nested scopes.
+    pub self_cpu_time_total_us: f64,
+    /// The CPU memory allocated in bytes, including nested operations.
+    pub cpu_memory_usage: i64,
+    /// The CPU memory allocated in bytes, excluding nested operations and
+    /// nested scopes.
+    pub self_cpu_memory_usage: i64,
+}
+
+impl AggregatedRecord {
+    fn new(key: &str) -> AggregatedRecord {
+        AggregatedRecord {
+            key: key.to_string(),
+            count: 0,
+            cpu_time_total_us: 0.,
+            self_cpu_time_total_us: 0.,
+            cpu_memory_usage: 0,
+            self_cpu_memory_usage: 0,
+        }
+    }
+}
+
+/// The records collected by the profiler.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    records: Vec<ProfilerRecord>,
+}
+
+impl Profile {
+    fn of_events(events: Vec<Event>) -> Profile {
+        let mut records: Vec<ProfilerRecord> = vec![];
+        // Indexes of the records currently open, per thread.
+        let mut stacks: HashMap<u64, Vec<(u64, usize)>> = HashMap::new();
+        for event in events.into_iter() {
+            let stack = stacks.entry(event.thread_id).or_insert_with(Vec::new);
+            match event.kind.as_str() {
+                "push" => {
+                    let parent = stack.last().map(|&(_, index)| index);
+                    let is_scope = event.is_user_scope;
+                    let scope = if is_scope {
+                        Some(event.name.clone())
+                    } else {
+                        parent.and_then(|p| records[p].scope.clone())
+                    };
+                    stack.push((event.handle, records.len()));
+                    records.push(ProfilerRecord {
+                        name: event.name,
+                        thread_id: event.thread_id,
+                        input_shapes: event.shapes,
+                        start_us: event.cpu_us,
+                        cpu_time_us: 0.,
+                        self_cpu_time_us: 0.,
+                        cpu_memory_usage: 0,
+                        self_cpu_memory_usage: 0,
+                        parent,
+                        scope,
+                        is_scope,
+                    })
+                }
+                "pop" => {
+                    let position = if event.handle == 0 {
+                        None
+                    } else {
+                        stack
+                            .iter()
+                            .rposition(|&(handle, _)| handle == event.handle)
+                    };
+                    let position = match position {
+                        Some(position) => position,
+                        None if stack.is_empty() => continue,
+                        None => stack.len() - 1,
+                    };
+                    for (_, index) in stack.drain(position..).rev() {
+                        let record = &mut records[index];
+                        record.cpu_time_us = event.cpu_us - record.start_us;
+                        record.self_cpu_time_us += record.cpu_time_us;
+                        let cpu_time_us = record.cpu_time_us;
+                        if let Some(parent) = record.parent {
+                            records[parent].self_cpu_time_us -= cpu_time_us;
+                        }
+                    }
+                }
+                "memory_alloc" => {
+                    for &(_, index) in stack.iter() {
+                        records[index].cpu_memory_usage += event.cpu_memory_usage
+                    }
+                    if let Some(&(_, index)) = stack.last() {
+                        records[index].self_cpu_memory_usage += event.cpu_memory_usage
+                    }
+                }
+                _ => {}
+            }
+        }
+        Profile { records }
+    }
+
+    /// The records of the profiled operations and scopes, ordered by start
+    /// time on each thread.
+    pub fn records(&self) -> &[ProfilerRecord] {
+        &self.records
+    }
+
+    /// Aggregates the records, the result is sorted by decreasing self CPU
+    /// time.
+    ///
+    /// When grouping by op, scopes are ignored. When grouping by scope, the
+    /// operations are attributed to their innermost scope so that nested
+    /// scopes do not count twice in the self values.
+    pub fn aggregate(&self, group_by: GroupBy) -> Vec<AggregatedRecord> {
+        let mut groups: Vec<AggregatedRecord> = vec![];
+        let mut indexes: HashMap<String, usize> = HashMap::new();
+        let mut group = |key: &str| -> usize {
+            *indexes.entry(key.to_string()).or_insert_with(|| {
+                groups.push(AggregatedRecord::new(key));
+                groups.len() - 1
+            })
+        };
+        let mut updates: Vec<(usize, &ProfilerRecord, bool)> = vec![];
+        for record in self.records.iter() {
+            match group_by {
+                GroupBy::Op => {
+                    if !record.is_scope {
+                        updates.push((group(&record.name), record, true))
+                    }
+                }
+                GroupBy::Scope => {
+                    let key = record.scope.as_deref().unwrap_or("");
+                    let index = group(key);
+                    // Operations outside of any scope are counted as top-level
+                    // entries of the empty scope.
+                    let is_entry = record.is_scope || (key.is_empty() && record.parent.is_none());
+                    updates.push((index, record, is_entry))
+                }
+            }
+        }
+        for (index, record, is_entry) in updates.into_iter() {
+            let group = &mut groups[index];
+            if is_entry {
+                group.count += 1;
+                group.cpu_time_total_us += record.cpu_time_us;
+                group.cpu_memory_usage += record.cpu_memory_usage;
+            }
+            group.self_cpu_time_total_us += record.self_cpu_time_us;
+            group.self_cpu_memory_usage += record.self_cpu_memory_usage;
+        }
+        groups.sort_by(|g1, g2| {
+            g2.self_cpu_time_total_us
+                .partial_cmp(&g1.self_cpu_time_total_us)
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+        groups
+    }
+
+    /// Returns a table summarizing the aggregated records.
+    pub fn table(&self, group_by: GroupBy) -> String {
+        let groups = self.aggregate(group_by);
+        let width = groups
+            .iter()
+            .map(|g| g.key.len())
+            .chain(std::iter::once(4))
+            .max()
+            .unwrap_or(4);
+        let mut table = format!(
+            "{:<width$}  {:>14}  {:>14}  {:>8}  {:>14}  {:>14}\n",
+            "Name",
+            "Self CPU (us)",
+            "CPU total (us)",
+            "Calls",
+            "Self CPU Mem",
+            "CPU Mem",
+            width = width
+        );
+        table.push_str(&"-".repeat(width + 76));
+        table.push('\n');
+        for g in groups.iter() {
+            table.push_str(&format!(
+                "{:<width$}  {:>14.3}  {:>14.3}  {:>8}  {:>14}  {:>14}\n",
+                g.key,
+                g.self_cpu_time_total_us,
+                g.cpu_time_total_us,
+                g.count,
+                g.self_cpu_memory_usage,
+                g.cpu_memory_usage,
+                width = width
+            ))
+        }
+        table
+    }
+
+    /// Returns the records using the Chrome trace event format, the result can
+    /// be loaded in `chrome://tracing`.
+    pub fn to_chrome_trace(&self) -> String {
+        let events: Vec<String> = self
+            .records
+            .iter()
+            .map(|r| {
+                let shapes: Vec<String> = r
+                    .input_shapes
+                    .iter()
+                    .map(|s| format!("{:?}", s).replace(' ', ""))
+                    .collect();
+                format!(
+                    "{{\"name\":{},\"ph\":\"X\",\"ts\":{},\"dur\":{},\"tid\":{},\"pid\":\"CPU functions\",\"args\":{{\"Input dims\":[{}],\"CPU memory usage\":{}}}}}",
+                    json_string(&r.name),
+                    r.start_us,
+                    r.cpu_time_us,
+                    r.thread_id,
+                    shapes.join(","),
+                    r.cpu_memory_usage,
+                )
+            })
+            .collect();
+        format!("{{\"traceEvents\":[\n{}\n]}}\n", events.join(",\n"))
+    }
+
+    /// Writes the records to a file using the Chrome trace event format.
+    pub fn export_chrome_trace<T: AsRef<std::path::Path>>(&self, path: T) -> Result<(), TchError> {
+        std::fs::write(path, self.to_chrome_trace())?;
+        Ok(())
+    }
+}
+
+fn json_string(s: &str) -> String {
+    let mut res = String::with_capacity(s.len() + 2);
+    res.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => res.push_str("\\\""),
+            '\\' => res.push_str("\\\\"),
+            '\n' => res.push_str("\\n"),
+            '\r' => res.push_str("\\r"),
+            '\t' => res.push_str("\\t"),
+            c if (c as u32) < 0x20 => res.push_str(&format!("\\u{:04x}", c as u32)),
+            c => res.push(c),
+        }
+    }
+    res.push('"');
+    res
+}
@@ -0,0 +1,244 @@
+//! Interoperability with PyTorch when writing Python extensions using PyO3.
+//!
+//! Tensors are exchanged with Python without copying the data: the
+//! `torch.Tensor` objects and the Rust `Tensor` values share the same
+//! underlying `at::Tensor`, including its autograd state.
+//!
+//! This module requires the `python-extension` feature, the `LIBTORCH`
+//! environment variable should point at the torch directory of the PyTorch
+//! install used by the Python interpreter, and the `torch` Python module has
+//! to be imported before the extension.
+//!
+//! ```ignore
+//! use pyo3::prelude::*;
+//! use tch::Tensor;
+//!
+//! #[pyfunction]
+//! fn add_one(xs: Tensor) -> Tensor {
+//!     xs + 1
+//! }
+//!
+//! #[pymodule]
+//! fn my_extension(_py: Python, m: &PyModule) -> PyResult<()> {
+//!     m.add_function(wrap_pyfunction!(add_one, m)?)?;
+//!     Ok(())
+//! }
+//! ```
+use super::jit::IValue;
+use crate::{nn::VarStore, TchError, Tensor};
+use libc::c_void;
+use pyo3::exceptions::{PyRuntimeError, PyTypeError};
+use pyo3::prelude::*;
+use pyo3::types::{PyBool, PyDict, PyFloat, PyList, PyLong, PyString, PyTuple};
+use pyo3::{AsPyPointer, ToPyObject};
+use torch_sys::*;
+
+impl From<TchError> for PyErr {
+    fn from(err: TchError) -> PyErr {
+        PyRuntimeError::new_err(err.to_string())
+    }
+}
+
+impl Tensor {
+    /// Returns true if the Python object is a `torch.Tensor`.
+    pub fn is_pyobject_tensor(obj: &PyAny) -> bool {
+        unsafe { thp_variable_check(obj.as_ptr() as *mut c_void) != 0 }
+    }
+
+    /// Gets the tensor wrapped by a `torch.Tensor` Python object, the
+    /// resulting tensor shares its storage and autograd state with `obj`.
+    pub fn f_from_pyobject(obj: &PyAny) -> Result<Tensor, TchError> {
+        let c_tensor = unsafe_torch_err!(thp_variable_unpack(obj.as_ptr() as *mut c_void));
+        Ok(Tensor { c_tensor })
+    }
+
+    /// Wraps a tensor in a `torch.Tensor` Python object sharing its storage
+    /// and autograd state.
+    pub fn f_to_pyobject(&self, py: Python) -> Result<PyObject, TchError> {
+        let obj = unsafe_torch_err!(thp_variable_wrap(self.c_tensor));
+        if obj.is_null() {
+            return Err(TchError::Convert(
+                "unable to wrap the tensor in a python object".to_string(),
+            ));
+        }
+        Ok(unsafe { PyObject::from_owned_ptr(py, obj as *mut pyo3::ffi::PyObject) })
+    }
+
+    /// Wraps a tensor in a `torch.Tensor` Python object sharing its storage
+    /// and autograd state.
+    pub fn to_pyobject(&self, py: Python) -> PyObject {
+        self.f_to_pyobject(py).unwrap()
+    }
+}
+
+impl<'source> FromPyObject<'source> for Tensor {
+    fn extract(obj: &'source PyAny) -> PyResult<Self> {
+        if !Tensor::is_pyobject_tensor(obj) {
+            return Err(PyTypeError::new_err(format!(
+                "expected a torch.Tensor, got {}",
+                obj.get_type().name()?
+            )));
+        }
+        Ok(Tensor::f_from_pyobject(obj)?)
+    }
+}
+
+impl ToPyObject for Tensor {
+    fn to_object(&self, py: Python) -> PyObject {
+        self.to_pyobject(py)
+    }
+}
+
+impl IntoPy<PyObject> for Tensor {
+    fn into_py(self, py: Python) -> PyObject {
+        self.to_pyobject(py)
+    }
+}
+
+impl<'source> FromPyObject<'source> for IValue {
+    fn extract(obj: &'source PyAny) -> PyResult<Self> {
+        if obj.is_none() {
+            Ok(IValue::None)
+        } else if Tensor::is_pyobject_tensor(obj) {
+            Ok(IValue::Tensor(obj.extract()?))
+        } else if obj.downcast::<PyBool>().is_ok() {
+            Ok(IValue::Bool(obj.extract()?))
+        } else if obj.downcast::<PyLong>().is_ok() {
+            Ok(IValue::Int(obj.extract()?))
+        } else if obj.downcast::<PyFloat>().is_ok() {
+            Ok(IValue::Double(obj.extract()?))
+        } else if obj.downcast::<PyString>().is_ok() {
+            Ok(IValue::String(obj.extract()?))
+        } else if let Ok(tuple) = obj.downcast::<PyTuple>() {
+            let values = tuple.iter().map(|v| v.extract()).collect::<PyResult<_>>()?;
+            Ok(IValue::Tuple(values))
+        } else if let Ok(list) = obj.downcast::<PyList>() {
+            let values: Vec<IValue> = list.iter().map(|v| v.extract()).collect::<PyResult<_>>()?;
+            Ok(ivalue_of_list(values))
+        } else if let Ok(dict) = obj.downcast::<PyDict>() {
+            let values = dict
+                .iter()
+                .map(|(k, v)| Ok((k.extract()?, v.extract()?)))
+                .collect::<PyResult<_>>()?;
+            Ok(IValue::GenericDict(values))
+        } else {
+            Err(PyTypeError::new_err(format!(
+                "cannot convert {} to an IValue",
+                obj.get_type().name()?
+            )))
+        }
+    }
+}
+
+// Uses the specialized list variants when all the elements have the same
+// type, e.g. so that lists of tensors can be passed to a `List[Tensor]`
+// argument.
+fn ivalue_of_list(values: Vec<IValue>) -> IValue {
+    macro_rules! try_list {
+        ($variant:ident, $list_variant:ident) => {
+            if !values.is_empty() && values.iter().all(|v| matches!(v, IValue::$variant(_))) {
+                let values = values
+                    .into_iter()
+                    .map(|v| match v {
+                        IValue::$variant(v) => v,
+                        _ => unreachable!(),
+                    })
+                    .collect();
+                return IValue::$list_variant(values);
+            }
+        };
+    }
+    try_list!(Tensor, TensorList);
+    try_list!(Int, IntList);
+    try_list!(Double, DoubleList);
+    try_list!(Bool, BoolList);
+    try_list!(String, StringList);
+    IValue::GenericList(values)
+}
+
+impl IValue {
+    /// Converts an IValue to a Python object, tensors are wrapped in
+    /// `torch.Tensor` objects sharing their storage.
+    ///
+    /// An error is returned when a dictionary key cannot be hashed by Python,
+    /// e.g. for list keys.
+    pub fn f_into_pyobject(self, py: Python) -> PyResult<PyObject> {
+        let obj = match self {
+            IValue::None => py.None(),
+            IValue::Tensor(t) => t.f_to_pyobject(py)?,
+            IValue::Double(v) => v.into_py(py),
+            IValue::Int(v) => v.into_py(py),
+            IValue::Bool(v) => v.into_py(py),
+            IValue::String(v) => v.into_py(py),
+            IValue::Tuple(vs) => {
+                let vs = vs
+                    .into_iter()
+                    .map(|v| v.f_into_pyobject(py))
+                    .collect::<PyResult<Vec<_>>>()?;
+                PyTuple::new(py, vs).into_py(py)
+            }
+            IValue::IntList(vs) => vs.into_py(py),
+            IValue::DoubleList(vs) => vs.into_py(py),
+            IValue::BoolList(vs) => vs.into_py(py),
+            IValue::StringList(vs) => vs.into_py(py),
+            IValue::TensorList(vs) => {
+                let vs = vs
+                    .iter()
+                    .map(|t| t.f_to_pyobject(py))
+                    .collect::<Result<Vec<_>, TchError>>()?;
+                PyList::new(py, vs).into_py(py)
+            }
+            IValue::GenericList(vs) => {
+                let vs = vs
+                    .into_iter()
+                    .map(|v| v.f_into_pyobject(py))
+                    .collect::<PyResult<Vec<_>>>()?;
+                PyList::new(py, vs).into_py(py)
+            }
+            IValue::GenericDict(vs) => {
+                let dict = PyDict::new(py);
+                for (k, v) in vs.into_iter() {
+                    dict.set_item(k.f_into_pyobject(py)?, v.f_into_pyobject(py)?)?
+                }
+                dict.into_py(py)
+            }
+        };
+        Ok(obj)
+    }
+}
+
+impl IntoPy<PyObject> for IValue {
+    fn into_py(self, py: Python) -> PyObject {
+        self.f_into_pyobject(py).unwrap()
+    }
+}
+
+/// Returns a dictionary mapping the variable names to `torch.Tensor` objects
+/// sharing memory with the variables of a var store, similar to a PyTorch
+/// `state_dict`.
+pub fn var_store_to_dict<'py>(py: Python<'py>, vs: &VarStore) -> PyResult<&'py PyDict> {
+    let dict = PyDict::new(py);
+    for (name, tensor) in vs.variables().into_iter() {
+        dict.set_item(name, tensor.f_to_pyobject(py)?)?
+    }
+    Ok(dict)
+}
+
+/// Copies the values of a dictionary of `torch.Tensor` objects, e.g. a
+/// PyTorch `state_dict`, to the variables of a var store.
+///
+/// All the variables of the var store have to be present in the dictionary,
+/// the set of variables in the var store is not changed.
+pub fn load_var_store_from_dict(vs: &mut VarStore, dict: &PyDict) -> PyResult<()> {
+    for (name, mut var) in vs.variables().into_iter() {
+        let src: Tensor = match dict.get_item(&name) {
+            Some(src) => src.extract()?,
+            None => {
+                return Err(TchError::TensorNameNotFound(name, "python dict".to_string()).into())
+            }
+        };
+        crate::no_grad(|| var.f_copy_(&src.f_to_device(vs.device())?))
+            .map_err(|e| e.path_context(&name))?;
+    }
+    Ok(())
+}
@@ -7,6 +7,7 @@ use super::{
 use crate::TchError;
 use libc::{c_char, c_int, c_void};
 use std::borrow::Borrow;
+use std::marker::PhantomData;
 use std::path::Path;
 use torch_sys::*;
 
@@ -25,6 +26,61 @@ pub extern "C" fn add_callback(data: *mut c_void, name: *const c_char, c_tensor:
     v.push((name, Tensor { c_tensor }))
 }
 
+extern "C" fn drop_boxed<T>(data: *mut c_void) {
+    let _ = unsafe { Box::from_raw(data as *mut T) };
+}
+
+// The state of a stream used by `write_callback`, errors are stored so that
+// they can be reported once the C++ function has returned.
+struct WriteStream<'a> {
+    stream: &'a mut dyn std::io::Write,
+    error: Option<std::io::Error>,
+}
+
+extern "C" fn write_callback(data: *mut c_void, buf: *const c_char, size: libc::size_t) -> c_int {
+    let data = unsafe { &mut *(data as *mut WriteStream) };
+    let buf = unsafe { std::slice::from_raw_parts(buf as *const u8, size) };
+    match data.stream.write_all(buf) {
+        Ok(()) => 1,
+        Err(err) => {
+            data.error = Some(err);
+            0
+        }
+    }
+}
+
+fn contiguous_strides(size: &[i64]) -> Vec<i64> {
+    let mut strides = vec![1; size.len()];
+    for i in (0..size.len().saturating_sub(1)).rev() {
+        strides[i] = strides[i + 1] * size[i + 1].max(1)
+    }
+    strides
+}
+
+/// A tensor using some memory borrowed from a mutable slice, see
+/// `Tensor::from_blob`.
+///
+/// The data is shared with the tensors derived from this one, e.g. via
+/// `shallow_clone` or views. These tensors must not outlive the borrow.
+pub struct BorrowedTensor<'a> {
+    tensor: Tensor,
+    _marker: PhantomData<&'a mut [u8]>,
+}
+
+impl<'a> std::ops::Deref for BorrowedTensor<'a> {
+    type Target = Tensor;
+
+    fn deref(&self) -> &Tensor {
+        &self.tensor
+    }
+}
+
+impl<'a> std::ops::DerefMut for BorrowedTensor<'a> {
+    fn deref_mut(&mut self) -> &mut Tensor {
+        &mut self.tensor
+    }
+}
+
 impl Tensor {
     /// Creates a new tensor.
     pub fn new() -> Tensor {
@@ -276,6 +332,19 @@ impl Tensor {
         unsafe_torch!(at_requires_grad(self.c_tensor)) != 0
     }
 
+    /// Enables the gradient of this non-leaf tensor to be populated by the
+    /// backward pass, it can then be accessed via `grad`.
+    pub fn f_retain_grad(&self) -> Result<(), TchError> {
+        unsafe_torch_err!(at_retain_grad(self.c_tensor));
+        Ok(())
+    }
+
+    /// Enables the gradient of this non-leaf tensor to be populated by the
+    /// backward pass, it can then be accessed via `grad`.
+    pub fn retain_grad(&self) {
+        self.f_retain_grad().unwrap()
+    }
+
     /// Returns the address of the first element of this tensor.
     pub fn data_ptr(&self) -> *mut c_void {
         unsafe_torch!(at_data_ptr(self.c_tensor))
@@ -473,6 +542,245 @@ impl Tensor {
         Self::f_of_slice(data).unwrap()
     }
 
+    /// Converts a vector to a tensor with the specified shape without copying
+    /// the data. The vector is dropped once the tensor storage is released.
+    pub fn f_from_vec<T: kind::Element + Send + 'static>(
+        data: Vec<T>,
+        size: &[i64],
+    ) -> Result<Tensor, TchError> {
+        if size.iter().any(|&s| s < 0) || size.iter().product::<i64>() as usize != data.len() {
+            return Err(TchError::Shape(format!(
+                "shape {:?} does not match the {} elements of the vector",
+                size,
+                data.len()
+            )));
+        }
+        let mut data = data;
+        let ptr = data.as_mut_ptr() as *mut c_void;
+        unsafe { Self::f_of_owned_blob(data, ptr, size, T::KIND) }
+    }
+
+    /// Creates a contiguous CPU tensor using the memory at `data`, `owner` is
+    /// dropped once the tensor storage is released.
+    ///
+    /// # Safety
+    ///
+    /// `data` must point to enough elements of the given kind for `size`, and
+    /// remain valid and not accessed through other means while `owner` is alive.
+    /// `owner` is dropped on the thread releasing the tensor storage.
+    pub(crate) unsafe fn f_of_owned_blob<D: Send + 'static>(
+        owner: D,
+        data: *mut c_void,
+        size: &[i64],
+        kind: Kind,
+    ) -> Result<Tensor, TchError> {
+        let strides = contiguous_strides(size);
+        #[allow(unused_unsafe)]
+        let c_tensor = unsafe_torch_err!(at_tensor_of_blob_with_deleter(
+            data,
+            size.as_ptr(),
+            size.len(),
+            strides.as_ptr(),
+            strides.len(),
+            kind.c_int(),
+            Device::Cpu.c_int(),
+            Box::into_raw(Box::new(owner)) as *mut c_void,
+            drop_boxed::<D>
+        ));
+        Ok(Tensor { c_tensor })
+    }
+
+    /// Converts a vector to a tensor with the specified shape without copying
+    /// the data. The vector is dropped once the tensor storage is released.
+    pub fn from_vec<T: kind::Element + Send + 'static>(data: Vec<T>, size: &[i64]) -> Tensor {
+        Self::f_from_vec(data, size).unwrap()
+    }
+
+    /// Creates a CPU tensor using the memory of a mutable slice, the element
+    /// at index `(i0, i1, ...)` is `data[i0 * strides[0] + i1 * strides[1] + ...]`.
+    ///
+    /// # Safety
+    ///
+    /// The returned tensor and all the tensors sharing its storage, e.g. the
+    /// ones obtained via `shallow_clone` or views, must be dropped before the
+    /// end of lifetime `'a`.
+    pub unsafe fn f_from_blob<'a, T: kind::Element>(
+        data: &'a mut [T],
+        size: &[i64],
+        strides: &[i64],
+    ) -> Result<BorrowedTensor<'a>, TchError> {
+        if size.len() != strides.len() {
+            return Err(TchError::Shape(format!(
+                "shape {:?} and strides {:?} have different lengths",
+                size, strides
+            )));
+        }
+        if size.iter().chain(strides.iter()).any(|&s| s < 0) {
+            return Err(TchError::Shape(format!(
+                "negative value in shape {:?} or strides {:?}",
+                size, strides
+            )));
+        }
+        if size.iter().all(|&s| s > 0) {
+            let last: i64 = size
+                .iter()
+                .zip(strides.iter())
+                .map(|(s, st)| (s - 1) * st)
+                .sum();
+            if last as usize >= data.len() {
+                return Err(TchError::Shape(format!(
+                    "shape {:?} and strides {:?} exceed the {} elements of the slice",
+                    size,
+                    strides,
+                    data.len()
+                )));
+            }
+        }
+        Self::f_borrow_raw(data.as_mut_ptr(), size, strides)
+    }
+
+    /// Creates a CPU tensor borrowing the memory at `data` for lifetime `'a`.
+    ///
+    /// # Safety
+    ///
+    /// All the elements described by `size` and `strides` must be valid and
+    /// not accessed through other means for lifetime `'a`, the returned tensor
+    /// and the tensors sharing its storage must not outlive `'a`.
+    pub(crate) unsafe fn f_borrow_raw<'a, T: kind::Element>(
+        data: *mut T,
+        size: &[i64],
+        strides: &[i64],
+    ) -> Result<BorrowedTensor<'a>, TchError> {
+        #[allow(unused_unsafe)]
+        let c_tensor = unsafe_torch_err!(at_tensor_of_blob(
+            data as *const c_void,
+            size.as_ptr(),
+            size.len(),
+            strides.as_ptr(),
+            strides.len(),
+            T::KIND.c_int(),
+            Device::Cpu.c_int()
+        ));
+        Ok(BorrowedTensor {
+            tensor: Tensor { c_tensor },
+            _marker: PhantomData,
+        })
+    }
+
+    /// Creates a CPU tensor using the memory of a mutable slice, the element
+    /// at index `(i0, i1, ...)` is `data[i0 * strides[0] + i1 * strides[1] + ...]`.
+    ///
+    /// # Safety
+    ///
+    /// See `f_from_blob`.
+    pub unsafe fn from_blob<'a, T: kind::Element>(
+        data: &'a mut [T],
+        size: &[i64],
+        strides: &[i64],
+    ) -> BorrowedTensor<'a> {
+        Self::f_from_blob(data, size, strides).unwrap()
+    }
+
+    // Checks that the elements of this tensor can be accessed from Rust as
+    // values of type `T`.
+    pub(crate) fn f_check_cpu_kind<T: kind::Element>(&self) -> Result<(), TchError> {
+        if !self.defined() {
+            return Err(TchError::Shape("undefined tensor".to_string()));
+        }
+        let kind = self.f_kind()?;
+        if kind != T::KIND {
+            return Err(TchError::Kind(format!(
+                "expected a tensor of kind {:?}, got {:?}",
+                T::KIND,
+                kind
+            )));
+        }
+        if self.device() != Device::Cpu {
+            return Err(TchError::Convert(format!(
+                "expected a cpu tensor, got {:?}",
+                self.device()
+            )));
+        }
+        Ok(())
+    }
+
+    fn f_check_slice_access<T: kind::Element>(&self) -> Result<(), TchError> {
+        self.f_check_cpu_kind::<T>()?;
+        let size = self.size();
+        if size.iter().all(|&s| s > 0) {
+            let mut expected_stride = 1;
+            for (&s, &stride) in size.iter().zip(self.stride().iter()).rev() {
+                if s != 1 && stride != expected_stride {
+                    return Err(TchError::Convert(
+                        "the tensor is not contiguous".to_string(),
+                    ));
+                }
+                expected_stride *= s
+            }
+        }
+        Ok(())
+    }
+
+    /// Returns the elements of a contiguous CPU tensor as a slice, an error is
+    /// returned if the tensor is not contiguous, not on the CPU, or if its kind
+    /// is not `T::KIND`. Use `Vec::<T>::from` to get a copy instead.
+    ///
+    /// # Safety
+    ///
+    /// The storage of this tensor is shared with the tensors obtained via
+    /// `shallow_clone`, views, etc. None of these tensors may be modified
+    /// while the returned slice is alive.
+    pub unsafe fn f_as_slice<T: kind::Element>(&self) -> Result<&[T], TchError> {
+        self.f_check_slice_access::<T>()?;
+        let numel = self.numel();
+        if numel == 0 {
+            return Ok(&[]);
+        }
+        Ok(std::slice::from_raw_parts(
+            self.data_ptr() as *const T,
+            numel,
+        ))
+    }
+
+    /// Returns the elements of a contiguous CPU tensor as a slice.
+    ///
+    /// # Safety
+    ///
+    /// See `f_as_slice`.
+    pub unsafe fn as_slice<T: kind::Element>(&self) -> &[T] {
+        self.f_as_slice().unwrap()
+    }
+
+    /// Returns the elements of a contiguous CPU tensor as a mutable slice, an
+    /// error is returned if the tensor is not contiguous, not on the CPU, or if
+    /// its kind is not `T::KIND`.
+    ///
+    /// # Safety
+    ///
+    /// The storage of this tensor is shared with the tensors obtained via
+    /// `shallow_clone`, views, etc. None of these tensors may be accessed
+    /// while the returned slice is alive.
+    pub unsafe fn f_as_mut_slice<T: kind::Element>(&mut self) -> Result<&mut [T], TchError> {
+        self.f_check_slice_access::<T>()?;
+        let numel = self.numel();
+        if numel == 0 {
+            return Ok(&mut []);
+        }
+        Ok(std::slice::from_raw_parts_mut(
+            self.data_ptr() as *mut T,
+            numel,
+        ))
+    }
+
+    /// Returns the elements of a contiguous CPU tensor as a mutable slice.
+    ///
+    /// # Safety
+    ///
+    /// See `f_as_mut_slice`.
+    pub unsafe fn as_mut_slice<T: kind::Element>(&mut self) -> &mut [T] {
+        self.f_as_mut_slice().unwrap()
+    }
+
     /// Converts some byte data to a tensor with some specified kind and shape.
     pub fn f_of_data_size(data: &[u8], size: &[i64], kind: Kind) -> Result<Tensor, TchError> {
         let data = data.as_ptr() as *const c_void;
@@ -636,11 +944,116 @@ impl Tensor {
         Ok(v)
     }
 
+    /// Saves a tensor to a stream.
+    ///
+    /// The file format is the same as the one used by the PyTorch C++ API.
+    pub fn save_to_stream<W: std::io::Write>(&self, mut stream: W) -> Result<(), TchError> {
+        let mut data = WriteStream {
+            stream: &mut stream,
+            error: None,
+        };
+        unsafe {
+            at_save_to_stream(
+                self.c_tensor,
+                &mut data as *mut _ as *mut c_void,
+                write_callback,
+            )
+        };
+        // The torch error is always cleared but the io error returned by the
+        // stream, if any, takes precedence as it is more informative.
+        let torch_result = super::utils::read_and_clean_error();
+        match data.error {
+            Some(err) => Err(err.into()),
+            None => torch_result,
+        }
+    }
+
+    /// Loads a tensor from a stream.
+    ///
+    /// The file format is the same as the one used by the PyTorch C++ API.
+    pub fn load_from_stream<R: std::io::Read>(mut stream: R) -> Result<Tensor, TchError> {
+        let mut buffer = Vec::new();
+        stream.read_to_end(&mut buffer)?;
+        let buffer_ptr = buffer.as_ptr() as *const c_char;
+        let c_tensor = unsafe_torch_err!(at_load_str(buffer_ptr, buffer.len()));
+        Ok(Tensor { c_tensor })
+    }
+
+    /// Saves some named tensors to a stream
+    ///
+    /// The file format is the same as the one used by the PyTorch C++ API.
+    pub fn save_multi_to_stream<S: AsRef<str>, T: AsRef<Tensor>, W: std::io::Write>(
+        named_tensors: &[(S, T)],
+        mut stream: W,
+    ) -> Result<(), TchError> {
+        let c_tensors = named_tensors
+            .iter()
+            .map(|nt| nt.1.as_ref().c_tensor)
+            .collect::<Vec<_>>();
+        let names = named_tensors
+            .iter()
+            .map(|nt| nt.0.as_ref().replace(".", "|").into_bytes())
+            .map(std::ffi::CString::new)
+            .collect::<Result<Vec<_>, _>>()?;
+        let name_ptrs = names.iter().map(|n| n.as_ptr()).collect::<Vec<_>>();
+        let mut data = WriteStream {
+            stream: &mut stream,
+            error: None,
+        };
+        unsafe {
+            at_save_multi_to_stream(
+                c_tensors.as_ptr(),
+                name_ptrs.as_ptr(),
+                names.len() as i32,
+                &mut data as *mut _ as *mut c_void,
+                write_callback,
+            )
+        };
+        let torch_result = super::utils::read_and_clean_error();
+        match data.error {
+            Some(err) => Err(err.into()),
+            None => torch_result,
+        }
+    }
+
+    /// Loads some named tensors from a stream to the CPU
+    ///
+    /// The file format is the same as the one used by the PyTorch C++ API.
+    pub fn load_multi_from_stream<R: std::io::Read>(
+        stream: R,
+    ) -> Result<Vec<(String, Tensor)>, TchError> {
+        Self::load_multi_from_stream_with_device(stream, Device::Cpu)
+    }
+
+    /// Loads some named tensors from a stream to a given device
+    ///
+    /// The file format is the same as the one used by the PyTorch C++ API.
+    pub fn load_multi_from_stream_with_device<R: std::io::Read>(
+        mut stream: R,
+        device: Device,
+    ) -> Result<Vec<(String, Tensor)>, TchError> {
+        let mut buffer = Vec::new();
+        stream.read_to_end(&mut buffer)?;
+        let buffer_ptr = buffer.as_ptr() as *const c_char;
+        let mut v: Vec<(String, Tensor)> = vec![];
+        unsafe_torch_err!(at_load_callback_str_with_device(
+            buffer_ptr,
+            buffer.len(),
+            &mut v as *mut _ as *mut c_void,
+            add_callback,
+            device.c_int(),
+        ));
+        Ok(v)
+    }
+
     /// Returns a string representation for the tensor.
     ///
     /// The representation will contain all the tensor element hence may be huge for
-    /// large tensors.
+    /// large tensors. Complex values are printed as `re+imi`.
     pub fn to_string(&self, lw: i64) -> Result<String, TchError> {
+        if let Kind::ComplexHalf | Kind::ComplexFloat | Kind::ComplexDouble = self.f_kind()? {
+            return self.complex_to_string();
+        }
         let s = unsafe_torch_err!(ptr_to_string(torch_sys::at_to_string(
             self.c_tensor,
             lw as c_int
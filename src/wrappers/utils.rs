@@ -80,6 +80,19 @@ pub fn set_num_threads(n_threads: i32) {
     unsafe_torch!(torch_sys::at_set_num_threads(n_threads))
 }
 
+/// Forces the use of deterministic algorithms.
+///
+/// When enabled, operations without a deterministic implementation return
+/// an error and deterministic implementations are selected when available.
+pub fn use_deterministic_algorithms(b: bool) {
+    unsafe_torch!(torch_sys::at_set_deterministic(if b { 1 } else { 0 }))
+}
+
+/// Returns true if the use of deterministic algorithms is enforced.
+pub fn are_deterministic_algorithms_enabled() -> bool {
+    unsafe_torch!(torch_sys::at_deterministic()) != 0
+}
+
 /// Quantization engines
 #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub enum QEngine {
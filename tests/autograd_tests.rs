@@ -0,0 +1,128 @@
+use tch::autograd::{CustomFunction, FunctionCtx};
+use tch::{Kind, TchError, Tensor};
+
+struct StraightThroughRound;
+
+impl CustomFunction for StraightThroughRound {
+    fn forward(&self, _: &mut FunctionCtx, xs: &[Tensor]) -> Result<Vec<Tensor>, TchError> {
+        Ok(vec![xs[0].f_round()?])
+    }
+
+    fn backward(&self, _: &mut FunctionCtx, grads: &[Tensor]) -> Result<Vec<Tensor>, TchError> {
+        Ok(vec![grads[0].shallow_clone()])
+    }
+}
+
+struct Square;
+
+impl CustomFunction for Square {
+    fn forward(&self, ctx: &mut FunctionCtx, xs: &[Tensor]) -> Result<Vec<Tensor>, TchError> {
+        ctx.f_save_for_backward(&[&xs[0]])?;
+        Ok(vec![&xs[0] * &xs[0]])
+    }
+
+    fn backward(&self, ctx: &mut FunctionCtx, grads: &[Tensor]) -> Result<Vec<Tensor>, TchError> {
+        let xs = ctx.f_saved_tensors()?;
+        Ok(vec![&grads[0] * &xs[0] * 2.0])
+    }
+}
+
+struct Failing;
+
+impl CustomFunction for Failing {
+    fn forward(&self, _: &mut FunctionCtx, xs: &[Tensor]) -> Result<Vec<Tensor>, TchError> {
+        Ok(vec![xs[0].shallow_clone()])
+    }
+
+    fn backward(&self, _: &mut FunctionCtx, _: &[Tensor]) -> Result<Vec<Tensor>, TchError> {
+        Err(TchError::Torch("custom backward failure".to_string()))
+    }
+}
+
+#[test]
+fn custom_function_backward() {
+    let xs = Tensor::of_slice(&[0.2f32, 1.7, -2.4]).set_requires_grad(true);
+    let ys = StraightThroughRound.apply(&[&xs]);
+    assert_eq!(ys.len(), 1);
+    assert_eq!(Vec::<f32>::from(&ys[0]), [0.0, 2.0, -2.0]);
+    (&ys[0] * 3.0).sum(Kind::Float).backward();
+    assert_eq!(Vec::<f32>::from(&xs.grad()), [3.0, 3.0, 3.0]);
+}
+
+#[test]
+fn custom_function_saved_tensors() {
+    let x = Tensor::from(3.0).set_requires_grad(true);
+    let y = &Square.apply(&[&x])[0] + &x;
+    assert_eq!(f64::from(&y), 12.0);
+    let dy_over_dx = Tensor::run_backward(&[y], &[&x], false, false);
+    assert_eq!(f64::from(&dy_over_dx[0]), 7.0);
+}
+
+#[test]
+fn custom_function_error() {
+    let x = Tensor::from(3.0).set_requires_grad(true);
+    let y = Failing.apply(&[&x]);
+    let err = Tensor::f_run_backward(&y, &[&x], false, false).unwrap_err();
+    assert!(err.to_string().contains("custom backward failure"));
+}
+
+#[test]
+fn retain_grad() {
+    let x = Tensor::of_slice(&[1.0f32, 2.0]).set_requires_grad(true);
+    let y = &x * 3.0;
+    y.retain_grad();
+    (&y * &y).sum(Kind::Float).backward();
+    assert_eq!(Vec::<f32>::from(&y.grad()), [6.0, 12.0]);
+    assert_eq!(Vec::<f32>::from(&x.grad()), [18.0, 36.0]);
+}
+
+#[test]
+fn register_hook() {
+    use std::sync::{Arc, Mutex};
+    let x = Tensor::of_slice(&[1.0f32, 2.0]).set_requires_grad(true);
+    let y = &x * 3.0;
+    let seen = Arc::new(Mutex::new(vec![]));
+    let seen_ = seen.clone();
+    let handle = y.register_hook(move |grad| {
+        seen_.lock().unwrap().push(Vec::<f32>::from(grad));
+        Some(grad * 2.0)
+    });
+    y.sum(Kind::Float).backward();
+    assert_eq!(*seen.lock().unwrap(), [[1.0, 1.0]]);
+    assert_eq!(Vec::<f32>::from(&x.grad()), [6.0, 6.0]);
+
+    handle.remove();
+    let mut x = x;
+    x.zero_grad();
+    let y = &x * 3.0;
+    let _handle = y.register_hook(|_| None);
+    y.sum(Kind::Float).backward();
+    assert_eq!(Vec::<f32>::from(&x.grad()), [3.0, 3.0]);
+    assert_eq!(seen.lock().unwrap().len(), 1);
+}
+
+#[test]
+fn remove_hook() {
+    let x = Tensor::of_slice(&[1.0f32, 2.0]).set_requires_grad(true);
+    let y = &x * 3.0;
+    let handle = y.register_hook(|grad| Some(grad * 0.0));
+    drop(handle);
+    y.sum(Kind::Float).backward();
+    assert_eq!(Vec::<f32>::from(&x.grad()), [3.0, 3.0]);
+}
+
+#[test]
+fn hook_handle_outlives_tensor() {
+    let x = Tensor::of_slice(&[1.0f32, 2.0]).set_requires_grad(true);
+    let data = std::sync::Arc::new(());
+    let data_ = data.clone();
+    let y = &x * 3.0;
+    let handle = y.register_hook(move |_| {
+        let _ = &data_;
+        None
+    });
+    // The handle does not keep the tensor alive, the hook gets released with it.
+    drop(y);
+    assert_eq!(std::sync::Arc::strong_count(&data), 1);
+    drop(handle);
+}
@@ -0,0 +1,187 @@
+use std::io::Write;
+use tch::nn::{self, ModuleT};
+use tch::vision::darknet::{self, Darknet};
+use tch::{Device, Tensor};
+
+const CONFIG: &str = "
+[net]
+width=32
+height=32
+channels=3
+
+[convolutional]
+batch_normalize=1
+filters=8
+size=3
+stride=1
+pad=1
+activation=leaky
+
+[maxpool]
+size=2
+stride=2
+
+[convolutional]
+filters=8
+size=3
+stride=1
+pad=1
+activation=mish
+
+[route]
+layers=-1
+groups=2
+group_id=1
+
+[convolutional]
+filters=8
+size=1
+stride=1
+pad=1
+activation=linear
+
+[shortcut]
+from=-3
+activation=linear
+
+[convolutional]
+filters=14
+size=1
+stride=1
+pad=1
+activation=linear
+
+[yolo]
+mask=0,1
+anchors=10,14, 23,27, 37,58
+classes=2
+num=3
+scale_x_y=1.05
+
+[route]
+layers=-3
+
+[upsample]
+stride=2
+
+[convolutional]
+filters=14
+size=1
+stride=1
+pad=1
+activation=linear
+
+[yolo]
+mask=1,2
+anchors=10,14, 23,27, 37,58
+classes=2
+num=3
+";
+
+#[test]
+fn darknet_model() {
+    let darknet: Darknet = CONFIG.parse().unwrap();
+    assert_eq!(darknet.height().unwrap(), 32);
+    assert_eq!(darknet.width().unwrap(), 32);
+    let vs = nn::VarStore::new(Device::Cpu);
+    let model = darknet.build_model(&vs.root()).unwrap();
+    let xs = Tensor::rand(&[2, 3, 32, 32], tch::kind::FLOAT_CPU);
+    let ys = model.forward_t(&xs, false);
+    assert_eq!(ys.size(), [2, 16 * 16 * 2 + 32 * 32 * 2, 7]);
+
+    let invalid: Darknet = "[net]\nwidth=32\nheight=32\n[dropout]\nprobability=.1"
+        .parse()
+        .unwrap();
+    assert!(invalid.build_model(&vs.root()).is_err());
+    assert!("[net]\nwidth".parse::<Darknet>().is_err());
+}
+
+#[test]
+fn darknet_grouped_route() {
+    // Each input layer of a grouped route is sliced before concatenating.
+    let darknet: Darknet = "
+[net]
+width=2
+height=2
+channels=4
+
+[maxpool]
+size=1
+stride=1
+
+[upsample]
+stride=1
+
+[route]
+layers=-1,-2
+groups=2
+group_id=1
+"
+    .parse()
+    .unwrap();
+    let vs = nn::VarStore::new(Device::Cpu);
+    let model = darknet.build_model(&vs.root()).unwrap();
+    let xs = Tensor::arange(4, tch::kind::FLOAT_CPU)
+        .view([1, 4, 1, 1])
+        .expand(&[1, 4, 2, 2], false);
+    let ys = model.forward_t(&xs, false);
+    assert_eq!(ys.size(), [1, 4, 2, 2]);
+    let channels: Vec<f32> = (0..4)
+        .map(|c| f32::from(ys.get(0).get(c).get(0).get(0)))
+        .collect();
+    assert_eq!(channels, [2., 3., 2., 3.]);
+}
+
+#[test]
+fn darknet_weights() {
+    let darknet: Darknet = CONFIG.parse().unwrap();
+    let vs = nn::VarStore::new(Device::Cpu);
+    let _model = darknet.build_model(&vs.root()).unwrap();
+    let nvalues = 32 + 216 + 8 + 576 + 8 + 32 + 14 + 112 + 14 + 112;
+    let filename = std::env::temp_dir().join(format!("tch-darknet-{}.weights", std::process::id()));
+    let mut file = std::fs::File::create(&filename).unwrap();
+    for v in [0i32, 2, 0].iter() {
+        file.write_all(&v.to_le_bytes()).unwrap();
+    }
+    file.write_all(&0u64.to_le_bytes()).unwrap();
+    for v in 0..nvalues {
+        file.write_all(&(v as f32).to_le_bytes()).unwrap();
+    }
+    drop(file);
+    darknet.load_weights(&vs.root(), &filename).unwrap();
+    let variables = vs.variables();
+    let bn_bias = &variables["0.batch_norm_0.bias"];
+    assert_eq!(Vec::<f32>::from(bn_bias), [0., 1., 2., 3., 4., 5., 6., 7.]);
+    let running_var = &variables["0.batch_norm_0.running_var"];
+    assert_eq!(f64::from(running_var.get(0)), 24.);
+    let weight = &variables["0.conv_0.weight"];
+    assert_eq!(weight.size(), [8, 3, 3, 3]);
+    assert_eq!(f64::from(weight.get(0).get(0).get(0).get(1)), 33.);
+    let bias = &variables["10.conv_10.bias"];
+    assert_eq!(f64::from(bias.get(13)), (nvalues - 112 - 1) as f64);
+
+    // A truncated file results in an error.
+    let data = std::fs::read(&filename).unwrap();
+    std::fs::write(&filename, &data[..data.len() - 4]).unwrap();
+    assert!(darknet.load_weights(&vs.root(), &filename).is_err());
+    std::fs::remove_file(&filename).unwrap();
+}
+
+#[test]
+fn darknet_detections() {
+    let predictions = Tensor::of_slice(&[
+        10f32, 10., 4., 4., 0.9, 0.2, 0.8, //
+        10.5, 10., 4., 4., 0.8, 0.1, 0.9, //
+        10., 10., 4., 4., 0.7, 0.9, 0.1, //
+        30., 30., 2., 2., 0.3, 0.5, 0.5,
+    ])
+    .view([4, 7]);
+    let detections = darknet::detections(&predictions, 0.5, 0.4).unwrap();
+    assert_eq!(detections.len(), 2);
+    let d = detections[0];
+    assert_eq!((d.xmin, d.ymin, d.xmax, d.ymax), (8., 8., 12., 12.));
+    assert_eq!(d.class_index, 1);
+    assert!((d.confidence - 0.9).abs() < 1e-6);
+    assert!((d.class_confidence - 0.8).abs() < 1e-6);
+    assert_eq!(detections[1].class_index, 0);
+}
@@ -0,0 +1,251 @@
+use tch::vision::detection;
+#[cfg(feature = "datasets")]
+use tch::vision::{coco, voc};
+use tch::{Kind, Tensor};
+
+#[cfg(feature = "datasets")]
+fn save_image(path: &std::path::Path, height: i64, width: i64) {
+    let image = Tensor::ones(&[3, height, width], (Kind::Uint8, tch::Device::Cpu)) * 128;
+    tch::vision::image::save(&image, path).unwrap();
+}
+
+fn target(boxes: &[f32], labels: &[i64], iscrowd: &[bool]) -> detection::Target {
+    detection::Target {
+        image_id: 0,
+        boxes: Tensor::of_slice(boxes).view([labels.len() as i64, 4]),
+        labels: Tensor::of_slice(labels),
+        iscrowd: Tensor::of_slice(iscrowd),
+        difficult: Tensor::zeros(&[labels.len() as i64], (Kind::Bool, tch::Device::Cpu)),
+        masks: None,
+    }
+}
+
+#[cfg(feature = "datasets")]
+#[test]
+fn coco_dataset() {
+    let dir = std::env::temp_dir().join(format!("tch-coco-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    save_image(&dir.join("a.png"), 4, 4);
+    save_image(&dir.join("b.png"), 3, 5);
+    let json = serde_json::json!({
+        "images": [
+            {"id": 7, "file_name": "b.png", "height": 3, "width": 5},
+            {"id": 3, "file_name": "a.png", "height": 4, "width": 4},
+        ],
+        "categories": [
+            {"id": 1, "name": "cat", "supercategory": "animal"},
+            {"id": 5, "name": "dog", "supercategory": "animal"},
+        ],
+        "annotations": [
+            {"id": 1, "image_id": 3, "category_id": 1, "bbox": [1, 1, 2, 2], "iscrowd": 0,
+             "segmentation": [[1, 1, 3, 1, 3, 3, 1, 3]]},
+            {"id": 2, "image_id": 3, "category_id": 5, "bbox": [1, 1, 2, 3], "iscrowd": 0,
+             "segmentation": {"size": [4, 4], "counts": "531O4"}},
+            {"id": 3, "image_id": 3, "category_id": 5, "bbox": [-2, 0, 10, 10], "iscrowd": 1,
+             "segmentation": {"size": [4, 4], "counts": [5, 3, 1, 2, 5]}},
+            {"id": 4, "image_id": 3, "category_id": 1, "bbox": [2, 2, 0, 3], "iscrowd": 0,
+             "segmentation": []},
+        ],
+    });
+    let annotation_file = dir.join("instances.json");
+    std::fs::write(&annotation_file, json.to_string()).unwrap();
+    let dataset = coco::CocoDataset::new(&dir, &annotation_file)
+        .unwrap()
+        .with_masks();
+    assert_eq!(dataset.len(), 2);
+    assert_eq!(dataset.categories()[1].name, "dog");
+
+    let (image, target) = dataset.f_get(0).unwrap();
+    assert_eq!(image.size(), [3, 4, 4]);
+    assert_eq!(target.image_id, 3);
+    assert_eq!(
+        Vec::<f32>::from(&target.boxes.view([-1])),
+        [1., 1., 3., 3., 1., 1., 3., 4., 0., 0., 4., 4.]
+    );
+    assert_eq!(Vec::<i64>::from(&target.labels), [1, 5, 5]);
+    assert_eq!(Vec::<bool>::from(&target.iscrowd), [false, false, true]);
+    let masks = target.masks.unwrap();
+    assert_eq!(masks.size(), [3, 4, 4]);
+    let polygon: Vec<u8> = vec![0, 0, 0, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 0, 0, 0];
+    let rle: Vec<u8> = vec![0, 0, 0, 0, 0, 1, 1, 0, 0, 1, 1, 0, 0, 1, 0, 0];
+    assert_eq!(Vec::<u8>::from(&masks.get(0).view([-1])), polygon);
+    assert_eq!(Vec::<u8>::from(&masks.get(1).view([-1])), rle);
+    assert_eq!(Vec::<u8>::from(&masks.get(2).view([-1])), rle);
+
+    let (image, target) = dataset.f_get(1).unwrap();
+    assert_eq!(image.size(), [3, 3, 5]);
+    assert_eq!(target.boxes.size(), [0, 4]);
+    assert_eq!(target.masks.unwrap().size(), [0, 3, 5]);
+    assert!(dataset.f_get(2).is_err());
+
+    // Run lengths encoded on too many characters are rejected.
+    let mut json = json;
+    json["annotations"][1]["segmentation"]["counts"] = "o".repeat(13).into();
+    std::fs::write(&annotation_file, json.to_string()).unwrap();
+    assert!(coco::CocoDataset::new(&dir, &annotation_file).is_err());
+
+    // Uncompressed counts that overflow when summed are rejected.
+    json["annotations"][1]["segmentation"]["counts"] = serde_json::json!([2, u64::MAX]);
+    std::fs::write(&annotation_file, json.to_string()).unwrap();
+    let dataset = coco::CocoDataset::new(&dir, &annotation_file)
+        .unwrap()
+        .with_masks();
+    assert!(dataset.f_get(0).is_err());
+
+    // Negative image sizes are rejected.
+    json["images"][1]["height"] = (-4).into();
+    std::fs::write(&annotation_file, json.to_string()).unwrap();
+    assert!(coco::CocoDataset::new(&dir, &annotation_file).is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[cfg(feature = "datasets")]
+#[test]
+fn voc_dataset() {
+    let dir = std::env::temp_dir().join(format!("tch-voc-{}", std::process::id()));
+    for subdir in ["Annotations", "ImageSets/Main", "JPEGImages"].iter() {
+        std::fs::create_dir_all(dir.join(subdir)).unwrap();
+    }
+    save_image(&dir.join("JPEGImages/000001.png"), 6, 8);
+    std::fs::write(dir.join("ImageSets/Main/train.txt"), "000001\n").unwrap();
+    let xml = r#"<?xml version="1.0" encoding="utf-8"?>
+<!-- A comment with <tags> -->
+<annotation>
+    <folder>VOC&amp;test</folder>
+    <filename>000001.png</filename>
+    <size><width>8</width><height>6</height><depth>3</depth></size>
+    <object>
+        <name>dog</name>
+        <pose>Left</pose>
+        <truncated>1</truncated>
+        <difficult>0</difficult>
+        <bndbox><xmin>2</xmin><ymin>1</ymin><xmax>5</xmax><ymax>6</ymax></bndbox>
+    </object>
+    <object>
+        <name>person</name>
+        <difficult>1</difficult>
+        <part/>
+        <bndbox><xmin>1</xmin><ymin>1</ymin><xmax>8</xmax><ymax>3</ymax></bndbox>
+    </object>
+</annotation>
+"#;
+    std::fs::write(dir.join("Annotations/000001.xml"), xml).unwrap();
+    let dataset = voc::VocDataset::new(&dir, "train").unwrap();
+    assert_eq!(dataset.len(), 1);
+    assert_eq!(dataset.ids(), ["000001"]);
+    let (image, target) = dataset.f_get(0).unwrap();
+    assert_eq!(image.size(), [3, 6, 8]);
+    assert_eq!(
+        Vec::<f32>::from(&target.boxes.view([-1])),
+        [1., 0., 5., 6., 0., 0., 8., 3.]
+    );
+    assert_eq!(Vec::<i64>::from(&target.labels), [11, 14]);
+    assert_eq!(Vec::<bool>::from(&target.iscrowd), [false, false]);
+    assert_eq!(Vec::<bool>::from(&target.difficult), [false, true]);
+
+    std::fs::write(dir.join("Annotations/000001.xml"), "<annotation><object>").unwrap();
+    assert!(voc::VocDataset::new(&dir, "train").is_err());
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn collate() {
+    let samples = vec![
+        (
+            Tensor::ones(&[3, 2, 4], tch::kind::FLOAT_CPU),
+            target(&[0., 0., 1., 1.], &[3], &[false]),
+        ),
+        (
+            Tensor::ones(&[3, 5, 3], tch::kind::FLOAT_CPU),
+            target(&[0., 0., 2., 2., 1., 1., 3., 3.], &[1, 2], &[false, true]),
+        ),
+    ];
+    let batch = detection::collate(&samples).unwrap();
+    assert_eq!(batch.images.size(), [2, 3, 5, 4]);
+    assert_eq!(f64::from(batch.images.sum(Kind::Float)), 3. * (8. + 15.));
+    assert_eq!(
+        Vec::<i64>::from(&batch.image_sizes.view([-1])),
+        [2, 4, 5, 3]
+    );
+    assert_eq!(batch.boxes.size(), [2, 2, 4]);
+    assert_eq!(Vec::<i64>::from(&batch.labels.view([-1])), [3, -1, 1, 2]);
+    assert_eq!(
+        Vec::<bool>::from(&batch.valid.view([-1])),
+        [true, false, true, true]
+    );
+    assert_eq!(
+        Vec::<bool>::from(&batch.iscrowd.view([-1])),
+        [false, false, false, true]
+    );
+    assert_eq!(
+        Vec::<bool>::from(&batch.difficult.view([-1])),
+        [false, false, false, false]
+    );
+    assert!(batch.masks.is_none());
+    assert!(detection::collate(&[]).is_err());
+}
+
+#[test]
+fn mean_average_precision() {
+    let gt = target(
+        &[0., 0., 10., 10., 20., 20., 30., 30.],
+        &[0, 1],
+        &[false, false],
+    );
+    let mut map = detection::MeanAveragePrecision::new();
+    map.update(
+        &gt.boxes,
+        &Tensor::of_slice(&[0.9f32, 0.8]),
+        &gt.labels,
+        &gt,
+    );
+    let summary = map.summarize();
+    assert_eq!(summary.map, 1.0);
+    assert_eq!(summary.per_iou_threshold.len(), 10);
+    assert_eq!(summary.per_class, [(0, 1.0), (1, 1.0)]);
+
+    // A false positive ranked between two true positives, detections matching
+    // crowd regions are ignored.
+    let gt = target(
+        &[0., 0., 10., 10., 20., 20., 30., 30., 40., 40., 60., 60.],
+        &[0, 0, 0],
+        &[false, false, true],
+    );
+    let boxes = Tensor::of_slice(&[
+        0f32, 0., 10., 10., 50., 0., 60., 10., 20., 20., 30., 30., 45., 45., 50., 50.,
+    ])
+    .view([4, 4]);
+    let scores = Tensor::of_slice(&[0.9f32, 0.8, 0.7, 0.95]);
+    let labels = Tensor::of_slice(&[0i64, 0, 0, 0]);
+    let mut map = detection::MeanAveragePrecision::new().iou_thresholds(&[0.5]);
+    map.update(&boxes, &scores, &labels, &gt);
+    let expected = (51. + 50. * 2. / 3.) / 101.;
+    assert!((map.summarize().map - expected).abs() < 1e-6);
+    map.reset();
+    assert!(map.summarize().map.is_nan());
+
+    // Difficult objects are ignored but only matched using the usual
+    // intersection over union, a detection inside a difficult object is a
+    // false positive whereas it is ignored for a crowd region.
+    let mut gt = target(
+        &[0., 0., 10., 10., 20., 20., 60., 60.],
+        &[0, 0],
+        &[false, false],
+    );
+    gt.difficult = Tensor::of_slice(&[false, true]);
+    let boxes = Tensor::of_slice(&[0f32, 0., 10., 10., 20., 20., 60., 60., 30., 30., 35., 35.])
+        .view([3, 4]);
+    let scores = Tensor::of_slice(&[0.8f32, 0.9, 0.85]);
+    let labels = Tensor::of_slice(&[0i64, 0, 0]);
+    let mut map = detection::MeanAveragePrecision::new().iou_thresholds(&[0.5]);
+    map.update(&boxes, &scores, &labels, &gt);
+    assert!((map.summarize().map - 0.5).abs() < 1e-6);
+    gt.iscrowd = gt.difficult;
+    gt.difficult = Tensor::of_slice(&[false, false]);
+    let mut map = detection::MeanAveragePrecision::new().iou_thresholds(&[0.5]);
+    map.update(&boxes, &scores, &labels, &gt);
+    assert!((map.summarize().map - 1.0).abs() < 1e-6);
+
+    let nan_scores = Tensor::of_slice(&[0.8f32, f32::NAN, 0.7]);
+    assert!(map.f_update(&boxes, &nan_scores, &labels, &gt).is_err());
+}
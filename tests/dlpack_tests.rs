@@ -0,0 +1,31 @@
+use tch::dlpack::{ManagedTensor, DL_CPU, DL_FLOAT};
+use tch::{Device, Kind, Tensor};
+
+#[test]
+fn to_dlpack() {
+    let t = Tensor::of_slice(&[1f32, 2., 3., 4., 5., 6.]).view((2, 3));
+    let managed = t.tr().to_dlpack();
+    assert_eq!(managed.shape(), [3, 2]);
+    assert_eq!(managed.strides(), [1, 3]);
+    assert_eq!(managed.device().unwrap(), Device::Cpu);
+    let dl_tensor = managed.dl_tensor();
+    assert_eq!(dl_tensor.ctx.device_type, DL_CPU);
+    assert_eq!(dl_tensor.dtype.code, DL_FLOAT);
+    assert_eq!(dl_tensor.dtype.bits, 32);
+    assert_eq!(dl_tensor.data, t.data_ptr());
+}
+
+#[test]
+fn dlpack_round_trip() {
+    let mut t = Tensor::of_slice(&[1i64, 2, 3, 4]);
+    let raw = t.to_dlpack().into_raw();
+    // Simulate another library handing the tensor back.
+    let managed = unsafe { ManagedTensor::from_raw(raw).unwrap() };
+    let t2 = Tensor::from_dlpack(managed);
+    assert_eq!(t2.kind(), Kind::Int64);
+    assert_eq!(t2.data_ptr(), t.data_ptr());
+    let _ = t.fill_(7);
+    assert_eq!(Vec::<i64>::from(&t2), [7, 7, 7, 7]);
+    drop(t);
+    assert_eq!(Vec::<i64>::from(&t2), [7, 7, 7, 7]);
+}
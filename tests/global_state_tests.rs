@@ -0,0 +1,43 @@
+// These tests change process-wide libtorch settings, they live in their own
+// test binary and are serialized so that they do not interfere with other
+// tests running in parallel.
+use std::sync::Mutex;
+use tch::Tensor;
+
+lazy_static::lazy_static! {
+    static ref GLOBAL_STATE: Mutex<()> = Mutex::new(());
+}
+
+fn lock() -> std::sync::MutexGuard<'static, ()> {
+    GLOBAL_STATE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+#[test]
+fn detect_anomaly() {
+    let _lock = lock();
+    let x = Tensor::from(0.0).set_requires_grad(true);
+    let y = &x / &x;
+    assert!(y.f_backward().is_ok());
+
+    {
+        let _guard = tch::autograd::detect_anomaly();
+        let y = &x / &x;
+        let err = y.f_backward().unwrap_err().to_string();
+        assert!(err.contains("DivBackward0"), "{}", err);
+        assert!(err.contains("Traceback of forward call"), "{}", err);
+    }
+    // The guard restores the previous mode.
+    let y = &x / &x;
+    assert!(y.f_backward().is_ok());
+}
+
+#[test]
+fn deterministic_algorithms() {
+    let _lock = lock();
+    let enabled = tch::are_deterministic_algorithms_enabled();
+    tch::use_deterministic_algorithms(true);
+    assert!(tch::are_deterministic_algorithms_enabled());
+    tch::use_deterministic_algorithms(false);
+    assert!(!tch::are_deterministic_algorithms_enabled());
+    tch::use_deterministic_algorithms(enabled);
+}
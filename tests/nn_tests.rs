@@ -360,3 +360,23 @@ fn linear() {
         ..Default::default()
     });
 }
+
+#[test]
+fn intermediate_layer_getter() {
+    let seq = nn::seq_t()
+        .add_fn(|xs| xs + 1)
+        .add_fn(|xs| xs * 2)
+        .add_fn(|xs| xs - 3)
+        .add_fn(|xs| xs * 10);
+    let getter = nn::IntermediateLayerGetter::new(seq, &[(2, "c"), (0, "a")]);
+    assert_eq!(getter.names(), ["a", "c"]);
+    let xs = Tensor::of_slice(&[1f32, 2.]);
+    let features = getter.forward_features(&xs, false);
+    assert_eq!(features.len(), 2);
+    assert_eq!(features[0].0, "a");
+    assert_eq!(Vec::<f32>::from(&features[0].1), [2., 3.]);
+    assert_eq!(features[1].0, "c");
+    assert_eq!(Vec::<f32>::from(&features[1].1), [1., 3.]);
+    // The layers after the last returned one are not evaluated.
+    assert_eq!(Vec::<f32>::from(&xs.apply_t(&getter, false)), [1., 3.]);
+}
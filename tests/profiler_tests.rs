@@ -0,0 +1,67 @@
+use tch::profiler::{self, GroupBy};
+use tch::{kind, nn, Device, Tensor};
+
+#[test]
+fn profile_ops() {
+    let vs = nn::VarStore::new(Device::Cpu);
+    let linear = nn::linear(&vs.root() / "fc", 4, 3, Default::default());
+    let xs = Tensor::randn(&[8, 4], kind::FLOAT_CPU);
+
+    let guard = profiler::profile();
+    let ys = {
+        let _scope = profiler::record_path(&(&vs.root() / "fc"));
+        nn::Module::forward(&linear, &xs)
+    };
+    let _zs = ys.relu();
+    let profile = guard.finish();
+
+    let records = profile.records();
+    let relu = records.iter().find(|r| r.name == "aten::relu").unwrap();
+    assert_eq!(relu.input_shapes, [[8, 3]]);
+    assert_eq!(relu.scope, None);
+    assert!(relu.cpu_time_us >= relu.self_cpu_time_us);
+    let scope = records.iter().find(|r| r.name == "fc").unwrap();
+    assert!(scope.is_scope);
+    let in_scope: Vec<_> = records
+        .iter()
+        .filter(|r| r.scope.as_deref() == Some("fc") && !r.is_scope)
+        .collect();
+    assert!(!in_scope.is_empty());
+    assert!(in_scope.iter().all(|r| r.start_us >= scope.start_us));
+
+    let by_op = profile.aggregate(GroupBy::Op);
+    let relu = by_op.iter().find(|g| g.key == "aten::relu").unwrap();
+    assert_eq!(relu.count, 1);
+    assert!(by_op.iter().all(|g| g.key != "fc"));
+    let by_scope = profile.aggregate(GroupBy::Scope);
+    let fc = by_scope.iter().find(|g| g.key == "fc").unwrap();
+    assert_eq!(fc.count, 1);
+    assert!(fc.cpu_time_total_us >= fc.self_cpu_time_total_us);
+    assert!(profile.table(GroupBy::Op).contains("aten::relu"));
+
+    let trace = profile.to_chrome_trace();
+    assert!(trace.starts_with("{\"traceEvents\":["));
+    assert!(trace.contains("\"name\":\"aten::relu\""));
+    assert!(trace.contains("\"Input dims\":[[8,3]]"));
+}
+
+#[test]
+fn scope_named_like_an_op() {
+    let xs = Tensor::randn(&[4], kind::FLOAT_CPU);
+    let guard = profiler::profile();
+    {
+        let _scope = profiler::record_function("aten::neg");
+        let _ys = xs.sin();
+    }
+    let _zs = xs.neg();
+    let profile = guard.finish();
+    let negs: Vec<_> = profile
+        .records()
+        .iter()
+        .filter(|r| r.name == "aten::neg")
+        .collect();
+    assert_eq!(negs.len(), 2);
+    assert!(negs[0].is_scope);
+    assert!(!negs[1].is_scope);
+    assert_eq!(negs[1].scope, None);
+}
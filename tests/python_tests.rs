@@ -0,0 +1,90 @@
+#![cfg(feature = "python-extension")]
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use tch::{nn, nn::VarStore, python, Device, IValue, Kind, Tensor};
+
+// The torch python module registers the tensor type used by the conversions.
+fn with_torch<F: FnOnce(Python) -> PyResult<()>>(f: F) {
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        py.import("torch")?;
+        f(py)
+    })
+    .unwrap()
+}
+
+#[test]
+fn tensor_round_trip() {
+    with_torch(|py| {
+        let t = Tensor::of_slice(&[1f32, 2., 3.]);
+        let obj = t.to_pyobject(py);
+        assert_eq!(obj.as_ref(py).get_type().name()?, "Tensor");
+        let mut t2: Tensor = obj.extract(py)?;
+        assert_eq!(t2, t);
+        // Both tensors share their storage.
+        let _ = t2.fill_(42.);
+        assert_eq!(Vec::<f32>::from(&t), [42., 42., 42.]);
+        assert!(py.None().extract::<Tensor>(py).is_err());
+        Ok(())
+    })
+}
+
+#[test]
+fn var_store_round_trip() {
+    with_torch(|py| {
+        let vs = VarStore::new(Device::Cpu);
+        let _linear = nn::linear(&vs.root() / "lin", 2, 3, Default::default());
+        let dict = python::var_store_to_dict(py, &vs)?;
+        assert_eq!(dict.len(), 2);
+
+        let mut vs2 = VarStore::new(Device::Cpu);
+        let _linear = nn::linear(&vs2.root() / "lin", 2, 3, Default::default());
+        python::load_var_store_from_dict(&mut vs2, dict)?;
+        let variables = vs.variables();
+        for (name, var) in vs2.variables().iter() {
+            assert_eq!(var, &variables[name]);
+        }
+        assert!(python::load_var_store_from_dict(&mut vs2, PyDict::new(py)).is_err());
+        Ok(())
+    })
+}
+
+#[test]
+fn ivalue_round_trip() {
+    with_torch(|py| {
+        let ivalue = IValue::Tuple(vec![
+            IValue::None,
+            IValue::Int(42),
+            IValue::Double(1.5),
+            IValue::Bool(true),
+            IValue::String("foo".to_string()),
+            IValue::IntList(vec![1, 2]),
+            IValue::GenericDict(vec![(
+                IValue::String("bar".to_string()),
+                IValue::Tensor(Tensor::ones(&[2], (Kind::Float, Device::Cpu))),
+            )]),
+        ]);
+        let obj = ivalue.f_into_pyobject(py)?;
+        let ivalue2: IValue = obj.extract(py)?;
+        assert_eq!(
+            ivalue2,
+            IValue::Tuple(vec![
+                IValue::None,
+                IValue::Int(42),
+                IValue::Double(1.5),
+                IValue::Bool(true),
+                IValue::String("foo".to_string()),
+                IValue::IntList(vec![1, 2]),
+                IValue::GenericDict(vec![(
+                    IValue::String("bar".to_string()),
+                    IValue::Tensor(Tensor::ones(&[2], (Kind::Float, Device::Cpu))),
+                )]),
+            ])
+        );
+
+        // List keys cannot be hashed by python.
+        let ivalue = IValue::GenericDict(vec![(IValue::IntList(vec![1]), IValue::None)]);
+        assert!(ivalue.f_into_pyobject(py).is_err());
+        Ok(())
+    })
+}
@@ -0,0 +1,63 @@
+#![cfg(feature = "serde")]
+use num_complex::Complex32;
+use tch::{Device, IValue, Kind, Tensor};
+
+#[test]
+fn json() {
+    let t = Tensor::of_slice(&[1i64, 2, 3, 4, 5, 6]).view((2, 3));
+    let json = serde_json::to_string(&t).unwrap();
+    assert_eq!(
+        json,
+        r#"{"kind":"Int64","shape":[2,3],"data":[[1,2,3],[4,5,6]]}"#
+    );
+    let t2: Tensor = serde_json::from_str(&json).unwrap();
+    assert_eq!(t2, t);
+
+    let t = Tensor::of_slice(&[0.5f32, -1.0]).to_kind(Kind::Half);
+    let t2: Tensor = serde_json::from_str(&serde_json::to_string(&t).unwrap()).unwrap();
+    assert_eq!(t2.kind(), Kind::Half);
+    assert_eq!(Vec::<f32>::from(&t2), [0.5, -1.0]);
+
+    let t = Tensor::of_slice(&[Complex32::new(1.0, 2.0)]);
+    let json = serde_json::to_string(&t).unwrap();
+    assert_eq!(
+        json,
+        r#"{"kind":"ComplexFloat","shape":[1],"data":[[1.0,2.0]]}"#
+    );
+    let t2: Tensor = serde_json::from_str(&json).unwrap();
+    assert_eq!(Vec::<Complex32>::from(&t2), [Complex32::new(1.0, 2.0)]);
+
+    let t: Tensor = serde_json::from_str(r#"{"kind":"Bool","shape":[],"data":true}"#).unwrap();
+    assert_eq!(t.size(), Vec::<i64>::new());
+    assert!(bool::from(&t));
+    let err = serde_json::from_str::<Tensor>(r#"{"kind":"Float","shape":[3],"data":[1]}"#);
+    assert!(err.is_err());
+}
+
+#[test]
+fn bincode() {
+    let t = Tensor::of_slice(&[1.0f64, 2.5, -3.0]).view((3, 1)).tr();
+    let bytes = bincode::serialize(&t).unwrap();
+    let t2: Tensor = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(t2.size(), [1, 3]);
+    assert_eq!(t2.kind(), Kind::Double);
+    assert_eq!(t2, t);
+}
+
+#[test]
+fn kind_device_ivalue() {
+    let json = serde_json::to_string(&(Kind::BFloat16, Device::Cuda(1))).unwrap();
+    assert_eq!(json, r#"["BFloat16",{"Cuda":1}]"#);
+    let (kind, device): (Kind, Device) = serde_json::from_str(&json).unwrap();
+    assert_eq!(kind, Kind::BFloat16);
+    assert_eq!(device, Device::Cuda(1));
+
+    let ivalue = IValue::Tuple(vec![
+        IValue::Int(42),
+        IValue::String("foo".to_string()),
+        IValue::Tensor(Tensor::of_slice(&[1u8, 2])),
+    ]);
+    let bytes = bincode::serialize(&ivalue).unwrap();
+    let ivalue2: IValue = bincode::deserialize(&bytes).unwrap();
+    assert_eq!(ivalue, ivalue2);
+}
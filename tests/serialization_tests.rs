@@ -23,6 +23,54 @@ fn save_and_load_multi() {
     assert_eq!(i64::from(&named_tensors[1].1.sum(tch::Kind::Float)), 57);
 }
 
+#[test]
+fn save_and_load_stream() {
+    let vec = [3.0, 1.0, 4.0, 1.0, 5.0].to_vec();
+    let t1 = Tensor::of_slice(&vec);
+    let mut buffer = Vec::new();
+    t1.save_to_stream(&mut buffer).unwrap();
+    let t2 = Tensor::load_from_stream(std::io::Cursor::new(buffer)).unwrap();
+    assert_eq!(Vec::<f64>::from(&t2), vec)
+}
+
+#[test]
+fn save_and_load_multi_stream() {
+    let pi = Tensor::of_slice(&[3.0, 1.0, 4.0, 1.0, 5.0]);
+    let e = Tensor::of_slice(&[2, 7, 1, 8, 2, 8, 1, 8, 2, 8, 4, 6]);
+    let mut buffer = Vec::new();
+    Tensor::save_multi_to_stream(&[(&"pi", &pi), (&"e.x", &e)], &mut buffer).unwrap();
+    let named_tensors = Tensor::load_multi_from_stream(buffer.as_slice()).unwrap();
+    assert_eq!(named_tensors.len(), 2);
+    assert_eq!(named_tensors[0].0, "pi");
+    assert_eq!(named_tensors[1].0, "e.x");
+    assert_eq!(i64::from(&named_tensors[1].1.sum(tch::Kind::Float)), 57);
+}
+
+#[test]
+fn load_stream_error() {
+    assert!(Tensor::load_from_stream(&b"not a tensor"[..]).is_err());
+}
+
+#[test]
+fn save_stream_error() {
+    struct FullWriter;
+    impl std::io::Write for FullWriter {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::new(std::io::ErrorKind::Other, "disk full"))
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let t = Tensor::of_slice(&[3.0, 1.0, 4.0]);
+    let err = t.save_to_stream(FullWriter).unwrap_err();
+    assert!(matches!(err, tch::TchError::Io(_)), "{}", err);
+    let err = Tensor::save_multi_to_stream(&[(&"t", &t)], FullWriter).unwrap_err();
+    assert!(matches!(err, tch::TchError::Io(_)), "{}", err);
+    // The torch error has been cleared.
+    assert!(t.save_to_stream(Vec::new()).is_ok());
+}
+
 #[test]
 fn save_and_load_npz() {
     let filename = std::env::temp_dir().join(format!("tch3-{}.npz", std::process::id()));
@@ -78,3 +126,148 @@ fn save_and_load_npy() {
         [3.0, 1.0, 4.0, 1.0, 5.0, 9.0]
     );
 }
+
+#[test]
+fn save_and_load_npy_complex() {
+    use num_complex::Complex64;
+    let filename = std::env::temp_dir().join(format!("tch7-{}.npy", std::process::id()));
+    let vs = [Complex64::new(1.0, -2.0), Complex64::new(0.5, 3.0)];
+    let t = Tensor::of_slice(&vs);
+    t.write_npy(&filename).unwrap();
+    let t = Tensor::read_npy(&filename).unwrap();
+    assert_eq!(t.kind(), Kind::ComplexDouble);
+    assert_eq!(Vec::<Complex64>::from(&t), vs);
+}
+
+// Builds the content of a npy file with the given header and data.
+fn npy_bytes(header: &str, data: &[u8]) -> Vec<u8> {
+    let mut header = header.to_string();
+    while (header.len() + 11) % 16 != 0 {
+        header.push(' ')
+    }
+    header.push('\n');
+    let mut bytes = b"\x93NUMPY\x01\x00".to_vec();
+    bytes.extend_from_slice(&(header.len() as u16).to_le_bytes());
+    bytes.extend_from_slice(header.as_bytes());
+    bytes.extend_from_slice(data);
+    bytes
+}
+
+#[test]
+fn load_npy_fortran_order() {
+    let data = [1i32, 4, 2, 5, 3, 6]
+        .iter()
+        .flat_map(|v| v.to_le_bytes().to_vec())
+        .collect::<Vec<_>>();
+    let bytes = npy_bytes(
+        "{'descr': '<i4', 'fortran_order': True, 'shape': (2, 3), }",
+        &data,
+    );
+    let t = Tensor::read_npy_from_stream(bytes.as_slice()).unwrap();
+    assert_eq!(t.size(), [2, 3]);
+    assert_eq!(Vec::<i32>::from(t.flatten(0, -1)), [1, 2, 3, 4, 5, 6]);
+}
+
+#[test]
+fn load_npy_big_endian_and_unsigned() {
+    let data = [1.5f64, -2.0]
+        .iter()
+        .flat_map(|v| v.to_be_bytes().to_vec())
+        .collect::<Vec<_>>();
+    let bytes = npy_bytes(
+        "{'descr': '>f8', 'fortran_order': False, 'shape': (2,), }",
+        &data,
+    );
+    let t = Tensor::read_npy_from_stream(bytes.as_slice()).unwrap();
+    assert_eq!(Vec::<f64>::from(&t), [1.5, -2.0]);
+
+    let data = [1u16, 65535]
+        .iter()
+        .flat_map(|v| v.to_be_bytes().to_vec())
+        .collect::<Vec<_>>();
+    let bytes = npy_bytes(
+        "{'descr': '>u2', 'fortran_order': False, 'shape': (2,), }",
+        &data,
+    );
+    let t = Tensor::read_npy_from_stream(bytes.as_slice()).unwrap();
+    assert_eq!(t.kind(), Kind::Int);
+    assert_eq!(Vec::<i32>::from(&t), [1, 65535]);
+
+    let bytes = npy_bytes(
+        "{'descr': '<u8', 'fortran_order': False, 'shape': (1,), }",
+        &u64::MAX.to_le_bytes(),
+    );
+    assert!(Tensor::read_npy_from_stream(bytes.as_slice()).is_err());
+
+    let bytes = npy_bytes(
+        "{'descr': '<f8', 'fortran_order': False, 'shape': (3,), }",
+        &[0u8; 16],
+    );
+    assert!(Tensor::read_npy_from_stream(bytes.as_slice()).is_err());
+}
+
+#[test]
+fn load_npy_malformed_shape() {
+    for shape in ["(-1, 2)", "(-2, -3)", "(4294967296, 4294967296, 2)"].iter() {
+        let header = format!(
+            "{{'descr': '<f4', 'fortran_order': False, 'shape': {}, }}",
+            shape
+        );
+        let bytes = npy_bytes(&header, &[0u8; 16]);
+        let err = Tensor::read_npy_from_stream(bytes.as_slice()).unwrap_err();
+        assert!(matches!(err, tch::TchError::FileFormat(_)), "{}", err);
+    }
+}
+
+#[test]
+fn save_and_load_npy_bool() {
+    let t = Tensor::of_slice(&[true, false, true]);
+    let mut buffer = Vec::new();
+    t.write_npy_to_stream(&mut buffer).unwrap();
+    let t = Tensor::read_npy_from_stream(buffer.as_slice()).unwrap();
+    assert_eq!(t.kind(), Kind::Bool);
+    assert_eq!(Vec::<bool>::from(&t), [true, false, true]);
+}
+
+#[test]
+fn save_and_load_npz_compressed() {
+    let filename = std::env::temp_dir().join(format!("tch8-{}.npz", std::process::id()));
+    let zeros = Tensor::zeros(&[1000], tch::kind::FLOAT_CPU);
+    let e = Tensor::of_slice(&[2, 7, 1, 8, 2, 8, 1, 8, 2, 8, 4, 6]);
+    Tensor::write_npz_compressed(&[(&"zeros", &zeros), (&"e", &e)], &filename).unwrap();
+    assert!(std::fs::metadata(&filename).unwrap().len() < 4000);
+    let named_tensors = Tensor::read_npz(&filename).unwrap();
+    assert_eq!(named_tensors.len(), 2);
+    assert_eq!(named_tensors[0].0, "zeros");
+    assert_eq!(named_tensors[0].1.size(), [1000]);
+    assert_eq!(i64::from(&named_tensors[1].1.sum(tch::Kind::Float)), 57);
+    std::fs::remove_file(filename).unwrap();
+}
+
+#[test]
+fn load_npy_mmap() {
+    let filename = std::env::temp_dir().join(format!("tch9-{}.npy", std::process::id()));
+    let pi = Tensor::of_slice(&[3.0, 1.0, 4.0, 1.0, 5.0, 9.0]).reshape(&[2, 3]);
+    pi.write_npy(&filename).unwrap();
+    let mut t = Tensor::read_npy_mmap(&filename).unwrap();
+    assert_eq!(t.size(), [2, 3]);
+    assert_eq!(
+        Vec::<f64>::from(t.flatten(0, -1)),
+        [3.0, 1.0, 4.0, 1.0, 5.0, 9.0]
+    );
+    // Modifying the tensor does not change the file.
+    let _ = t.fill_(0.0);
+    let t = Tensor::read_npy(&filename).unwrap();
+    assert_eq!(f64::from(t.sum(Kind::Double)), 23.0);
+
+    // Truncated files are rejected rather than mapped past their end.
+    let len = std::fs::metadata(&filename).unwrap().len();
+    let file = std::fs::OpenOptions::new()
+        .write(true)
+        .open(&filename)
+        .unwrap();
+    file.set_len(len - 8).unwrap();
+    let err = Tensor::read_npy_mmap(&filename).unwrap_err();
+    assert!(matches!(err, tch::TchError::FileFormat(_)), "{}", err);
+    std::fs::remove_file(filename).unwrap();
+}
@@ -1,5 +1,5 @@
 use tch::{Device, Kind, Tensor};
-use tch::{IndexOp, NewAxis};
+use tch::{Ellipsis, IndexOp, NewAxis, Step, TensorIndexer};
 
 #[test]
 fn integer_index() {
@@ -193,3 +193,88 @@ fn indexing_doc() {
     let t = tensor.i((.., .., NewAxis));
     assert_eq!(t.size(), &[2, 3, 1]);
 }
+
+#[test]
+fn ellipsis_and_step_index() {
+    let tensor = Tensor::arange(24, (Kind::Int64, Device::Cpu)).view((2, 3, 4));
+    let t = tensor.i((Ellipsis, 1));
+    assert_eq!(t.size(), &[2, 3]);
+    assert_eq!(
+        Vec::<i64>::from(t.contiguous().view(-1)),
+        [1, 5, 9, 13, 17, 21]
+    );
+    let t = tensor.i((1, Ellipsis, NewAxis));
+    assert_eq!(t.size(), &[3, 4, 1]);
+    let t = tensor.i((.., Ellipsis, 1..3, 2));
+    assert_eq!(Vec::<i64>::from(t.contiguous().view(-1)), [6, 10, 18, 22]);
+    assert!(tensor.f_i((Ellipsis, 0, Ellipsis)).is_err());
+
+    let t = tensor.i((0, 0, Step(.., 2)));
+    assert_eq!(Vec::<i64>::from(t), [0, 2]);
+    let t = tensor.i((0, 0, Step(1.., 2)));
+    assert_eq!(Vec::<i64>::from(t), [1, 3]);
+    let t = tensor.i((0, Step(0..=2, 2), -1));
+    assert_eq!(Vec::<i64>::from(t), [3, 11]);
+    assert!(tensor.f_i(Step(.., 0)).is_err());
+}
+
+#[test]
+fn mask_index() {
+    let tensor = Tensor::of_slice(&[1, 2, 3, 4, 5, 6]).view((2, 3));
+    let t = tensor.i((.., vec![true, false, true]));
+    assert_eq!(t.size(), &[2, 2]);
+    assert_eq!(Vec::<i64>::from(t.contiguous().view(-1)), [1, 3, 4, 6]);
+    let mask = tensor.gt(2);
+    let t = tensor.i(&mask);
+    assert_eq!(Vec::<i64>::from(t), [3, 4, 5, 6]);
+    assert!(tensor.f_i(vec![true, false, true]).is_err());
+}
+
+#[test]
+fn numpy_index() {
+    let tensor = Tensor::arange(12, (Kind::Int64, Device::Cpu)).view((3, 4));
+    let t = tensor.i((vec![0, 2], vec![1, 3]));
+    assert_eq!(t.size(), &[2, 2]);
+    let t = tensor.i_numpy((vec![0, 2], vec![1, 3]));
+    assert_eq!(Vec::<i64>::from(t), [1, 11]);
+    let index = Tensor::of_slice(&[0, 2, 1, 1]).view((2, 2));
+    let t = tensor.i_numpy((&index, 0));
+    assert_eq!(t.size(), &[2, 2]);
+    assert_eq!(Vec::<i64>::from(t.view(-1)), [0, 8, 4, 4]);
+    assert!(tensor.f_i((&index, 0)).is_err());
+}
+
+#[test]
+fn index_set() {
+    let mut tensor = Tensor::zeros(&[2, 3], (Kind::Int64, Device::Cpu));
+    tensor.i_set((.., 1), &Tensor::of_slice(&[1, 2]));
+    tensor.i_set((1, vec![0, 2]), &Tensor::from(7));
+    let _ = tensor.i_mut((0, 2..)).fill_(5);
+    assert_eq!(Vec::<i64>::from(tensor.view(-1)), [0, 1, 5, 7, 2, 7]);
+
+    let mask = tensor.gt(4);
+    tensor.i_set(&mask, &Tensor::from(-1));
+    assert_eq!(Vec::<i64>::from(tensor.view(-1)), [0, 1, -1, -1, 2, -1]);
+
+    let mut tensor = Tensor::zeros(&[3, 3], (Kind::Float, Device::Cpu));
+    tensor.i_set((vec![0, 2], vec![0, 2]), &Tensor::from(1.0));
+    assert_eq!(
+        Vec::<f32>::from(tensor.view(-1)),
+        [1.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0, 1.0]
+    );
+    tensor.i_set_numpy((vec![0, 1], vec![1, 2]), &Tensor::from(2.0));
+    assert_eq!(
+        Vec::<f32>::from(tensor.view(-1)),
+        [1.0, 2.0, 1.0, 0.0, 0.0, 2.0, 1.0, 0.0, 1.0]
+    );
+    assert!(tensor.f_i_mut((.., vec![0])).is_err());
+}
+
+#[test]
+fn long_index() {
+    let tensor = Tensor::zeros(&[2, 2, 2, 2, 2, 2, 2, 2, 2], (Kind::Float, Device::Cpu));
+    let t = tensor.i((0, 1, 0, 1, 0, 1, 0, .., NewAxis));
+    assert_eq!(t.size(), &[2, 1, 2]);
+    let index: Vec<TensorIndexer> = (0..9).map(|_| TensorIndexer::from(0)).collect();
+    assert_eq!(tensor.i(index).size(), Vec::<i64>::new());
+}
@@ -1,8 +1,9 @@
 use anyhow::Result;
-use half::f16;
+use half::{bf16, f16};
+use num_complex::{Complex32, Complex64};
 use std::convert::{TryFrom, TryInto};
 use std::f32;
-use tch::{Device, Tensor};
+use tch::{Device, IndexOp, Tensor};
 
 #[test]
 #[cfg(feature = "cuda-tests")]
@@ -250,6 +251,72 @@ mod ndarray_tests {
         assert_eq!(Vec::<i64>::from(tensor).as_slice(), nd.as_slice().unwrap());
     }
 
+    #[test]
+    fn from_ndarray_complex() {
+        let nd = ndarray::arr1(&[Complex32::new(1.0, 2.0), Complex32::new(-3.0, 0.5)]).into_dyn();
+        let tensor = Tensor::try_from(&nd).unwrap();
+        assert_eq!(tensor.kind(), tch::Kind::ComplexFloat);
+        let nd2 = ndarray::ArrayD::<Complex32>::try_from(&tensor).unwrap();
+        assert_eq!(nd, nd2);
+    }
+
+    #[test]
+    fn into_ndarray_fixed_dims() {
+        let tensor = Tensor::of_slice(&[1i16, 2, 3, 4, 5, 6]).reshape(&[2, 3]);
+        let nd = ndarray::Array2::<i16>::try_from(&tensor).unwrap();
+        assert_eq!(nd, ndarray::arr2(&[[1, 2, 3], [4, 5, 6]]));
+        let nd = ndarray::Array3::<i16>::try_from(tensor.reshape(&[1, 2, 3])).unwrap();
+        assert_eq!(nd.shape(), [1, 2, 3]);
+        assert!(ndarray::Array3::<i16>::try_from(&tensor).is_err());
+    }
+
+    #[test]
+    fn ndarray_view() {
+        let mut tensor = Tensor::of_slice(&[1u8, 2, 3, 4, 5, 6]).reshape(&[2, 3]);
+        let transposed = tensor.tr();
+        unsafe {
+            let view = transposed.to_ndarray_view::<u8, ndarray::Ix2>();
+            assert_eq!(view, ndarray::arr2(&[[1, 4], [2, 5], [3, 6]]));
+            assert!(tensor.f_to_ndarray_view::<i8, ndarray::Ix2>().is_err());
+            assert!(tensor.f_to_ndarray_view::<u8, ndarray::Ix1>().is_err());
+            let mut view = tensor.to_ndarray_view_mut::<u8, ndarray::IxDyn>();
+            view[[1, 2]] = 42;
+        }
+        assert_eq!(Vec::<u8>::from(&tensor.view(-1)), [1, 2, 3, 4, 5, 42]);
+        let mut expanded = Tensor::of_slice(&[1u8, 2]).expand(&[3, 2], false);
+        let mut overlapping = Tensor::of_slice(&[1u8, 2, 3, 4]).as_strided(&[3, 2], &[1, 1], None);
+        unsafe {
+            assert!(expanded
+                .f_to_ndarray_view_mut::<u8, ndarray::Ix2>()
+                .is_err());
+            assert!(overlapping
+                .f_to_ndarray_view_mut::<u8, ndarray::Ix2>()
+                .is_err());
+            assert!(expanded
+                .i(0)
+                .f_to_ndarray_view::<u8, ndarray::Ix1>()
+                .is_ok());
+        }
+    }
+
+    #[test]
+    fn from_ndarray_view() {
+        use ndarray::s;
+        let mut nd = ndarray::arr2(&[[1f32, 2., 3.], [4., 5., 6.]]);
+        let tensor = Tensor::try_from(nd.slice(s![.., 1..;2])).unwrap();
+        assert_eq!(tensor.size(), [2, 1]);
+        assert_eq!(Vec::<f32>::from(&tensor.view(-1)), [2., 5.]);
+        {
+            let mut tensor = unsafe { Tensor::from_ndarray_view_mut(nd.slice_mut(s![.., ..;2])) };
+            assert_eq!(tensor.size(), [2, 2]);
+            assert_eq!(tensor.stride(), [3, 2]);
+            let _ = tensor.fill_(0.);
+        }
+        assert_eq!(nd, ndarray::arr2(&[[0., 2., 0.], [0., 5., 0.]]));
+        let tensor = unsafe { Tensor::from_ndarray_view(nd.t()) };
+        assert_eq!(Vec::<f32>::from(&tensor.get(1)), [2., 5.]);
+    }
+
     #[test]
     fn from_ndarray_bool() {
         let nd = ndarray::arr2(&[[true, false], [true, true]]);
@@ -269,6 +336,30 @@ fn from_primitive() -> Result<()> {
     assert_eq!(Vec::<f32>::from(Tensor::try_from(1_f32)?), vec![1.0]);
     assert_eq!(Vec::<f64>::from(Tensor::try_from(1_f64)?), vec![1.0]);
     assert_eq!(Vec::<bool>::from(Tensor::try_from(true)?), vec![true]);
+    assert_eq!(
+        Vec::<bf16>::from(Tensor::try_from(bf16::from_f64(1.0))?),
+        vec![bf16::from_f64(1.0)]
+    );
+    assert_eq!(
+        Vec::<Complex32>::from(Tensor::try_from(Complex32::new(1.0, 2.0))?),
+        vec![Complex32::new(1.0, 2.0)]
+    );
+    Ok(())
+}
+
+#[test]
+fn complex_to_string() -> Result<()> {
+    let values = [
+        Complex32::new(1.0, 2.0),
+        Complex32::new(-3.0, 0.5),
+        Complex32::new(0.0, -1.0),
+        Complex32::new(4.0, 0.0),
+    ];
+    let t = Tensor::of_slice(&values).view([2, 2]);
+    assert_eq!(
+        t.to_string(80)?,
+        "[[1+2i, -3+0.5i],\n [0-1i, 4+0i]]\n[ ComplexFloat{2,2} ]"
+    );
     Ok(())
 }
 
@@ -302,6 +393,20 @@ fn from_vec() -> Result<()> {
         Vec::<bool>::from(Tensor::try_from(vec![true, false])?),
         vec![true, false]
     );
+    assert_eq!(
+        Vec::<bf16>::from(Tensor::try_from(vec![
+            bf16::from_f64(-1.0),
+            bf16::from_f64(2.5)
+        ])?),
+        vec![bf16::from_f64(-1.0), bf16::from_f64(2.5)]
+    );
+    assert_eq!(
+        Vec::<Complex64>::from(Tensor::try_from(vec![
+            Complex64::new(-1.0, 0.5),
+            Complex64::new(0.0, 2.0)
+        ])?),
+        vec![Complex64::new(-1.0, 0.5), Complex64::new(0.0, 2.0)]
+    );
     Ok(())
 }
 
@@ -492,3 +597,58 @@ fn quantized() {
     let t = t.dequantize();
     assert_eq!(Vec::<f32>::from(&t), [-1f32, 0., 1., 2., 24.5, 0.4]);
 }
+
+#[test]
+fn complex() {
+    let t = Tensor::of_slice(&[Complex64::new(1.0, 2.0), Complex64::new(3.0, -1.0)]);
+    assert_eq!(format!("{:?}", t), "[1+2i, 3-1i]");
+    assert_eq!(format!("{:?}", t.get(1)), "[3-1i]");
+    let real = Tensor::of_slice(&[1.0f32, 2.0]).to_kind(tch::Kind::ComplexFloat);
+    assert_eq!(
+        Vec::<Complex32>::from(&real),
+        [Complex32::new(1.0, 0.0), Complex32::new(2.0, 0.0)]
+    );
+}
+
+#[test]
+fn from_vec_no_copy() {
+    let t = Tensor::from_vec(vec![1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0], &[2, 3]);
+    assert_eq!(t.size(), [2, 3]);
+    assert_eq!(Vec::<f32>::from(t.get(1)), [4.0, 5.0, 6.0]);
+    let t2 = t.shallow_clone();
+    drop(t);
+    assert_eq!(
+        unsafe { t2.as_slice::<f32>() },
+        [1.0, 2.0, 3.0, 4.0, 5.0, 6.0]
+    );
+    assert!(Tensor::f_from_vec(vec![1i64, 2, 3], &[2, 2]).is_err());
+}
+
+#[test]
+fn from_blob() {
+    let mut data = [1i64, 2, 3, 4, 5, 6];
+    {
+        let mut t = unsafe { Tensor::from_blob(&mut data, &[3, 2], &[1, 3]) };
+        assert_eq!(Vec::<i64>::from(t.get(0)), [1, 4]);
+        assert!(unsafe { t.f_as_slice::<i64>() }.is_err());
+        let _ = t.fill_(7);
+    }
+    assert_eq!(data, [7; 6]);
+    assert!(unsafe { Tensor::f_from_blob(&mut data, &[3, 3], &[3, 1]) }.is_err());
+    assert!(unsafe { Tensor::f_from_blob(&mut data, &[2], &[1, 1]) }.is_err());
+}
+
+#[test]
+fn as_slice() {
+    let mut t = Tensor::of_slice(&[1.0, 2.0, 3.0, 4.0]).view((2, 2));
+    unsafe {
+        assert_eq!(t.as_slice::<f64>(), [1.0, 2.0, 3.0, 4.0]);
+        t.as_mut_slice::<f64>()[1] = 5.0;
+    }
+    assert_eq!(Vec::<f64>::from(&t.view(-1)), [1.0, 5.0, 3.0, 4.0]);
+    unsafe {
+        assert!(t.f_as_slice::<f32>().is_err());
+        assert!(t.tr().f_as_slice::<f64>().is_err());
+        assert_eq!(t.i((.., 1..)).i(0).as_slice::<f64>(), [5.0]);
+    }
+}
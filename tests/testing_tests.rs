@@ -0,0 +1,90 @@
+use tch::autograd::{CustomFunction, FunctionCtx};
+use tch::testing::{assert_close, f_gradcheck, gradcheck, gradgradcheck, GradcheckConfig};
+use tch::{Kind, TchError, Tensor};
+
+// A square function with an incorrect gradient.
+struct BadSquare;
+
+impl CustomFunction for BadSquare {
+    fn forward(&self, ctx: &mut FunctionCtx, xs: &[Tensor]) -> Result<Vec<Tensor>, TchError> {
+        ctx.f_save_for_backward(&[&xs[0]])?;
+        Ok(vec![&xs[0] * &xs[0]])
+    }
+
+    fn backward(&self, ctx: &mut FunctionCtx, grads: &[Tensor]) -> Result<Vec<Tensor>, TchError> {
+        let xs = ctx.f_saved_tensors()?;
+        Ok(vec![&grads[0] * &xs[0]])
+    }
+}
+
+#[test]
+fn gradcheck_pass() {
+    let x = Tensor::of_slice(&[0.5f32, -1.0, 2.0]).set_requires_grad(true);
+    let w = Tensor::of_slice(&[1.5f32, 2.0, -0.5]).set_requires_grad(true);
+    gradcheck(
+        |xs| (&xs[0] * &xs[1]).sin() * &xs[0],
+        &[x, w],
+        &GradcheckConfig::default(),
+    );
+}
+
+#[test]
+fn gradcheck_non_contiguous() {
+    let x = Tensor::of_slice(&[0.5f64, -1.0, 2.0, 0.3, 1.2, -0.7])
+        .view([2, 3])
+        .tr()
+        .set_requires_grad(true);
+    assert_eq!(x.stride(), [1, 3]);
+    let c = Tensor::of_slice(&[1.0f64, 2.0, 3.0, 4.0, 5.0, 6.0]).view([3, 2]);
+    gradcheck(|xs| (&xs[0] * &c).sin(), &[x], &GradcheckConfig::default());
+}
+
+#[test]
+fn gradcheck_fail() {
+    let x = Tensor::of_slice(&[1.0, 2.0, 3.0]).set_requires_grad(true);
+    let report = f_gradcheck(
+        |xs| BadSquare.apply(&[&xs[0]]).remove(0),
+        &[x],
+        &GradcheckConfig::default(),
+    )
+    .unwrap();
+    assert!(!report.passed);
+    assert_eq!(report.input, 0);
+    assert_eq!(report.index, [2]);
+    assert_eq!(report.output_index, [2]);
+    assert!((report.analytical - 3.0).abs() < 1e-6);
+    assert!((report.numerical - 6.0).abs() < 1e-4);
+}
+
+#[test]
+fn gradgradcheck_pass() {
+    let x = Tensor::of_slice(&[0.5, -1.0, 2.0]).set_requires_grad(true);
+    gradgradcheck(
+        |xs| &xs[0] * &xs[0] * &xs[0] + xs[0].exp(),
+        &[x],
+        &GradcheckConfig::default(),
+    );
+}
+
+#[test]
+fn assert_close_pass() {
+    let xs = Tensor::of_slice(&[1.0f32, 2.0, 3.0]);
+    let ys = Tensor::of_slice(&[1.0, 2.0 + 1e-7, 3.0]);
+    assert_close(&xs, &ys, 1e-5, 1e-8);
+    assert_close(&xs.sum(Kind::Float), &ys.sum(Kind::Double), 1e-5, 1e-8);
+}
+
+#[test]
+#[should_panic(expected = "1 / 3 elements mismatch")]
+fn assert_close_fail() {
+    let xs = Tensor::of_slice(&[1.0f32, 2.0, 3.0]);
+    let ys = Tensor::of_slice(&[1.0f32, 2.5, 3.0]);
+    assert_close(&xs, &ys, 1e-5, 1e-8);
+}
+
+#[test]
+#[should_panic(expected = "shape mismatch")]
+fn assert_close_shape() {
+    let xs = Tensor::of_slice(&[1.0f32, 2.0, 3.0]);
+    assert_close(&xs, &xs.view([3, 1]), 1e-5, 1e-8);
+}
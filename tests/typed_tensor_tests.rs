@@ -0,0 +1,47 @@
+use std::convert::TryFrom;
+use tch::{Device, Kind, Tensor, TypedTensor};
+
+#[test]
+fn typed_conversions() {
+    let t = Tensor::of_slice(&[3i64, 1, 4]);
+    let typed = TypedTensor::<i64>::try_from(&t).unwrap();
+    assert_eq!(typed.to_vec(), [3, 1, 4]);
+    assert!(TypedTensor::<f32>::try_from(&t).is_err());
+    assert!(t.f_typed::<i32>().is_err());
+    let t: Tensor = typed.into();
+    assert_eq!(t.kind(), Kind::Int64);
+}
+
+#[test]
+fn typed_ops() {
+    let xs = TypedTensor::<f32>::of_slice(&[1.0, 2.0, 3.0]);
+    let ys = TypedTensor::<f32>::ones(&[3], Device::Cpu);
+    let zs = &xs * &xs + &ys;
+    assert_eq!(zs.to_vec(), [2.0, 5.0, 10.0]);
+    assert_eq!((&zs / &xs).to_vec(), [2.0, 2.5, 10.0 / 3.0]);
+    assert_eq!(zs.sum().to_vec(), [17.0]);
+    assert_eq!((-&xs).max().to_vec(), [-1.0]);
+    let mask = zs.gt1(&xs.sum());
+    assert_eq!(mask.to_vec(), [false, false, true]);
+    let count: TypedTensor<i64> = mask.sum();
+    assert_eq!(count.to_vec(), [1]);
+    let bytes = TypedTensor::<u8>::of_slice(&[200, 100]);
+    assert_eq!(bytes.sum().to_vec(), [300i64]);
+    assert_eq!(zs.masked_select(&mask).to_vec(), [10.0]);
+    assert_eq!(zs.argmax(None, false).to_vec(), [2]);
+    let stacked = TypedTensor::stack(&[&xs, &ys], 0);
+    assert_eq!(stacked.size(), [2, 3]);
+    assert_eq!(
+        stacked.transpose(0, 1).contiguous().to_vec(),
+        [1.0, 1.0, 2.0, 1.0, 3.0, 1.0]
+    );
+}
+
+#[test]
+fn typed_to_kind() {
+    let xs = TypedTensor::<i32>::from_vec(vec![1, 2, 3, 4], &[2, 2]);
+    assert_eq!(unsafe { xs.as_slice() }, [1, 2, 3, 4]);
+    let ys: TypedTensor<f64> = xs.to_kind();
+    assert_eq!(ys.kind(), Kind::Double);
+    assert_eq!(ys.mean().to_vec(), [2.5]);
+}
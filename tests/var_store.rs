@@ -54,6 +54,51 @@ fn save_and_load_var_store() {
     fs::remove_file(filename).unwrap();
 }
 
+#[test]
+fn save_and_load_var_store_stream() {
+    let add = |vs: &tch::nn::Path| {
+        let v = vs.sub("a").sub("b").ones("t2", &[3]);
+        let u = vs.zeros("t1", &[4]);
+        (u, v)
+    };
+    let vs1 = VarStore::new(Device::Cpu);
+    let mut vs2 = VarStore::new(Device::Cpu);
+    let (mut u1, mut v1) = add(&vs1.root());
+    let (u2, v2) = add(&vs2.root());
+    tch::no_grad(|| {
+        u1 += 42.0;
+        v1 *= 2.0;
+    });
+    let mut buffer = Vec::new();
+    vs1.save_to_stream(&mut buffer).unwrap();
+    vs2.load_from_stream(buffer.as_slice()).unwrap();
+    assert_eq!(f64::from(&u2.mean(Kind::Float)), 42.0);
+    assert_eq!(f64::from(&v2.mean(Kind::Float)), 2.0);
+}
+
+#[test]
+fn save_and_load_var_store_npz() {
+    let filename = std::env::temp_dir().join(format!("tch-vs-npz-{}.npz", std::process::id()));
+    let add = |vs: &tch::nn::Path| {
+        let v = vs.sub("a").sub("b").ones("t2", &[3]);
+        let u = vs.zeros("t1", &[4]);
+        (u, v)
+    };
+    let vs1 = VarStore::new(Device::Cpu);
+    let mut vs2 = VarStore::new(Device::Cpu);
+    let (mut u1, mut v1) = add(&vs1.root());
+    let (u2, v2) = add(&vs2.root());
+    tch::no_grad(|| {
+        u1 += 42.0;
+        v1 *= 2.0;
+    });
+    vs1.save_npz(&filename).unwrap();
+    vs2.load_npz(&filename).unwrap();
+    assert_eq!(f64::from(&u2.mean(Kind::Float)), 42.0);
+    assert_eq!(f64::from(&v2.mean(Kind::Float)), 2.0);
+    fs::remove_file(filename).unwrap();
+}
+
 #[test]
 fn save_and_load_partial_var_store() {
     let filename = std::env::temp_dir().join(format!(
@@ -0,0 +1,120 @@
+use tch::vision::ops::{self, AnchorGenerator, BoxCoder, BoxFormat};
+use tch::{Device, Kind, Tensor};
+
+fn from_slice(data: &[f32]) -> Tensor {
+    Tensor::of_slice(data).view([-1, 4])
+}
+
+fn to_vec(t: &Tensor) -> Vec<f32> {
+    Vec::<f32>::from(&t.contiguous().view([-1]))
+}
+
+fn assert_close(t: &Tensor, expected: &[f32]) {
+    let values = to_vec(t);
+    assert_eq!(values.len(), expected.len());
+    for (v, e) in values.iter().zip(expected.iter()) {
+        assert!((v - e).abs() < 1e-4, "{:?} {:?}", values, expected)
+    }
+}
+
+#[test]
+fn box_convert() {
+    let boxes = from_slice(&[1., 2., 5., 8., 0., 0., 2., 2.]);
+    let xywh = ops::box_convert(&boxes, BoxFormat::Xyxy, BoxFormat::Xywh).unwrap();
+    assert_close(&xywh, &[1., 2., 4., 6., 0., 0., 2., 2.]);
+    let cxcywh = ops::box_convert(&xywh, BoxFormat::Xywh, BoxFormat::Cxcywh).unwrap();
+    assert_close(&cxcywh, &[3., 5., 4., 6., 1., 1., 2., 2.]);
+    let xyxy = ops::box_convert(&cxcywh, BoxFormat::Cxcywh, BoxFormat::Xyxy).unwrap();
+    assert_close(&xyxy, &to_vec(&boxes));
+    assert_close(&ops::box_area(&boxes).unwrap(), &[24., 4.]);
+    let clipped = ops::clip_boxes_to_image(&boxes, 6, 4).unwrap();
+    assert_close(&clipped, &[1., 2., 4., 6., 0., 0., 2., 2.]);
+    let keep = ops::remove_small_boxes(&boxes, 3.).unwrap();
+    assert_eq!(Vec::<i64>::from(&keep), [0]);
+    assert!(ops::box_area(&Tensor::zeros(&[2, 3], tch::kind::FLOAT_CPU)).is_err());
+}
+
+#[test]
+fn box_iou() {
+    let boxes1 = from_slice(&[0., 0., 2., 2., 0., 0., 1., 1.]);
+    let boxes2 = from_slice(&[1., 1., 3., 3., 4., 4., 5., 5.]);
+    let iou = ops::box_iou(&boxes1, &boxes2).unwrap();
+    assert_eq!(iou.size(), [2, 2]);
+    assert_close(&iou, &[1. / 7., 0., 0., 0.]);
+    let giou = ops::generalized_box_iou(&boxes1, &boxes2).unwrap();
+    assert_close(
+        &giou,
+        &[1. / 7. - 2. / 9., -20. / 25., -4. / 9., -23. / 25.],
+    );
+    let diou = ops::distance_box_iou(&boxes1, &boxes1).unwrap();
+    assert_close(&diou, &[1., 0.25 - 0.5 / 8., 0.25 - 0.5 / 8., 1.]);
+}
+
+#[test]
+fn nms() {
+    let boxes = from_slice(&[
+        0., 0., 10., 10., 1., 1., 11., 11., 20., 20., 30., 30., 0., 0., 10., 9.,
+    ]);
+    let scores = Tensor::of_slice(&[0.9f32, 0.95, 0.5, 0.3]);
+    let keep = ops::nms(&boxes, &scores, 0.5).unwrap();
+    assert_eq!(Vec::<i64>::from(&keep), [1, 2]);
+    let keep = ops::nms(&boxes, &scores, 0.9).unwrap();
+    assert_eq!(Vec::<i64>::from(&keep), [1, 0, 2, 3]);
+    let idxs = Tensor::of_slice(&[0i64, 1, 0, 0]);
+    let keep = ops::batched_nms(&boxes, &scores, &idxs, 0.5).unwrap();
+    assert_eq!(Vec::<i64>::from(&keep), [1, 0, 2]);
+    let empty = Tensor::zeros(&[0, 4], tch::kind::FLOAT_CPU);
+    let scores = Tensor::zeros(&[0], tch::kind::FLOAT_CPU);
+    assert_eq!(ops::nms(&empty, &scores, 0.5).unwrap().size(), [0]);
+}
+
+#[test]
+fn roi_ops() {
+    let input = Tensor::arange(16, tch::kind::FLOAT_CPU).view([1, 1, 4, 4]);
+    let rois = Tensor::of_slice(&[0f32, 0., 0., 3., 3.]).view([1, 5]);
+    let pooled = ops::roi_pool(&input, &rois, (2, 2), 1.).unwrap();
+    assert_eq!(pooled.size(), [1, 1, 2, 2]);
+    assert_close(&pooled, &[5., 7., 13., 15.]);
+    // The bins are computed on the unclipped regions.
+    let rois = Tensor::of_slice(&[0f32, 2., 2., 5., 5., 0., -1., -1., 1., 1.]).view([2, 5]);
+    let pooled = ops::roi_pool(&input, &rois, (2, 2), 1.).unwrap();
+    assert_close(&pooled, &[15., 0., 0., 0., 0., 1., 4., 5.]);
+
+    let input = Tensor::arange(4, tch::kind::FLOAT_CPU)
+        .view([1, 1, 1, 4])
+        .expand(&[2, 3, 4, 4], false);
+    let rois = Tensor::of_slice(&[1f32, 0., 0., 2., 2.]).view([1, 5]);
+    let aligned = ops::roi_align(&input, &rois, (1, 2), 1., 1, true).unwrap();
+    assert_eq!(aligned.size(), [1, 3, 1, 2]);
+    assert_close(&aligned, &[0., 1., 0., 1., 0., 1.]);
+    let scaled = Tensor::of_slice(&[1f32, 0., 0., 4., 4.]).view([1, 5]);
+    let aligned2 = ops::roi_align(&input, &scaled, (1, 2), 0.5, 1, true).unwrap();
+    assert_close(&aligned2, &to_vec(&aligned));
+}
+
+#[test]
+fn anchors_and_box_coder() {
+    let generator = AnchorGenerator::new(&[32.], &[0.5, 1., 2.]);
+    assert_eq!(generator.num_anchors_per_location(), 3);
+    let cell_anchors = generator.cell_anchors(Device::Cpu).unwrap();
+    assert_close(
+        &cell_anchors,
+        &[
+            -23., -11., 23., 11., -16., -16., 16., 16., -11., -23., 11., 23.,
+        ],
+    );
+    let anchors = generator
+        .grid_anchors((2, 3), (16, 8), Device::Cpu)
+        .unwrap();
+    assert_eq!(anchors.size(), [2 * 3 * 3, 4]);
+    assert_close(&anchors.get(4), &[-8., -16., 24., 16.]);
+    assert_close(&anchors.get(10), &[-16., 0., 16., 32.]);
+
+    let coder = BoxCoder::new([10., 10., 5., 5.]);
+    let boxes = from_slice(&[0., 0., 10., 20., 5., 5., 8., 9.]);
+    let anchors = from_slice(&[1., 1., 9., 19., 4., 4., 10., 10.]);
+    let offsets = coder.encode(&boxes, &anchors).unwrap();
+    assert_eq!(offsets.kind(), Kind::Float);
+    let decoded = coder.decode(&offsets, &anchors).unwrap();
+    assert_close(&decoded, &to_vec(&boxes));
+}
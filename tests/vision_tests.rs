@@ -1,5 +1,5 @@
 use tch;
-use tch::{nn, vision, Tensor};
+use tch::{nn, vision, IndexOp, Tensor};
 
 #[test]
 fn mobilenet() {
@@ -10,6 +10,132 @@ fn mobilenet() {
     assert_eq!(logits.size(), [1, 1000]);
 }
 
+#[test]
+fn classification_models() {
+    let img = Tensor::zeros(&[1, 3, 64, 64], tch::kind::FLOAT_CPU);
+    let vs = nn::VarStore::new(tch::Device::Cpu);
+    let root = vs.root();
+    let nets: Vec<Box<dyn nn::ModuleT>> = vec![
+        Box::new(vision::resnet::resnext50_32x4d(&(&root / "resnext"), 10)),
+        Box::new(vision::resnet::wide_resnet50_2(&(&root / "wide"), 10)),
+        Box::new(vision::mobilenet::v3_small(&(&root / "mobilenet"), 10)),
+        Box::new(vision::shufflenet::v2_x0_5(&(&root / "shufflenet"), 10)),
+        Box::new(vision::regnet::regnet_y_400mf(&(&root / "regnet"), 10)),
+        Box::new(vision::convnext::convnext_tiny(&(&root / "convnext"), 10)),
+    ];
+    for net in nets.iter() {
+        assert_eq!(net.forward_t(&img, false).size(), [1, 10]);
+    }
+    // Variables follow the torchvision naming.
+    let variables = vs.variables();
+    for (name, size) in [
+        ("resnext.layer1.0.conv2.weight", vec![128, 4, 3, 3]),
+        ("wide.layer1.0.conv2.weight", vec![128, 128, 3, 3]),
+        ("mobilenet.features.1.block.1.fc1.weight", vec![8, 16, 1, 1]),
+        ("mobilenet.classifier.3.weight", vec![10, 1024]),
+        ("shufflenet.stage2.0.branch1.0.weight", vec![24, 1, 3, 3]),
+        (
+            "regnet.trunk_output.block1.block1-0.f.se.fc1.weight",
+            vec![8, 48, 1, 1],
+        ),
+        ("regnet.fc.weight", vec![10, 440]),
+        ("convnext.features.1.0.layer_scale", vec![96, 1, 1]),
+        ("convnext.features.2.1.weight", vec![192, 96, 2, 2]),
+    ]
+    .iter()
+    {
+        assert_eq!(variables[*name].size(), *size, "{}", name);
+    }
+}
+
+#[test]
+fn vision_transformer() {
+    let vs = nn::VarStore::new(tch::Device::Cpu);
+    let net = vision::vit::deit_tiny_16(&vs.root(), 10);
+    let img = Tensor::zeros(&[2, 3, 224, 224], tch::kind::FLOAT_CPU);
+    assert_eq!(img.apply(&net).size(), [2, 10]);
+    let variables = vs.variables();
+    assert_eq!(variables["encoder.pos_embedding"].size(), [1, 197, 192]);
+    assert_eq!(
+        variables["encoder.layers.encoder_layer_11.self_attention.in_proj_weight"].size(),
+        [576, 192]
+    );
+    assert_eq!(variables["heads.head.weight"].size(), [10, 192]);
+}
+
+#[test]
+fn backbone_features() {
+    let vs = nn::VarStore::new(tch::Device::Cpu);
+    let img = Tensor::zeros(&[1, 3, 64, 64], tch::kind::FLOAT_CPU);
+    let resnet = vision::resnet::resnet18_features(&vs.root());
+    let features = resnet.forward_features(&img, false);
+    let sizes: Vec<_> = features
+        .iter()
+        .map(|(n, t)| (n.as_str(), t.size()))
+        .collect();
+    assert_eq!(
+        sizes,
+        [
+            ("layer1", vec![1, 64, 16, 16]),
+            ("layer2", vec![1, 128, 8, 8]),
+            ("layer3", vec![1, 256, 4, 4]),
+            ("layer4", vec![1, 512, 2, 2]),
+        ]
+    );
+    let mobilenet = vision::mobilenet::v2_features(&(&vs.root() / "mobilenet"));
+    let sizes: Vec<_> = mobilenet
+        .forward_features(&img, false)
+        .iter()
+        .map(|(_, t)| t.size())
+        .collect();
+    assert_eq!(
+        sizes,
+        [
+            [1, 24, 16, 16],
+            [1, 32, 8, 8],
+            [1, 96, 4, 4],
+            [1, 1280, 2, 2]
+        ]
+    );
+
+    let root = vs.root();
+    let backbones: Vec<(nn::IntermediateLayerGetter, [i64; 4])> = vec![
+        (
+            vision::mobilenet::v3_small_features(&(&root / "mobilenet_v3")),
+            [16, 24, 48, 576],
+        ),
+        (
+            vision::shufflenet::v2_x0_5_features(&(&root / "shufflenet")),
+            [24, 48, 96, 1024],
+        ),
+        (
+            vision::regnet::regnet_y_400mf_features(&(&root / "regnet")),
+            [48, 104, 208, 440],
+        ),
+        (
+            vision::convnext::convnext_tiny_features(&(&root / "convnext")),
+            [96, 192, 384, 768],
+        ),
+    ];
+    for (backbone, channels) in backbones.iter() {
+        assert_eq!(backbone.names(), ["layer1", "layer2", "layer3", "layer4"]);
+        let sizes: Vec<_> = backbone
+            .forward_features(&img, false)
+            .iter()
+            .map(|(_, t)| t.size())
+            .collect();
+        assert_eq!(
+            sizes,
+            [
+                [1, channels[0], 16, 16],
+                [1, channels[1], 8, 8],
+                [1, channels[2], 4, 4],
+                [1, channels[3], 2, 2]
+            ]
+        );
+    }
+}
+
 #[test]
 fn resize() {
     // Check that resizing returns a tensor with the appropriate dimensions.
@@ -33,3 +159,488 @@ fn resize_preserve_aspect_ratio() {
     let resized_img = vision::image::resize(&img, 32, 8).unwrap();
     assert_eq!(resized_img.size(), [3, 8, 32]);
 }
+
+#[test]
+fn transforms_shapes() {
+    use vision::transforms::*;
+    let img = Tensor::rand(&[3, 32, 48], tch::kind::FLOAT_CPU);
+    let t = compose()
+        .add(Resize::shorter_side(16))
+        .add(CenterCrop::new(16, 16))
+        .add(ColorJitter::new(0.4, 0.4, 0.4, 0.1))
+        .add(RandomAffine::new(30.0))
+        .add(GaussianBlur::new(3))
+        .add(RandAugment::default())
+        .add(AutoAugment::new(AutoAugmentPolicy::ImageNet))
+        .add(Normalize::imagenet())
+        .seed(42);
+    assert_eq!(img.apply(&t).size(), [3, 16, 16]);
+    let batch = Tensor::rand(&[4, 3, 32, 48], tch::kind::FLOAT_CPU);
+    assert_eq!(batch.apply(&t).size(), [4, 3, 16, 16]);
+    let t = compose().add(RandomResizedCrop::new(8, 12)).seed(1);
+    assert_eq!(batch.apply(&t).size(), [4, 3, 8, 12]);
+
+    let img = Tensor::ones(&[3, 8, 6], tch::kind::INT64_CPU).to_kind(tch::Kind::Uint8) * 200;
+    let t = compose().add(Resize::new(4, 4));
+    let out = img.apply(&t);
+    assert_eq!(out.size(), [3, 4, 4]);
+    assert_eq!(out.kind(), tch::Kind::Uint8);
+    assert_eq!(Vec::<u8>::from(out.flatten(0, -1)), [200u8; 48]);
+}
+
+#[test]
+fn transforms_seed() {
+    use vision::transforms::*;
+    let batch = Tensor::rand(&[2, 3, 16, 16], tch::kind::FLOAT_CPU);
+    let pipeline = |seed| {
+        compose()
+            .add(RandomHorizontalFlip::new(0.5))
+            .add(RandomRotation::new(45.0))
+            .add(ColorJitter::new(0.5, 0.5, 0.5, 0.2))
+            .seed(seed)
+    };
+    let out1 = batch.apply(&pipeline(7));
+    let out2 = batch.apply(&pipeline(7));
+    assert_eq!(
+        Vec::<f32>::from(out1.flatten(0, -1)),
+        Vec::<f32>::from(out2.flatten(0, -1))
+    );
+}
+
+#[test]
+fn transforms_functional() {
+    use vision::transforms::*;
+    let img = Tensor::of_slice(&[0.0f32, 0.25, 0.5, 1.0]).view([1, 2, 2]);
+    let out = solarize(&img, 0.5).unwrap();
+    assert_eq!(Vec::<f32>::from(out.flatten(0, -1)), [0.0, 0.25, 0.5, 0.0]);
+    let out = autocontrast(&(&img * 0.5)).unwrap();
+    assert_eq!(Vec::<f32>::from(out.flatten(0, -1)), [0.0, 0.25, 0.5, 1.0]);
+    let out = normalize(&img, &[0.5], &[0.5]).unwrap();
+    assert_eq!(Vec::<f32>::from(out.flatten(0, -1)), [-1.0, -0.5, 0.0, 1.0]);
+    let out = affine(&img, &AffineParams::default()).unwrap();
+    assert!(f64::from((out - &img).abs().max()) < 1e-6);
+    let rgb = Tensor::of_slice(&[0.2f32, 0.7, 0.4]).view([3, 1, 1]);
+    let out = adjust_hue(&rgb, 0.0).unwrap();
+    assert!(f64::from((out - &rgb).abs().max()) < 1e-5);
+    let out = adjust_saturation(&rgb, 0.0).unwrap();
+    assert!(f64::from(out.max() - out.min()) < 1e-6);
+    let out = posterize(&img.to_kind(tch::Kind::Double), 1).unwrap();
+    assert_eq!(out.kind(), tch::Kind::Double);
+    assert_eq!(
+        Vec::<f64>::from(out.flatten(0, -1)),
+        [0.0, 0.0, 128.0 / 255.0, 128.0 / 255.0]
+    );
+    let bytes = Tensor::of_slice(&[0u8, 63, 130, 255]).view([1, 2, 2]);
+    let out = posterize(&bytes, 2).unwrap();
+    assert_eq!(out.kind(), tch::Kind::Uint8);
+    assert_eq!(Vec::<u8>::from(out.flatten(0, -1)), [0, 0, 128, 192]);
+}
+
+#[test]
+fn random_flip_probability() {
+    use vision::transforms::{RandomHorizontalFlip, Transform};
+    let img = Tensor::arange(6, tch::kind::FLOAT_CPU).view([1, 2, 3]);
+    let mut rng = rand::thread_rng();
+    // Probabilities outside of [0, 1] are clamped.
+    let flipped = RandomHorizontalFlip::new(1.5)
+        .apply(&img, &mut rng)
+        .unwrap();
+    assert_eq!(flipped, img.flip(&[2]));
+    let kept = RandomHorizontalFlip::new(-0.5)
+        .apply(&img, &mut rng)
+        .unwrap();
+    assert_eq!(kept, img);
+}
+
+#[test]
+fn image_folder() {
+    use vision::image_folder::ImageFolder;
+    let root = std::env::temp_dir().join(format!("tch-image-folder-{}", std::process::id()));
+    for (class, count) in [("cat", 3), ("dog", 2)].iter() {
+        let dir = root.join(class);
+        std::fs::create_dir_all(&dir).unwrap();
+        for i in 0..*count {
+            let image =
+                Tensor::ones(&[3, 8, 6], tch::kind::INT64_CPU).to_kind(tch::Kind::Uint8) * i;
+            vision::image::save(&image, dir.join(format!("{}.png", i))).unwrap();
+        }
+    }
+    let dataset = ImageFolder::new(&root).unwrap().with_transform(
+        vision::transforms::compose()
+            .add(vision::transforms::ToFloat)
+            .add(vision::transforms::Resize::new(4, 4)),
+    );
+    assert_eq!(dataset.len(), 5);
+    assert_eq!(dataset.classes(), ["cat", "dog"]);
+    assert_eq!(dataset.class_to_idx()["dog"], 1);
+    let batches = dataset
+        .iter(2)
+        .shuffle()
+        .seed(42)
+        .num_workers(2)
+        .return_smaller_last_batch()
+        .collect::<Result<Vec<_>, _>>()
+        .unwrap();
+    assert_eq!(batches.len(), 3);
+    assert_eq!(batches[0].0.size(), [2, 3, 4, 4]);
+    let labels = Tensor::cat(&batches.iter().map(|b| &b.1).collect::<Vec<_>>(), 0);
+    assert_eq!(i64::from(labels.sum(tch::Kind::Int64)), 2);
+    let sequential = dataset.iter(2).shuffle().seed(42).map(|b| b.unwrap().1);
+    for (ys1, ys2) in sequential.zip(batches.iter().map(|b| &b.1)) {
+        assert_eq!(Vec::<i64>::from(&ys1), Vec::<i64>::from(ys2));
+    }
+
+    std::fs::write(root.join("dog").join("corrupt.png"), b"not an image").unwrap();
+    let dataset = ImageFolder::new(&root).unwrap();
+    assert_eq!(dataset.len(), 6);
+    assert!(dataset.iter(6).any(|b| b.is_err()));
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let name = std::ffi::OsStr::from_bytes(b"bad\xff");
+        std::fs::create_dir_all(root.join(name)).unwrap();
+        assert!(ImageFolder::new(&root).is_err());
+    }
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn image_bytes() {
+    use vision::image::{ChannelMode, DecodeConfig, ImageFormat};
+    let image = Tensor::arange(2 * 4 * 3, tch::kind::INT64_CPU)
+        .to_kind(tch::Kind::Uint8)
+        .view([3, 2, 4]);
+    let png = vision::image::encode_to_vec(&image, ImageFormat::Png).unwrap();
+    let decoded = vision::image::load_from_bytes(&png).unwrap();
+    assert_eq!(decoded, image);
+    let config = DecodeConfig {
+        mode: ChannelMode::Rgba,
+        ..Default::default()
+    };
+    let decoded = vision::image::load_from_bytes_with_config(&png, &config).unwrap();
+    assert_eq!(decoded.size(), [4, 2, 4]);
+    assert_eq!(i64::from(decoded.get(3).min()), 255);
+    let config = DecodeConfig {
+        mode: ChannelMode::Unchanged,
+        ..Default::default()
+    };
+    let gray = image.narrow(0, 0, 1);
+    let png = vision::image::encode_to_vec(&gray, ImageFormat::Png).unwrap();
+    let decoded = vision::image::load_from_bytes_with_config(&png, &config).unwrap();
+    assert_eq!(decoded, gray);
+
+    let low = vision::image::encode_to_vec(&image, ImageFormat::Jpeg { quality: 10 }).unwrap();
+    let high = vision::image::encode_to_vec(&image, ImageFormat::Jpeg { quality: 100 }).unwrap();
+    assert!(low.len() < high.len());
+    assert!(vision::image::load_from_bytes(b"not an image").is_err());
+
+    // Inserts an EXIF segment with an orientation of 6, i.e. a rotation of
+    // 90 degrees clockwise, after the jpeg start of image marker.
+    let mut exif =
+        b"Exif\0\0MM\0\x2a\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01\0\x06\0\0\0\0\0\0".to_vec();
+    let len = exif.len() as u16 + 2;
+    exif.splice(
+        0..0,
+        [0xff, 0xe1, (len >> 8) as u8, len as u8].iter().cloned(),
+    );
+    let mut jpeg = high;
+    jpeg.splice(2..2, exif.into_iter());
+    let config = DecodeConfig {
+        apply_exif_orientation: true,
+        ..Default::default()
+    };
+    let decoded = vision::image::load_from_bytes_with_config(&jpeg, &config).unwrap();
+    assert_eq!(decoded.size(), [3, 4, 2]);
+    let decoded = vision::image::load_from_bytes(&jpeg).unwrap();
+    assert_eq!(decoded.size(), [3, 2, 4]);
+}
+
+// Builds a 16 bits grayscale png image, the encoder only writes 8 bits images.
+fn png_16_bit(width: u32, height: u32, values: &[u16]) -> Vec<u8> {
+    use std::io::Write;
+    fn crc32(data: &[u8]) -> u32 {
+        let mut crc = 0xffff_ffffu32;
+        for &byte in data.iter() {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 == 1 {
+                    (crc >> 1) ^ 0xedb8_8320
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        !crc
+    }
+    fn chunk(png: &mut Vec<u8>, typ: &[u8], data: &[u8]) {
+        png.extend_from_slice(&(data.len() as u32).to_be_bytes());
+        let start = png.len();
+        png.extend_from_slice(typ);
+        png.extend_from_slice(data);
+        let crc = crc32(&png[start..]);
+        png.extend_from_slice(&crc.to_be_bytes());
+    }
+    let mut raw = vec![];
+    for row in values.chunks(width as usize) {
+        // Each row starts with its filter type, 0 for no filtering.
+        raw.push(0u8);
+        for v in row.iter() {
+            raw.extend_from_slice(&v.to_be_bytes());
+        }
+    }
+    let mut encoder = flate2::write::ZlibEncoder::new(vec![], flate2::Compression::default());
+    encoder.write_all(&raw).unwrap();
+    let mut ihdr = vec![];
+    ihdr.extend_from_slice(&width.to_be_bytes());
+    ihdr.extend_from_slice(&height.to_be_bytes());
+    // A bit depth of 16, grayscale, no interlacing.
+    ihdr.extend_from_slice(&[16, 0, 0, 0, 0]);
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    chunk(&mut png, b"IHDR", &ihdr);
+    chunk(&mut png, b"IDAT", &encoder.finish().unwrap());
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+#[test]
+fn image_bytes_16_bit() {
+    use vision::image::{ChannelMode, DecodeConfig};
+    let png = png_16_bit(2, 2, &[0, 1000, 40000, 65535]);
+    let config = DecodeConfig {
+        mode: ChannelMode::Unchanged,
+        allow_16_bit: true,
+        ..Default::default()
+    };
+    let decoded = vision::image::load_from_bytes_with_config(&png, &config).unwrap();
+    assert_eq!(decoded.kind(), tch::Kind::Int);
+    assert_eq!(decoded.size(), [1, 2, 2]);
+    // The values are not wrapped to negative int16 values.
+    assert_eq!(
+        Vec::<i32>::from(decoded.flatten(0, -1)),
+        [0, 1000, 40000, 65535]
+    );
+    let config = DecodeConfig {
+        mode: ChannelMode::Unchanged,
+        ..Default::default()
+    };
+    let decoded = vision::image::load_from_bytes_with_config(&png, &config).unwrap();
+    assert_eq!(decoded.kind(), tch::Kind::Uint8);
+    assert_eq!(Vec::<u8>::from(decoded.flatten(0, -1)), [0, 3, 156, 255]);
+}
+
+#[test]
+fn segmentation_models() {
+    let vs = nn::VarStore::new(tch::Device::Cpu);
+    let root = vs.root();
+    let img = Tensor::zeros(&[2, 3, 64, 64], tch::kind::FLOAT_CPU);
+    let deeplab = vision::segmentation::deeplabv3_resnet50(&(&root / "deeplab"), 21, true);
+    let (out, aux) = deeplab.forward_aux(&img, false);
+    assert_eq!(out.size(), [2, 21, 64, 64]);
+    assert_eq!(aux.unwrap().size(), [2, 21, 64, 64]);
+    let fcn = vision::segmentation::fcn_resnet50(&(&root / "fcn"), 21, false);
+    assert_eq!(img.apply_t(&fcn, false).size(), [2, 21, 64, 64]);
+    let unet = vision::segmentation::unet_resnet18(&(&root / "unet"), 3);
+    assert_eq!(img.apply_t(&unet, false).size(), [2, 3, 64, 64]);
+    let variables = vs.variables();
+    for (name, size) in [
+        (
+            "deeplab.backbone.layer4.0.conv2.weight",
+            vec![512, 512, 3, 3],
+        ),
+        (
+            "deeplab.classifier.0.convs.4.1.weight",
+            vec![256, 2048, 1, 1],
+        ),
+        (
+            "deeplab.classifier.0.project.0.weight",
+            vec![256, 1280, 1, 1],
+        ),
+        ("deeplab.classifier.4.weight", vec![21, 256, 1, 1]),
+        ("deeplab.aux_classifier.4.weight", vec![21, 256, 1, 1]),
+        ("fcn.classifier.0.weight", vec![512, 2048, 3, 3]),
+    ]
+    .iter()
+    {
+        assert_eq!(variables[*name].size(), *size, "{}", name);
+    }
+}
+
+#[test]
+fn segmentation_metrics() {
+    use vision::segmentation::{dice_loss, iou_loss, ConfusionMatrix};
+    let targets = Tensor::of_slice(&[0i64, 1, 1, 0]).view([1, 2, 2]);
+    let logits = targets
+        .one_hot(2)
+        .permute(&[0, 3, 1, 2])
+        .to_kind(tch::Kind::Float)
+        * 20.;
+    assert!(f64::from(dice_loss(&logits, &targets, 1.).unwrap()) < 1e-3);
+    assert!(f64::from(iou_loss(&logits, &targets, 1.).unwrap()) < 1e-3);
+    assert!(f64::from(dice_loss(&-&logits, &targets, 1.).unwrap()) > 0.9);
+    assert!(dice_loss(&logits, &targets.view([1, 4]), 1.).is_err());
+
+    let mut confusion_matrix = ConfusionMatrix::new(2);
+    let predictions = Tensor::of_slice(&[0i64, 1, 1, 1]).view([2, 2]);
+    let targets = Tensor::of_slice(&[0i64, 1, 0, 255]).view([2, 2]);
+    confusion_matrix.update(&predictions, &targets);
+    assert_eq!(
+        Vec::<i64>::from(&confusion_matrix.matrix().view([-1])),
+        [1, 1, 0, 1]
+    );
+    assert_eq!(confusion_matrix.iou(), [0.5, 0.5]);
+    assert_eq!(confusion_matrix.mean_iou(), 0.5);
+    assert!((confusion_matrix.pixel_accuracy() - 2. / 3.).abs() < 1e-6);
+    assert!(ConfusionMatrix::new(2).mean_iou().is_nan());
+}
+
+#[test]
+fn mask_augmentation() {
+    let mask = Tensor::randint(10, &[4, 8, 8], tch::kind::INT64_CPU);
+    let images = mask
+        .unsqueeze(1)
+        .to_kind(tch::Kind::Float)
+        .repeat(&[1, 3, 1, 1]);
+    let (images, masks) = vision::dataset::augmentation_with_mask(&images, &mask, true, 2);
+    assert_eq!(images.size(), [4, 3, 8, 8]);
+    assert_eq!(masks.size(), [4, 8, 8]);
+    assert_eq!(masks.kind(), tch::Kind::Int64);
+    assert_eq!(images.i((.., 0)), masks.to_kind(tch::Kind::Float));
+    let (cropped, cropped_masks) = vision::dataset::random_crop_with_mask(&images, &masks, 0);
+    assert_eq!(cropped, images);
+    assert_eq!(cropped_masks, masks);
+}
+
+fn mat_element(typ: u32, data: &[u8]) -> Vec<u8> {
+    let mut element = typ.to_le_bytes().to_vec();
+    element.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    element.extend_from_slice(data);
+    while element.len() % 8 != 0 {
+        element.push(0)
+    }
+    element
+}
+
+fn mat_matrix(name: &str, class: u8, dims: &[i32], typ: u32, data: &[u8]) -> Vec<u8> {
+    let dims: Vec<u8> = dims.iter().flat_map(|d| d.to_le_bytes().to_vec()).collect();
+    let mut content = mat_element(6, &[class, 0, 0, 0, 0, 0, 0, 0]);
+    content.extend(mat_element(5, &dims));
+    content.extend(mat_element(1, name.as_bytes()));
+    content.extend(mat_element(typ, data));
+    mat_element(14, &content)
+}
+
+#[test]
+fn dataset_loaders() {
+    use flate2::{write::GzEncoder, write::ZlibEncoder, Compression};
+    use std::io::Write;
+    let dir = std::env::temp_dir().join(format!("tch-datasets-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+
+    // MNIST style files, the training ones being gzip compressed.
+    let idx_images = |n: u8| {
+        [
+            &[0, 0, 8, 3, 0, 0, 0, n, 0, 0, 0, 2, 0, 0, 0, 2],
+            &[255; 8][..],
+        ]
+        .concat()
+    };
+    let idx_labels = |n: u8| [&[0, 0, 8, 1, 0, 0, 0, n], &[7, 3][..]].concat();
+    let gzip = |data: &[u8]| {
+        let mut encoder = GzEncoder::new(vec![], Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    };
+    std::fs::write(dir.join("train-images-idx3-ubyte.gz"), gzip(&idx_images(2))).unwrap();
+    std::fs::write(dir.join("train-labels-idx1-ubyte.gz"), gzip(&idx_labels(2))).unwrap();
+    std::fs::write(dir.join("t10k-images-idx3-ubyte"), &idx_images(2)).unwrap();
+    std::fs::write(dir.join("t10k-labels-idx1-ubyte"), &idx_labels(2)).unwrap();
+    let mnist = vision::mnist::load_fashion_dir(&dir).unwrap();
+    assert_eq!(mnist.train_images.size(), [2, 4]);
+    assert_eq!(Vec::<i64>::from(&mnist.train_labels), [7, 3]);
+    assert_eq!(mnist.test_images.size(), [2, 4]);
+
+    // CIFAR-100, each record starts with the coarse and fine labels.
+    let record = |coarse: u8, fine: u8| [&[coarse, fine], &[0u8; 3072][..]].concat();
+    std::fs::write(
+        dir.join("train.bin"),
+        [record(1, 42), record(19, 99)].concat(),
+    )
+    .unwrap();
+    std::fs::write(dir.join("test.bin"), record(3, 7)).unwrap();
+    let fine = vision::cifar::load_cifar100_dir(&dir, vision::cifar::Cifar100Labels::Fine).unwrap();
+    assert_eq!(fine.train_images.size(), [2, 3, 32, 32]);
+    assert_eq!(Vec::<i64>::from(&fine.train_labels), [42, 99]);
+    assert_eq!(fine.labels, 100);
+    let coarse =
+        vision::cifar::load_cifar100_dir(&dir, vision::cifar::Cifar100Labels::Coarse).unwrap();
+    assert_eq!(Vec::<i64>::from(&coarse.test_labels), [3]);
+    assert_eq!(coarse.labels, 20);
+
+    // SVHN MAT-file v5 with an uncompressed image array and compressed labels.
+    let mut x = vec![0u8; 32 * 32 * 3 * 2];
+    // Column-major index of row 0, column 1, channel 0 of the second image.
+    x[32 + 32 * 32 * 3] = 255;
+    let y: Vec<u8> = [10f64, 3.]
+        .iter()
+        .flat_map(|v| v.to_le_bytes().to_vec())
+        .collect();
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    encoder
+        .write_all(&mat_matrix("y", 6, &[2, 1], 9, &y))
+        .unwrap();
+    let y = encoder.finish().unwrap();
+    let mut mat = vec![b' '; 116];
+    mat.extend_from_slice(&[0; 8]);
+    mat.extend_from_slice(&[0, 1, b'I', b'M']);
+    mat.extend(mat_matrix("X", 9, &[32, 32, 3, 2], 2, &x));
+    mat.extend_from_slice(&15u32.to_le_bytes());
+    mat.extend_from_slice(&(y.len() as u32).to_le_bytes());
+    mat.extend(y);
+    std::fs::write(dir.join("train_32x32.mat"), &mat).unwrap();
+    std::fs::write(dir.join("test_32x32.mat"), &mat).unwrap();
+    let svhn = vision::svhn::load_dir(&dir).unwrap();
+    assert_eq!(svhn.train_images.size(), [2, 3, 32, 32]);
+    assert_eq!(Vec::<i64>::from(&svhn.train_labels), [0, 3]);
+    assert_eq!(f64::from(svhn.train_images.get(1).get(0).get(0).get(1)), 1.);
+    assert_eq!(f64::from(svhn.train_images.sum(tch::Kind::Float)), 1.);
+    // Negative dimensions and uint64 values that do not fit in an int64 are
+    // rejected.
+    let header = mat[..128 + mat_matrix("X", 9, &[32, 32, 3, 2], 2, &x).len()].to_vec();
+    let y = [3u64, 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes().to_vec())
+        .collect::<Vec<_>>();
+    let mat = [header.clone(), mat_matrix("y", 15, &[-2, -1], 13, &y)].concat();
+    std::fs::write(dir.join("train_32x32.mat"), &mat).unwrap();
+    assert!(vision::svhn::load_dir(&dir).is_err());
+    let y = [3u64, u64::MAX]
+        .iter()
+        .flat_map(|v| v.to_le_bytes().to_vec())
+        .collect::<Vec<_>>();
+    let mat = [header, mat_matrix("y", 15, &[2, 1], 13, &y)].concat();
+    std::fs::write(dir.join("train_32x32.mat"), &mat).unwrap();
+    assert!(vision::svhn::load_dir(&dir).is_err());
+
+    // STL-10 images are also stored in column-major order.
+    let mut x = vec![0u8; 3 * 96 * 96];
+    x[96] = 255;
+    std::fs::write(dir.join("train_X.bin"), &x).unwrap();
+    std::fs::write(dir.join("train_y.bin"), &[10]).unwrap();
+    std::fs::write(dir.join("test_X.bin"), &x).unwrap();
+    std::fs::write(dir.join("test_y.bin"), &[1]).unwrap();
+    let stl10 = vision::stl10::load_dir(&dir).unwrap();
+    assert_eq!(stl10.train_images.size(), [1, 3, 96, 96]);
+    assert_eq!(
+        f64::from(stl10.train_images.get(0).get(0).get(0).get(1)),
+        1.
+    );
+    assert_eq!(Vec::<i64>::from(&stl10.train_labels), [9]);
+    assert_eq!(Vec::<i64>::from(&stl10.test_labels), [0]);
+    // Labels have to be between 1 and 10, with one label per image.
+    for labels in [&[0u8][..], &[11], &[1, 2]].iter() {
+        std::fs::write(dir.join("test_y.bin"), labels).unwrap();
+        assert!(vision::stl10::load_dir(&dir).is_err());
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+}
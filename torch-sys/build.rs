@@ -5,25 +5,32 @@
 //
 // On Linux, the TORCH_CUDA_VERSION environment variable can be used,
 // like 9.0, 90, or cu90 to specify the version of CUDA to use for libtorch.
+//
+// The python-extension feature requires LIBTORCH to point at the torch
+// directory of a PyTorch python install as it links to libtorch_python. The
+// python headers are located using the interpreter specified by the
+// PYTHON_SYS_EXECUTABLE environment variable, python3 by default.
 
 use anyhow::{bail, Result};
 use cmake::Config;
 use curl::easy::Easy;
 use serde::{de::Error as _, Deserialize, Deserializer};
 use std::{
-    fs, io,
+    env, fs, io,
     io::Write,
     path::{Path, PathBuf},
+    process::Command,
 };
 
 use env_config::*;
 
 const TORCH_VERSION: &str = "1.7.0";
-const RETURN_ENV_VARS: [&str; 4] = [
+const RETURN_ENV_VARS: [&str; 5] = [
     "TORCH_CUDA_VERSION",
     "LIBTORCH",
     "LIBTORCH_CXX11_ABI",
     "LIBTORCH_USE_CMAKE",
+    "PYTHON_SYS_EXECUTABLE",
 ];
 
 mod env_config {
@@ -160,11 +167,21 @@ fn main() -> Result<()> {
     let use_cuda = detect_cuda(&config, &libtorch);
     let use_hip = detect_hip(&config, &libtorch);
 
+    let use_python = cfg!(feature = "python-extension");
+
     // build ffi library
     if config.libtorch_use_cmake {
+        if use_python {
+            bail!("the python-extension feature is not supported with LIBTORCH_USE_CMAKE")
+        }
         cmake(&libtorch)?;
     } else {
-        make(&config, &libtorch, use_cuda, use_hip)?;
+        let python_include = if use_python {
+            Some(python_include_dir()?)
+        } else {
+            None
+        };
+        make(&config, &libtorch, use_cuda, use_hip, python_include)?;
     }
 
     // add environment variables re-run guards
@@ -190,6 +207,9 @@ fn main() -> Result<()> {
         println!("cargo:rustc-link-lib=torch_hip");
         println!("cargo:rustc-link-lib=c10_hip");
     }
+    if use_python {
+        println!("cargo:rustc-link-lib=torch_python");
+    }
     if !config.target.contains("msvc") && !config.target.contains("apple") {
         println!("cargo:rustc-link-lib=gomp");
     }
@@ -279,11 +299,27 @@ fn prepare_libtorch_dir(config: &EnvConfig) -> Result<PathBuf> {
     Ok(libtorch_dir)
 }
 
+fn python_include_dir() -> Result<PathBuf> {
+    let python = env::var("PYTHON_SYS_EXECUTABLE").unwrap_or_else(|_| "python3".to_string());
+    let output = Command::new(&python)
+        .arg("-c")
+        .arg("import sysconfig; print(sysconfig.get_path('include'))")
+        .output()?;
+    if !output.status.success() {
+        bail!(
+            "unable to get the python include directory using {}",
+            python
+        )
+    }
+    Ok(PathBuf::from(String::from_utf8(output.stdout)?.trim()))
+}
+
 fn make(
     config: &EnvConfig,
     libtorch: impl AsRef<Path>,
     use_cuda: bool,
     use_hip: bool,
+    python_include: Option<PathBuf>,
 ) -> Result<()> {
     let libtorch = libtorch.as_ref();
     let cuda_dependency = if use_cuda || use_hip {
@@ -298,9 +334,13 @@ fn make(
     println!("cargo:rerun-if-changed=libtch/stb_image_write.h");
     println!("cargo:rerun-if-changed=libtch/stb_image_resize.h");
     println!("cargo:rerun-if-changed=libtch/stb_image.h");
+    let mut build = cc::Build::new();
+    if let Some(python_include) = python_include {
+        build.define("TCH_PYTHON", None).include(python_include);
+    }
     match &config.cargo_cfg_target_os {
         Os::Linux | Os::MacOs => {
-            cc::Build::new()
+            build
                 .cpp(true)
                 .pic(true)
                 .warnings(false)
@@ -320,7 +360,7 @@ fn make(
             // TODO: Pass "/link" "LIBPATH:{}" to cl.exe in order to emulate rpath.
             //       Not yet supported by cc=rs.
             //       https://github.com/alexcrichton/cc-rs/issues/323
-            cc::Build::new()
+            build
                 .cpp(true)
                 .pic(true)
                 .warnings(false)
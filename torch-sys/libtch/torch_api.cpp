@@ -1,10 +1,20 @@
 #include<torch/csrc/autograd/engine.h>
+#include<torch/csrc/autograd/anomaly_mode.h>
+#include<torch/csrc/autograd/profiler.h>
+#include<c10/util/Backtrace.h>
 #include<torch/csrc/jit/runtime/graph_executor.h>
 #include <torch/csrc/jit/passes/fixup_trace_scope_blocks.h>
 #include <torch/csrc/jit/passes/normalize_ops.h>
 #include<torch/torch.h>
 #include<ATen/autocast_mode.h>
+#include<ATen/DLConvertor.h>
+#include<sstream>
+#ifdef TCH_PYTHON
+#include<torch/csrc/autograd/python_variable.h>
+#endif
 #include<torch/script.h>
+#include<climits>
+#include<memory>
 #include<stdexcept>
 #include<vector>
 #include "torch_api.h"
@@ -56,6 +66,31 @@ tensor at_tensor_of_blob(void *data, int64_t *dims, size_t ndims, int64_t *strid
   return nullptr;
 }
 
+tensor at_tensor_of_blob_with_deleter(void *data, int64_t *dims, size_t ndims, int64_t *strides, size_t nstrides, int type, int device, void *deleter_data, void (*deleter)(void *)) {
+  // The deleter is run once the storage is released, or straight away if the
+  // tensor cannot be created.
+  auto guard = std::shared_ptr<void>(deleter_data, deleter);
+  PROTECT(
+    at::TensorOptions blobOptions = at::TensorOptions().device(device_of_int(device)).dtype(torch::ScalarType(type));
+    return new torch::Tensor(torch::from_blob(data, torch::IntArrayRef(dims, ndims), torch::IntArrayRef(strides, nstrides), [guard](void *) {}, blobOptions));
+  )
+  return nullptr;
+}
+
+void *at_to_dlpack(tensor t) {
+  PROTECT(
+    return at::toDLPack(*t);
+  )
+  return nullptr;
+}
+
+tensor at_from_dlpack(void *dlpack) {
+  PROTECT(
+    return new torch::Tensor(at::fromDLPack((DLManagedTensor *)dlpack));
+  )
+  return nullptr;
+}
+
 tensor at_tensor_of_data(void *vs, int64_t *dims, size_t ndims, size_t element_size_in_bytes, int type) {
   PROTECT(
     torch::Tensor tensor = torch::zeros(torch::IntArrayRef(dims, ndims), torch::ScalarType(type));
@@ -188,6 +223,67 @@ int at_device(tensor t) {
   return -2;
 }
 
+// libtorch only records the forward traceback used by anomaly detection when
+// running from Python, the default engine returns no anomaly metadata. When
+// anomaly detection gets enabled, the default engine is replaced with an engine
+// recording C++ backtraces. The traceback of the node that failed is appended
+// to the error of the graph task being executed so that concurrent backward
+// passes do not see each other's tracebacks.
+struct TchAnomalyMetadata : torch::autograd::AnomalyMetadata {
+  std::string traceback;
+
+  void store_stack() {
+    traceback = c10::get_backtrace(2);
+  }
+
+  void print_stack(const std::string& current_node_name) {}
+
+  void assign_parent(const std::shared_ptr<torch::autograd::Node>& parent_node) {}
+};
+
+struct TchEngine : torch::autograd::Engine {
+  static torch::autograd::Engine& get_tch_engine() {
+    static TchEngine *engine = new TchEngine();
+    return *engine;
+  }
+
+  std::unique_ptr<torch::autograd::AnomalyMetadata> make_anomaly_metadata() override {
+    return std::unique_ptr<torch::autograd::AnomalyMetadata>(new TchAnomalyMetadata());
+  }
+
+  void thread_on_exception(
+      std::shared_ptr<torch::autograd::GraphTask> graph_task,
+      const std::shared_ptr<torch::autograd::Node>& fn,
+      std::exception& e) override {
+    auto metadata = fn && torch::autograd::AnomalyMode::is_enabled()
+      ? dynamic_cast<TchAnomalyMetadata*>(fn->metadata())
+      : nullptr;
+    if (metadata == nullptr || metadata->traceback.empty()) {
+      torch::autograd::Engine::thread_on_exception(graph_task, fn, e);
+      return;
+    }
+    // The graph task stores the exception currently being handled.
+    try {
+      throw std::runtime_error(
+        std::string(e.what()) + "\nError detected in " + fn->name() +
+        ". Traceback of forward call that caused the error:\n" + metadata->traceback);
+    } catch (std::exception& err) {
+      torch::autograd::Engine::thread_on_exception(graph_task, fn, err);
+    }
+  }
+};
+
+int at_anomaly_set_enabled(int b) {
+  PROTECT(
+    bool is_enabled = torch::autograd::AnomalyMode::is_enabled();
+    if (b && !torch::autograd::Engine::get_default_engine().make_anomaly_metadata())
+      torch::autograd::set_default_engine_stub(TchEngine::get_tch_engine);
+    torch::autograd::AnomalyMode::set_enabled(b);
+    return is_enabled;
+  )
+  return -1;
+}
+
 void at_backward(tensor t, int keep_graph, int create_graph) {
   PROTECT(t->backward({}, keep_graph, create_graph);)
 }
@@ -197,6 +293,10 @@ int at_requires_grad(tensor t) {
   return -1;
 }
 
+void at_retain_grad(tensor t) {
+  PROTECT(t->retain_grad();)
+}
+
 int at_grad_set_enabled(int b) {
   PROTECT(
     bool is_enabled = torch::autograd::GradMode::is_enabled();
@@ -293,6 +393,69 @@ void at_save_multi(tensor *tensors, char **tensor_names, int ntensors, char *fil
   )
 }
 
+// An output stream buffer forwarding the written bytes to a callback.
+class WriteCallbackBuf : public std::streambuf {
+ public:
+  WriteCallbackBuf(void *stream, write_cb f) : stream(stream), f(f) {}
+
+ protected:
+  std::streamsize xsputn(const char *s, std::streamsize n) override {
+    return f(stream, s, n) ? n : 0;
+  }
+
+  int overflow(int c) override {
+    if (c == EOF) return 0;
+    char ch = c;
+    return f(stream, &ch, 1) ? c : EOF;
+  }
+
+ private:
+  void *stream;
+  write_cb f;
+};
+
+void at_save_to_stream(tensor t, void *stream, write_cb f) {
+  PROTECT(
+    WriteCallbackBuf buf(stream, f);
+    std::ostream out(&buf);
+    torch::save(*t, out);
+    if (!out) throw std::runtime_error("error when writing the tensor");
+  )
+}
+
+void at_save_multi_to_stream(tensor *tensors, char **tensor_names, int ntensors, void *stream, write_cb f) {
+  PROTECT(
+    torch::serialize::OutputArchive archive;
+    for (int i = 0; i < ntensors; ++i)
+      archive.write(std::string(tensor_names[i]), *(tensors[i]), /* buffer=*/ false);
+    WriteCallbackBuf buf(stream, f);
+    std::ostream out(&buf);
+    archive.save_to(out);
+    if (!out) throw std::runtime_error("error when writing the tensors");
+  )
+}
+
+tensor at_load_str(const char *data, size_t sz) {
+  PROTECT(
+    std::istringstream in(std::string(data, sz));
+    torch::Tensor tensor;
+    torch::load(tensor, in);
+    return new torch::Tensor(tensor);
+  )
+  return nullptr;
+}
+
+void at_load_callback_str_with_device(const char *buf, size_t sz, void *data, void (*f)(void *, char *, tensor), int device_id) {
+  PROTECT(
+    std::istringstream in(std::string(buf, sz));
+    auto module = torch::jit::load(in, device_of_int(device_id));
+    for (const auto &p : module.named_parameters()) {
+      auto v = p.value;
+      f(data, (char*)p.name.c_str(), new torch::Tensor(v));
+    }
+  )
+}
+
 void at_load_multi(tensor *tensors, char **tensor_names, int ntensors, char *filename) {
   PROTECT(
     torch::serialize::InputArchive archive;
@@ -401,6 +564,85 @@ int at_save_image(tensor tensor, char *filename) {
   return -1;
 }
 
+tensor at_load_image_from_memory(const unsigned char *data, size_t sz, int channels, int allow_16_bit) {
+  PROTECT(
+    int w = -1;
+    int h = -1;
+    int c = -1;
+    if (sz > INT_MAX)
+      throw std::invalid_argument("image data is too large");
+    bool is_16_bit = allow_16_bit && stbi_is_16_bit_from_memory(data, sz);
+    // The decoded data is released even if an exception is raised below.
+    std::unique_ptr<void, decltype(&stbi_image_free)> image_data(
+      is_16_bit
+        ? (void*)stbi_load_16_from_memory(data, sz, &w, &h, &c, channels)
+        : (void*)stbi_load_from_memory(data, sz, &w, &h, &c, channels),
+      stbi_image_free);
+    if (!image_data)
+      throw std::invalid_argument(stbi_failure_reason());
+    if (channels != 0) c = channels;
+    int64_t numel = (int64_t)h * (int64_t)w * (int64_t)c;
+    torch::Tensor tensor;
+    if (is_16_bit) {
+      // There is no unsigned 16 bits scalar type so the values are widened.
+      auto blob = torch::from_blob(image_data.get(), { h, w, c }, at::ScalarType::Short);
+      tensor = blob.to(at::ScalarType::Int).bitwise_and(0xffff);
+    }
+    else {
+      tensor = torch::empty({ h, w, c }, at::ScalarType::Byte);
+      memcpy(tensor.data_ptr(), image_data.get(), numel);
+    }
+    return new torch::Tensor(tensor);
+  )
+  return nullptr;
+}
+
+struct ImageWriteContext {
+  void *stream;
+  write_cb f;
+  bool ok;
+};
+
+static void image_write_func(void *context, void *data, int size) {
+  ImageWriteContext *ctx = (ImageWriteContext*)context;
+  if (ctx->ok && !ctx->f(ctx->stream, (const char*)data, size)) ctx->ok = false;
+}
+
+void at_encode_image(tensor tensor, int format, int quality, void *stream, write_cb f) {
+  PROTECT(
+    auto sizes = tensor->sizes();
+    if (tensor->device().type() != at::kCPU)
+      throw std::invalid_argument("the input tensor has to be on cpu");
+    if (sizes.size() != 3)
+      throw std::invalid_argument("invalid number of dimensions, should be 3");
+    int h = sizes[0];
+    int w = sizes[1];
+    int c = sizes[2];
+    auto tmp_tensor = tensor->contiguous();
+    void *tensor_data = tmp_tensor.data_ptr();
+    ImageWriteContext ctx = { stream, f, true };
+    int res = 0;
+    switch (format) {
+      case 0:
+        res = stbi_write_png_to_func(image_write_func, &ctx, w, h, c, tensor_data, 0);
+        break;
+      case 1:
+        res = stbi_write_jpg_to_func(image_write_func, &ctx, w, h, c, tensor_data, quality);
+        break;
+      case 2:
+        res = stbi_write_bmp_to_func(image_write_func, &ctx, w, h, c, tensor_data);
+        break;
+      case 3:
+        res = stbi_write_tga_to_func(image_write_func, &ctx, w, h, c, tensor_data);
+        break;
+      default:
+        throw std::invalid_argument("unknown image format");
+    }
+    if (!res) throw std::runtime_error("error when encoding the image");
+    if (!ctx.ok) throw std::runtime_error("error when writing the image");
+  )
+}
+
 int at_get_num_interop_threads() {
   PROTECT(return at::get_num_interop_threads();)
   return -1;
@@ -419,6 +661,15 @@ void at_set_num_threads(int n_threads) {
   PROTECT(at::set_num_threads(n_threads);)
 }
 
+void at_set_deterministic(int b) {
+  PROTECT(at::globalContext().setDeterministic(b);)
+}
+
+int at_deterministic() {
+  PROTECT(return at::globalContext().deterministic();)
+  return -1;
+}
+
 void at_set_qengine(int qengine_id) {
   PROTECT(
     at::QEngine qengine = at::QEngine::NoQEngine;
@@ -493,6 +744,191 @@ void at_run_backward(tensor *tensors,
   )
 }
 
+// State of a custom function, owned by the autograd graph so that the
+// Rust data is released when the backward node gets destroyed.
+struct CustomFunctionData : torch::CustomClassHolder {
+  void *data;
+  custom_function_cb forward;
+  custom_function_cb backward;
+  void (*free_data)(void *);
+
+  CustomFunctionData(void *data, custom_function_cb forward, custom_function_cb backward, void (*free_data)(void *))
+    : data(data), forward(forward), backward(backward), free_data(free_data) {}
+
+  ~CustomFunctionData() {
+    free_data(data);
+  }
+};
+
+void custom_function_push(void *outputs, tensor t) {
+  ((vector<torch::Tensor>*)outputs)->push_back(*t);
+}
+
+void callback_check(char *err) {
+  if (err != nullptr) {
+    std::string msg(err);
+    free(err);
+    throw std::runtime_error(msg);
+  }
+}
+
+struct CustomFunction : public torch::autograd::Function<CustomFunction> {
+  static torch::autograd::variable_list forward(
+      torch::autograd::AutogradContext *ctx,
+      torch::TensorList inputs,
+      c10::intrusive_ptr<CustomFunctionData> fn) {
+    ctx->saved_data["custom_function"] = c10::IValue::make_capsule(fn);
+    vector<tensor> c_inputs;
+    for (auto &input : inputs) c_inputs.push_back(const_cast<torch::Tensor*>(&input));
+    torch::autograd::variable_list outputs;
+    callback_check(fn->forward(fn->data, ctx, c_inputs.data(), c_inputs.size(), &outputs, custom_function_push));
+    return outputs;
+  }
+
+  static torch::autograd::variable_list backward(
+      torch::autograd::AutogradContext *ctx,
+      torch::autograd::variable_list grad_outputs) {
+    auto fn = c10::static_intrusive_pointer_cast<CustomFunctionData>(ctx->saved_data["custom_function"].toCapsule());
+    vector<tensor> c_grad_outputs;
+    for (auto &grad_output : grad_outputs) c_grad_outputs.push_back(&grad_output);
+    torch::autograd::variable_list grad_inputs;
+    callback_check(fn->backward(fn->data, ctx, c_grad_outputs.data(), c_grad_outputs.size(), &grad_inputs, custom_function_push));
+    // The function data argument does not get any gradient.
+    grad_inputs.push_back(torch::Tensor());
+    return grad_inputs;
+  }
+};
+
+void at_custom_function_apply(tensor *inputs,
+                              int ninputs,
+                              void *data,
+                              custom_function_cb forward,
+                              custom_function_cb backward,
+                              void (*free_data)(void *),
+                              void *outputs,
+                              void (*f)(void *, tensor)) {
+  auto fn = c10::make_intrusive<CustomFunctionData>(data, forward, backward, free_data);
+  PROTECT(
+    auto inputs_ = of_carray_tensor(inputs, ninputs);
+    auto outputs_ = CustomFunction::apply(torch::TensorList(inputs_), fn);
+    for (auto &output : outputs_)
+      f(outputs, new torch::Tensor(output));
+  )
+}
+
+void at_autograd_ctx_save_for_backward(autograd_ctx ctx, tensor *tensors, int ntensors) {
+  PROTECT(
+    ctx->save_for_backward(of_carray_tensor(tensors, ntensors));
+  )
+}
+
+void at_autograd_ctx_saved_tensors(autograd_ctx ctx, void *data, void (*f)(void *, tensor)) {
+  PROTECT(
+    for (auto &t : ctx->get_saved_variables())
+      f(data, new torch::Tensor(t));
+  )
+}
+
+void at_autograd_ctx_mark_non_differentiable(autograd_ctx ctx, tensor *tensors, int ntensors) {
+  PROTECT(
+    ctx->mark_non_differentiable(of_carray_tensor(tensors, ntensors));
+  )
+}
+
+struct HookData {
+  void *data;
+  void (*free_data)(void *);
+
+  HookData(void *data, void (*free_data)(void *)) : data(data), free_data(free_data) {}
+
+  ~HookData() {
+    free_data(data);
+  }
+};
+
+int at_register_hook(tensor t, void *data, tensor_hook_cb f, void (*free_data)(void *)) {
+  auto hook_data = std::make_shared<HookData>(data, free_data);
+  PROTECT(
+    return t->register_hook([hook_data, f](torch::Tensor grad) {
+      tensor result = nullptr;
+      callback_check(f(hook_data->data, &grad, &result));
+      if (result == nullptr) return torch::Tensor();
+      torch::Tensor result_ = *result;
+      delete result;
+      return result_;
+    });
+  )
+  return -1;
+}
+
+typedef c10::weak_intrusive_ptr<c10::TensorImpl, c10::UndefinedTensorImpl> weak_tensor_impl;
+
+void *at_weak_ref(tensor t) {
+  PROTECT(return new weak_tensor_impl(t->getIntrusivePtr());)
+  return nullptr;
+}
+
+void at_free_weak_ref(void *weak) {
+  delete static_cast<weak_tensor_impl*>(weak);
+}
+
+void at_remove_hook(void *weak, int pos) {
+  PROTECT(
+    // The hooks are released with the tensor, there is nothing to remove
+    // once it has been deallocated.
+    auto impl = static_cast<weak_tensor_impl*>(weak)->lock();
+    if (impl.defined()) torch::Tensor(impl).remove_hook(pos);
+  )
+}
+
+void at_profiler_enable(int record_shapes, int profile_memory) {
+  PROTECT(
+    torch::autograd::profiler::enableProfiler(torch::autograd::profiler::ProfilerConfig(
+      torch::autograd::profiler::ProfilerState::CPU, record_shapes, profile_memory));
+  )
+}
+
+void at_profiler_disable(void *data, profiler_event_cb f) {
+  PROTECT(
+    auto event_lists = torch::autograd::profiler::disableProfiler();
+    // Event times are reported relative to the mark added when enabling the profiler.
+    const torch::autograd::profiler::Event *start = nullptr;
+    for (auto &events : event_lists)
+      for (auto &e : events)
+        if (start == nullptr && std::string(e.name()) == "__start_profile")
+          start = &e;
+    for (auto &events : event_lists) {
+      for (auto &e : events) {
+        if (start == nullptr) start = &e;
+        vector<int64_t> dims;
+        vector<int64_t> ndims;
+        for (auto &shape : e.shapes()) {
+          ndims.push_back(shape.size());
+          dims.insert(dims.end(), shape.begin(), shape.end());
+        }
+        // kind() returns a temporary string, it has to outlive the callback.
+        std::string kind = e.kind();
+        int is_user_scope = e.scope() == static_cast<uint8_t>(at::RecordScope::USER_SCOPE);
+        f(data, kind.c_str(), e.name(), e.thread_id(), e.handle(), is_user_scope,
+          start->cpu_elapsed_us(e), e.cpu_memory_usage(), dims.data(), ndims.data(), ndims.size());
+      }
+    }
+  )
+}
+
+void *at_profiler_record_function_enter(char *name) {
+  PROTECT(
+    auto rec = new at::RecordFunction(at::RecordScope::USER_SCOPE);
+    rec->before(std::string(name));
+    return rec;
+  )
+  return nullptr;
+}
+
+void at_profiler_record_function_exit(void *rec) {
+  PROTECT(delete (at::RecordFunction*)rec;)
+}
+
 optimizer ato_adam(double learning_rate,
                    double beta1,
                    double beta2,
@@ -790,6 +1226,10 @@ void atc_set_benchmark_cudnn(int b) {
   at::globalContext().setBenchmarkCuDNN(b);
 }
 
+void atc_set_deterministic_cudnn(int b) {
+  at::globalContext().setDeterministicCuDNN(b);
+}
+
 module atm_load(char *filename) {
   PROTECT(
     return new torch::jit::script::Module(torch::jit::load(filename));
@@ -1253,3 +1693,25 @@ void ati_free(ivalue i) {
 }
 
 #include "torch_api_generated.cpp.h"
+
+#ifdef TCH_PYTHON
+int thp_variable_check(void *obj) {
+  return THPVariable_Check((PyObject *)obj);
+}
+
+tensor thp_variable_unpack(void *obj) {
+  PROTECT(
+    if (!THPVariable_Check((PyObject *)obj))
+      throw std::invalid_argument("expected a torch.Tensor");
+    return new torch::Tensor(((THPVariable *)obj)->cdata);
+  )
+  return nullptr;
+}
+
+void *thp_variable_wrap(tensor t) {
+  PROTECT(
+    return THPVariable_Wrap(*t);
+  )
+  return nullptr;
+}
+#endif
@@ -11,6 +11,7 @@ typedef torch::Scalar *scalar;
 typedef torch::optim::Optimizer *optimizer;
 typedef torch::jit::script::Module *module;
 typedef torch::jit::IValue *ivalue;
+typedef torch::autograd::AutogradContext *autograd_ctx;
 #define PROTECT(x) \
   try { \
     x \
@@ -23,16 +24,24 @@ typedef void *optimizer;
 typedef void *scalar;
 typedef void *module;
 typedef void *ivalue;
+typedef void *autograd_ctx;
 #endif
 
 char *get_and_reset_last_err(); // thread-local
 void at_manual_seed(int64_t);
 tensor at_new_tensor();
 tensor at_tensor_of_blob(void *data, int64_t *dims, size_t ndims, int64_t *strides, size_t nstrides, int type, int device);
+/* [deleter] is called on [deleter_data] when the tensor storage is released. */
+tensor at_tensor_of_blob_with_deleter(void *data, int64_t *dims, size_t ndims, int64_t *strides, size_t nstrides, int type, int device, void *deleter_data, void (*deleter)(void *));
 tensor at_tensor_of_data(void *vs, int64_t *dims, size_t ndims, size_t element_size_in_bytes, int type);
 void at_copy_data(tensor tensor, void *vs, size_t numel, size_t element_size_in_bytes);
 tensor at_shallow_clone(tensor);
 
+/* Returns a DLManagedTensor*, the caller owns it and has to call its deleter. */
+void *at_to_dlpack(tensor);
+/* Takes a DLManagedTensor*, its deleter is called when the storage is released. */
+tensor at_from_dlpack(void *);
+
 void *at_data_ptr(tensor);
 int at_defined(tensor);
 int at_is_mkldnn(tensor);
@@ -53,7 +62,9 @@ bool at_autocast_set_enabled(bool b);
 
 void at_backward(tensor, int, int);
 int at_requires_grad(tensor);
+void at_retain_grad(tensor);
 int at_grad_set_enabled(int);
+int at_anomaly_set_enabled(int);
 
 tensor at_get(tensor, int index);
 void at_fill_double(tensor, double);
@@ -75,6 +86,18 @@ int at_save_image(tensor, char *filename);
 tensor at_resize_image(tensor, int w, int h);
 
 void at_save_multi(tensor *tensors, char **tensor_names, int ntensors, char *filename);
+
+/* [write_cb] is called with the serialized bytes, it returns 0 on errors. */
+typedef int (*write_cb)(void *, const char *, size_t);
+void at_save_to_stream(tensor, void *stream, write_cb);
+void at_save_multi_to_stream(tensor *tensors, char **tensor_names, int ntensors, void *stream, write_cb);
+tensor at_load_str(const char *data, size_t sz);
+/* [channels] is the number of channels to decode to, 0 to keep the image ones.
+   16 bits images are decoded to int32 tensors when [allow_16_bit] is set. */
+tensor at_load_image_from_memory(const unsigned char *data, size_t sz, int channels, int allow_16_bit);
+/* [format] is 0 for png, 1 for jpg, 2 for bmp, and 3 for tga. */
+void at_encode_image(tensor, int format, int quality, void *stream, write_cb);
+void at_load_callback_str_with_device(const char *buf, size_t sz, void *data, void (*f)(void *, char *, tensor), int device_id);
 /* [at_load_multi] takes as input an array of nullptr for [tensors]. */
 void at_load_multi(tensor *tensors, char **tensor_names, int ntensors, char *filename);
 /* [at_load_multi_] takes as input an array of allocation [tensors]. */
@@ -93,6 +116,10 @@ void at_set_num_threads(int n_threads);
 
 void at_set_qengine(int qengine);
 
+void at_set_deterministic(int b);
+
+int at_deterministic();
+
 void at_free(tensor);
 
 void at_run_backward(tensor *tensors,
@@ -103,6 +130,59 @@ void at_run_backward(tensor *tensors,
                       int keep_graph,
                       int create_graph);
 
+/* Callbacks used by custom autograd functions. [tensors] are borrowed, the
+   results are returned by calling [push] on [outputs]. A non-null return
+   value is an error message allocated with malloc. */
+typedef char *(*custom_function_cb)(void *data,
+                                    autograd_ctx ctx,
+                                    tensor *tensors,
+                                    int ntensors,
+                                    void *outputs,
+                                    void (*push)(void *, tensor));
+/* [data] is owned by the created autograd node and released via [free_data]. */
+void at_custom_function_apply(tensor *inputs,
+                              int ninputs,
+                              void *data,
+                              custom_function_cb forward,
+                              custom_function_cb backward,
+                              void (*free_data)(void *),
+                              void *outputs,
+                              void (*f)(void *, tensor));
+void at_autograd_ctx_save_for_backward(autograd_ctx, tensor *tensors, int ntensors);
+void at_autograd_ctx_saved_tensors(autograd_ctx, void *data, void (*f)(void *, tensor));
+void at_autograd_ctx_mark_non_differentiable(autograd_ctx, tensor *tensors, int ntensors);
+
+/* Hooks are called with the gradient of the tensor, [result] can be set to a
+   new tensor replacing this gradient. A non-null return value is an error
+   message allocated with malloc. */
+typedef char *(*tensor_hook_cb)(void *data, tensor grad, tensor *result);
+/* [data] is owned by the hook and released via [free_data]. */
+int at_register_hook(tensor, void *data, tensor_hook_cb f, void (*free_data)(void *));
+/* Weak references do not keep the tensor nor its autograd graph alive. */
+void *at_weak_ref(tensor);
+void at_free_weak_ref(void *);
+void at_remove_hook(void *weak, int pos);
+
+/* [cpu_us] is relative to the start of the profiler. [is_user_scope] is set
+   for the ranges created by at_profiler_record_function_enter. The input
+   shapes are flattened in [dims], [ndims] contains the rank of each of the
+   [nshapes] shapes. */
+typedef void (*profiler_event_cb)(void *data,
+                                  const char *kind,
+                                  const char *name,
+                                  uint64_t thread_id,
+                                  uint64_t handle,
+                                  int is_user_scope,
+                                  double cpu_us,
+                                  int64_t cpu_memory_usage,
+                                  int64_t *dims,
+                                  int64_t *ndims,
+                                  int nshapes);
+void at_profiler_enable(int record_shapes, int profile_memory);
+void at_profiler_disable(void *data, profiler_event_cb f);
+void *at_profiler_record_function_enter(char *name);
+void at_profiler_record_function_exit(void *);
+
 optimizer ato_adam(double learning_rate,
                    double beta1,
                    double beta2,
@@ -144,6 +224,7 @@ int atc_cuda_device_count();
 int atc_cuda_is_available();
 int atc_cudnn_is_available();
 void atc_set_benchmark_cudnn(int b);
+void atc_set_deterministic_cudnn(int b);
 
 module atm_load(char *);
 module atm_load_on_device(char *, int device);
@@ -208,6 +289,13 @@ int ati_tag(ivalue);
 
 void ati_free(ivalue);
 
+#ifdef TCH_PYTHON
+/* These functions take and return PyObject*, the GIL has to be held. */
+int thp_variable_check(void *);
+tensor thp_variable_unpack(void *);
+void *thp_variable_wrap(tensor);
+#endif
+
 #include "torch_api_generated.h"
 
 #ifdef __cplusplus
@@ -33,6 +33,7 @@ extern "C" {
     pub fn at_dim(arg: *mut C_tensor) -> size_t;
     pub fn at_get(arg: *mut C_tensor, index: c_int) -> *mut C_tensor;
     pub fn at_requires_grad(arg: *mut C_tensor) -> c_int;
+    pub fn at_retain_grad(arg: *mut C_tensor);
     pub fn at_shape(arg: *mut C_tensor, sz: *mut i64);
     pub fn at_stride(arg: *mut C_tensor, sz: *mut i64);
     pub fn at_double_value_at_indexes(arg: *mut C_tensor, idx: *const i64, idx_len: c_int) -> f64;
@@ -42,6 +43,8 @@ extern "C" {
     pub fn at_set_num_interop_threads(n_threads: c_int);
     pub fn at_set_num_threads(n_threads: c_int);
     pub fn at_set_qengine(qengine: c_int);
+    pub fn at_set_deterministic(b: c_int);
+    pub fn at_deterministic() -> c_int;
     pub fn at_free(arg: *mut C_tensor);
     pub fn at_run_backward(
         arg: *const *mut C_tensor,
@@ -86,7 +89,21 @@ extern "C" {
         kind: c_int,
         device: c_int,
     ) -> *mut C_tensor;
+    pub fn at_tensor_of_blob_with_deleter(
+        vs: *mut c_void,
+        dims: *const i64,
+        ndims: size_t,
+        strides: *const i64,
+        nstrides: size_t,
+        kind: c_int,
+        device: c_int,
+        deleter_data: *mut c_void,
+        deleter: extern "C" fn(*mut c_void),
+    ) -> *mut C_tensor;
+    pub fn at_to_dlpack(arg: *mut C_tensor) -> *mut c_void;
+    pub fn at_from_dlpack(dlpack: *mut c_void) -> *mut C_tensor;
     pub fn at_grad_set_enabled(b: c_int) -> c_int;
+    pub fn at_anomaly_set_enabled(b: c_int) -> c_int;
     pub fn at_save(arg: *mut C_tensor, filename: *const c_char);
     pub fn at_load(filename: *const c_char) -> *mut C_tensor;
     pub fn at_save_multi(
@@ -95,6 +112,22 @@ extern "C" {
         n: c_int,
         filename: *const c_char,
     );
+    pub fn at_save_to_stream(arg: *mut C_tensor, stream: *mut c_void, f: WriteCallback);
+    pub fn at_save_multi_to_stream(
+        args: *const *mut C_tensor,
+        names: *const *const c_char,
+        n: c_int,
+        stream: *mut c_void,
+        f: WriteCallback,
+    );
+    pub fn at_load_str(data: *const c_char, sz: size_t) -> *mut C_tensor;
+    pub fn at_load_callback_str_with_device(
+        buf: *const c_char,
+        sz: size_t,
+        data: *mut c_void,
+        f: extern "C" fn(*mut c_void, name: *const c_char, t: *mut C_tensor),
+        device_id: c_int,
+    );
     pub fn at_load_callback(
         filename: *const c_char,
         data: *mut c_void,
@@ -117,12 +150,92 @@ extern "C" {
     pub fn atc_cuda_is_available() -> c_int;
     pub fn atc_cudnn_is_available() -> c_int;
     pub fn atc_set_benchmark_cudnn(b: c_int);
+    pub fn atc_set_deterministic_cudnn(b: c_int);
 }
 
 extern "C" {
     pub fn get_and_reset_last_err() -> *mut c_char;
 }
 
+#[repr(C)]
+pub struct C_autograd_ctx {
+    _private: [u8; 0],
+}
+
+pub type ProfilerEventCallback = extern "C" fn(
+    data: *mut c_void,
+    kind: *const c_char,
+    name: *const c_char,
+    thread_id: u64,
+    handle: u64,
+    is_user_scope: c_int,
+    cpu_us: f64,
+    cpu_memory_usage: i64,
+    dims: *const i64,
+    ndims: *const i64,
+    nshapes: c_int,
+);
+
+/// Receives some serialized bytes, returns 0 on errors.
+pub type WriteCallback =
+    extern "C" fn(stream: *mut c_void, buf: *const c_char, size: size_t) -> c_int;
+
+pub type TensorHookCallback = extern "C" fn(
+    data: *mut c_void,
+    grad: *mut C_tensor,
+    result: *mut *mut C_tensor,
+) -> *mut c_char;
+
+pub type CustomFunctionCallback = extern "C" fn(
+    data: *mut c_void,
+    ctx: *mut C_autograd_ctx,
+    tensors: *const *mut C_tensor,
+    ntensors: c_int,
+    outputs: *mut c_void,
+    push: extern "C" fn(*mut c_void, *mut C_tensor),
+) -> *mut c_char;
+
+extern "C" {
+    pub fn at_custom_function_apply(
+        inputs: *const *mut C_tensor,
+        ninputs: c_int,
+        data: *mut c_void,
+        forward: CustomFunctionCallback,
+        backward: CustomFunctionCallback,
+        free_data: extern "C" fn(*mut c_void),
+        outputs: *mut c_void,
+        f: extern "C" fn(*mut c_void, t: *mut C_tensor),
+    );
+    pub fn at_autograd_ctx_save_for_backward(
+        ctx: *mut C_autograd_ctx,
+        tensors: *const *mut C_tensor,
+        ntensors: c_int,
+    );
+    pub fn at_autograd_ctx_saved_tensors(
+        ctx: *mut C_autograd_ctx,
+        data: *mut c_void,
+        f: extern "C" fn(*mut c_void, t: *mut C_tensor),
+    );
+    pub fn at_autograd_ctx_mark_non_differentiable(
+        ctx: *mut C_autograd_ctx,
+        tensors: *const *mut C_tensor,
+        ntensors: c_int,
+    );
+    pub fn at_register_hook(
+        arg: *mut C_tensor,
+        data: *mut c_void,
+        f: TensorHookCallback,
+        free_data: extern "C" fn(*mut c_void),
+    ) -> c_int;
+    pub fn at_weak_ref(arg: *mut C_tensor) -> *mut c_void;
+    pub fn at_free_weak_ref(weak: *mut c_void);
+    pub fn at_remove_hook(weak: *mut c_void, pos: c_int);
+    pub fn at_profiler_enable(record_shapes: c_int, profile_memory: c_int);
+    pub fn at_profiler_disable(data: *mut c_void, f: ProfilerEventCallback);
+    pub fn at_profiler_record_function_enter(name: *const c_char) -> *mut c_void;
+    pub fn at_profiler_record_function_exit(rec: *mut c_void);
+}
+
 #[repr(C)]
 pub struct C_optimizer {
     _private: [u8; 0],
@@ -162,6 +275,19 @@ extern "C" {
     pub fn at_save_image(arg: *mut C_tensor, filename: *const c_char) -> c_int;
     pub fn at_load_image(filename: *const c_char) -> *mut C_tensor;
     pub fn at_resize_image(arg: *mut C_tensor, out_w: c_int, out_h: c_int) -> *mut C_tensor;
+    pub fn at_load_image_from_memory(
+        data: *const u8,
+        sz: size_t,
+        channels: c_int,
+        allow_16_bit: c_int,
+    ) -> *mut C_tensor;
+    pub fn at_encode_image(
+        arg: *mut C_tensor,
+        format: c_int,
+        quality: c_int,
+        stream: *mut c_void,
+        f: WriteCallback,
+    );
 }
 
 #[repr(C)]
@@ -258,3 +384,12 @@ extern "C" {
 extern "C" {
     pub fn dummy_cuda_dependency();
 }
+
+#[cfg(feature = "python-extension")]
+extern "C" {
+    // The PyObject pointers are passed as void pointers to avoid depending
+    // on a specific Python binding crate.
+    pub fn thp_variable_check(obj: *mut c_void) -> c_int;
+    pub fn thp_variable_unpack(obj: *mut c_void) -> *mut C_tensor;
+    pub fn thp_variable_wrap(arg: *mut C_tensor) -> *mut c_void;
+}